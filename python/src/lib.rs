@@ -0,0 +1,195 @@
+//! Python bindings for lintrunner.
+//!
+//! PyTorch's CI tooling is written in Python, and used to drive lintrunner by
+//! running it as a subprocess and parsing its JSON output. This module wraps
+//! [`Runner`] instead, so that linters run in-process and results come back as
+//! Python objects:
+//!
+//! ```python
+//! import lintrunner
+//!
+//! results = lintrunner.run(".lintrunner.toml", all_files=True)
+//! for lint in results["lints"]:
+//!     print(lint["path"], lint["code"], lint["name"])
+//! ```
+//!
+//! Relative paths are relative to the working directory of the Python
+//! process, like they are to that of the `lintrunner` CLI.
+
+use std::convert::TryFrom;
+use std::path::PathBuf;
+
+use anyhow::Result;
+use lintrunner::{
+    lint_config::LintRunnerConfig,
+    linter::Linter,
+    path::AbsPath,
+    runner::{ConfigSource, PatchPolicy, Runner},
+    LintResults, PathsOpt,
+};
+use pyo3::exceptions::PyRuntimeError;
+use pyo3::prelude::*;
+use pyo3::types::{PyDict, PyList};
+use serde_json::{json, Value};
+
+/// Raise `err` as a `RuntimeError`, with its causes but without a backtrace.
+fn to_py_err(err: anyhow::Error) -> PyErr {
+    PyRuntimeError::new_err(format!("{:#}", err))
+}
+
+/// Convert JSON to the equivalent Python object.
+fn to_python<'py>(py: Python<'py>, value: &Value) -> PyResult<Bound<'py, PyAny>> {
+    Ok(match value {
+        Value::Null => py.None().into_bound(py),
+        Value::Bool(b) => b.into_pyobject(py)?.to_owned().into_any(),
+        Value::Number(n) => match (n.as_i64(), n.as_u64()) {
+            (Some(i), _) => i.into_pyobject(py)?.into_any(),
+            (None, Some(u)) => u.into_pyobject(py)?.into_any(),
+            // Unwrap is fine because a number that isn't an integer is a
+            // float.
+            (None, None) => n.as_f64().unwrap().into_pyobject(py)?.into_any(),
+        },
+        Value::String(s) => s.into_pyobject(py)?.into_any(),
+        Value::Array(values) => {
+            let list = PyList::empty(py);
+            for value in values {
+                list.append(to_python(py, value)?)?;
+            }
+            list.into_any()
+        }
+        Value::Object(map) => {
+            let dict = PyDict::new(py);
+            for (key, value) in map {
+                dict.set_item(key, to_python(py, value)?)?;
+            }
+            dict.into_any()
+        }
+    })
+}
+
+fn runner(config: PathBuf, take: Option<Vec<String>>, skip: Option<Vec<String>>) -> Runner {
+    let mut runner = Runner::new(ConfigSource::File(config));
+    if let Some(take) = take {
+        runner = runner.take(take);
+    }
+    if let Some(skip) = skip {
+        runner = runner.skip(skip);
+    }
+    runner
+}
+
+fn linter_json(linter: &Linter) -> Value {
+    let patterns = |patterns: &[glob::Pattern]| -> Vec<String> {
+        patterns.iter().map(|p| p.as_str().to_string()).collect()
+    };
+    json!({
+        "code": linter.code,
+        "include_patterns": patterns(&linter.include_patterns),
+        "exclude_patterns": patterns(&linter.exclude_patterns),
+        "command": linter.commands,
+        "init_command": linter.init_commands,
+        "config_path": linter.config_path.display().to_string(),
+    })
+}
+
+fn results_json(results: &LintResults) -> Result<Value> {
+    let mut lints: Vec<_> = results.lints.values().flatten().collect();
+    lints.sort_by(|a, b| (&a.path, a.line, &a.code).cmp(&(&b.path, b.line, &b.code)));
+    let durations: serde_json::Map<String, Value> = results
+        .durations
+        .iter()
+        .map(|(code, duration)| (code.clone(), json!(duration.as_secs_f64())))
+        .collect();
+    Ok(json!({
+        "lints": serde_json::to_value(lints)?,
+        "exit_code": results.exit_code(),
+        "failing_lints": results.failing_lints,
+        "linter_failure": results.worst_failure.map(|failure| failure.title()),
+        "missing_linters": results.missing_linters,
+        "no_matches": results.no_matches,
+        "durations": durations,
+    }))
+}
+
+/// Read the config at `config`, with its local overlay, and return it as a
+/// dict.
+#[pyfunction]
+#[pyo3(signature = (config = PathBuf::from(".lintrunner.toml")))]
+fn parse_config(py: Python<'_>, config: PathBuf) -> PyResult<Bound<'_, PyAny>> {
+    let config = AbsPath::try_from(config)
+        .and_then(|config| LintRunnerConfig::new(&config, false, true))
+        .map_err(to_py_err)?;
+    to_python(
+        py,
+        &serde_json::to_value(config).map_err(|e| to_py_err(e.into()))?,
+    )
+}
+
+/// Return the linters in `config` that would run, as dicts.
+#[pyfunction]
+#[pyo3(signature = (config = PathBuf::from(".lintrunner.toml"), take = None, skip = None))]
+fn list_linters(
+    py: Python<'_>,
+    config: PathBuf,
+    take: Option<Vec<String>>,
+    skip: Option<Vec<String>>,
+) -> PyResult<Bound<'_, PyAny>> {
+    let linters = runner(config, take, skip).linters().map_err(to_py_err)?;
+    to_python(py, &Value::Array(linters.iter().map(linter_json).collect()))
+}
+
+/// Run the linters in `config` and return the results as a dict. By default,
+/// the files changed in the working tree and HEAD are linted, like with the
+/// CLI. Lint messages are dicts with the fields of lintrunner's JSON output.
+#[pyfunction]
+#[pyo3(signature = (
+    config = PathBuf::from(".lintrunner.toml"),
+    paths = None,
+    all_files = false,
+    take = None,
+    skip = None,
+    apply_patches = false,
+    jobs = None,
+))]
+#[allow(clippy::too_many_arguments)]
+fn run(
+    py: Python<'_>,
+    config: PathBuf,
+    paths: Option<Vec<String>>,
+    all_files: bool,
+    take: Option<Vec<String>>,
+    skip: Option<Vec<String>>,
+    apply_patches: bool,
+    jobs: Option<usize>,
+) -> PyResult<Bound<'_, PyAny>> {
+    let mut runner = runner(config, take, skip);
+    runner = match (paths, all_files) {
+        (Some(_), true) => {
+            return Err(PyRuntimeError::new_err(
+                "Pass either `paths` or `all_files`, not both",
+            ))
+        }
+        (Some(paths), false) => runner.paths(PathsOpt::Paths(paths)),
+        (None, true) => runner.paths(PathsOpt::AllFiles),
+        (None, false) => runner,
+    };
+    if apply_patches {
+        runner = runner.patch_policy(PatchPolicy::Apply);
+    }
+    if let Some(jobs) = jobs {
+        runner = runner.jobs(jobs);
+    }
+    // Linters run on threads of their own, so let other Python threads run
+    // meanwhile.
+    let results = py.detach(|| runner.run()).map_err(to_py_err)?;
+    to_python(py, &results_json(&results).map_err(to_py_err)?)
+}
+
+#[pymodule]
+#[pyo3(name = "lintrunner")]
+fn lintrunner_python(module: &Bound<'_, PyModule>) -> PyResult<()> {
+    module.add_function(wrap_pyfunction!(parse_config, module)?)?;
+    module.add_function(wrap_pyfunction!(list_linters, module)?)?;
+    module.add_function(wrap_pyfunction!(run, module)?)?;
+    Ok(())
+}
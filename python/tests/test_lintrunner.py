@@ -0,0 +1,67 @@
+"""Tests for the lintrunner Python bindings.
+
+Build and install the bindings first, e.g. with `maturin develop`, then run
+`python -m unittest discover python/tests`.
+"""
+
+import os
+import tempfile
+import unittest
+
+import lintrunner
+
+CONFIG = """\
+[[linter]]
+code = 'WS'
+include_patterns = ['*.txt']
+command = 'builtin:whitespace'
+
+[[linter]]
+code = 'EOF'
+include_patterns = ['*.txt']
+command = 'builtin:newline-eof'
+"""
+
+
+class LintrunnerTest(unittest.TestCase):
+    def setUp(self):
+        self.dir = tempfile.TemporaryDirectory()
+        self.config = os.path.join(self.dir.name, ".lintrunner.toml")
+        with open(self.config, "w") as f:
+            f.write(CONFIG)
+        self.file = os.path.join(self.dir.name, "a.txt")
+        with open(self.file, "w") as f:
+            f.write("x \n")
+
+    def tearDown(self):
+        self.dir.cleanup()
+
+    def test_parse_config(self):
+        config = lintrunner.parse_config(self.config)
+        self.assertEqual([linter["code"] for linter in config["linter"]], ["WS", "EOF"])
+
+    def test_list_linters(self):
+        linters = lintrunner.list_linters(self.config, skip=["EOF"])
+        self.assertEqual([linter["code"] for linter in linters], ["WS"])
+        self.assertEqual(linters[0]["include_patterns"], ["*.txt"])
+
+    def test_run(self):
+        results = lintrunner.run(self.config, paths=[self.file])
+        self.assertEqual(results["exit_code"], 1)
+        [lint] = results["lints"]
+        self.assertEqual(lint["code"], "WS")
+        self.assertEqual(lint["line"], 1)
+        self.assertEqual(lint["replacement"], "x\n")
+
+        results = lintrunner.run(self.config, paths=[self.file], apply_patches=True)
+        self.assertEqual(results["lints"], [])
+        with open(self.file) as f:
+            self.assertEqual(f.read(), "x\n")
+
+    def test_errors(self):
+        with self.assertRaisesRegex(RuntimeError, "Could not read lintrunner config"):
+            lintrunner.run(os.path.join(self.dir.name, "missing.toml"))
+
+
+if __name__ == "__main__":
+    unittest.main()
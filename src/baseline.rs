@@ -0,0 +1,235 @@
+//! Reporting only the issues that are new compared to an earlier run.
+//!
+//! Every run stores a fingerprint of each issue it found. Fingerprints use
+//! the text of the flagged line rather than its number, so an issue still
+//! matches after code above it moves, and paths relative to the root of the
+//! repo, so they match across checkouts.
+
+use std::{collections::HashMap, convert::TryFrom, path::Path};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    lint_message::LintMessage,
+    path::{path_relative_from, AbsPath},
+};
+
+/// Bumped whenever fingerprints are computed differently, so that runs which
+/// stored the old kind aren't compared against the new.
+pub const FINGERPRINT_VERSION: u32 = 2;
+
+/// The fingerprints of the issues each linter reported in one run.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct RunLints {
+    /// The commit that was checked out during the run.
+    pub head: String,
+    /// How the fingerprints were computed. Runs from before this was recorded
+    /// used version 1.
+    #[serde(default = "first_fingerprint_version")]
+    pub fingerprint_version: u32,
+    pub linters: HashMap<String, Vec<String>>,
+}
+
+fn first_fingerprint_version() -> u32 {
+    1
+}
+
+/// The issues each linter reported at the commit new issues are measured
+/// against.
+#[derive(Debug, Default)]
+pub struct Baseline {
+    linters: HashMap<String, Vec<String>>,
+}
+
+impl Baseline {
+    /// Build a baseline for `base` from past runs, most recent first. Each
+    /// linter's issues come from the most recent run at `base` that ran it.
+    pub fn from_runs(base: &str, runs: impl IntoIterator<Item = RunLints>) -> Baseline {
+        let mut linters = HashMap::new();
+        for run in runs {
+            if run.head != base || run.fingerprint_version != FINGERPRINT_VERSION {
+                continue;
+            }
+            for (code, fingerprints) in run.linters {
+                linters.entry(code).or_insert(fingerprints);
+            }
+        }
+        Baseline { linters }
+    }
+
+    pub fn has_linter(&self, code: &str) -> bool {
+        self.linters.contains_key(code)
+    }
+
+    /// Drop the issues `code` already had in the baseline. `fingerprints`
+    /// are the fingerprints of `lints`. Issues are matched one to one, so if
+    /// the same issue now occurs one more time, one of them is reported.
+    pub fn remove_known(
+        &self,
+        code: &str,
+        lints: Vec<LintMessage>,
+        fingerprints: &[String],
+    ) -> Vec<LintMessage> {
+        let mut known: HashMap<&str, usize> = HashMap::new();
+        for fingerprint in self.linters.get(code).into_iter().flatten() {
+            *known.entry(fingerprint).or_default() += 1;
+        }
+        lints
+            .into_iter()
+            .zip(fingerprints)
+            .filter(
+                |(_, fingerprint)| match known.get_mut(fingerprint.as_str()) {
+                    Some(count) if *count > 0 => {
+                        *count -= 1;
+                        false
+                    }
+                    _ => true,
+                },
+            )
+            .map(|(lint, _)| lint)
+            .collect()
+    }
+}
+
+/// Fingerprint each of `lints`, in order, with paths relative to `root`. The
+/// fingerprints linters sent are kept.
+pub fn lint_fingerprints(lints: &[LintMessage], root: Option<&Path>) -> Vec<String> {
+    let mut files: HashMap<&str, Option<Vec<String>>> = HashMap::new();
+    lints
+        .iter()
+        .map(|lint| {
+            if let Some(fingerprint) = &lint.fingerprint {
+                return fingerprint.clone();
+            }
+            let line_text = match (&lint.path, lint.line) {
+                (Some(path), Some(line)) => files
+                    .entry(path)
+                    .or_insert_with(|| {
+                        std::fs::read_to_string(path)
+                            .ok()
+                            .map(|contents| contents.lines().map(str::to_string).collect())
+                    })
+                    .as_ref()
+                    .and_then(|lines| lines.get(line.saturating_sub(1)))
+                    .map(|text| text.trim().to_string()),
+                _ => None,
+            };
+            let path = lint
+                .path
+                .as_deref()
+                .map(|path| normalize_path(path, root))
+                .unwrap_or_default();
+            let mut hasher = blake3::Hasher::new();
+            for part in [&path, &lint.code, &lint.name] {
+                hasher.update(part.as_bytes());
+                hasher.update(b"\0");
+            }
+            // Without the flagged line, fall back to the description, which
+            // hopefully says what's wrong without mentioning a line number.
+            match line_text {
+                Some(text) => hasher.update(text.as_bytes()),
+                None => hasher.update(lint.description.as_deref().unwrap_or_default().as_bytes()),
+            };
+            hasher.finalize().to_string()
+        })
+        .collect()
+}
+
+/// `path` relative to `root` if it is inside it, with forward slashes.
+pub(crate) fn normalize_path(path: &str, root: Option<&Path>) -> String {
+    let relative = root.and_then(|root| {
+        let path = AbsPath::try_from(path).ok()?;
+        path_relative_from(&path, root)
+    });
+    match relative {
+        Some(relative) => relative.to_string_lossy().replace('\\', "/"),
+        None => path.replace('\\', "/"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lint_message::LintSeverity;
+
+    fn lint(path: &str, line: usize) -> LintMessage {
+        LintMessage {
+            path: Some(path.to_string()),
+            line: Some(line),
+            description: Some(format!("Bad on line {}", line)),
+            ..LintMessage::for_test("TEST", LintSeverity::Error, "bad")
+        }
+    }
+
+    #[test]
+    fn known_issues_survive_line_shifts() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("a.py");
+        let path = path.to_str().unwrap();
+
+        std::fs::write(path, "bad()\nok()\n").unwrap();
+        let before = vec![lint(path, 1)];
+        let baseline = Baseline::from_runs(
+            "base",
+            vec![RunLints {
+                head: "base".to_string(),
+                fingerprint_version: FINGERPRINT_VERSION,
+                linters: [("TEST".to_string(), lint_fingerprints(&before, None))].into(),
+            }],
+        );
+
+        // Two lines were added at the top, one with a new issue.
+        std::fs::write(path, "bad()  \n\nbad()\nok()\n").unwrap();
+        let after = vec![lint(path, 1), lint(path, 3)];
+        let fingerprints = lint_fingerprints(&after, None);
+        let new = baseline.remove_known("TEST", after, &fingerprints);
+        assert_eq!(new.len(), 1);
+        assert!(baseline.has_linter("TEST"));
+        assert!(!baseline.has_linter("OTHER"));
+    }
+
+    #[test]
+    fn baseline_uses_most_recent_run_at_base() {
+        let run = |head: &str, fingerprint: &str| RunLints {
+            head: head.to_string(),
+            fingerprint_version: FINGERPRINT_VERSION,
+            linters: [("TEST".to_string(), vec![fingerprint.to_string()])].into(),
+        };
+        let outdated = RunLints {
+            fingerprint_version: 1,
+            ..run("base", "outdated")
+        };
+        let baseline = Baseline::from_runs(
+            "base",
+            vec![
+                outdated,
+                run("other", "x"),
+                run("base", "new"),
+                run("base", "old"),
+            ],
+        );
+        assert_eq!(baseline.linters["TEST"], vec!["new".to_string()]);
+    }
+
+    #[test]
+    fn fingerprints_use_paths_relative_to_root() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().canonicalize().unwrap();
+        std::fs::create_dir(root.join("src")).unwrap();
+        std::fs::write(root.join("src/a.py"), "bad()\n").unwrap();
+        let path = root.join("src/a.py").display().to_string();
+
+        assert_eq!(normalize_path(&path, Some(&root)), "src/a.py");
+        assert_ne!(
+            lint_fingerprints(&[lint(&path, 1)], Some(&root)),
+            lint_fingerprints(&[lint(&path, 1)], None)
+        );
+
+        let mut linter_fingerprint = lint(&path, 1);
+        linter_fingerprint.fingerprint = Some("from-linter".to_string());
+        assert_eq!(
+            lint_fingerprints(&[linter_fingerprint], Some(&root)),
+            vec!["from-linter".to_string()]
+        );
+    }
+}
@@ -0,0 +1,191 @@
+//! Check that files start with a copyright/license header.
+
+use anyhow::{bail, Context, Result};
+use chrono::Datelike;
+use regex::Regex;
+
+use super::{read_text, BuiltinContext};
+use crate::lint_message::LintMessage;
+use crate::path::AbsPath;
+
+const YEAR_PLACEHOLDER: &str = "{{YEAR}}";
+
+/// A header template, e.g.
+/// ```text
+/// # Copyright (c) {{YEAR}} Acme Corp.
+/// # SPDX-License-Identifier: MIT
+/// ```
+/// `{{YEAR}}` matches a single year or a range like `2019-2023`.
+struct Template {
+    text: String,
+    pattern: Regex,
+}
+
+impl Template {
+    fn new(text: String) -> Result<Template> {
+        let mut pattern = String::from("^");
+        for (idx, part) in text.split(YEAR_PLACEHOLDER).enumerate() {
+            if idx == 1 {
+                pattern.push_str(r"(?P<start>\d{4})(?:\s*-\s*(?P<end>\d{4}))?");
+            } else if idx > 1 {
+                pattern.push_str(r"\d{4}(?:\s*-\s*\d{4})?");
+            }
+            pattern.push_str(&regex::escape(part));
+        }
+        Ok(Template {
+            pattern: Regex::new(&pattern)?,
+            text,
+        })
+    }
+
+    fn render(&self, year: i32) -> String {
+        self.text.replace(YEAR_PLACEHOLDER, &year.to_string())
+    }
+}
+
+/// The result of checking a file's header.
+#[derive(Debug, PartialEq)]
+enum HeaderProblem {
+    Missing { replacement: String },
+    OutdatedYear { replacement: String },
+}
+
+fn check_header(
+    contents: &str,
+    template: &Template,
+    year: i32,
+    update_year: bool,
+) -> Option<HeaderProblem> {
+    // Keep shebangs as the first line.
+    let (shebang, body) = match contents.strip_prefix("#!") {
+        Some(_) => contents.split_at(contents.find('\n').map_or(contents.len(), |i| i + 1)),
+        None => ("", contents),
+    };
+
+    let captures = match template.pattern.captures(body) {
+        Some(captures) => captures,
+        None => {
+            return Some(HeaderProblem::Missing {
+                replacement: format!("{}{}{}", shebang, template.render(year), body),
+            })
+        }
+    };
+    if !update_year {
+        return None;
+    }
+
+    // Only the first `{{YEAR}}` is captured and updated.
+    let start = captures.name("start")?;
+    let last = captures.name("end").unwrap_or(start);
+    if last.as_str().parse::<i32>().ok()? >= year {
+        return None;
+    }
+    let replacement = format!(
+        "{}{}{}-{}{}",
+        shebang,
+        &body[..start.start()],
+        start.as_str(),
+        year,
+        &body[last.end()..]
+    );
+    Some(HeaderProblem::OutdatedYear { replacement })
+}
+
+pub fn header(context: &BuiltinContext, path: &AbsPath) -> Result<Vec<LintMessage>> {
+    // Unwrap is fine because `template` is a required option.
+    let template_path = context.config_dir.join(context.option("template").unwrap());
+    let template = std::fs::read_to_string(&template_path).with_context(|| {
+        format!(
+            "Failed to read header template '{}'",
+            template_path.display()
+        )
+    })?;
+    let template = Template::new(template)?;
+    let update_year = match context.option("update_year").unwrap_or("false") {
+        "true" => true,
+        "false" => false,
+        other => bail!("Invalid update_year '{}', expected true or false", other),
+    };
+
+    let contents = match read_text(path)? {
+        Some(contents) => contents,
+        None => return Ok(Vec::new()),
+    };
+    // Don't add headers to empty files, like Python's `__init__.py`.
+    if contents.trim().is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let year = chrono::Local::now().year();
+    let (name, description, replacement) =
+        match check_header(&contents, &template, year, update_year) {
+            None => return Ok(Vec::new()),
+            Some(HeaderProblem::Missing { replacement }) => (
+                "missing header",
+                "File does not start with the expected copyright/license header.",
+                replacement,
+            ),
+            Some(HeaderProblem::OutdatedYear { replacement }) => (
+                "outdated header",
+                "The copyright year in the header is out of date.",
+                replacement,
+            ),
+        };
+    Ok(vec![context.patch(
+        path,
+        Some(1),
+        name,
+        description.to_string(),
+        contents,
+        replacement,
+    )])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn template() -> Template {
+        Template::new("# Copyright (c) {{YEAR}} Acme\n# SPDX-License-Identifier: MIT\n".to_string())
+            .unwrap()
+    }
+
+    fn check(contents: &str, update_year: bool) -> Option<String> {
+        match check_header(contents, &template(), 2024, update_year)? {
+            HeaderProblem::Missing { replacement } => Some(format!("missing: {}", replacement)),
+            HeaderProblem::OutdatedYear { replacement } => {
+                Some(format!("outdated: {}", replacement))
+            }
+        }
+    }
+
+    #[test]
+    fn headers_are_inserted() {
+        let header = "# Copyright (c) 2019-2023 Acme\n# SPDX-License-Identifier: MIT\n";
+        assert_eq!(check(&format!("{}x = 1\n", header), false), None);
+        assert_eq!(
+            check("#!/usr/bin/env python3\nx = 1\n", false).unwrap(),
+            "missing: #!/usr/bin/env python3\n\
+             # Copyright (c) 2024 Acme\n# SPDX-License-Identifier: MIT\nx = 1\n"
+        );
+        assert!(check("# SPDX-License-Identifier: MIT\n", false)
+            .unwrap()
+            .starts_with("missing"));
+    }
+
+    #[test]
+    fn years_are_updated() {
+        let header = "# Copyright (c) 2019 Acme\n# SPDX-License-Identifier: MIT\n";
+        assert_eq!(
+            check(header, true).unwrap(),
+            "outdated: # Copyright (c) 2019-2024 Acme\n# SPDX-License-Identifier: MIT\n"
+        );
+        let header = "# Copyright (c) 2019-2022 Acme\n# SPDX-License-Identifier: MIT\n";
+        assert_eq!(
+            check(header, true).unwrap(),
+            "outdated: # Copyright (c) 2019-2024 Acme\n# SPDX-License-Identifier: MIT\n"
+        );
+        let header = "# Copyright (c) 2024 Acme\n# SPDX-License-Identifier: MIT\n";
+        assert_eq!(check(header, true), None);
+    }
+}
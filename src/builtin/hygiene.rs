@@ -0,0 +1,357 @@
+//! Basic file hygiene checks: whitespace, line endings, conflict markers and
+//! file modes.
+
+use anyhow::{bail, Result};
+
+use super::{format_numbers, read_text, BuiltinContext};
+use crate::lint_message::{LintMessage, LintSeverity};
+use crate::path::AbsPath;
+
+const BOM: char = '\u{feff}';
+
+/// Split a line (as produced by `split_inclusive('\n')`) into its content and
+/// its line ending.
+pub(super) fn split_ending(line: &str) -> (&str, &str) {
+    if let Some(content) = line.strip_suffix("\r\n") {
+        (content, "\r\n")
+    } else if let Some(content) = line.strip_suffix('\n') {
+        (content, "\n")
+    } else {
+        (line, "")
+    }
+}
+
+/// Apply `fix` to every line of `contents`. Returns the fixed contents and the
+/// (1-indexed) numbers of the lines that changed.
+fn fix_lines(contents: &str, fix: impl Fn(&str) -> Option<String>) -> (String, Vec<usize>) {
+    let mut fixed = String::with_capacity(contents.len());
+    let mut changed = Vec::new();
+    for (idx, line) in contents.split_inclusive('\n').enumerate() {
+        let (content, ending) = split_ending(line);
+        match fix(content) {
+            Some(replacement) => {
+                changed.push(idx + 1);
+                fixed.push_str(&replacement);
+            }
+            None => fixed.push_str(content),
+        }
+        fixed.push_str(ending);
+    }
+    (fixed, changed)
+}
+
+pub fn trailing_whitespace(context: &BuiltinContext, path: &AbsPath) -> Result<Vec<LintMessage>> {
+    let contents = match read_text(path)? {
+        Some(contents) => contents,
+        None => return Ok(Vec::new()),
+    };
+    let (fixed, lines) = fix_lines(&contents, |content| {
+        let trimmed = content.trim_end_matches([' ', '\t']);
+        (trimmed.len() != content.len()).then(|| trimmed.to_string())
+    });
+    if lines.is_empty() {
+        return Ok(Vec::new());
+    }
+    Ok(vec![context.patch(
+        path,
+        lines.first().copied(),
+        "trailing whitespace",
+        format!("Trailing whitespace on line {}.", format_numbers(&lines)),
+        contents,
+        fixed,
+    )])
+}
+
+pub fn newline_eof(context: &BuiltinContext, path: &AbsPath) -> Result<Vec<LintMessage>> {
+    let contents = match read_text(path)? {
+        Some(contents) => contents,
+        None => return Ok(Vec::new()),
+    };
+    if contents.is_empty() || contents.ends_with('\n') {
+        return Ok(Vec::new());
+    }
+    let fixed = format!("{}\n", contents);
+    Ok(vec![context.patch(
+        path,
+        Some(contents.lines().count()),
+        "missing newline at end of file",
+        "Files should end with a newline.".to_string(),
+        contents,
+        fixed,
+    )])
+}
+
+pub fn tabs(context: &BuiltinContext, path: &AbsPath) -> Result<Vec<LintMessage>> {
+    let use_tabs = match context.option("indent").unwrap_or("spaces") {
+        "spaces" => false,
+        "tabs" => true,
+        other => bail!("Invalid indent '{}', expected 'spaces' or 'tabs'", other),
+    };
+    let tab_width = match context.option("tab_width") {
+        Some(width) => match width.parse::<usize>() {
+            Ok(width) if width > 0 => width,
+            _ => bail!("Invalid tab_width '{}', expected a positive number", width),
+        },
+        None => 4,
+    };
+
+    let contents = match read_text(path)? {
+        Some(contents) => contents,
+        None => return Ok(Vec::new()),
+    };
+    let (fixed, lines) = fix_lines(&contents, |content| {
+        let rest = content.trim_start_matches([' ', '\t']);
+        let indent = &content[..content.len() - rest.len()];
+        let needs_fix = if use_tabs {
+            indent.contains(&" ".repeat(tab_width))
+        } else {
+            indent.contains('\t')
+        };
+        if !needs_fix {
+            return None;
+        }
+
+        // Compute the visual width of the indentation, then re-render it.
+        let width = indent.chars().fold(0, |width, c| match c {
+            '\t' => (width / tab_width + 1) * tab_width,
+            _ => width + 1,
+        });
+        let new_indent = if use_tabs {
+            format!(
+                "{}{}",
+                "\t".repeat(width / tab_width),
+                " ".repeat(width % tab_width)
+            )
+        } else {
+            " ".repeat(width)
+        };
+        Some(format!("{}{}", new_indent, rest))
+    });
+    if lines.is_empty() {
+        return Ok(Vec::new());
+    }
+    let (name, description) = if use_tabs {
+        (
+            "space indentation",
+            "Indentation uses spaces instead of tabs",
+        )
+    } else {
+        ("tab indentation", "Indentation uses tabs instead of spaces")
+    };
+    Ok(vec![context.patch(
+        path,
+        lines.first().copied(),
+        name,
+        format!("{} on line {}.", description, format_numbers(&lines)),
+        contents,
+        fixed,
+    )])
+}
+
+pub fn bom(context: &BuiltinContext, path: &AbsPath) -> Result<Vec<LintMessage>> {
+    let contents = match read_text(path)? {
+        Some(contents) => contents,
+        None => return Ok(Vec::new()),
+    };
+    let fixed = match contents.strip_prefix(BOM) {
+        Some(fixed) => fixed.to_string(),
+        None => return Ok(Vec::new()),
+    };
+    Ok(vec![context.patch(
+        path,
+        Some(1),
+        "byte order mark",
+        "File starts with a UTF-8 byte order mark.".to_string(),
+        contents,
+        fixed,
+    )])
+}
+
+pub fn crlf(context: &BuiltinContext, path: &AbsPath) -> Result<Vec<LintMessage>> {
+    let contents = match read_text(path)? {
+        Some(contents) => contents,
+        None => return Ok(Vec::new()),
+    };
+    let lines: Vec<usize> = contents
+        .split_inclusive('\n')
+        .enumerate()
+        .filter(|(_, line)| line.ends_with("\r\n"))
+        .map(|(idx, _)| idx + 1)
+        .collect();
+    if lines.is_empty() {
+        return Ok(Vec::new());
+    }
+    let fixed = contents.replace("\r\n", "\n");
+    Ok(vec![context.patch(
+        path,
+        lines.first().copied(),
+        "CRLF line ending",
+        format!("CRLF line ending on line {}.", format_numbers(&lines)),
+        contents,
+        fixed,
+    )])
+}
+
+pub fn merge_conflict(context: &BuiltinContext, path: &AbsPath) -> Result<Vec<LintMessage>> {
+    let contents = match read_text(path)? {
+        Some(contents) => contents,
+        None => return Ok(Vec::new()),
+    };
+    let is_marker = |content: &str, marker: &str| {
+        content == marker || content.starts_with(&format!("{} ", marker))
+    };
+
+    let mut messages = Vec::new();
+    let mut in_conflict = false;
+    for (idx, line) in contents.split_inclusive('\n').enumerate() {
+        let (content, _) = split_ending(line);
+        // `=======` on its own is common (e.g. reStructuredText headings), so
+        // only flag it inside a conflict.
+        let flagged = if is_marker(content, "<<<<<<<") {
+            in_conflict = true;
+            true
+        } else if in_conflict && content == "=======" {
+            true
+        } else if in_conflict && is_marker(content, ">>>>>>>") {
+            in_conflict = false;
+            true
+        } else {
+            false
+        };
+        if flagged {
+            messages.push(context.message(
+                path,
+                Some(idx + 1),
+                LintSeverity::Error,
+                "merge conflict marker",
+                format!("Unresolved merge conflict marker `{}`.", content),
+            ));
+        }
+    }
+    Ok(messages)
+}
+
+#[cfg(unix)]
+pub fn exec_bit(context: &BuiltinContext, path: &AbsPath) -> Result<Vec<LintMessage>> {
+    use std::io::Read;
+    use std::os::unix::fs::PermissionsExt;
+
+    let mode = std::fs::metadata(path)?.permissions().mode();
+    if mode & 0o111 == 0 {
+        return Ok(Vec::new());
+    }
+    let mut shebang = [0; 2];
+    let is_script =
+        std::fs::File::open(path)?.read_exact(&mut shebang).is_ok() && &shebang == b"#!";
+    if is_script {
+        return Ok(Vec::new());
+    }
+    Ok(vec![context.message(
+        path,
+        None,
+        LintSeverity::Warning,
+        "executable bit",
+        format!(
+            "File is executable but has no shebang. Either add one or run \
+             `git update-index --chmod=-x {}`.",
+            path.display()
+        ),
+    )])
+}
+
+#[cfg(not(unix))]
+pub fn exec_bit(_context: &BuiltinContext, _path: &AbsPath) -> Result<Vec<LintMessage>> {
+    Ok(Vec::new())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::builtin::{check_contents, CheckFn};
+
+    fn check(check: CheckFn, options: &[(&str, &str)], contents: &str) -> Vec<LintMessage> {
+        check_contents(check, options, contents.as_bytes())
+    }
+
+    fn replacement(messages: &[LintMessage]) -> &str {
+        assert_eq!(messages.len(), 1);
+        messages[0].replacement.as_deref().unwrap()
+    }
+
+    #[test]
+    fn trailing_whitespace_is_stripped() {
+        let messages = check(trailing_whitespace, &[], "a \nb\r\nc\t \r\nd  ");
+        assert_eq!(replacement(&messages), "a\nb\r\nc\r\nd");
+        assert_eq!(messages[0].line, Some(1));
+        assert_eq!(
+            messages[0].description.as_deref(),
+            Some("Trailing whitespace on line 1, 3 and 4.")
+        );
+        assert!(check(trailing_whitespace, &[], "a\nb\n").is_empty());
+    }
+
+    #[test]
+    fn newline_is_added_at_eof() {
+        let messages = check(newline_eof, &[], "a\nb");
+        assert_eq!(replacement(&messages), "a\nb\n");
+        assert_eq!(messages[0].line, Some(2));
+        assert!(check(newline_eof, &[], "a\n").is_empty());
+        assert!(check(newline_eof, &[], "").is_empty());
+    }
+
+    #[test]
+    fn indentation_is_fixed() {
+        let messages = check(tabs, &[], "\tx\n  \ty\nz\t\n");
+        assert_eq!(replacement(&messages), "    x\n    y\nz\t\n");
+
+        let options = [("indent", "tabs"), ("tab_width", "2")];
+        let messages = check(tabs, &options, "    x\n   y\n z\n");
+        assert_eq!(replacement(&messages), "\t\tx\n\t y\n z\n");
+    }
+
+    #[test]
+    fn bom_and_crlf_are_removed() {
+        let messages = check(bom, &[], "\u{feff}a\n");
+        assert_eq!(replacement(&messages), "a\n");
+        assert!(check(bom, &[], "a\n").is_empty());
+
+        let messages = check(crlf, &[], "a\r\nb\nc\r\n");
+        assert_eq!(replacement(&messages), "a\nb\nc\n");
+        assert_eq!(
+            messages[0].description.as_deref(),
+            Some("CRLF line ending on line 1 and 3.")
+        );
+    }
+
+    #[test]
+    fn merge_conflicts_are_found() {
+        let contents = "Title\n=======\n<<<<<<< HEAD\na\n=======\nb\n>>>>>>> branch\n";
+        let lines: Vec<_> = check(merge_conflict, &[], contents)
+            .iter()
+            .map(|message| message.line.unwrap())
+            .collect();
+        assert_eq!(lines, vec![3, 5, 7]);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn exec_bit_requires_shebang() {
+        use std::collections::HashMap;
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let context = BuiltinContext {
+            code: "HYGIENE",
+            config_dir: dir.path(),
+            options: HashMap::new(),
+        };
+        let check = |contents: &str| {
+            let path = dir.path().join("file");
+            std::fs::write(&path, contents).unwrap();
+            std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o755)).unwrap();
+            exec_bit(&context, &AbsPath::try_from(path).unwrap()).unwrap()
+        };
+        assert_eq!(check("data").len(), 1);
+        assert!(check("#!/bin/sh\n").is_empty());
+    }
+}
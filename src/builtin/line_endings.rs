@@ -0,0 +1,121 @@
+//! Enforce LF or CRLF line endings, chosen per file by glob pattern.
+
+use anyhow::{bail, Result};
+use glob::Pattern;
+
+use super::hygiene::split_ending;
+use super::{format_numbers, read_text, BuiltinContext};
+use crate::lint_message::LintMessage;
+use crate::linter::matches_relative_path;
+use crate::matcher::PatternOptions;
+use crate::path::AbsPath;
+
+fn parse_patterns(patterns: Option<&str>) -> Result<Vec<Pattern>> {
+    patterns
+        .unwrap_or_default()
+        .split(',')
+        .filter(|pattern| !pattern.is_empty())
+        .map(|pattern| Ok(Pattern::new(pattern)?))
+        .collect()
+}
+
+fn parse_ending(ending: &str) -> Result<&'static str> {
+    Ok(match ending {
+        "lf" => "\n",
+        "crlf" => "\r\n",
+        _ => bail!("Invalid line ending '{}', expected 'lf' or 'crlf'", ending),
+    })
+}
+
+/// Convert every line ending in `contents` to `ending`. Returns the converted
+/// contents and the (1-indexed) numbers of the lines that changed.
+fn convert(contents: &str, ending: &str) -> (String, Vec<usize>) {
+    let mut converted = String::with_capacity(contents.len());
+    let mut changed = Vec::new();
+    for (idx, line) in contents.split_inclusive('\n').enumerate() {
+        let (content, old_ending) = split_ending(line);
+        converted.push_str(content);
+        if old_ending.is_empty() {
+            continue;
+        }
+        if old_ending != ending {
+            changed.push(idx + 1);
+        }
+        converted.push_str(ending);
+    }
+    (converted, changed)
+}
+
+pub fn line_endings(context: &BuiltinContext, path: &AbsPath) -> Result<Vec<LintMessage>> {
+    let default = parse_ending(context.option("default").unwrap_or("lf"))?;
+    let lf_patterns = parse_patterns(context.option("lf"))?;
+    let crlf_patterns = parse_patterns(context.option("crlf"))?;
+    let matches = |patterns: &[Pattern]| {
+        patterns.iter().any(|pattern| {
+            matches_relative_path(context.config_dir, path, pattern, PatternOptions::default())
+        })
+    };
+    let ending = if matches(&crlf_patterns) {
+        "\r\n"
+    } else if matches(&lf_patterns) {
+        "\n"
+    } else {
+        default
+    };
+
+    let contents = match read_text(path)? {
+        Some(contents) => contents,
+        None => return Ok(Vec::new()),
+    };
+    let (converted, lines) = convert(&contents, ending);
+    if lines.is_empty() {
+        return Ok(Vec::new());
+    }
+    let expected = if ending == "\n" { "LF" } else { "CRLF" };
+    Ok(vec![context.patch(
+        path,
+        lines.first().copied(),
+        "line endings",
+        format!(
+            "Expected {} line endings, but line {} differs.",
+            expected,
+            format_numbers(&lines)
+        ),
+        contents,
+        converted,
+    )])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::builtin::check_contents;
+
+    fn check(options: &[(&str, &str)], contents: &str) -> Option<String> {
+        let messages = check_contents(line_endings, options, contents.as_bytes());
+        messages.first().map(|m| m.replacement.clone().unwrap())
+    }
+
+    #[test]
+    fn line_endings_are_converted() {
+        assert_eq!(check(&[], "a\r\nb\nc"), Some("a\nb\nc".to_string()));
+        assert_eq!(check(&[], "a\nb\n"), None);
+        assert_eq!(
+            check(&[("default", "crlf")], "a\r\nb\nc"),
+            Some("a\r\nb\r\nc".to_string())
+        );
+    }
+
+    #[test]
+    fn line_endings_are_chosen_by_pattern() {
+        // The file checked by `check_contents` is named file.txt.
+        assert_eq!(
+            check(&[("crlf", "*.bat,*.txt")], "a\n"),
+            Some("a\r\n".to_string())
+        );
+        assert_eq!(
+            check(&[("default", "crlf"), ("lf", "*.txt")], "a\r\n"),
+            Some("a\n".to_string())
+        );
+    }
+}
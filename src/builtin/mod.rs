@@ -0,0 +1,293 @@
+//! Linters implemented natively in lintrunner, so that simple checks don't
+//! need an adapter written in another language.
+//!
+//! A builtin is selected with `command = "builtin:<name>"`. Options can be
+//! passed as additional `key=value` elements in the command list, e.g.
+//! `command = ['builtin:tabs', 'indent=tabs']`.
+
+use std::collections::HashMap;
+use std::path::Path;
+
+use anyhow::{bail, Context, Result};
+
+use crate::lint_message::{LintMessage, LintSeverity};
+use crate::path::AbsPath;
+
+mod header;
+mod hygiene;
+mod line_endings;
+mod utf8;
+
+pub const BUILTIN_PREFIX: &str = "builtin:";
+
+type CheckFn = fn(&BuiltinContext, &AbsPath) -> Result<Vec<LintMessage>>;
+
+struct Builtin {
+    name: &'static str,
+    options: &'static [&'static str],
+    required_options: &'static [&'static str],
+    check: CheckFn,
+}
+
+const BUILTINS: &[Builtin] = &[
+    Builtin {
+        name: "whitespace",
+        options: &[],
+        required_options: &[],
+        check: hygiene::trailing_whitespace,
+    },
+    Builtin {
+        name: "newline-eof",
+        options: &[],
+        required_options: &[],
+        check: hygiene::newline_eof,
+    },
+    Builtin {
+        name: "tabs",
+        options: &["indent", "tab_width"],
+        required_options: &[],
+        check: hygiene::tabs,
+    },
+    Builtin {
+        name: "bom",
+        options: &[],
+        required_options: &[],
+        check: hygiene::bom,
+    },
+    Builtin {
+        name: "crlf",
+        options: &[],
+        required_options: &[],
+        check: hygiene::crlf,
+    },
+    Builtin {
+        name: "merge-conflict",
+        options: &[],
+        required_options: &[],
+        check: hygiene::merge_conflict,
+    },
+    Builtin {
+        name: "exec-bit",
+        options: &[],
+        required_options: &[],
+        check: hygiene::exec_bit,
+    },
+    Builtin {
+        name: "line-endings",
+        options: &["default", "lf", "crlf"],
+        required_options: &[],
+        check: line_endings::line_endings,
+    },
+    Builtin {
+        name: "header",
+        options: &["template", "update_year"],
+        required_options: &["template"],
+        check: header::header,
+    },
+    Builtin {
+        name: "utf8",
+        options: &["encodings", "transcode_from"],
+        required_options: &[],
+        check: utf8::utf8,
+    },
+];
+
+/// Everything a builtin check needs to know besides the file being checked.
+pub struct BuiltinContext<'a> {
+    code: &'a str,
+    config_dir: &'a Path,
+    options: HashMap<&'a str, &'a str>,
+}
+
+impl<'a> BuiltinContext<'a> {
+    fn option(&self, key: &str) -> Option<&'a str> {
+        self.options.get(key).copied()
+    }
+
+    /// Build a lint message about `path`.
+    fn message(
+        &self,
+        path: &Path,
+        line: Option<usize>,
+        severity: LintSeverity,
+        name: &str,
+        description: String,
+    ) -> LintMessage {
+        LintMessage {
+            path: Some(path.display().to_string()),
+            line,
+            char: None,
+            code: self.code.to_string(),
+            severity,
+            name: name.to_string(),
+            description: Some(description),
+            original: None,
+            replacement: None,
+            edits: Vec::new(),
+            fingerprint: None,
+            first_seen: None,
+        }
+    }
+
+    /// Build a lint message that replaces the contents of `path`.
+    fn patch(
+        &self,
+        path: &Path,
+        line: Option<usize>,
+        name: &str,
+        description: String,
+        original: String,
+        replacement: String,
+    ) -> LintMessage {
+        LintMessage {
+            original: Some(original),
+            replacement: Some(replacement),
+            ..self.message(path, line, LintSeverity::Warning, name, description)
+        }
+    }
+}
+
+/// If `command` selects a builtin linter, return its name.
+pub fn builtin_name(command: &[String]) -> Option<&str> {
+    command.first()?.strip_prefix(BUILTIN_PREFIX)
+}
+
+fn parse(command: &[String]) -> Result<(&'static Builtin, HashMap<&str, &str>)> {
+    // Unwrap is fine because callers check this is a builtin first.
+    let name = builtin_name(command).unwrap();
+    let builtin = match BUILTINS.iter().find(|builtin| builtin.name == name) {
+        Some(builtin) => builtin,
+        None => bail!(
+            "Unknown builtin linter '{}'. Available builtins: {}",
+            name,
+            BUILTINS
+                .iter()
+                .map(|builtin| builtin.name)
+                .collect::<Vec<_>>()
+                .join(", ")
+        ),
+    };
+
+    let mut options = HashMap::new();
+    for arg in &command[1..] {
+        let (key, value) = arg
+            .split_once('=')
+            .with_context(|| format!("Invalid option '{}', expected key=value", arg))?;
+        if !builtin.options.contains(&key) {
+            bail!(
+                "Unknown option '{}' for builtin '{}'. Valid options: {:?}",
+                key,
+                name,
+                builtin.options
+            );
+        }
+        options.insert(key, value);
+    }
+    for required in builtin.required_options {
+        if !options.contains_key(required) {
+            bail!("Builtin '{}' requires the option '{}'", name, required);
+        }
+    }
+    Ok((builtin, options))
+}
+
+/// Check that `command` names an existing builtin, with valid options.
+pub fn validate(command: &[String]) -> Result<()> {
+    parse(command).map(|_| ())
+}
+
+/// Run the builtin linter selected by `command` on `files`.
+pub fn run(
+    code: &str,
+    command: &[String],
+    files: &[AbsPath],
+    config_dir: &Path,
+) -> Result<Vec<LintMessage>> {
+    let (builtin, options) = parse(command)?;
+    let context = BuiltinContext {
+        code,
+        config_dir,
+        options,
+    };
+    let mut messages = Vec::new();
+    for file in files {
+        messages.extend(
+            (builtin.check)(&context, file)
+                .with_context(|| format!("Failed to check '{}'", file.display()))?,
+        );
+    }
+    Ok(messages)
+}
+
+/// Read `path` as text. Returns None for files that aren't UTF-8 or look
+/// binary, which text-based checks should skip.
+fn read_text(path: &Path) -> Result<Option<String>> {
+    let bytes = std::fs::read(path)?;
+    if bytes.contains(&0) {
+        return Ok(None);
+    }
+    Ok(String::from_utf8(bytes).ok())
+}
+
+/// Format a list of line numbers or offsets for a description, e.g.
+/// "3, 7 and 9".
+fn format_numbers(lines: &[usize]) -> String {
+    const MAX_LINES: usize = 10;
+    let mut formatted: Vec<String> = lines
+        .iter()
+        .take(MAX_LINES)
+        .map(|line| line.to_string())
+        .collect();
+    if lines.len() > MAX_LINES {
+        formatted.push(format!("{} more", lines.len() - MAX_LINES));
+    }
+    match formatted.split_last() {
+        Some((last, [])) => last.clone(),
+        Some((last, rest)) => format!("{} and {}", rest.join(", "), last),
+        None => String::new(),
+    }
+}
+
+/// Run `check` on a temporary file with `contents`.
+#[cfg(test)]
+fn check_contents(check: CheckFn, options: &[(&str, &str)], contents: &[u8]) -> Vec<LintMessage> {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("file.txt");
+    std::fs::write(&path, contents).unwrap();
+    let context = BuiltinContext {
+        code: "TEST",
+        config_dir: dir.path(),
+        options: options.iter().copied().collect(),
+    };
+    check(&context, &AbsPath::try_from(path).unwrap()).unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn command(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    #[test]
+    fn builtins_are_validated() {
+        assert!(validate(&command(&["builtin:whitespace"])).is_ok());
+        assert!(validate(&command(&["builtin:tabs", "indent=tabs"])).is_ok());
+        assert!(validate(&command(&["builtin:nope"])).is_err());
+        assert!(validate(&command(&["builtin:tabs", "indent"])).is_err());
+        assert!(validate(&command(&["builtin:whitespace", "indent=tabs"])).is_err());
+        assert!(validate(&command(&["builtin:header"])).is_err());
+        assert_eq!(builtin_name(&command(&["flake8"])), None);
+    }
+
+    #[test]
+    fn lines_are_formatted() {
+        assert_eq!(format_numbers(&[3]), "3");
+        assert_eq!(format_numbers(&[3, 7, 9]), "3, 7 and 9");
+        assert_eq!(
+            format_numbers(&(1..=12).collect::<Vec<_>>()),
+            "1, 2, 3, 4, 5, 6, 7, 8, 9, 10 and 2 more"
+        );
+    }
+}
@@ -0,0 +1,190 @@
+//! Check that files are encoded as UTF-8 (or another accepted encoding).
+
+use anyhow::{bail, Result};
+
+use super::{format_numbers, BuiltinContext};
+use crate::lint_message::{LintMessage, LintSeverity};
+use crate::path::AbsPath;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Encoding {
+    Ascii,
+    Utf8,
+    Utf16,
+    Latin1,
+}
+
+impl Encoding {
+    fn parse(name: &str) -> Result<Encoding> {
+        Ok(match name.to_ascii_lowercase().as_str() {
+            "ascii" => Encoding::Ascii,
+            "utf-8" | "utf8" => Encoding::Utf8,
+            "utf-16" | "utf16" => Encoding::Utf16,
+            "latin-1" | "latin1" | "iso-8859-1" => Encoding::Latin1,
+            _ => bail!(
+                "Unknown encoding '{}', expected one of ascii, utf-8, utf-16, latin-1",
+                name
+            ),
+        })
+    }
+
+    /// Decode `bytes`, or return None if they aren't valid in this encoding.
+    fn decode(self, bytes: &[u8]) -> Option<String> {
+        match self {
+            Encoding::Ascii => bytes
+                .is_ascii()
+                .then(|| String::from_utf8_lossy(bytes).into_owned()),
+            Encoding::Utf8 => String::from_utf8(bytes.to_vec()).ok(),
+            Encoding::Utf16 => decode_utf16(bytes),
+            Encoding::Latin1 => Some(bytes.iter().map(|&b| b as char).collect()),
+        }
+    }
+}
+
+/// Decode UTF-16 with a byte order mark; without one we can't tell UTF-16
+/// apart from arbitrary binary data.
+fn decode_utf16(bytes: &[u8]) -> Option<String> {
+    let (rest, big_endian) = match bytes {
+        [0xFF, 0xFE, rest @ ..] => (rest, false),
+        [0xFE, 0xFF, rest @ ..] => (rest, true),
+        _ => return None,
+    };
+    if rest.len() % 2 != 0 {
+        return None;
+    }
+    let units = rest.chunks_exact(2).map(|c| {
+        if big_endian {
+            u16::from_be_bytes([c[0], c[1]])
+        } else {
+            u16::from_le_bytes([c[0], c[1]])
+        }
+    });
+    char::decode_utf16(units)
+        .collect::<Result<String, _>>()
+        .ok()
+}
+
+/// Byte offsets of every invalid UTF-8 sequence in `bytes`.
+fn invalid_offsets(bytes: &[u8]) -> Vec<usize> {
+    let mut offsets = Vec::new();
+    let mut start = 0;
+    while let Err(e) = std::str::from_utf8(&bytes[start..]) {
+        let offset = start + e.valid_up_to();
+        offsets.push(offset);
+        match e.error_len() {
+            Some(len) => start = offset + len,
+            // The file ends in the middle of a sequence.
+            None => break,
+        }
+    }
+    offsets
+}
+
+fn line_of(bytes: &[u8], offset: usize) -> usize {
+    bytes[..offset].iter().filter(|&&b| b == b'\n').count() + 1
+}
+
+pub fn utf8(context: &BuiltinContext, path: &AbsPath) -> Result<Vec<LintMessage>> {
+    let encodings = context
+        .option("encodings")
+        .unwrap_or("utf-8")
+        .split(',')
+        .map(Encoding::parse)
+        .collect::<Result<Vec<_>>>()?;
+    let transcode_from = context
+        .option("transcode_from")
+        .map(Encoding::parse)
+        .transpose()?;
+
+    let bytes = std::fs::read(path)?;
+    if encodings
+        .iter()
+        .any(|encoding| encoding.decode(&bytes).is_some())
+    {
+        return Ok(Vec::new());
+    }
+    // Anything with NUL bytes that isn't UTF-16 is binary.
+    if bytes.contains(&0) && decode_utf16(&bytes).is_none() {
+        return Ok(Vec::new());
+    }
+
+    let mut message = if encodings == [Encoding::Ascii] {
+        let offset = bytes.iter().position(|b| !b.is_ascii()).unwrap_or(0);
+        context.message(
+            path,
+            Some(line_of(&bytes, offset)),
+            LintSeverity::Error,
+            "non-ASCII text",
+            format!("Non-ASCII byte at offset {}.", offset),
+        )
+    } else {
+        let offsets = invalid_offsets(&bytes);
+        // Valid UTF-8 that wasn't accepted, e.g. `encodings=ascii,utf-16`.
+        let (line, description) = match offsets.first() {
+            Some(&first) => (
+                line_of(&bytes, first),
+                format!("Invalid UTF-8 at byte offset {}.", format_numbers(&offsets)),
+            ),
+            None => (1, "File is not in an accepted encoding.".to_string()),
+        };
+        context.message(
+            path,
+            Some(line),
+            LintSeverity::Error,
+            "invalid encoding",
+            description,
+        )
+    };
+
+    if let Some(replacement) = transcode_from.and_then(|encoding| encoding.decode(&bytes)) {
+        message.original = Some(String::from_utf8_lossy(&bytes).into_owned());
+        message.replacement = Some(replacement);
+    }
+    Ok(vec![message])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::builtin::check_contents;
+
+    fn check(options: &[(&str, &str)], contents: &[u8]) -> Vec<LintMessage> {
+        check_contents(utf8, options, contents)
+    }
+
+    #[test]
+    fn invalid_sequences_are_reported() {
+        assert!(check(&[], "héllo\n".as_bytes()).is_empty());
+
+        let messages = check(&[], b"ok\ncaf\xe9\n\xff\n");
+        assert_eq!(messages.len(), 1);
+        assert_eq!(messages[0].line, Some(2));
+        assert_eq!(
+            messages[0].description.as_deref(),
+            Some("Invalid UTF-8 at byte offset 6 and 8.")
+        );
+        assert_eq!(messages[0].replacement, None);
+
+        // Binary files are skipped.
+        assert!(check(&[], b"\x00\xff").is_empty());
+    }
+
+    #[test]
+    fn encodings_are_configurable() {
+        let utf16: Vec<u8> = [0xFF, 0xFE]
+            .into_iter()
+            .chain("hi\n".encode_utf16().flat_map(u16::to_le_bytes))
+            .collect();
+        assert_eq!(check(&[], &utf16).len(), 1);
+        assert!(check(&[("encodings", "utf-8,utf-16")], &utf16).is_empty());
+
+        let messages = check(&[("encodings", "ascii")], "a\nhé\n".as_bytes());
+        assert_eq!(messages[0].line, Some(2));
+    }
+
+    #[test]
+    fn latin1_is_transcoded() {
+        let messages = check(&[("transcode_from", "latin-1")], b"caf\xe9\n");
+        assert_eq!(messages[0].replacement.as_deref(), Some("café\n"));
+    }
+}
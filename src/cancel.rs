@@ -0,0 +1,241 @@
+//! Graceful handling of Ctrl-C (SIGINT) and SIGTERM.
+//!
+//! Signals are forwarded to a dedicated thread, so that handling them can do
+//! real work. On the first signal, every running linter process (and
+//! everything it started) is terminated. If a lint run is in progress, it
+//! winds down and reports the results it has; otherwise lintrunner exits
+//! immediately. A second signal always exits immediately.
+//!
+//! On Windows, Ctrl-C and Ctrl-Break are handled the same way, and reported
+//! as SIGINT.
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicI32, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+
+use crate::process_tree::ProcessTree;
+
+/// The signal that cancelled this run, or 0.
+static SIGNAL: AtomicI32 = AtomicI32::new(0);
+/// How many cancellable scopes are active.
+static ACTIVE_SCOPES: AtomicUsize = AtomicUsize::new(0);
+/// Linter processes that are currently running.
+static CHILDREN: Mutex<Option<HashMap<u32, Arc<ProcessTree>>>> = Mutex::new(None);
+
+/// Whether lintrunner has been asked to stop.
+pub fn is_cancelled() -> bool {
+    SIGNAL.load(Ordering::SeqCst) != 0
+}
+
+/// The exit code to use if lintrunner was cancelled: 128 plus the signal
+/// number, following shell conventions (130 for Ctrl-C).
+pub fn exit_code() -> Option<i32> {
+    match SIGNAL.load(Ordering::SeqCst) {
+        0 => None,
+        signal => Some(128 + signal),
+    }
+}
+
+/// While this guard is alive, a signal cancels the current operation
+/// instead of exiting right away.
+pub struct CancelScope(());
+
+pub fn cancellable() -> CancelScope {
+    ACTIVE_SCOPES.fetch_add(1, Ordering::SeqCst);
+    CancelScope(())
+}
+
+impl Drop for CancelScope {
+    fn drop(&mut self) {
+        ACTIVE_SCOPES.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// Keeps a linter's process tree registered for termination on
+/// cancellation, until dropped.
+pub struct ChildGuard {
+    pid: u32,
+}
+
+/// Register a running linter's process tree, so that it is terminated if
+/// lintrunner is cancelled.
+pub fn register_child(tree: ProcessTree) -> ChildGuard {
+    let pid = tree.pid();
+    let tree = Arc::new(tree);
+    CHILDREN
+        .lock()
+        .unwrap()
+        .get_or_insert_with(HashMap::new)
+        .insert(pid, Arc::clone(&tree));
+    // We may have been cancelled just before the process was registered.
+    if is_cancelled() {
+        tree.terminate();
+    }
+    ChildGuard { pid }
+}
+
+impl Drop for ChildGuard {
+    fn drop(&mut self) {
+        if let Some(children) = CHILDREN.lock().unwrap().as_mut() {
+            children.remove(&self.pid);
+        }
+    }
+}
+
+fn registered_children() -> Vec<Arc<ProcessTree>> {
+    CHILDREN
+        .lock()
+        .unwrap()
+        .iter()
+        .flat_map(|children| children.values().cloned())
+        .collect()
+}
+
+/// Terminate all registered process trees, and kill any that are still
+/// around after a grace period.
+fn terminate_children() {
+    for tree in registered_children() {
+        tree.terminate();
+    }
+    let deadline = std::time::Instant::now() + std::time::Duration::from_secs(2);
+    while std::time::Instant::now() < deadline {
+        if registered_children().is_empty() {
+            return;
+        }
+        std::thread::sleep(std::time::Duration::from_millis(50));
+    }
+    for tree in registered_children() {
+        tree.kill();
+    }
+}
+
+fn exit_now(code: i32) -> ! {
+    // Progress bars hide the cursor; don't leave the terminal without one.
+    let _ = crate::streams::diagnostics().term().show_cursor();
+    std::process::exit(code);
+}
+
+/// The write end of the pipe that forwards signals to the handling thread.
+#[cfg(unix)]
+static SIGNAL_PIPE: AtomicI32 = AtomicI32::new(-1);
+
+#[cfg(unix)]
+extern "C" fn forward_signal(signal: libc::c_int) {
+    let byte = signal as u8;
+    // SAFETY: write is async-signal-safe, and `byte` outlives the call.
+    unsafe {
+        libc::write(
+            SIGNAL_PIPE.load(Ordering::SeqCst),
+            &byte as *const u8 as *const libc::c_void,
+            1,
+        );
+    }
+}
+
+fn handle_signal(signal: i32) {
+    let first = SIGNAL
+        .compare_exchange(0, signal, Ordering::SeqCst, Ordering::SeqCst)
+        .is_ok();
+    if !first || ACTIVE_SCOPES.load(Ordering::SeqCst) == 0 {
+        terminate_children();
+        exit_now(128 + signal);
+    }
+    log::warn!("Interrupted, stopping linters. Press Ctrl-C again to exit immediately.");
+    std::thread::spawn(terminate_children);
+}
+
+/// Start handling SIGINT and SIGTERM.
+#[cfg(unix)]
+pub fn install_handler() -> anyhow::Result<()> {
+    use std::io::Read;
+    use std::os::unix::io::FromRawFd;
+
+    // The signal handler itself can only do async-signal-safe things, so it
+    // just forwards the signal through a pipe to a thread that handles it.
+    let mut fds = [0; 2];
+    // SAFETY: `fds` has room for the two file descriptors.
+    if unsafe { libc::pipe(fds.as_mut_ptr()) } != 0 {
+        return Err(std::io::Error::last_os_error().into());
+    }
+    for fd in fds {
+        // SAFETY: `fd` was just created by pipe.
+        unsafe {
+            libc::fcntl(fd, libc::F_SETFD, libc::FD_CLOEXEC);
+        }
+    }
+    SIGNAL_PIPE.store(fds[1], Ordering::SeqCst);
+    // SAFETY: we own the read end of the pipe.
+    let mut reader = unsafe { std::fs::File::from_raw_fd(fds[0]) };
+    std::thread::spawn(move || {
+        let mut byte = [0];
+        while reader.read_exact(&mut byte).is_ok() {
+            handle_signal(byte[0] as i32);
+        }
+    });
+
+    for signal in [libc::SIGINT, libc::SIGTERM] {
+        // SAFETY: `forward_signal` only calls async-signal-safe functions.
+        unsafe {
+            libc::signal(
+                signal,
+                forward_signal as extern "C" fn(libc::c_int) as libc::sighandler_t,
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Start handling Ctrl-C and Ctrl-Break. These are reported like SIGINT.
+#[cfg(windows)]
+pub fn install_handler() -> anyhow::Result<()> {
+    #[link(name = "kernel32")]
+    extern "system" {
+        fn SetConsoleCtrlHandler(
+            handler: Option<unsafe extern "system" fn(u32) -> i32>,
+            add: i32,
+        ) -> i32;
+    }
+
+    // Windows runs console control handlers on a new thread, so there's no
+    // need to forward anything.
+    unsafe extern "system" fn handler(_event: u32) -> i32 {
+        handle_signal(2);
+        1
+    }
+
+    // SAFETY: `handler` has the signature SetConsoleCtrlHandler expects.
+    if unsafe { SetConsoleCtrlHandler(Some(handler), 1) } == 0 {
+        return Err(std::io::Error::last_os_error().into());
+    }
+    Ok(())
+}
+
+#[cfg(not(any(unix, windows)))]
+pub fn install_handler() -> anyhow::Result<()> {
+    Ok(())
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use std::os::unix::process::ExitStatusExt;
+
+    #[test]
+    fn registered_children_are_terminated() {
+        let mut command = std::process::Command::new("sleep");
+        command.arg("30");
+        ProcessTree::prepare(&mut command);
+        let mut child = command.spawn().unwrap();
+        let pid = child.id();
+        let guard = register_child(ProcessTree::new(&child));
+        let waiter = std::thread::spawn(move || {
+            let status = child.wait().unwrap();
+            drop(guard);
+            status
+        });
+
+        terminate_children();
+        assert_eq!(waiter.join().unwrap().signal(), Some(libc::SIGTERM));
+        assert!(registered_children().iter().all(|tree| tree.pid() != pid));
+    }
+}
@@ -0,0 +1,86 @@
+//! Detecting that lintrunner runs in CI.
+//!
+//! CI log viewers aren't terminals, even when they claim to be, and some of
+//! them turn specially formatted lines into annotations. So in a CI system
+//! lintrunner recognizes, it prints plain progress lines instead of spinners,
+//! and reports issues in the system's own annotation format if it has one.
+//! `--progress` and `--output` (or `output` in the user config) override
+//! both.
+
+use crate::RenderOpt;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CiProvider {
+    GithubActions,
+    Gitlab,
+    Buildkite,
+    AzurePipelines,
+}
+
+impl CiProvider {
+    /// The CI system lintrunner is running in, if it is one it recognizes.
+    pub fn detect() -> Option<CiProvider> {
+        detect_from(|name| std::env::var(name).ok())
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            CiProvider::GithubActions => "GitHub Actions",
+            CiProvider::Gitlab => "GitLab CI",
+            CiProvider::Buildkite => "Buildkite",
+            CiProvider::AzurePipelines => "Azure Pipelines",
+        }
+    }
+
+    /// The output format that annotates the build with lint issues, for
+    /// systems that pick annotations out of the log. Buildkite and GitLab
+    /// need a separate upload step instead, so they get the default output.
+    pub fn annotation_output(self) -> Option<RenderOpt> {
+        match self {
+            CiProvider::GithubActions => Some(RenderOpt::Github),
+            CiProvider::AzurePipelines => Some(RenderOpt::Azure),
+            CiProvider::Gitlab | CiProvider::Buildkite => None,
+        }
+    }
+}
+
+fn detect_from(var: impl Fn(&str) -> Option<String>) -> Option<CiProvider> {
+    let is_set = |name| var(name).is_some_and(|value| !value.is_empty() && value != "false");
+    if is_set("GITHUB_ACTIONS") {
+        Some(CiProvider::GithubActions)
+    } else if is_set("GITLAB_CI") {
+        Some(CiProvider::Gitlab)
+    } else if is_set("BUILDKITE") {
+        Some(CiProvider::Buildkite)
+    } else if is_set("TF_BUILD") {
+        Some(CiProvider::AzurePipelines)
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ci_is_detected_from_env() {
+        let detect = |vars: &[(&str, &str)]| {
+            detect_from(|name| {
+                vars.iter()
+                    .find(|(var, _)| *var == name)
+                    .map(|(_, value)| value.to_string())
+            })
+        };
+        assert_eq!(detect(&[]), None);
+        assert_eq!(
+            detect(&[("GITHUB_ACTIONS", "true")]),
+            Some(CiProvider::GithubActions)
+        );
+        assert_eq!(
+            detect(&[("TF_BUILD", "True")]),
+            Some(CiProvider::AzurePipelines)
+        );
+        assert_eq!(detect(&[("BUILDKITE", "false"), ("GITLAB_CI", "")]), None);
+    }
+}
@@ -0,0 +1,178 @@
+//! `lintrunner clean`: removing the state lintrunner keeps for a repo.
+//!
+//! When a linter's environment gets corrupted, e.g. a half-installed venv or
+//! a stale cache, the quickest fix is usually to throw the state away and
+//! start over. This removes it without having to know where the data
+//! directory is or what is inside it.
+
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use clap::ArgEnum;
+use console::style;
+use log::warn;
+
+use crate::{linter::Linter, persistent_data::PersistentDataStore, streams};
+
+/// What kind of state to remove.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ArgEnum)]
+pub enum CleanTarget {
+    /// What `lintrunner init` installed and recorded, like the linters'
+    /// `{{DATA_DIR}}`s, so that the next run asks for `init` again.
+    Init,
+    /// Linters' `cache_dirs` and recorded linter durations.
+    Cache,
+    /// Logs and results of past runs, used by `rage`, `stats` and `query`.
+    Logs,
+    /// All of the above, except for linters' `cache_dirs`. Those live in the
+    /// repo rather than in lintrunner's data directory, so they are only
+    /// removed when asked for explicitly.
+    All,
+}
+
+/// Remove the `target` kind of state for `linters`, printing each path
+/// removed.
+pub fn do_clean(
+    linters: &[Linter],
+    persistent_data_store: &PersistentDataStore,
+    target: CleanTarget,
+) -> Result<i32> {
+    let paths = clean_paths(linters, persistent_data_store, target)?;
+
+    let stderr = streams::diagnostics().term();
+    for path in &paths {
+        remove(path)?;
+        stderr.write_line(&format!("Removed {}", style(path.display()).bold()))?;
+    }
+    if paths.is_empty() {
+        stderr.write_line("Nothing to clean.")?;
+    }
+    Ok(0)
+}
+
+fn clean_paths(
+    linters: &[Linter],
+    persistent_data_store: &PersistentDataStore,
+    target: CleanTarget,
+) -> Result<Vec<PathBuf>> {
+    let mut paths = persistent_data_store.state_paths(target)?;
+    if target == CleanTarget::Cache {
+        for linter in linters {
+            // Unwrap is fine because the config path must be a file.
+            let config_dir = linter.config_path.parent().unwrap();
+            for dir in &linter.cache_dirs {
+                if !dir.exists() || paths.contains(dir) {
+                    continue;
+                }
+                if !is_inside(dir, config_dir)? {
+                    warn!(
+                        "Not removing cache dir '{}' of linter '{}', as it is not inside '{}'",
+                        dir.display(),
+                        linter.code,
+                        config_dir.display()
+                    );
+                    continue;
+                }
+                paths.push(dir.clone());
+            }
+        }
+    }
+    Ok(paths)
+}
+
+/// Whether `path` is strictly inside `dir`, once symlinks and `..` are
+/// resolved. `cache_dirs` can name any path, like `.` or `~/.cache`, and
+/// those must not be removed.
+fn is_inside(path: &Path, dir: &Path) -> Result<bool> {
+    let path = path.canonicalize()?;
+    let dir = dir.canonicalize()?;
+    Ok(path != dir && path.starts_with(&dir))
+}
+
+fn remove(path: &Path) -> Result<()> {
+    if path.is_dir() {
+        std::fs::remove_dir_all(path)
+    } else {
+        std::fs::remove_file(path)
+    }
+    .with_context(|| format!("Failed to remove '{}'", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        lint_config::{get_linters_from_config, LintRunnerConfig},
+        path::AbsPath,
+        persistent_data::{ExitInfo, RunInfo},
+    };
+    use std::convert::TryFrom;
+
+    fn run_info(timestamp: &str) -> RunInfo {
+        RunInfo {
+            args: vec!["lintrunner".to_string()],
+            timestamp: timestamp.to_string(),
+        }
+    }
+
+    #[test]
+    fn only_the_chosen_state_is_removed() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        std::fs::write(dir.path().join(".lintrunner.toml"), "")?;
+        let config_path = AbsPath::try_from(dir.path().join(".lintrunner.toml"))?;
+        let data_path = dir.path().join("data");
+
+        let old_run = PersistentDataStore::new(&config_path, Some(&data_path), run_info("0"))?;
+        old_run.write_run_info(ExitInfo { code: 0, err: None })?;
+        old_run.linter_data_dir("MYPY")?;
+        old_run.update_last_init(&config_path)?;
+
+        let store = PersistentDataStore::new(&config_path, Some(&data_path), run_info("1"))?;
+        assert_eq!(
+            clean_paths(&[], &store, CleanTarget::Cache)?,
+            Vec::<PathBuf>::new()
+        );
+
+        do_clean(&[], &store, CleanTarget::Init)?;
+        assert!(store.last_init()?.is_none());
+        assert_eq!(store.past_runs()?.len(), 1);
+
+        do_clean(&[], &store, CleanTarget::All)?;
+        assert!(store.past_runs()?.is_empty());
+        // The current run can still write its log.
+        std::fs::write(store.log_file(), "")?;
+        Ok(())
+    }
+
+    #[test]
+    fn only_cache_dirs_inside_the_repo_are_removed() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let repo = dir.path().join("repo");
+        std::fs::create_dir_all(repo.join(".mypy_cache"))?;
+        let config_path = repo.join(".lintrunner.toml");
+        let config = "\
+            [[linter]]
+            code = 'MYPY'
+            include_patterns = ['**']
+            command = ['mypy']
+            cache_dirs = ['.mypy_cache', '.', '..', '/']
+            ";
+        std::fs::write(&config_path, config)?;
+        let config_path = AbsPath::try_from(config_path)?;
+        let linters = get_linters_from_config(
+            &LintRunnerConfig::new_from_string(config)?.linters,
+            None,
+            None,
+            &config_path,
+        )?;
+        let store =
+            PersistentDataStore::new(&config_path, Some(&dir.path().join("data")), run_info("0"))?;
+
+        assert_eq!(
+            clean_paths(&linters, &store, CleanTarget::Cache)?,
+            vec![repo.join(".mypy_cache")]
+        );
+        assert!(clean_paths(&linters, &store, CleanTarget::All)?.is_empty());
+        Ok(())
+    }
+}
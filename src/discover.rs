@@ -0,0 +1,444 @@
+//! `lintrunner discover`: propose linters for a repo.
+//!
+//! Adopting lintrunner means writing a config, and usually adapters, before
+//! seeing any results. To make that first step smaller, `discover` looks at
+//! the repo's files and the configs of tools it already uses (`.flake8`,
+//! `.clang-format`, `[tool.black]` in `pyproject.toml`, ...) and proposes a
+//! config with matching linters from a small built-in registry.
+
+use std::collections::{BTreeMap, HashSet};
+use std::io::Write;
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use console::style;
+
+use crate::{
+    git::get_tracked_files_in, lint_config::LintRunnerConfig, parallel::walk_dir, path::AbsPath,
+    streams,
+};
+
+/// Runs one of the adapters from the `lintrunner-adapters` Python package.
+macro_rules! adapter {
+    ($name:literal) => {
+        &[
+            "python3",
+            "-m",
+            "lintrunner_adapters",
+            "run",
+            $name,
+            "--",
+            "@{{PATHSFILE}}",
+        ]
+    };
+}
+
+/// Installs `package` with the `pip_init` adapter from `lintrunner-adapters`.
+macro_rules! pip_init {
+    ($package:literal) => {
+        &[
+            "python3",
+            "-m",
+            "lintrunner_adapters",
+            "run",
+            "pip_init",
+            "--dry-run={{DRYRUN}}",
+            $package,
+        ]
+    };
+}
+
+/// A linter in the registry, and how to tell whether a repo would want it.
+struct Candidate {
+    code: &'static str,
+    /// Files, relative to the root of the repo, whose presence means the repo
+    /// uses the tool.
+    configs: &'static [&'static str],
+    /// Files the tool can be configured in along with other tools, and the
+    /// text that shows it is, e.g. `[tool.black]` in `pyproject.toml`.
+    config_sections: &'static [(&'static str, &'static str)],
+    /// Extensions of the files the tool lints. If the tool has no config to
+    /// look for, having such files is enough to propose it.
+    extensions: &'static [&'static str],
+    include_patterns: &'static [&'static str],
+    /// `None` if there is no ready-made adapter for the tool.
+    command: Option<&'static [&'static str]>,
+    init_command: Option<&'static [&'static str]>,
+    is_formatter: bool,
+}
+
+const PYTHON: &[&str] = &["py", "pyi"];
+const CPP: &[&str] = &["c", "cc", "cpp", "cxx", "h", "hpp"];
+const JS: &[&str] = &["js", "jsx", "ts", "tsx"];
+
+const REGISTRY: &[Candidate] = &[
+    Candidate {
+        code: "WHITESPACE",
+        configs: &[],
+        config_sections: &[],
+        extensions: &[],
+        include_patterns: &["**"],
+        command: Some(&["builtin:whitespace"]),
+        init_command: None,
+        is_formatter: false,
+    },
+    Candidate {
+        code: "NEWLINE",
+        configs: &[],
+        config_sections: &[],
+        extensions: &[],
+        include_patterns: &["**"],
+        command: Some(&["builtin:newline-eof"]),
+        init_command: None,
+        is_formatter: false,
+    },
+    Candidate {
+        code: "FLAKE8",
+        configs: &[".flake8"],
+        config_sections: &[("setup.cfg", "[flake8]"), ("tox.ini", "[flake8]")],
+        extensions: PYTHON,
+        include_patterns: &["**/*.py", "**/*.pyi"],
+        command: Some(adapter!("flake8_linter")),
+        init_command: Some(pip_init!("flake8")),
+        is_formatter: false,
+    },
+    Candidate {
+        code: "RUFF",
+        configs: &["ruff.toml", ".ruff.toml"],
+        config_sections: &[("pyproject.toml", "[tool.ruff")],
+        extensions: PYTHON,
+        include_patterns: &["**/*.py", "**/*.pyi"],
+        command: Some(adapter!("ruff_linter")),
+        init_command: Some(pip_init!("ruff")),
+        is_formatter: false,
+    },
+    Candidate {
+        code: "MYPY",
+        configs: &["mypy.ini", ".mypy.ini"],
+        config_sections: &[("pyproject.toml", "[tool.mypy]"), ("setup.cfg", "[mypy]")],
+        extensions: PYTHON,
+        include_patterns: &["**/*.py", "**/*.pyi"],
+        command: Some(adapter!("mypy_linter")),
+        init_command: Some(pip_init!("mypy")),
+        is_formatter: false,
+    },
+    Candidate {
+        code: "BLACK",
+        configs: &[],
+        config_sections: &[("pyproject.toml", "[tool.black]")],
+        extensions: PYTHON,
+        include_patterns: &["**/*.py", "**/*.pyi"],
+        command: Some(adapter!("black_linter")),
+        init_command: Some(pip_init!("black")),
+        is_formatter: true,
+    },
+    Candidate {
+        code: "CLANGFORMAT",
+        configs: &[".clang-format", "_clang-format"],
+        config_sections: &[],
+        extensions: CPP,
+        include_patterns: &[
+            "**/*.c", "**/*.cc", "**/*.cpp", "**/*.cxx", "**/*.h", "**/*.hpp",
+        ],
+        command: Some(adapter!("clangformat_linter")),
+        init_command: Some(pip_init!("clang-format")),
+        is_formatter: true,
+    },
+    Candidate {
+        code: "SHELLCHECK",
+        configs: &[],
+        config_sections: &[],
+        extensions: &["sh", "bash"],
+        include_patterns: &["**/*.sh", "**/*.bash"],
+        command: Some(adapter!("shellcheck_linter")),
+        init_command: Some(pip_init!("shellcheck-py")),
+        is_formatter: false,
+    },
+    Candidate {
+        code: "RUSTFMT",
+        configs: &["rustfmt.toml", ".rustfmt.toml", "Cargo.toml"],
+        config_sections: &[],
+        extensions: &["rs"],
+        include_patterns: &["**/*.rs"],
+        command: Some(adapter!("rustfmt_linter")),
+        init_command: None,
+        is_formatter: true,
+    },
+    Candidate {
+        code: "ESLINT",
+        configs: &[
+            ".eslintrc",
+            ".eslintrc.js",
+            ".eslintrc.cjs",
+            ".eslintrc.json",
+            ".eslintrc.yml",
+            ".eslintrc.yaml",
+            "eslint.config.js",
+            "eslint.config.mjs",
+        ],
+        config_sections: &[("package.json", "\"eslint\"")],
+        extensions: JS,
+        include_patterns: &["**/*.js", "**/*.jsx", "**/*.ts", "**/*.tsx"],
+        command: None,
+        init_command: None,
+        is_formatter: false,
+    },
+    Candidate {
+        code: "PRETTIER",
+        configs: &[
+            ".prettierrc",
+            ".prettierrc.json",
+            ".prettierrc.yml",
+            ".prettierrc.yaml",
+            ".prettierrc.js",
+            "prettier.config.js",
+        ],
+        config_sections: &[("package.json", "\"prettier\"")],
+        extensions: JS,
+        include_patterns: &["**/*.js", "**/*.jsx", "**/*.ts", "**/*.tsx"],
+        command: None,
+        init_command: None,
+        is_formatter: true,
+    },
+];
+
+impl Candidate {
+    /// Why the repo at `root`, with `extension_counts` files of each
+    /// extension, would want this linter, or `None` if it wouldn't.
+    fn reason(&self, root: &Path, extension_counts: &BTreeMap<String, usize>) -> Option<String> {
+        let num_files: usize = self
+            .extensions
+            .iter()
+            .filter_map(|ext| extension_counts.get(*ext))
+            .sum();
+        if !self.extensions.is_empty() && num_files == 0 {
+            return None;
+        }
+        if let Some(config) = self.configs.iter().find(|c| root.join(c).is_file()) {
+            return Some(format!("found {}", config));
+        }
+        for (file, needle) in self.config_sections {
+            let contents = std::fs::read_to_string(root.join(file)).unwrap_or_default();
+            if contents.contains(needle) {
+                return Some(format!("found {} in {}", needle, file));
+            }
+        }
+        if !self.configs.is_empty() || !self.config_sections.is_empty() {
+            return None;
+        }
+        if self.extensions.is_empty() {
+            return Some("useful for any repo".to_string());
+        }
+        let extensions = self
+            .extensions
+            .iter()
+            .map(|ext| format!("*.{}", ext))
+            .collect::<Vec<_>>()
+            .join(", ");
+        Some(format!(
+            "found {} {} ({})",
+            num_files,
+            if num_files == 1 { "file" } else { "files" },
+            extensions
+        ))
+    }
+
+    /// The config for this linter, preceded by a comment saying why it was
+    /// proposed. Linters without an adapter are only described in comments.
+    fn to_toml(&self, reason: &str) -> String {
+        let Some(command) = self.command else {
+            return format!(
+                "# {}: {}. There is no ready-made adapter for it;\n\
+                 # {}\n",
+                self.code,
+                reason,
+                self.new_linter_hint()
+            );
+        };
+        let mut toml = format!(
+            "# {}.\n[[linter]]\ncode = '{}'\ninclude_patterns = {}\ncommand = {}\n",
+            capitalize(reason),
+            self.code,
+            toml_list(self.include_patterns),
+            toml_list(command),
+        );
+        if let Some(init_command) = self.init_command {
+            toml.push_str(&format!("init_command = {}\n", toml_list(init_command)));
+        }
+        if self.is_formatter {
+            toml.push_str("is_formatter = true\n");
+        }
+        toml
+    }
+}
+
+impl Candidate {
+    /// How to get started on an adapter for a linter without one.
+    fn new_linter_hint(&self) -> String {
+        format!(
+            "`lintrunner new-linter --name {}` writes one to fill in.",
+            self.code
+        )
+    }
+}
+
+fn capitalize(s: &str) -> String {
+    let mut chars = s.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+fn toml_list(items: &[&str]) -> String {
+    let items: Vec<String> = items.iter().map(|item| format!("'{}'", item)).collect();
+    format!("[{}]", items.join(", "))
+}
+
+/// How many files of each extension there are under `root`: the files git
+/// tracks, or all of them outside of a git repo.
+fn count_extensions(root: &AbsPath) -> Result<BTreeMap<String, usize>> {
+    let files = match get_tracked_files_in(root)? {
+        Some(files) => files,
+        None => walk_dir(root)?,
+    };
+    let mut counts = BTreeMap::new();
+    for file in files {
+        if let Some(ext) = file.extension() {
+            *counts.entry(ext.to_string_lossy().to_string()).or_default() += 1;
+        }
+    }
+    Ok(counts)
+}
+
+/// The linters in the registry that the config `contents` already has, or
+/// already has the hint to write an adapter for.
+fn already_proposed(contents: &str) -> Result<HashSet<&'static str>> {
+    let config = LintRunnerConfig::new_from_string(contents)?;
+    Ok(REGISTRY
+        .iter()
+        .filter(|candidate| {
+            config.linters.iter().any(|l| l.code == candidate.code)
+                || contents.contains(&candidate.new_linter_hint())
+        })
+        .map(|candidate| candidate.code)
+        .collect())
+}
+
+/// The config proposed for the repo at `root`, leaving out the linters in
+/// `skip`.
+fn propose(
+    root: &Path,
+    extension_counts: &BTreeMap<String, usize>,
+    skip: &HashSet<&str>,
+) -> Vec<String> {
+    REGISTRY
+        .iter()
+        .filter(|candidate| !skip.contains(candidate.code))
+        .filter_map(|candidate| {
+            let reason = candidate.reason(root, extension_counts)?;
+            Some(candidate.to_toml(&reason))
+        })
+        .collect()
+}
+
+/// Print the config proposed for the repo the config at `config_path` is in,
+/// or with `write`, add it to the config.
+pub fn do_discover(config_path: &Path, write: bool) -> Result<i32> {
+    let root = match config_path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => AbsPath::try_from(dir)?,
+        _ => AbsPath::try_from(std::env::current_dir()?)?,
+    };
+    let existing = if config_path.exists() {
+        let contents = std::fs::read_to_string(config_path)
+            .with_context(|| format!("Failed to read config at '{}'", config_path.display()))?;
+        Some(contents)
+    } else {
+        None
+    };
+    let skip = match &existing {
+        Some(contents) => already_proposed(contents)?,
+        None => HashSet::new(),
+    };
+
+    let proposals = propose(&root, &count_extensions(&root)?, &skip);
+    let stderr = streams::diagnostics().term();
+    if proposals.is_empty() {
+        stderr.write_line("Found no linters to add.")?;
+        return Ok(0);
+    }
+
+    let mut toml = String::new();
+    if existing.is_none() {
+        toml.push_str(
+            "# Proposed by `lintrunner discover`. Commands that aren't builtin use the\n\
+             # adapters from the lintrunner-adapters Python package, which needs to be\n\
+             # installed (`pip install lintrunner-adapters`) before `lintrunner init`.\n\n",
+        );
+    }
+    toml.push_str(&proposals.join("\n"));
+
+    if !write {
+        streams::results().write_all(toml.as_bytes())?;
+        return Ok(0);
+    }
+
+    let mut config_file = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(config_path)?;
+    if let Some(contents) = &existing {
+        if !contents.is_empty() {
+            if !contents.ends_with('\n') {
+                writeln!(config_file)?;
+            }
+            writeln!(config_file)?;
+        }
+    }
+    write!(config_file, "{}", toml)?;
+    stderr.write_line(&format!(
+        "{} Added the proposed linters to {}. Review them, then run `lintrunner init`.",
+        style("Done!").green().bold(),
+        config_path.display()
+    ))?;
+    Ok(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn linters_are_proposed_for_the_tools_in_use() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        std::fs::write(dir.path().join(".flake8"), "[flake8]\n")?;
+        std::fs::write(dir.path().join("pyproject.toml"), "[tool.black]\n")?;
+        std::fs::write(dir.path().join("package.json"), "{\"eslint\": {}}\n")?;
+        // A config for a tool whose files the repo doesn't have.
+        std::fs::write(dir.path().join(".clang-format"), "")?;
+        let counts = BTreeMap::from([
+            ("py".to_string(), 3),
+            ("js".to_string(), 1),
+            ("sh".to_string(), 1),
+        ]);
+
+        let proposals = propose(dir.path(), &counts, &HashSet::new());
+        let toml = proposals.join("\n");
+        assert!(toml.contains("# Found .flake8.\n[[linter]]\ncode = 'FLAKE8'"));
+        assert!(toml.contains("# Found [tool.black] in pyproject.toml."));
+        assert!(toml.contains("# ESLINT: found \"eslint\" in package.json."));
+        assert!(!toml.contains("CLANGFORMAT"));
+        assert!(toml.contains("# Found 1 file (*.sh, *.bash).\n[[linter]]\ncode = 'SHELLCHECK'"));
+        let config = LintRunnerConfig::new_from_string(&toml)?;
+        let codes: Vec<_> = config.linters.iter().map(|l| l.code.as_str()).collect();
+        assert_eq!(
+            codes,
+            ["WHITESPACE", "NEWLINE", "FLAKE8", "BLACK", "SHELLCHECK"]
+        );
+
+        // Linters that are already configured aren't proposed again.
+        let skip = already_proposed(&toml)?;
+        assert!(propose(dir.path(), &counts, &skip).is_empty());
+        Ok(())
+    }
+}
@@ -0,0 +1,184 @@
+//! Finding the files that make a linter slow.
+//!
+//! Timing every file on its own would mean starting the linter once per file,
+//! which is both slow and misleading for linters with a high startup cost.
+//! Instead, a linter's files are split into a few batches, and each batch is
+//! timed. Batches that take much longer per file than the typical batch are
+//! split in half and timed again, until the files responsible are isolated.
+//! Files in the other batches are attributed an equal share of their batch's
+//! time.
+
+use std::time::{Duration, Instant};
+
+use anyhow::Result;
+use console::{style, Term};
+use log::debug;
+
+use crate::{
+    linter::Linter,
+    path::{get_display_path, AbsPath},
+};
+
+/// How many times slower per file than the typical batch a batch must be to
+/// be bisected.
+const OUTLIER_FACTOR: u32 = 3;
+/// Batches faster than this aren't worth bisecting, however slow per file.
+const MIN_BISECT_DURATION: Duration = Duration::from_millis(500);
+/// How many extra runs of a linter bisecting may take.
+const MAX_BISECT_RUNS: usize = 50;
+
+/// How long a linter took on a file.
+#[derive(Debug)]
+pub struct FileTime {
+    pub path: AbsPath,
+    pub duration: Duration,
+    /// Whether the file was timed on its own, rather than attributed a share
+    /// of its batch's time.
+    pub measured: bool,
+}
+
+/// Attribute the time it takes to lint `files` to the individual files, slowest
+/// first. `time` runs the linter on some of the files and returns how long it
+/// took.
+pub fn attribute_times(
+    files: &[AbsPath],
+    batches: usize,
+    mut time: impl FnMut(&[AbsPath]) -> Duration,
+) -> Vec<FileTime> {
+    if files.is_empty() {
+        return Vec::new();
+    }
+    let per_file = |batch: &[AbsPath], duration: Duration| duration / batch.len() as u32;
+    let batch_size = files.len().div_ceil(batches.max(1));
+    let mut queue: Vec<(&[AbsPath], Duration)> = files
+        .chunks(batch_size)
+        .map(|batch| (batch, time(batch)))
+        .collect();
+    let mut costs: Vec<Duration> = queue
+        .iter()
+        .map(|(batch, duration)| per_file(batch, *duration))
+        .collect();
+    costs.sort();
+    let typical = costs[costs.len() / 2];
+
+    let mut times = Vec::new();
+    let mut runs = 0;
+    while let Some((batch, duration)) = queue.pop() {
+        if batch.len() == 1 {
+            times.push(FileTime {
+                path: batch[0].clone(),
+                duration,
+                measured: true,
+            });
+            continue;
+        }
+        let outlier =
+            per_file(batch, duration) > typical * OUTLIER_FACTOR && duration >= MIN_BISECT_DURATION;
+        if outlier && runs + 2 <= MAX_BISECT_RUNS {
+            runs += 2;
+            let (first, second) = batch.split_at(batch.len() / 2);
+            queue.push((first, time(first)));
+            queue.push((second, time(second)));
+            continue;
+        }
+        let share = per_file(batch, duration);
+        times.extend(batch.iter().map(|path| FileTime {
+            path: path.clone(),
+            duration: share,
+            measured: false,
+        }));
+    }
+    times.sort_by(|a, b| {
+        b.duration
+            .cmp(&a.duration)
+            .then_with(|| a.path.cmp(&b.path))
+    });
+    times
+}
+
+/// Time each linter on the files it matches, and show its `top` slowest
+/// files. The linters run one at a time, so that they don't slow each other
+/// down.
+pub fn do_slow_files(
+    linters: &[Linter],
+    files: Vec<AbsPath>,
+    batches: usize,
+    top: usize,
+) -> Result<i32> {
+    let stdout = Term::stdout();
+    let current_dir = std::env::current_dir()?;
+    for linter in linters {
+        let matches = linter.get_matches(&files);
+        if matches.is_empty() {
+            continue;
+        }
+        let mut runs = 0;
+        let times = attribute_times(&matches, batches, |batch| {
+            runs += 1;
+            let start = Instant::now();
+            let (_, failure) = linter.run_on_matches(batch.to_vec());
+            if let Some(failure) = failure {
+                debug!(
+                    "Linter {} failed while being timed: {}",
+                    linter.code,
+                    failure.title()
+                );
+            }
+            start.elapsed()
+        });
+        stdout.write_line(&format!(
+            "{} {} ({} files, {} runs)",
+            style(">>>").bold(),
+            style(&linter.code).bold(),
+            matches.len(),
+            runs
+        ))?;
+        for time in times.iter().take(top) {
+            let marker = if time.measured { " " } else { "~" };
+            stdout.write_line(&format!(
+                "  {:>8.2}s{}  {}",
+                time.duration.as_secs_f64(),
+                marker,
+                get_display_path(&time.path.to_string_lossy(), &current_dir)
+            ))?;
+        }
+    }
+    stdout.write_line(&format!(
+        "{}",
+        style("~ marks files whose time is their share of a batch's time").dim()
+    ))?;
+    Ok(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn slow_files_are_isolated() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let mut files = Vec::new();
+        for i in 0..16 {
+            let path = dir.path().join(format!("{:02}.cpp", i));
+            std::fs::write(&path, "")?;
+            files.push(AbsPath::try_from(path)?);
+        }
+        let slow = files[11].clone();
+        let mut runs = 0;
+        let times = attribute_times(&files, 4, |batch| {
+            runs += 1;
+            let slow_files = batch.iter().filter(|path| **path == slow).count() as u32;
+            Duration::from_millis(50)
+                + Duration::from_millis(100) * batch.len() as u32
+                + Duration::from_secs(20) * slow_files
+        });
+        assert_eq!(times.len(), files.len());
+        assert_eq!(times[0].path, slow);
+        assert!(times[0].measured);
+        assert!(times[0].duration >= Duration::from_secs(20));
+        assert!(!times[times.len() - 1].measured);
+        // Four batches, then 4 -> 2 -> 1 files.
+        assert_eq!(runs, 8);
+        Ok(())
+    }
+}
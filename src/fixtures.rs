@@ -0,0 +1,200 @@
+//! `lintrunner test-linter`: check a linter's output on fixture files against
+//! recorded expectations.
+
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use console::style;
+use similar::TextDiff;
+
+use crate::{
+    lint_message::LintMessage,
+    linter::Linter,
+    parallel::walk_dir,
+    path::{path_relative_from, AbsPath},
+};
+
+const EXPECTED_SUFFIX: &str = ".expected.json";
+
+/// Make the paths in `messages` relative to `fixtures_dir`, and sort them, so
+/// that the output doesn't depend on where the fixtures live.
+fn normalize(messages: Vec<LintMessage>, fixtures_dir: &Path) -> Result<String> {
+    let current_dir = std::env::current_dir()?;
+    let mut messages: Vec<LintMessage> = messages
+        .into_iter()
+        .map(|mut message| {
+            if let Some(path) = &message.path {
+                let absolute = current_dir.join(path);
+                if let Some(relative) = path_relative_from(&absolute, fixtures_dir) {
+                    message.path = Some(relative.to_string_lossy().replace('\\', "/"));
+                }
+            }
+            message
+        })
+        .collect();
+    messages.sort_by(|a, b| {
+        (&a.path, a.line, a.char, &a.name, &a.description).cmp(&(
+            &b.path,
+            b.line,
+            b.char,
+            &b.name,
+            &b.description,
+        ))
+    });
+    let mut json = serde_json::to_string_pretty(&messages)?;
+    json.push('\n');
+    Ok(json)
+}
+
+fn print_diff(expected: &str, actual: &str) {
+    let diff = TextDiff::from_lines(expected, actual);
+    for line in diff
+        .unified_diff()
+        .header("expected", "actual")
+        .to_string()
+        .lines()
+    {
+        let line = if line.starts_with('+') {
+            style(line).green()
+        } else if line.starts_with('-') {
+            style(line).red()
+        } else {
+            style(line).dim()
+        };
+        println!("    {}", line);
+    }
+}
+
+/// Run the linter named `code` on every file in `fixtures_dir`, one at a time,
+/// and compare its lint messages to those recorded in `<file>.expected.json`.
+/// With `update`, record the current messages instead.
+pub fn do_test_linter(
+    linters: &[Linter],
+    code: &str,
+    fixtures_dir: &str,
+    update: bool,
+) -> Result<i32> {
+    let linter = linters
+        .iter()
+        .find(|linter| linter.code == code)
+        .with_context(|| format!("No linter named '{}' in the config", code))?;
+    let fixtures_dir = AbsPath::try_from(fixtures_dir)
+        .with_context(|| format!("Could not find fixtures at '{}'", fixtures_dir))?;
+    test_linter(linter, &fixtures_dir, update)
+}
+
+fn test_linter(linter: &Linter, fixtures_dir: &AbsPath, update: bool) -> Result<i32> {
+    let mut fixtures: Vec<AbsPath> = walk_dir(fixtures_dir)?
+        .into_iter()
+        .filter(|path| !path.to_string_lossy().ends_with(EXPECTED_SUFFIX))
+        .collect();
+    fixtures.sort();
+
+    let mut failed = 0;
+    for fixture in &fixtures {
+        let name = path_relative_from(fixture, fixtures_dir)
+            .unwrap_or_else(|| fixture.to_path_buf())
+            .display()
+            .to_string();
+        let expected_path = format!("{}{}", fixture.display(), EXPECTED_SUFFIX);
+
+        // Bypass include/exclude patterns: fixtures usually live outside the
+        // paths a linter is configured for.
+        let (messages, failure) = linter.run_on_matches(vec![fixture.clone()]);
+        if failure.is_some() {
+            failed += 1;
+            println!("{} {}", style("FAIL").red().bold(), name);
+            for message in &messages {
+                println!("    {}", message.description.as_deref().unwrap_or_default());
+            }
+            continue;
+        }
+        let actual = normalize(messages, fixtures_dir)?;
+
+        if update {
+            std::fs::write(&expected_path, &actual)
+                .with_context(|| format!("Failed to write '{}'", expected_path))?;
+            println!("{} {}", style("UPDATED").cyan().bold(), name);
+            continue;
+        }
+
+        let expected = match std::fs::read_to_string(&expected_path) {
+            Ok(expected) => expected,
+            Err(_) => {
+                failed += 1;
+                println!(
+                    "{} {} (no {}, run with --update to create it)",
+                    style("FAIL").red().bold(),
+                    name,
+                    expected_path
+                );
+                continue;
+            }
+        };
+        // Compare parsed JSON, so that hand-edited expectations don't need to
+        // match our formatting exactly.
+        let matches = match serde_json::from_str::<serde_json::Value>(&expected) {
+            Ok(expected) => serde_json::from_str::<serde_json::Value>(&actual)? == expected,
+            Err(_) => false,
+        };
+        if matches {
+            println!("{} {}", style("PASS").green().bold(), name);
+        } else {
+            failed += 1;
+            println!("{} {}", style("FAIL").red().bold(), name);
+            print_diff(&expected, &actual);
+        }
+    }
+
+    println!(
+        "\n{} fixtures, {} passed, {} failed.",
+        fixtures.len(),
+        fixtures.len() - failed,
+        failed
+    );
+    Ok(if failed > 0 { 1 } else { 0 })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lint_config::{get_linters_from_config, LintRunnerConfig};
+
+    #[test]
+    fn fixtures_are_compared() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let config_path = dir.path().join(".lintrunner.toml");
+        let config = "[[linter]]\ncode = 'WS'\ninclude_patterns = ['*.py']\n\
+                      command = 'builtin:whitespace'\n";
+        std::fs::write(&config_path, config)?;
+        let config = LintRunnerConfig::new_from_string(config)?;
+        let linters = get_linters_from_config(
+            &config.linters,
+            None,
+            None,
+            &AbsPath::try_from(config_path)?,
+        )?;
+
+        let fixtures_dir = dir.path().join("fixtures");
+        std::fs::create_dir(&fixtures_dir)?;
+        std::fs::write(fixtures_dir.join("bad.txt"), "x \n")?;
+        std::fs::write(fixtures_dir.join("good.txt"), "x\n")?;
+        let fixtures_dir = AbsPath::try_from(fixtures_dir)?;
+
+        // Missing expectations fail.
+        assert_eq!(test_linter(&linters[0], &fixtures_dir, false)?, 1);
+
+        assert_eq!(test_linter(&linters[0], &fixtures_dir, true)?, 0);
+        let expected = std::fs::read_to_string(fixtures_dir.join("bad.txt.expected.json"))?;
+        assert!(expected.contains("\"path\": \"bad.txt\""));
+        assert_eq!(
+            std::fs::read_to_string(fixtures_dir.join("good.txt.expected.json"))?,
+            "[]\n"
+        );
+        assert_eq!(test_linter(&linters[0], &fixtures_dir, false)?, 0);
+
+        std::fs::write(fixtures_dir.join("good.txt"), "x\t\n")?;
+        assert_eq!(test_linter(&linters[0], &fixtures_dir, false)?, 1);
+        Ok(())
+    }
+}
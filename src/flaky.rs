@@ -0,0 +1,158 @@
+//! Detecting linters whose results change even though their inputs didn't.
+//!
+//! Every run records a fingerprint of each linter's inputs (its command and
+//! the contents of the files it was given) and of the results it reported.
+//! A linter that reported different results for the same inputs in past
+//! runs is flaky.
+
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{lint_message::LintMessage, linter::Linter, path::AbsPath};
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LinterFingerprint {
+    /// Hash of the linter's command and the files it was given.
+    pub inputs: String,
+    /// Hash of the lint messages the linter reported.
+    pub results: String,
+}
+
+/// A linter that reported different results for the same inputs.
+#[derive(Debug, PartialEq, Eq)]
+pub struct FlakyLinter {
+    /// How many recorded runs had the inputs with inconsistent results.
+    pub runs: usize,
+    /// How many different results those runs had.
+    pub distinct_results: usize,
+}
+
+pub fn fingerprint_inputs(linter: &Linter, files: &[AbsPath]) -> String {
+    let mut hasher = blake3::Hasher::new();
+    for arg in &linter.commands {
+        hasher.update(arg.as_bytes());
+        hasher.update(b"\0");
+    }
+    for file in files {
+        hasher.update(file.to_string_lossy().as_bytes());
+        hasher.update(b"\0");
+        // Files that can't be read (e.g. deleted ones) still count, just
+        // without their contents.
+        if let Ok(contents) = std::fs::read(file) {
+            hasher.update(blake3::hash(&contents).as_bytes());
+        }
+    }
+    hasher.finalize().to_string()
+}
+
+pub fn fingerprint_results(lints: &[LintMessage]) -> String {
+    // Linters don't necessarily report issues in a stable order.
+    let mut serialized: Vec<String> = lints
+        .iter()
+        .map(|lint| serde_json::to_string(lint).unwrap_or_default())
+        .collect();
+    serialized.sort();
+    let mut hasher = blake3::Hasher::new();
+    for lint in serialized {
+        hasher.update(lint.as_bytes());
+        hasher.update(b"\n");
+    }
+    hasher.finalize().to_string()
+}
+
+/// Find the flaky linters in `history`, which has the fingerprints recorded
+/// by each past run.
+pub fn find_flaky_linters(
+    history: &[HashMap<String, LinterFingerprint>],
+) -> BTreeMap<String, FlakyLinter> {
+    // linter -> inputs -> (runs, results)
+    let mut seen: BTreeMap<&str, HashMap<&str, (usize, BTreeSet<&str>)>> = BTreeMap::new();
+    for run in history {
+        for (code, fingerprint) in run {
+            let (runs, results) = seen
+                .entry(code)
+                .or_default()
+                .entry(&fingerprint.inputs)
+                .or_default();
+            *runs += 1;
+            results.insert(&fingerprint.results);
+        }
+    }
+
+    let mut flaky = BTreeMap::new();
+    for (code, by_inputs) in seen {
+        let mut runs = 0;
+        let mut distinct_results = 0;
+        for (input_runs, results) in by_inputs.values() {
+            if results.len() > 1 {
+                runs += input_runs;
+                distinct_results = distinct_results.max(results.len());
+            }
+        }
+        if distinct_results > 0 {
+            flaky.insert(
+                code.to_string(),
+                FlakyLinter {
+                    runs,
+                    distinct_results,
+                },
+            );
+        }
+    }
+    flaky
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(fingerprints: &[(&str, &str, &str)]) -> HashMap<String, LinterFingerprint> {
+        fingerprints
+            .iter()
+            .map(|(code, inputs, results)| {
+                (
+                    code.to_string(),
+                    LinterFingerprint {
+                        inputs: inputs.to_string(),
+                        results: results.to_string(),
+                    },
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn flips_on_identical_inputs_are_flaky() {
+        let history = vec![
+            run(&[("MYPY", "a", "1"), ("FLAKE8", "a", "1")]),
+            run(&[("MYPY", "a", "2"), ("FLAKE8", "b", "2")]),
+            run(&[("MYPY", "a", "1"), ("FLAKE8", "b", "2")]),
+        ];
+        let flaky = find_flaky_linters(&history);
+        // FLAKE8's results only changed along with its inputs.
+        assert_eq!(flaky.keys().collect::<Vec<_>>(), vec!["MYPY"]);
+        assert_eq!(
+            flaky["MYPY"],
+            FlakyLinter {
+                runs: 3,
+                distinct_results: 2
+            }
+        );
+    }
+
+    #[test]
+    fn results_are_order_independent() {
+        let lint = |name: &str| {
+            LintMessage::for_test("TEST", crate::lint_message::LintSeverity::Error, name)
+        };
+        assert_eq!(
+            fingerprint_results(&[lint("a"), lint("b")]),
+            fingerprint_results(&[lint("b"), lint("a")])
+        );
+        assert_ne!(
+            fingerprint_results(&[lint("a")]),
+            fingerprint_results(&[lint("b")])
+        );
+    }
+}
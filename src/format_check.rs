@@ -0,0 +1,137 @@
+//! `lintrunner format --check`: running formatters without changing files.
+//!
+//! Like `black --check`, this lists the files formatters would change and
+//! fails if there are any, so that CI can check formatting without having to
+//! diff the checkout afterwards.
+
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+
+use anyhow::Result;
+use console::style;
+use similar::TextDiff;
+
+use crate::{
+    lint_message::LintMessage,
+    linter::Linter,
+    path::{get_display_path, AbsPath},
+    persistent_data::PersistentDataStore,
+    progress::ProgressOpt,
+    render::render_lint_messages,
+    run_linters, streams, CodeFilter, RunOptions,
+};
+
+/// Run `linters` on `files` without applying their fixes, and list the files
+/// that would be reformatted, with a diff of the changes if `show_diff`.
+pub fn do_format_check(
+    linters: Vec<Linter>,
+    files: Vec<AbsPath>,
+    progress: ProgressOpt,
+    jobs: Option<usize>,
+    show_diff: bool,
+    persistent_data_store: &PersistentDataStore,
+) -> Result<i32> {
+    let results = run_linters(
+        linters,
+        files,
+        RunOptions {
+            apply_patches: false,
+            fix_filter: CodeFilter::default(),
+            strict_missing: false,
+            code_filter: CodeFilter::default(),
+            progress,
+            stream: None,
+            expected_durations: persistent_data_store.linter_durations()?,
+            record_fingerprints: false,
+            baseline: None,
+            issue_history: None,
+            jobs,
+            sink: None,
+        },
+    )?;
+
+    let mut stdout = streams::results().term();
+    // Formatters can also report problems they can't fix, such as syntax
+    // errors, or fail outright; those are shown as usual.
+    let mut other_lints: HashMap<Option<String>, Vec<LintMessage>> = HashMap::new();
+    for (path, lints) in &results.lints {
+        let unfixable: Vec<_> = lints.iter().filter(|l| !l.has_fix()).cloned().collect();
+        if !unfixable.is_empty() {
+            other_lints.insert(path.clone(), unfixable);
+        }
+    }
+    if !other_lints.is_empty() {
+        render_lint_messages(&mut stdout, &other_lints)?;
+    }
+
+    let diffs = reformat_diffs(results.lints.values().flatten(), &std::env::current_dir()?)?;
+    for (path, diff) in &diffs {
+        stdout.write_line(&format!("would reformat {}", style(path).bold()))?;
+        if show_diff {
+            stdout.write_str(diff)?;
+        }
+    }
+
+    let summary = match diffs.len() {
+        0 => "All files are formatted.".to_string(),
+        1 => "1 file would be reformatted.".to_string(),
+        n => format!("{} files would be reformatted.", n),
+    };
+    streams::diagnostics().term().write_line(&summary)?;
+
+    Ok(match results.exit_code() {
+        0 if !diffs.is_empty() => 1,
+        code => code,
+    })
+}
+
+/// A unified diff of the changes `lints` would make to each file, by path
+/// relative to `current_dir`. When several formatters would change the same
+/// file, their diffs follow each other.
+fn reformat_diffs<'a>(
+    lints: impl IntoIterator<Item = &'a LintMessage>,
+    current_dir: &Path,
+) -> Result<BTreeMap<String, String>> {
+    let mut diffs: BTreeMap<String, String> = BTreeMap::new();
+    for lint in lints {
+        for fix in lint.fixes()? {
+            if fix.original == fix.replacement {
+                continue;
+            }
+            let path = get_display_path(&fix.path, current_dir);
+            let diff = TextDiff::from_lines(&fix.original, &fix.replacement)
+                .unified_diff()
+                .header(&path, &path)
+                .to_string();
+            diffs.entry(path).or_default().push_str(&diff);
+        }
+    }
+    Ok(diffs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lint_message::LintSeverity;
+
+    #[test]
+    fn only_changed_files_are_listed() -> Result<()> {
+        let lint = |path: &str, replacement: &str| LintMessage {
+            path: Some(path.to_string()),
+            original: Some("a = 1\n".to_string()),
+            replacement: Some(replacement.to_string()),
+            ..LintMessage::for_test("FMT", LintSeverity::Warning, "format")
+        };
+        let mut unfixable = lint("c.py", "");
+        unfixable.replacement = None;
+        let lints = [lint("b.py", "a = 2\n"), lint("a.py", "a = 1\n"), unfixable];
+
+        let diffs = reformat_diffs(&lints, &std::env::current_dir()?)?;
+        assert_eq!(diffs.keys().collect::<Vec<_>>(), ["b.py"]);
+        assert_eq!(
+            diffs["b.py"],
+            "--- b.py\n+++ b.py\n@@ -1 +1 @@\n-a = 1\n+a = 2\n"
+        );
+        Ok(())
+    }
+}
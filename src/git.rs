@@ -1,20 +1,48 @@
-use std::{collections::HashSet, convert::TryFrom, process::Command};
+use std::{
+    collections::HashSet,
+    convert::TryFrom,
+    path::{Path, PathBuf},
+    process::Command,
+};
 
 use crate::{
     log_utils::{ensure_output, log_files},
+    parallel,
     path::AbsPath,
 };
-use anyhow::{ensure, Context, Result};
-use log::debug;
-use regex::Regex;
+use anyhow::{bail, ensure, Context, Result};
+use log::{debug, warn};
 
 pub fn get_head() -> Result<String> {
-    let output = Command::new("git").arg("rev-parse").arg("HEAD").output()?;
+    get_head_in(&AbsPath::try_from(".")?)
+}
+
+/// The checked-out commit of the repo containing `dir`. This works with a
+/// detached HEAD too.
+fn get_head_in(dir: &AbsPath) -> Result<String> {
+    let output = Command::new("git")
+        .args(["rev-parse", "--verify", "HEAD^{commit}"])
+        .current_dir(dir)
+        .output()?;
     ensure_output("git rev-parse", &output)?;
     let head = std::str::from_utf8(&output.stdout)?.trim();
     Ok(head.to_string())
 }
 
+/// The name of the branch that is checked out, or `HEAD` if none is.
+/// This works before the first commit too.
+pub fn get_branch() -> Result<String> {
+    let output = Command::new("git")
+        .args(["symbolic-ref", "--quiet", "--short", "HEAD"])
+        .output()?;
+    // With --quiet, a detached HEAD exits with 1 and nothing else.
+    if output.status.code() == Some(1) && output.stderr.is_empty() {
+        return Ok("HEAD".to_string());
+    }
+    ensure_output("git symbolic-ref", &output)?;
+    Ok(std::str::from_utf8(&output.stdout)?.trim().to_string())
+}
+
 pub fn get_paths_from_cmd(paths_cmd: &str) -> Result<Vec<AbsPath>> {
     debug!("Running paths_cmd: {}", paths_cmd);
     if paths_cmd.is_empty() {
@@ -37,17 +65,87 @@ pub fn get_paths_from_cmd(paths_cmd: &str) -> Result<Vec<AbsPath>> {
         .collect::<HashSet<String>>();
     let mut files = files.into_iter().collect::<Vec<String>>();
     files.sort();
-    files
+    parallel::filter_map(&files, |file| Some(AbsPath::try_from(file)))
         .into_iter()
-        .map(AbsPath::try_from)
         .collect::<Result<_>>()
 }
 
+/// Get all files tracked by git under `dir`. Returns None if `dir` is not
+/// inside a git repository.
+pub fn get_tracked_files_in(dir: &AbsPath) -> Result<Option<Vec<AbsPath>>> {
+    let output = Command::new("git")
+        .args(["ls-files", "-z", "--"])
+        .arg(".")
+        .current_dir(dir)
+        .output()?;
+    if !output.status.success() {
+        debug!("'{}' is not in a git repository", dir.display());
+        return Ok(None);
+    }
+    let files =
+        std::str::from_utf8(&output.stdout).context("failed to parse git ls-files output")?;
+    let files: Vec<&str> = files.split('\0').filter(|f| !f.is_empty()).collect();
+    Ok(Some(
+        parallel::filter_map(&files, |f| {
+            let path = dir.join(f);
+            // Skip submodules, as well as files that have been deleted from
+            // the working tree.
+            path.is_file().then(|| AbsPath::try_from(path))
+        })
+        .into_iter()
+        .collect::<Result<_>>()?,
+    ))
+}
+
+/// Whether `rev` refers to the branch's upstream or push destination, e.g.
+/// `@{upstream}`, `@{u}`, `main@{push}`.
+fn is_tracking_revspec(rev: &str) -> bool {
+    let rev = rev.to_ascii_lowercase();
+    ["@{upstream}", "@{u}", "@{push}"]
+        .iter()
+        .any(|suffix| rev.contains(suffix))
+}
+
+/// Whether HEAD is detached, as it usually is in CI checkouts.
+fn is_head_detached(git_root: &AbsPath) -> Result<bool> {
+    let output = Command::new("git")
+        .args(["symbolic-ref", "--quiet", "HEAD"])
+        .current_dir(git_root)
+        .output()?;
+    Ok(!output.status.success())
+}
+
+/// Resolve `rev` to a commit hash. Tracking revspecs like `@{upstream}` don't
+/// mean anything with a detached HEAD, so those resolve to the checked-out
+/// commit instead.
+pub fn resolve_revision(git_root: &AbsPath, rev: &str) -> Result<String> {
+    let output = Command::new("git")
+        .args(["rev-parse", "--verify", "--quiet"])
+        .arg(format!("{rev}^{{commit}}"))
+        .current_dir(git_root)
+        .output()?;
+    if output.status.success() {
+        return Ok(std::str::from_utf8(&output.stdout)?.trim().to_string());
+    }
+    if is_tracking_revspec(rev) {
+        if is_head_detached(git_root)? {
+            warn!("HEAD is detached, so '{rev}' doesn't exist. Using the checked-out commit.");
+            return get_head_in(git_root);
+        }
+        bail!(
+            "'{rev}' doesn't exist. Does the current branch have one? \
+             Set it with `git branch --set-upstream-to`."
+        );
+    }
+    bail!("'{rev}' isn't a valid revision");
+}
+
 pub fn get_merge_base_with(git_root: &AbsPath, merge_base_with: &str) -> Result<String> {
+    let commit = resolve_revision(git_root, merge_base_with)?;
     let output = Command::new("git")
         .arg("merge-base")
         .arg("HEAD")
-        .arg(merge_base_with)
+        .arg(&commit)
         .current_dir(git_root)
         .output()?;
 
@@ -59,18 +157,110 @@ pub fn get_merge_base_with(git_root: &AbsPath, merge_base_with: &str) -> Result<
     Ok(merge_base.to_string())
 }
 
-pub fn get_changed_files(git_root: &AbsPath, relative_to: Option<&str>) -> Result<Vec<AbsPath>> {
+/// Branches to try, in order, when the remote doesn't say what its default
+/// branch is.
+const DEFAULT_BRANCH_CANDIDATES: &[&str] = &["origin/main", "origin/master", "main", "master"];
+
+/// Guess the branch that feature branches are based on: whatever `origin/HEAD`
+/// points to, or else the first of the usual suspects that exists. Returns
+/// None if there is no such branch.
+pub fn get_default_branch(git_root: &AbsPath) -> Result<Option<String>> {
+    let output = Command::new("git")
+        .args(["symbolic-ref", "--quiet", "refs/remotes/origin/HEAD"])
+        .current_dir(git_root)
+        .output()?;
+    if output.status.success() {
+        let target = std::str::from_utf8(&output.stdout)?.trim();
+        if let Some(branch) = target.strip_prefix("refs/remotes/") {
+            return Ok(Some(branch.to_string()));
+        }
+    }
+    for candidate in DEFAULT_BRANCH_CANDIDATES {
+        let status = Command::new("git")
+            .args(["rev-parse", "--verify", "--quiet"])
+            .arg(format!("{candidate}^{{commit}}"))
+            .current_dir(git_root)
+            .stdout(std::process::Stdio::null())
+            .status()?;
+        if status.success() {
+            return Ok(Some(candidate.to_string()));
+        }
+    }
+    Ok(None)
+}
+
+/// The merge base of HEAD with the default branch (see
+/// [`get_default_branch`]), or None if it can't be determined, e.g. because
+/// there is no such branch or the checkout is too shallow to find it.
+pub fn get_default_merge_base(git_root: &AbsPath) -> Result<Option<String>> {
+    let branch = match get_default_branch(git_root)? {
+        Some(branch) => branch,
+        None => {
+            debug!("Couldn't find a default branch, linting changes in HEAD");
+            return Ok(None);
+        }
+    };
+    match get_merge_base_with(git_root, &branch) {
+        Ok(merge_base) => {
+            debug!("Linting changes since the merge base with {branch}: {merge_base}");
+            Ok(Some(merge_base))
+        }
+        Err(err) => {
+            debug!("{err}, linting changes in HEAD");
+            Ok(None)
+        }
+    }
+}
+
+/// The files touched by a change, from the output of `git diff-* --name-status`.
+#[derive(Default)]
+struct NameStatus {
+    changed: HashSet<String>,
+    deleted: HashSet<String>,
+}
+
+fn parse_name_status(output: &str) -> NameStatus {
     // Output of --name-status looks like:
     // D    src/lib.rs
     // M    foo/bar.baz
-    let re = Regex::new(r"^[A-Z]\s+")?;
+    // R095 old/name.rs    new/name.rs
+    // where the separators are tabs.
+    let mut name_status = NameStatus::default();
+    for line in output.lines().filter(|line| !line.is_empty()) {
+        let mut fields = line.split('\t');
+        let status = fields.next().unwrap_or_default();
+        let paths: Vec<&str> = fields.collect();
+        match (status.chars().next(), paths.as_slice()) {
+            (Some('D'), [path]) => {
+                name_status.deleted.insert(path.to_string());
+            }
+            // Renames list the old path, then the new one. Only the new one
+            // exists anymore.
+            (Some('R'), [old, new]) => {
+                debug!("Renamed: {} -> {}", old, new);
+                name_status.deleted.insert(old.to_string());
+                name_status.changed.insert(new.to_string());
+            }
+            (Some('C'), [_, new]) => {
+                name_status.changed.insert(new.to_string());
+            }
+            (_, [path]) => {
+                name_status.changed.insert(path.to_string());
+            }
+            _ => debug!("Couldn't parse git output line: {}", line),
+        }
+    }
+    name_status
+}
 
+pub fn get_changed_files(git_root: &AbsPath, relative_to: Option<&str>) -> Result<Vec<AbsPath>> {
     // Retrieve changed files in current commit.
     let mut args = vec![
         "diff-tree",
         "--ignore-submodules",
         "--no-commit-id",
         "--name-status",
+        "-M",
         "-r",
     ];
     if let Some(relative_to) = relative_to {
@@ -84,17 +274,7 @@ pub fn get_changed_files(git_root: &AbsPath, relative_to: Option<&str>) -> Resul
         .output()?;
     ensure_output("git diff-tree", &output)?;
 
-    let commit_files_str = std::str::from_utf8(&output.stdout)?;
-
-    let commit_files: HashSet<String> = commit_files_str
-        .split('\n')
-        .map(|x| x.to_string())
-        // Filter out deleted files.
-        .filter(|line| !line.starts_with('D'))
-        // Strip the status prefix.
-        .map(|line| re.replace(&line, "").to_string())
-        .filter(|line| !line.is_empty())
-        .collect();
+    let commit_files = parse_name_status(std::str::from_utf8(&output.stdout)?).changed;
 
     log_files("Linting commit diff files: ", &commit_files);
 
@@ -104,36 +284,23 @@ pub fn get_changed_files(git_root: &AbsPath, relative_to: Option<&str>) -> Resul
         .arg("--ignore-submodules")
         .arg("--no-commit-id")
         .arg("--name-status")
+        .arg("-M")
         .arg("-r")
         .arg("HEAD")
         .current_dir(git_root)
         .output()?;
     ensure_output("git diff-index", &output)?;
 
-    let working_tree_files_str = std::str::from_utf8(&output.stdout)?;
-    let working_tree_files: HashSet<String> = working_tree_files_str
-        .lines()
-        .filter(|line| !line.is_empty())
-        // Filter out deleted files.
-        .filter(|line| !line.starts_with('D'))
-        // Strip the status prefix.
-        .map(|line| re.replace(line, "").to_string())
-        .collect();
+    let NameStatus {
+        changed: working_tree_files,
+        deleted: deleted_working_tree_files,
+    } = parse_name_status(std::str::from_utf8(&output.stdout)?);
 
     log_files("Linting working tree diff files: ", &working_tree_files);
 
-    let deleted_working_tree_files: HashSet<String> = working_tree_files_str
-        .lines()
-        .filter(|line| !line.is_empty())
-        // Filter IN deleted files.
-        .filter(|line| line.starts_with('D'))
-        // Strip the status prefix.
-        .map(|line| re.replace(line, "").to_string())
-        .collect();
-
     log_files(
         "These files were deleted in the working tree and won't be checked: ",
-        &working_tree_files,
+        &deleted_working_tree_files,
     );
 
     let all_files = working_tree_files
@@ -154,6 +321,78 @@ pub fn get_changed_files(git_root: &AbsPath, relative_to: Option<&str>) -> Resul
         .collect::<Result<_>>()
 }
 
+/// Get the files with changes staged in the index, relative to HEAD. The
+/// files are linted as they are in the working tree, which may include
+/// unstaged changes too. Files that were deleted are skipped.
+pub fn get_staged_files(git_root: &AbsPath) -> Result<Vec<AbsPath>> {
+    let output = Command::new("git")
+        .args([
+            "diff-index",
+            "--cached",
+            "--ignore-submodules",
+            "--no-commit-id",
+            "--name-status",
+            "-M",
+            "-r",
+            "HEAD",
+        ])
+        .current_dir(git_root)
+        .output()?;
+    ensure_output("git diff-index", &output)?;
+
+    let staged = parse_name_status(std::str::from_utf8(&output.stdout)?).changed;
+    log_files("Linting staged files: ", &staged);
+
+    Ok(staged
+        .into_iter()
+        .filter_map(|file| AbsPath::try_from(git_root.join(file)).ok())
+        .collect())
+}
+
+/// Get the untracked files in the working tree that aren't ignored.
+pub fn get_untracked_files(git_root: &AbsPath) -> Result<Vec<AbsPath>> {
+    let output = Command::new("git")
+        .args(["ls-files", "--others", "--exclude-standard", "-z"])
+        .current_dir(git_root)
+        .output()?;
+    ensure_output("git ls-files", &output)?;
+
+    let untracked: Vec<&str> = std::str::from_utf8(&output.stdout)?
+        .split('\0')
+        .filter(|file| !file.is_empty())
+        .collect();
+    log_files("Linting untracked files: ", &untracked);
+
+    Ok(untracked
+        .into_iter()
+        .filter_map(|file| AbsPath::try_from(git_root.join(file)).ok())
+        .collect())
+}
+
+/// Get all files changed in commits since `since`, which can be any date
+/// accepted by `git log --since` (e.g. "2 weeks ago"). Files that have been
+/// deleted since are skipped.
+pub fn get_files_changed_since(git_root: &AbsPath, since: &str) -> Result<Vec<AbsPath>> {
+    let output = Command::new("git")
+        .arg("log")
+        .arg(format!("--since={}", since))
+        .args(["--name-only", "--pretty=format:", "-z", "HEAD"])
+        .current_dir(git_root)
+        .output()?;
+    ensure_output("git log", &output)?;
+
+    let files: HashSet<&str> = std::str::from_utf8(&output.stdout)?
+        .split(['\0', '\n'])
+        .filter(|file| !file.is_empty())
+        .collect();
+    log_files(&format!("Linting files changed since {}: ", since), &files);
+
+    Ok(files
+        .into_iter()
+        .filter_map(|file| AbsPath::try_from(git_root.join(file)).ok())
+        .collect())
+}
+
 // Retrieve the git root based on the current working directory.
 pub fn get_git_root() -> Result<AbsPath> {
     let output = Command::new("git")
@@ -165,6 +404,20 @@ pub fn get_git_root() -> Result<AbsPath> {
     AbsPath::try_from(root)
 }
 
+/// The root of the git repo containing `dir`, if any.
+pub fn get_git_root_of(dir: &Path) -> Option<PathBuf> {
+    let output = Command::new("git")
+        .args(["rev-parse", "--show-toplevel"])
+        .current_dir(dir)
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    let root = std::str::from_utf8(&output.stdout).ok()?.trim();
+    Some(PathBuf::from(root))
+}
+
 #[cfg(test)]
 mod tests {
     use std::{convert::TryFrom, fs::OpenOptions, io::Write};
@@ -233,6 +486,27 @@ mod tests {
             Ok(())
         }
 
+        fn commit_at(&self, message: &str, date: &str) -> Result<()> {
+            let output = Command::new("git")
+                .args(&["commit", "-m", message])
+                .env("GIT_AUTHOR_DATE", date)
+                .env("GIT_COMMITTER_DATE", date)
+                .current_dir(self.root.path())
+                .output()?;
+            assert!(output.status.success());
+            Ok(())
+        }
+
+        fn files_changed_since(&self, since: &str) -> Result<Vec<String>> {
+            let git_root = AbsPath::try_from(self.root.path())?;
+            let mut files = get_files_changed_since(&git_root, since)?
+                .into_iter()
+                .map(|abs_path| abs_path.file_name().unwrap().to_string_lossy().to_string())
+                .collect::<Vec<_>>();
+            files.sort();
+            Ok(files)
+        }
+
         fn changed_files(&self, relative_to: Option<&str>) -> Result<Vec<String>> {
             let git_root = AbsPath::try_from(self.root.path())?;
             let files = get_changed_files(&git_root, relative_to)?;
@@ -243,10 +517,90 @@ mod tests {
             Ok(files)
         }
 
+        fn tracked_files_in(&self, dir: &str) -> Result<Vec<String>> {
+            let dir = AbsPath::try_from(self.root.path().join(dir))?;
+            let files = get_tracked_files_in(&dir)?.unwrap();
+            let files = files
+                .into_iter()
+                .map(|abs_path| abs_path.file_name().unwrap().to_string_lossy().to_string())
+                .collect::<Vec<_>>();
+            Ok(files)
+        }
+
         fn merge_base_with(&self, merge_base_with: &str) -> Result<String> {
             let git_root = AbsPath::try_from(self.root.path())?;
             get_merge_base_with(&git_root, merge_base_with)
         }
+
+        fn git(&self, args: &[&str]) -> Result<()> {
+            let output = Command::new("git")
+                .args(args)
+                .current_dir(self.root.path())
+                .output()?;
+            assert!(output.status.success());
+            Ok(())
+        }
+
+        fn default_branch(&self) -> Result<Option<String>> {
+            let git_root = AbsPath::try_from(self.root.path())?;
+            get_default_branch(&git_root)
+        }
+    }
+
+    #[test]
+    fn tracking_revisions() -> Result<()> {
+        let git = GitCheckout::new()?;
+        let git_root = AbsPath::try_from(git.root.path())?;
+        git.write_file("test_1.txt", "Initial commit")?;
+        git.add(".")?;
+        git.commit("commit 1")?;
+        git.git(&["branch", "-M", "base"])?;
+        let base = get_head_in(&git_root)?;
+
+        git.checkout_new_branch("feature")?;
+        git.write_file("test_2.txt", "foo")?;
+        git.add(".")?;
+        git.commit("commit 2")?;
+        let feature = get_head_in(&git_root)?;
+        assert!(resolve_revision(&git_root, "@{upstream}").is_err());
+
+        git.git(&["branch", "--set-upstream-to", "base"])?;
+        assert_eq!(resolve_revision(&git_root, "@{upstream}")?, base);
+        assert_eq!(resolve_revision(&git_root, "HEAD~")?, base);
+        assert!(resolve_revision(&git_root, "nonexistent").is_err());
+
+        // CI checkouts usually don't have a branch, so there's no upstream.
+        git.git(&["checkout", "--detach"])?;
+        assert_eq!(resolve_revision(&git_root, "@{u}")?, feature);
+        assert_eq!(get_merge_base_with(&git_root, "@{push}")?, feature);
+        Ok(())
+    }
+
+    #[test]
+    fn default_branch() -> Result<()> {
+        let git = GitCheckout::new()?;
+        git.write_file("test_1.txt", "Initial commit")?;
+        git.add(".")?;
+        git.commit("commit 1")?;
+        git.git(&["branch", "-M", "trunk"])?;
+        assert_eq!(git.default_branch()?, None);
+
+        git.git(&["branch", "master"])?;
+        assert_eq!(git.default_branch()?.as_deref(), Some("master"));
+
+        // A remote branch beats a local one.
+        git.git(&["update-ref", "refs/remotes/origin/main", "HEAD"])?;
+        assert_eq!(git.default_branch()?.as_deref(), Some("origin/main"));
+
+        // And the remote's HEAD beats guessing.
+        git.git(&["update-ref", "refs/remotes/origin/trunk", "HEAD"])?;
+        git.git(&[
+            "symbolic-ref",
+            "refs/remotes/origin/HEAD",
+            "refs/remotes/origin/trunk",
+        ])?;
+        assert_eq!(git.default_branch()?.as_deref(), Some("origin/trunk"));
+        Ok(())
     }
 
     // Should properly detect changes in the commit (and not check other files)
@@ -449,4 +803,88 @@ mod tests {
         }
         Ok(())
     }
+
+    #[test]
+    fn tracked_files_in_dir() -> Result<()> {
+        let git = GitCheckout::new()?;
+        std::fs::create_dir(git.root.path().join("subdir"))?;
+        git.write_file("test_1.txt", "Initial commit")?;
+        git.write_file("subdir/test_2.txt", "Initial commit")?;
+        git.write_file("subdir/test_3.txt", "Initial commit")?;
+        git.add(".")?;
+        git.commit("commit 1")?;
+
+        // Untracked files should not be picked up.
+        git.write_file("subdir/untracked.txt", "blah")?;
+        // Neither should deleted ones.
+        git.rm_file("subdir/test_3.txt")?;
+
+        let files = git.tracked_files_in("subdir")?;
+        assert_eq!(files, vec!["test_2.txt".to_string()]);
+        Ok(())
+    }
+
+    #[test]
+    fn changed_since() -> Result<()> {
+        let git = GitCheckout::new()?;
+        git.write_file("old.txt", "old")?;
+        git.add(".")?;
+        git.commit_at("commit 1", "2000-01-01T00:00:00")?;
+
+        git.write_file("new_1.txt", "new")?;
+        git.write_file("deleted.txt", "new")?;
+        git.add(".")?;
+        git.commit_at("commit 2", "2020-01-01T00:00:00")?;
+
+        git.write_file("new_2.txt", "new")?;
+        git.rm_file("deleted.txt")?;
+        git.add(".")?;
+        git.commit_at("commit 3", "2020-02-01T00:00:00")?;
+
+        assert_eq!(
+            git.files_changed_since("2010-01-01")?,
+            vec!["new_1.txt", "new_2.txt"]
+        );
+        assert_eq!(git.files_changed_since("2020-01-15")?, vec!["new_2.txt"]);
+        Ok(())
+    }
+
+    #[test]
+    fn renames_are_linted_at_new_path() -> Result<()> {
+        let git = GitCheckout::new()?;
+        git.write_file("old_name.txt", "some contents that git can match up")?;
+        git.write_file("other.txt", "other")?;
+        git.add(".")?;
+        git.commit("commit 1")?;
+
+        let output = Command::new("git")
+            .args(&["mv", "old_name.txt", "new_name.txt"])
+            .current_dir(git.root.path())
+            .output()?;
+        assert!(output.status.success());
+        git.commit("rename")?;
+
+        // Rename in the working tree, too.
+        let output = Command::new("git")
+            .args(&["mv", "other.txt", "renamed_other.txt"])
+            .current_dir(git.root.path())
+            .output()?;
+        assert!(output.status.success());
+
+        let mut files = git.changed_files(None)?;
+        files.sort();
+        assert_eq!(files, vec!["new_name.txt", "renamed_other.txt"]);
+        Ok(())
+    }
+
+    #[test]
+    fn name_status_is_parsed() {
+        let name_status = parse_name_status("M\ta.rs\nD\tb.rs\nR087\tc.rs\td.rs\nA\te f.rs\n");
+        let mut changed: Vec<_> = name_status.changed.into_iter().collect();
+        changed.sort();
+        assert_eq!(changed, vec!["a.rs", "d.rs", "e f.rs"]);
+        let mut deleted: Vec<_> = name_status.deleted.into_iter().collect();
+        deleted.sort();
+        assert_eq!(deleted, vec!["b.rs", "c.rs"]);
+    }
 }
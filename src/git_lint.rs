@@ -0,0 +1,274 @@
+//! Running lintrunner as `git lint`.
+//!
+//! Git runs `git-lint` from `PATH` for `git lint`, so a copy of (or link to)
+//! the binary with that name works, as does a git alias set up with
+//! `lintrunner install-git-alias`. Either way, the arguments are those a git
+//! user would pass to `git diff` or `git status`, and are translated into
+//! lintrunner's:
+//!
+//! - `--cached`/`--staged` lints the staged changes (`--staged`).
+//! - `-u`/`--untracked-files` also lints untracked files (`--untracked`).
+//! - `-a`/`--all` lints every file (`--all-files`).
+//! - A commit lints the changes since it (`--revision`).
+//! - Pathspecs are the paths to lint, except exclusions like `:!vendor/`,
+//!   which become `--exclude`s. `:/path` and `:(top)path` are relative to the
+//!   root of the repo.
+//!
+//! Anything else starting with `-` is passed through as a lintrunner option.
+
+use std::{
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use anyhow::{ensure, Context, Result};
+
+/// The argument the git alias passes first, to ask for git-style arguments.
+pub const GIT_LINT_MARKER: &str = "--as-git-lint";
+const GIT_LINT_NAME: &str = "git-lint";
+
+/// If lintrunner was invoked as `git lint`, the git-style arguments it was
+/// passed (without the program name).
+pub fn git_lint_args(args: &[String]) -> Option<&[String]> {
+    let program = Path::new(args.first()?).file_stem()?;
+    if program == GIT_LINT_NAME {
+        return Some(&args[1..]);
+    }
+    match args.get(1) {
+        Some(marker) if marker == GIT_LINT_MARKER => Some(&args[2..]),
+        _ => None,
+    }
+}
+
+/// What lintrunner needs to know about its own arguments and the repo to
+/// translate git-style arguments.
+pub struct Translator<'a> {
+    /// Whether a lintrunner option (like `--take` or `-r`) takes a value.
+    pub takes_value: &'a dyn Fn(&str) -> bool,
+    /// Whether `arg` names a lintrunner subcommand.
+    pub is_subcommand: &'a dyn Fn(&str) -> bool,
+    /// Whether `arg` names a commit.
+    pub is_revision: &'a dyn Fn(&str) -> bool,
+    /// The root of the repo, if in one.
+    pub repo_root: Option<PathBuf>,
+}
+
+impl Translator<'_> {
+    /// Translate git-style `args` into lintrunner arguments, starting with
+    /// `program`.
+    pub fn translate(&self, program: &str, args: &[String]) -> Vec<String> {
+        let mut translated = vec![program.to_string()];
+        let mut paths = Vec::new();
+        let mut has_config = false;
+        let mut only_paths = false;
+        let mut first_positional = true;
+
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            if !only_paths {
+                let git_option = match arg.as_str() {
+                    "--" => {
+                        only_paths = true;
+                        continue;
+                    }
+                    "--cached" | "--staged" => Some("--staged"),
+                    "-u" | "--untracked-files" => Some("--untracked"),
+                    "-a" | "--all" => Some("--all-files"),
+                    _ => None,
+                };
+                if let Some(option) = git_option {
+                    translated.push(option.to_string());
+                    continue;
+                }
+                if arg.starts_with('-') && arg.len() > 1 {
+                    has_config |= arg == "--config" || arg.starts_with("--config=");
+                    translated.push(arg.clone());
+                    if !arg.contains('=') && (self.takes_value)(arg) {
+                        translated.extend(args.next().cloned());
+                    }
+                    continue;
+                }
+                let first = std::mem::replace(&mut first_positional, false);
+                if first && !Path::new(arg).exists() && (self.is_subcommand)(arg) {
+                    translated.push(arg.clone());
+                    continue;
+                }
+                if !Path::new(arg).exists() && (self.is_revision)(arg) {
+                    translated.push("--revision".to_string());
+                    translated.push(arg.clone());
+                    continue;
+                }
+            }
+            match self.pathspec(arg) {
+                Pathspec::Include(path) => paths.push(path),
+                Pathspec::Exclude(path) => {
+                    translated.push("--exclude".to_string());
+                    translated.push(path);
+                }
+            }
+        }
+
+        // Git commands work from anywhere in the repo, so look for the config
+        // at the root unless there's one here.
+        if !has_config && !Path::new(".lintrunner.toml").exists() {
+            if let Some(repo_root) = &self.repo_root {
+                translated.push("--config".to_string());
+                translated.push(repo_root.join(".lintrunner.toml").display().to_string());
+            }
+        }
+        translated.extend(paths);
+        translated
+    }
+
+    fn pathspec(&self, spec: &str) -> Pathspec {
+        let (exclude, top, path) = parse_pathspec(spec);
+        let path = match (&self.repo_root, top) {
+            (Some(repo_root), true) => repo_root.join(path).display().to_string(),
+            _ => path.to_string(),
+        };
+        if exclude {
+            Pathspec::Exclude(path)
+        } else {
+            Pathspec::Include(path)
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum Pathspec {
+    Include(String),
+    Exclude(String),
+}
+
+/// Split a git pathspec into whether it excludes, whether it is relative to
+/// the top of the repo, and the path itself. Magic lintrunner doesn't need
+/// (like `glob` or `literal`) is dropped.
+fn parse_pathspec(spec: &str) -> (bool, bool, &str) {
+    if let Some(rest) = spec.strip_prefix(":(") {
+        if let Some((magic, path)) = rest.split_once(')') {
+            let has = |word| magic.split(',').any(|magic| magic.trim() == word);
+            return (has("exclude"), has("top"), path);
+        }
+    }
+    if let Some(rest) = spec.strip_prefix(':') {
+        let magic_len = rest
+            .find(|c| !matches!(c, '!' | '^' | '/'))
+            .unwrap_or(rest.len());
+        let (magic, path) = rest.split_at(magic_len);
+        let path = path.strip_prefix(':').unwrap_or(path);
+        return (magic.contains(['!', '^']), magic.contains('/'), path);
+    }
+    (false, false, spec)
+}
+
+/// Whether `arg` names a commit in the current repo.
+pub fn is_revision(arg: &str) -> bool {
+    Command::new("git")
+        .args(["rev-parse", "--verify", "--quiet"])
+        .arg(format!("{}^{{commit}}", arg))
+        .output()
+        .is_ok_and(|output| output.status.success())
+}
+
+/// When run through a git alias, git runs the command from the root of the
+/// repo, and sets GIT_PREFIX to where the user actually was. Go back there,
+/// so that paths are relative to the right directory.
+pub fn restore_git_prefix() -> Result<()> {
+    if let Ok(prefix) = std::env::var("GIT_PREFIX") {
+        if !prefix.is_empty() {
+            std::env::set_current_dir(&prefix)
+                .with_context(|| format!("Failed to change directory to '{}'", prefix))?;
+        }
+    }
+    Ok(())
+}
+
+/// Set up the `git lint` alias to run this binary.
+pub fn install_git_alias(local: bool) -> Result<i32> {
+    let exe = std::env::current_exe().context("Failed to find the lintrunner binary")?;
+    let alias = format!(
+        "!{} {}",
+        shell_words::quote(&exe.display().to_string()),
+        GIT_LINT_MARKER
+    );
+    let scope = if local { "--local" } else { "--global" };
+    let status = Command::new("git")
+        .args(["config", scope, "alias.lint", &alias])
+        .status()
+        .context("Failed to run git, is it installed?")?;
+    ensure!(status.success(), "Failed to set the git alias");
+    println!("Installed `git lint` ({} alias.lint = {})", scope, alias);
+    Ok(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn translate(args: &[&str]) -> Vec<String> {
+        let translator = Translator {
+            takes_value: &|arg| matches!(arg, "--take" | "-r"),
+            is_subcommand: &|arg| arg == "format",
+            is_revision: &|arg| arg.starts_with("HEAD"),
+            repo_root: Some(PathBuf::from("/repo")),
+        };
+        let args: Vec<String> = args.iter().map(|arg| arg.to_string()).collect();
+        let mut translated = translator.translate("lintrunner", &args);
+        // Whether the config is found depends on where the test runs.
+        if let Some(i) = translated.iter().position(|arg| arg == "--config") {
+            translated.drain(i..i + 2);
+        }
+        translated
+    }
+
+    #[test]
+    fn git_lint_is_detected() {
+        let args = |args: &[&str]| args.iter().map(|arg| arg.to_string()).collect::<Vec<_>>();
+        assert_eq!(
+            git_lint_args(&args(&["/usr/bin/git-lint", "-u"])),
+            Some(&args(&["-u"])[..])
+        );
+        assert_eq!(
+            git_lint_args(&args(&["lintrunner", GIT_LINT_MARKER, "--cached"])),
+            Some(&args(&["--cached"])[..])
+        );
+        assert_eq!(git_lint_args(&args(&["lintrunner", "--cached"])), None);
+    }
+
+    #[test]
+    fn git_args_are_translated() {
+        assert_eq!(
+            translate(&["--cached", "--take", "FLAKE8", "src", ":!src/vendor"]),
+            vec![
+                "lintrunner",
+                "--staged",
+                "--take",
+                "FLAKE8",
+                "--exclude",
+                "src/vendor",
+                "src"
+            ]
+        );
+        assert_eq!(
+            translate(&["format", "-u", "HEAD~2", "--", "HEAD", ":/docs"]),
+            vec![
+                "lintrunner",
+                "format",
+                "--untracked",
+                "--revision",
+                "HEAD~2",
+                "HEAD",
+                "/repo/docs"
+            ]
+        );
+    }
+
+    #[test]
+    fn pathspecs_are_parsed() {
+        assert_eq!(parse_pathspec("src/*.py"), (false, false, "src/*.py"));
+        assert_eq!(parse_pathspec(":^vendor"), (true, false, "vendor"));
+        assert_eq!(parse_pathspec(":!/:vendor"), (true, true, "vendor"));
+        assert_eq!(parse_pathspec(":(exclude,top)a/b"), (true, true, "a/b"));
+        assert_eq!(parse_pathspec(":(glob)**/*.rs"), (false, false, "**/*.rs"));
+    }
+}
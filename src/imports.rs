@@ -0,0 +1,302 @@
+//! Importing linter definitions shared between repos.
+//!
+//! A config can pull in linters from elsewhere with a top-level `import`:
+//! ```toml
+//! import = ['github:org/lint-configs//python.toml@v1']
+//! ```
+//! so that an organization can maintain one set of linter definitions that
+//! many repos use. Imported files are cached, and can be pinned to their
+//! BLAKE3 checksum:
+//! ```toml
+//! import = [{ source = 'github:org/lint-configs//python.toml@v1', checksum = '...' }]
+//! ```
+//! A pinned import is only fetched when the cached copy doesn't match the
+//! checksum, so it keeps working offline. Unpinned imports are fetched on
+//! every run, and the cached copy is only used if that fails.
+
+use std::path::{Path, PathBuf};
+
+use anyhow::{anyhow, ensure, Context, Result};
+use directories::ProjectDirs;
+use log::{debug, warn};
+use serde::Deserialize;
+
+use crate::{offline, publish::http_get};
+
+/// An entry of `import`: where to import from, optionally pinned to the
+/// checksum of the file.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(untagged)]
+pub enum ImportSpec {
+    Source(String),
+    Pinned {
+        source: String,
+        checksum: Option<String>,
+    },
+}
+
+impl ImportSpec {
+    /// Parse the value of `import`: one entry, or a list of them.
+    pub fn parse_all(value: toml::Value) -> Result<Vec<ImportSpec>> {
+        let values = match value {
+            toml::Value::Array(values) => values,
+            value => vec![value],
+        };
+        values
+            .into_iter()
+            .map(|value| {
+                value.try_into().context(
+                    "Each `import` must be a source, or a table with `source` and `checksum`",
+                )
+            })
+            .collect()
+    }
+
+    pub fn source(&self) -> &str {
+        match self {
+            ImportSpec::Source(source) | ImportSpec::Pinned { source, .. } => source,
+        }
+    }
+
+    fn checksum(&self) -> Option<&str> {
+        match self {
+            ImportSpec::Source(_) => None,
+            ImportSpec::Pinned { checksum, .. } => checksum.as_deref(),
+        }
+    }
+}
+
+/// Where an imported file lives.
+#[derive(Debug, PartialEq, Eq)]
+enum ImportSource {
+    /// `github:<owner>/<repo>//<path>@<ref>`
+    GitHub {
+        repo: String,
+        path: String,
+        rev: String,
+    },
+    /// An `http://` or `https://` URL.
+    Url(String),
+    /// A path, relative to the directory containing the config.
+    Path(PathBuf),
+}
+
+impl ImportSource {
+    fn parse(source: &str, config_dir: &Path) -> Result<ImportSource> {
+        if let Some(rest) = source.strip_prefix("github:") {
+            let (location, rev) = rest.rsplit_once('@').with_context(|| {
+                format!(
+                    "Import '{}' must name a tag, branch or commit after '@'",
+                    source
+                )
+            })?;
+            let (repo, path) = location.split_once("//").with_context(|| {
+                format!(
+                    "Import '{}' must be of the form github:owner/repo//path@ref",
+                    source
+                )
+            })?;
+            ensure!(
+                repo.split('/').count() == 2 && !path.is_empty() && !rev.is_empty(),
+                "Import '{}' must be of the form github:owner/repo//path@ref",
+                source
+            );
+            return Ok(ImportSource::GitHub {
+                repo: repo.to_string(),
+                path: path.to_string(),
+                rev: rev.to_string(),
+            });
+        }
+        if source.starts_with("https://") || source.starts_with("http://") {
+            return Ok(ImportSource::Url(source.to_string()));
+        }
+        Ok(ImportSource::Path(config_dir.join(source)))
+    }
+
+    fn url(&self) -> Option<String> {
+        match self {
+            ImportSource::GitHub { repo, path, rev } => Some(format!(
+                "https://raw.githubusercontent.com/{}/{}/{}",
+                repo, rev, path
+            )),
+            ImportSource::Url(url) => Some(url.clone()),
+            ImportSource::Path(_) => None,
+        }
+    }
+}
+
+/// Where fetched imports are cached.
+fn cache_dir() -> Result<PathBuf> {
+    let project_dirs = ProjectDirs::from("", "", "lintrunner")
+        .ok_or_else(|| anyhow!("Could not find project directories"))?;
+    Ok(project_dirs.cache_dir().join("imports"))
+}
+
+fn checksum(contents: &str) -> String {
+    blake3::hash(contents.as_bytes()).to_string()
+}
+
+/// Fail unless `contents` has the checksum `spec` is pinned to, if any.
+fn verify(spec: &ImportSpec, contents: &str) -> Result<()> {
+    if let Some(expected) = spec.checksum() {
+        let actual = checksum(contents);
+        ensure!(
+            actual == expected,
+            "Import '{}' has checksum {}, but the config pins it to {}. If the \
+             change is expected, update `checksum`.",
+            spec.source(),
+            actual,
+            expected
+        );
+    }
+    Ok(())
+}
+
+/// Where the file `spec` imports is written: its path, or for a remote
+/// import, its source.
+pub fn location(spec: &ImportSpec, config_dir: &Path) -> Result<PathBuf> {
+    Ok(match ImportSource::parse(spec.source(), config_dir)? {
+        ImportSource::Path(path) => path,
+        _ => PathBuf::from(spec.source()),
+    })
+}
+
+/// The contents of the file `spec` imports, relative to `config_dir`.
+pub fn load(spec: &ImportSpec, config_dir: &Path) -> Result<String> {
+    match ImportSource::parse(spec.source(), config_dir)? {
+        ImportSource::Path(path) => {
+            let contents = std::fs::read_to_string(&path)
+                .with_context(|| format!("Failed to read import '{}'", path.display()))?;
+            verify(spec, &contents)?;
+            Ok(contents)
+        }
+        source => {
+            // Unwrap is fine because only paths have no URL.
+            let url = source.url().unwrap();
+            let github_token = match source {
+                ImportSource::GitHub { .. } => std::env::var("GITHUB_TOKEN").ok(),
+                _ => None,
+            };
+            load_remote(spec, &cache_dir()?, || fetch(&url, github_token.as_deref()))
+        }
+    }
+}
+
+/// The contents of a remote import, from the cache in `cache_dir` or from
+/// `fetch`, which updates the cache.
+fn load_remote(
+    spec: &ImportSpec,
+    cache_dir: &Path,
+    fetch: impl FnOnce() -> Result<String>,
+) -> Result<String> {
+    let cache_path = cache_dir.join(format!("{}.toml", checksum(spec.source())));
+    let cached = std::fs::read_to_string(&cache_path).ok();
+
+    if let (Some(cached), Some(_)) = (&cached, spec.checksum()) {
+        if verify(spec, cached).is_ok() {
+            debug!("Using cached import '{}'", spec.source());
+            return Ok(cached.clone());
+        }
+    }
+    let fetched = match (fetch(), cached) {
+        (Ok(fetched), _) => fetched,
+        (Err(err), Some(cached)) if spec.checksum().is_none() => {
+            // With --offline, using the cache is expected.
+            if offline::is_offline() {
+                debug!("Using cached import '{}': {}", spec.source(), err);
+            } else {
+                warn!(
+                    "Using the cached copy of import '{}', as fetching it failed: {}",
+                    spec.source(),
+                    err
+                );
+            }
+            return Ok(cached);
+        }
+        (Err(err), _) => return Err(err),
+    };
+    verify(spec, &fetched)?;
+
+    std::fs::create_dir_all(cache_dir)?;
+    std::fs::write(&cache_path, &fetched)
+        .with_context(|| format!("Failed to cache import at '{}'", cache_path.display()))?;
+    Ok(fetched)
+}
+
+/// Fetch an import from `url`.
+fn fetch(url: &str, github_token: Option<&str>) -> Result<String> {
+    offline::ensure_online(&format!("Importing '{}'", url))?;
+    debug!("Fetching import from {}", url);
+    let authorization = github_token.map(|token| format!("token {}", token));
+    let headers: Vec<(&str, &str)> = authorization
+        .iter()
+        .map(|authorization| ("Authorization", authorization.as_str()))
+        .collect();
+    let contents =
+        http_get(url, &headers).with_context(|| format!("Failed to fetch import '{}'", url))?;
+    String::from_utf8(contents).context("Imported file is not valid UTF-8")
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::bail;
+
+    use super::*;
+
+    #[test]
+    fn sources_are_parsed() -> Result<()> {
+        let dir = Path::new("/repo");
+        let source = ImportSource::parse("github:org/lint-configs//python/flake8.toml@v1", dir)?;
+        assert_eq!(
+            source.url().as_deref(),
+            Some("https://raw.githubusercontent.com/org/lint-configs/v1/python/flake8.toml")
+        );
+        assert_eq!(
+            ImportSource::parse("tools/lint.toml", dir)?,
+            ImportSource::Path(PathBuf::from("/repo/tools/lint.toml"))
+        );
+        assert!(ImportSource::parse("github:org/lint-configs//python.toml", dir).is_err());
+        assert!(ImportSource::parse("github:org//python.toml@v1", dir).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn pinned_imports_are_cached() -> Result<()> {
+        let cache_dir = tempfile::tempdir()?;
+        let contents = "[[linter]]\n";
+        let pinned = ImportSpec::Pinned {
+            source: "https://example.com/lint.toml".to_string(),
+            checksum: Some(checksum(contents)),
+        };
+        let unreachable = || -> Result<String> { bail!("no network") };
+
+        // The first load fetches, later ones don't need to.
+        assert!(load_remote(&pinned, cache_dir.path(), unreachable).is_err());
+        let loaded = load_remote(&pinned, cache_dir.path(), || Ok(contents.to_string()))?;
+        assert_eq!(loaded, contents);
+        assert_eq!(
+            load_remote(&pinned, cache_dir.path(), unreachable)?,
+            contents
+        );
+
+        // Changed contents don't match the pin.
+        let changed = ImportSpec::Pinned {
+            source: "https://example.com/lint.toml".to_string(),
+            checksum: Some(checksum("changed")),
+        };
+        let err = load_remote(&changed, cache_dir.path(), || Ok(contents.to_string()))
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("update `checksum`"), "{}", err);
+
+        // Unpinned imports are fetched again, but fall back to the cache.
+        let unpinned = ImportSpec::Source("https://example.com/lint.toml".to_string());
+        let loaded = load_remote(&unpinned, cache_dir.path(), || Ok("new".to_string()))?;
+        assert_eq!(loaded, "new");
+        assert_eq!(
+            load_remote(&unpinned, cache_dir.path(), unreachable)?,
+            "new"
+        );
+        Ok(())
+    }
+}
@@ -1,6 +1,15 @@
-use crate::{lint_config::LintRunnerConfig, persistent_data::PersistentDataStore};
+use crate::{
+    lint_config::LintRunnerConfig,
+    linter::Linter,
+    persistent_data::PersistentDataStore,
+    streams,
+    verify::{find_problems, InitState},
+};
 use anyhow::Result;
 use console::{style, Term};
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+use std::path::PathBuf;
 
 // Check whether or not the currently configured init commands are different
 // from the last time we ran `init`, and warn the user if so.
@@ -8,7 +17,7 @@ pub fn check_init_changed(
     persistent_data_store: &PersistentDataStore,
     current_config: &LintRunnerConfig,
 ) -> Result<()> {
-    let stderr = Term::stderr();
+    let stderr = streams::diagnostics().term();
 
     let last_init = persistent_data_store.last_init()?;
     if last_init.is_none() {
@@ -26,14 +35,20 @@ pub fn check_init_changed(
     let last_init = last_init.unwrap();
     let old_config = LintRunnerConfig::new_from_string(&last_init)?;
 
-    let old_init_commands: Vec<_> = old_config.linters.iter().map(|l| &l.init_command).collect();
-    let current_init_commands: Vec<_> = current_config
+    // Only linters that are new or whose init commands changed need to be
+    // initialized again; removing a linter (e.g. in the local config) doesn't.
+    let old_init_commands: HashMap<_, _> = old_config
         .linters
         .iter()
-        .map(|l| &l.init_command)
+        .map(|l| (&l.code, &l.init_command))
         .collect();
+    let init_changed = current_config.linters.iter().any(|l| {
+        old_init_commands
+            .get(&l.code)
+            .map_or(l.init_command.is_some(), |old| *old != &l.init_command)
+    });
 
-    if old_init_commands != current_init_commands {
+    if init_changed {
         stderr.write_line(&format!(
             "{}",
             style(
@@ -47,3 +62,239 @@ pub fn check_init_changed(
 
     Ok(())
 }
+
+/// What `lintrunner init` will do for a linter.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "action", rename_all = "snake_case")]
+pub enum InitAction {
+    /// Initialize the linter, for these reasons.
+    Run { reasons: Vec<String> },
+    /// Skip the linter, since nothing changed since it was initialized.
+    UpToDate,
+    /// The linter has no `init_command` and no container.
+    NothingToDo,
+}
+
+/// One linter's part of an [`InitPlan`].
+#[derive(Debug, Serialize)]
+pub struct InitStep {
+    pub code: String,
+    #[serde(flatten)]
+    pub action: InitAction,
+    /// The container image that will be pulled, if any.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub container: Option<String>,
+    /// The init command, with placeholders substituted as for a real run.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub command: Option<Vec<String>>,
+    pub working_directory: PathBuf,
+    /// The linters initialized before this one because of its `run_after`.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub run_after: Vec<String>,
+}
+
+/// Which linters `lintrunner init` initializes, in which order, and how.
+#[derive(Debug, Serialize)]
+pub struct InitPlan {
+    pub steps: Vec<InitStep>,
+}
+
+impl InitPlan {
+    /// Work out how to initialize `linters`, given the state each was last
+    /// initialized in. Linters come after the linters in their `run_after`,
+    /// and otherwise in config order. With `force`, every linter with
+    /// something to do is initialized, even if it is up to date.
+    pub fn new(
+        linters: &[Linter],
+        recorded: &BTreeMap<String, InitState>,
+        force: bool,
+    ) -> InitPlan {
+        let steps = init_order(linters)
+            .into_iter()
+            .map(|index| {
+                let linter = &linters[index];
+                let recorded = recorded.get(&linter.code);
+                let action = if linter.init_commands.is_none() && linter.container.is_none() {
+                    InitAction::NothingToDo
+                } else if force {
+                    InitAction::Run {
+                        reasons: vec!["--force was passed".to_string()],
+                    }
+                } else {
+                    let mut reasons = find_problems(linter, recorded);
+                    if reasons.is_empty() && recorded.is_none() {
+                        reasons.push("never initialized".to_string());
+                    }
+                    if linter.container.is_some() {
+                        reasons.push("its container is pulled every time".to_string());
+                    }
+                    if reasons.is_empty() {
+                        InitAction::UpToDate
+                    } else {
+                        InitAction::Run { reasons }
+                    }
+                };
+                InitStep {
+                    code: linter.code.clone(),
+                    action,
+                    container: linter.container.clone(),
+                    command: linter.init_command_args(false),
+                    working_directory: linter.get_config_dir().to_path_buf(),
+                    run_after: linter
+                        .run_after
+                        .iter()
+                        .filter(|code| linters.iter().any(|l| &l.code == *code))
+                        .cloned()
+                        .collect(),
+                }
+            })
+            .collect();
+        InitPlan { steps }
+    }
+
+    /// Print the plan for people to read.
+    pub fn print(&self, stdout: &Term) -> Result<()> {
+        let width = self
+            .steps
+            .iter()
+            .map(|step| step.code.len())
+            .max()
+            .unwrap_or(0);
+        for (i, step) in self.steps.iter().enumerate() {
+            let action = match &step.action {
+                InitAction::Run { reasons } => {
+                    format!("{} ({})", style("run").green(), reasons.join(", "))
+                }
+                InitAction::UpToDate => format!("{}", style("up to date, skipped").dim()),
+                InitAction::NothingToDo => format!("{}", style("nothing to do").dim()),
+            };
+            stdout.write_line(&format!(
+                "{:>3}. {:width$}  {}",
+                i + 1,
+                style(&step.code).bold(),
+                action,
+                width = width
+            ))?;
+            if !matches!(step.action, InitAction::Run { .. }) {
+                continue;
+            }
+            let detail = |label: &str, value: String| {
+                stdout.write_line(&format!("     {} {}", style(label).dim(), value))
+            };
+            if !step.run_after.is_empty() {
+                detail("after:", step.run_after.join(", "))?;
+            }
+            if let Some(container) = &step.container {
+                detail("pull:", container.clone())?;
+            }
+            if let Some(command) = &step.command {
+                detail("command:", shell_words::join(command))?;
+                detail(
+                    "working directory:",
+                    step.working_directory.display().to_string(),
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// The order to initialize `linters` in, as indices: each linter after the
+/// linters in its `run_after`, and otherwise in the order they were given.
+fn init_order(linters: &[Linter]) -> Vec<usize> {
+    let mut order: Vec<usize> = Vec::with_capacity(linters.len());
+    let mut placed = vec![false; linters.len()];
+    while order.len() < linters.len() {
+        let ready = (0..linters.len()).find(|&index| {
+            !placed[index]
+                && linters[index].run_after.iter().all(|code| {
+                    linters
+                        .iter()
+                        .zip(&placed)
+                        .all(|(linter, placed)| &linter.code != code || *placed)
+                })
+        });
+        // The config is checked for cycles, but don't loop forever if one
+        // slips through.
+        let index = ready.unwrap_or_else(|| placed.iter().position(|p| !p).unwrap());
+        placed[index] = true;
+        order.push(index);
+    }
+    order
+}
+
+#[cfg(test)]
+mod tests {
+    use std::convert::TryFrom;
+
+    use super::*;
+    use crate::{lint_config::get_linters_from_config, path::AbsPath};
+
+    #[test]
+    fn plan_is_ordered_and_skips_up_to_date_linters() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let config_path = dir.path().join(".lintrunner.toml");
+        let config = "\
+            [[linter]]
+            code = 'B'
+            include_patterns = ['**']
+            command = ['sh', '-c', 'true']
+            init_command = ['sh', '-c', 'true', '--dry-run={{DRYRUN}}']
+            run_after = ['C']
+
+            [[linter]]
+            code = 'A'
+            include_patterns = ['**']
+            command = ['sh', '-c', 'true']
+            init_command = ['sh', '-c', 'true', '{{DRYRUN}}']
+
+            [[linter]]
+            code = 'C'
+            include_patterns = ['**']
+            command = ['sh', '-c', 'true']
+            ";
+        std::fs::write(&config_path, config)?;
+        let config = LintRunnerConfig::new_from_string(config)?;
+        let linters = get_linters_from_config(
+            &config.linters,
+            None,
+            None,
+            &AbsPath::try_from(config_path.as_path())?,
+        )?;
+        let mut recorded = BTreeMap::new();
+        recorded.insert("A".to_string(), InitState::capture(&linters[1]));
+
+        let plan = InitPlan::new(&linters, &recorded, false);
+        let steps: Vec<_> = plan
+            .steps
+            .iter()
+            .map(|step| (step.code.as_str(), step.action.clone()))
+            .collect();
+        assert_eq!(
+            steps,
+            [
+                ("A", InitAction::UpToDate),
+                ("C", InitAction::NothingToDo),
+                (
+                    "B",
+                    InitAction::Run {
+                        reasons: vec!["never initialized".to_string()]
+                    }
+                ),
+            ]
+        );
+        // The plan shows the command a real run would use.
+        assert_eq!(
+            plan.steps[2].command.as_deref(),
+            Some(&["sh", "-c", "true", "--dry-run=0"].map(String::from)[..])
+        );
+        assert_eq!(plan.steps[2].run_after, ["C"]);
+
+        let plan = InitPlan::new(&linters, &recorded, true);
+        assert!(matches!(plan.steps[0].action, InitAction::Run { .. }));
+        let json = serde_json::to_value(&plan)?;
+        assert_eq!(json["steps"][0]["action"], "run");
+        assert_eq!(json["steps"][1]["action"], "nothing_to_do");
+        Ok(())
+    }
+}
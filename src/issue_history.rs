@@ -0,0 +1,191 @@
+//! Tracking how long issues have been around.
+//!
+//! Every run records when, and at which commit, each issue it found (by
+//! fingerprint, see [`crate::baseline`]) was first seen. Issues found again
+//! are reported with that `first_seen`, `lintrunner stats --ages` summarizes
+//! the ages of each linter's issues, and a linter's `escalate_after_days`
+//! turns warnings that have been ignored for too long into errors. Issues no
+//! run has found for a year are forgotten, to keep the history small.
+
+use std::collections::{BTreeMap, HashMap};
+
+use chrono::{DateTime, Duration, SecondsFormat, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    baseline::FINGERPRINT_VERSION,
+    lint_message::{FirstSeen, LintMessage, LintSeverity},
+};
+
+const FORGET_AFTER_DAYS: i64 = 365;
+
+/// When each issue was first and last found, by linter and fingerprint.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct IssueHistory {
+    /// How the fingerprints were computed. History recorded with other
+    /// fingerprints is ignored.
+    #[serde(default)]
+    fingerprint_version: u32,
+    linters: BTreeMap<String, BTreeMap<String, IssueRecord>>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct IssueRecord {
+    first_seen: FirstSeen,
+    /// In RFC 3339 format.
+    last_seen: String,
+}
+
+impl IssueHistory {
+    fn linter(&self, code: &str) -> Option<&BTreeMap<String, IssueRecord>> {
+        if self.fingerprint_version != FINGERPRINT_VERSION {
+            return None;
+        }
+        self.linters.get(code)
+    }
+
+    /// Fill in when each of `lints`, reported by `code`, was first seen, and
+    /// make warnings first seen at least `escalate_after_days` ago errors.
+    /// The lints must have their fingerprints.
+    pub fn apply(
+        &self,
+        code: &str,
+        lints: &mut [LintMessage],
+        escalate_after_days: Option<u32>,
+        now: DateTime<Utc>,
+    ) {
+        let Some(records) = self.linter(code) else {
+            return;
+        };
+        for lint in lints {
+            let Some(record) = lint
+                .fingerprint
+                .as_ref()
+                .and_then(|fingerprint| records.get(fingerprint))
+            else {
+                continue;
+            };
+            let first_seen = record.first_seen.clone();
+            let too_old = match (escalate_after_days, first_seen.age(now)) {
+                (Some(days), Some(age)) => age >= Duration::days(days.into()),
+                _ => false,
+            };
+            if too_old && lint.severity == LintSeverity::Warning {
+                lint.severity = LintSeverity::Error;
+            }
+            lint.first_seen = Some(first_seen);
+        }
+    }
+
+    /// Record the issues found in a run at `commit`, given as the
+    /// fingerprints of each linter's issues.
+    pub fn record(
+        &mut self,
+        lint_fingerprints: &HashMap<String, Vec<String>>,
+        commit: Option<&str>,
+        now: DateTime<Utc>,
+    ) {
+        if self.fingerprint_version != FINGERPRINT_VERSION {
+            self.fingerprint_version = FINGERPRINT_VERSION;
+            self.linters.clear();
+        }
+        let timestamp = now.to_rfc3339_opts(SecondsFormat::Secs, true);
+        for (code, fingerprints) in lint_fingerprints {
+            let records = self.linters.entry(code.clone()).or_default();
+            for fingerprint in fingerprints {
+                records
+                    .entry(fingerprint.clone())
+                    .or_insert_with(|| IssueRecord {
+                        first_seen: FirstSeen {
+                            timestamp: timestamp.clone(),
+                            commit: commit.map(str::to_string),
+                        },
+                        last_seen: String::new(),
+                    })
+                    .last_seen = timestamp.clone();
+            }
+        }
+
+        let forget_before = now - Duration::days(FORGET_AFTER_DAYS);
+        for records in self.linters.values_mut() {
+            records.retain(|_, record| {
+                DateTime::parse_from_rfc3339(&record.last_seen)
+                    .is_ok_and(|last_seen| last_seen >= forget_before)
+            });
+        }
+        self.linters.retain(|_, records| !records.is_empty());
+    }
+
+    /// The ages of the issues each linter found the last time it ran.
+    pub fn ages(&self, now: DateTime<Utc>) -> BTreeMap<&str, Vec<Duration>> {
+        if self.fingerprint_version != FINGERPRINT_VERSION {
+            return BTreeMap::new();
+        }
+        self.linters
+            .iter()
+            .map(|(code, records)| {
+                // Unwrap is fine because empty linters are never kept.
+                let last_run = records
+                    .values()
+                    .map(|record| &record.last_seen)
+                    .max()
+                    .unwrap();
+                let ages = records
+                    .values()
+                    .filter(|record| &record.last_seen == last_run)
+                    .filter_map(|record| record.first_seen.age(now))
+                    .collect();
+                (code.as_str(), ages)
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn lint(fingerprint: &str) -> LintMessage {
+        LintMessage {
+            path: Some("a.py".to_string()),
+            line: Some(1),
+            fingerprint: Some(fingerprint.to_string()),
+            ..LintMessage::for_test("TEST", LintSeverity::Warning, "bad")
+        }
+    }
+
+    #[test]
+    fn old_warnings_are_escalated() {
+        let start = Utc.with_ymd_and_hms(2024, 1, 1, 0, 0, 0).unwrap();
+        let later = start + Duration::days(100);
+        let fingerprints = |fingerprints: &[&str]| {
+            HashMap::from([(
+                "TEST".to_string(),
+                fingerprints.iter().map(|f| f.to_string()).collect(),
+            )])
+        };
+        let mut history = IssueHistory::default();
+        history.record(&fingerprints(&["old"]), Some("abc"), start);
+        history.record(&fingerprints(&["old", "new"]), Some("def"), later);
+
+        let mut lints = vec![lint("old"), lint("new"), lint("unknown")];
+        history.apply("TEST", &mut lints, Some(90), later);
+        assert_eq!(lints[0].severity, LintSeverity::Error);
+        assert_eq!(
+            lints[0].first_seen.as_ref().unwrap().commit.as_deref(),
+            Some("abc")
+        );
+        assert_eq!(lints[1].severity, LintSeverity::Warning);
+        assert!(lints[1].first_seen.is_some());
+        assert!(lints[2].first_seen.is_none());
+
+        let ages = history.ages(later);
+        assert_eq!(ages["TEST"].len(), 2);
+        assert!(ages["TEST"].contains(&Duration::days(100)));
+
+        // Issues not seen for a year are forgotten.
+        history.record(&fingerprints(&["new"]), None, later + Duration::days(400));
+        assert_eq!(history.linters["TEST"].len(), 1);
+    }
+}
@@ -1,37 +1,105 @@
-use anyhow::{bail, Context, Result};
+use anyhow::{bail, ensure, Context, Result};
+use baseline::{Baseline, RunLints};
 use clap::ArgEnum;
 use console::{style, Term};
-use indicatif::{MultiProgress, ProgressBar};
-use linter::Linter;
-use log::debug;
-use path::AbsPath;
+use flaky::LinterFingerprint;
+use indicatif::ProgressBar;
+use init::{InitAction, InitPlan};
+use issue_history::IssueHistory;
+use lint_config::find_pattern_line;
+use linter::{
+    BudgetOverrun, Granularity, Linter, LinterFailure, MatchReason, PatternMatch, SkipReason,
+};
+use log::{debug, warn};
+use metrics::{export_metrics, MetricsSink};
+use parallel::walk_dir;
+use path::{dedup_case_aliases, get_display_path, AbsPath};
 use persistent_data::PersistentDataStore;
-use render::{render_lint_messages, render_lint_messages_json};
-use std::collections::HashMap;
+use progress::{LintProgress, ProgressOpt};
+use publish::{notify_webhook, Webhook};
+use render::{
+    render_lint_messages, render_lint_messages_arc, render_lint_messages_azure,
+    render_lint_messages_buildkite, render_lint_messages_csv, render_lint_messages_gerrit,
+    render_lint_messages_github, render_lint_messages_json, render_lint_messages_json_pretty,
+    render_lint_messages_json_v1, render_lint_messages_sonarqube, render_lint_messages_summary,
+    render_lint_messages_teamcity,
+    sink::{RenderEvent, RenderSink},
+};
+use scheduler::Scheduler;
+use serde::Deserialize;
 use std::collections::HashSet;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
 use std::convert::TryFrom;
-use std::fs::OpenOptions;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
 use std::sync::{Arc, Mutex};
 use std::thread;
+use std::time::{Duration, Instant};
+use tee::TeeJson;
+use verify::InitState;
 
+pub mod baseline;
+pub mod builtin;
+pub mod cancel;
+pub mod ci;
+pub mod clean;
+pub mod discover;
+pub mod file_timing;
+pub mod fixtures;
+pub mod flaky;
+pub mod format_check;
 pub mod git;
+pub mod git_lint;
+pub mod imports;
 pub mod init;
+pub mod issue_history;
 pub mod lint_config;
 pub mod lint_message;
 pub mod linter;
 pub mod log_utils;
+pub mod matcher;
+pub mod metrics;
+pub mod mock;
+pub mod normalize;
+pub mod offline;
+pub mod parallel;
+pub mod patches;
 pub mod path;
 pub mod persistent_data;
+pub mod process_tree;
+pub mod progress;
+pub mod publish;
+pub mod query;
 pub mod rage;
 pub mod render;
+pub mod runner;
+pub mod sandbox;
+pub mod scaffold;
+pub mod scheduler;
+pub mod shell;
+pub mod stats;
+pub mod streams;
+pub mod tee;
+pub mod theme;
+pub mod user_config;
+pub mod verify;
+pub mod version;
+pub mod watchdog;
 
 use git::get_changed_files;
+use git::get_files_changed_since;
 use git::get_git_root;
+use git::get_head;
 use git::get_paths_from_cmd;
-use lint_message::LintMessage;
+use git::get_staged_files;
+use git::get_tracked_files_in;
+use git::get_untracked_files;
+use lint_message::{merge_fixes, LintMessage, LintSeverity};
 use render::PrintedLintErrors;
 
-use crate::git::get_merge_base_with;
+use crate::git::{get_default_merge_base, get_merge_base_with, resolve_revision};
+use crate::linter::matches_relative_path;
+use crate::matcher::{LinterMatcher, PatternOptions};
 use crate::render::render_lint_messages_oneline;
 
 fn group_lints_by_file(
@@ -46,32 +114,40 @@ fn group_lints_by_file(
     });
 }
 
-fn apply_patches(lint_messages: &[LintMessage]) -> Result<()> {
-    let mut patched_paths = HashSet::new();
-    for lint_message in lint_messages {
-        if let (Some(replacement), Some(path)) = (&lint_message.replacement, &lint_message.path) {
-            let path = AbsPath::try_from(path)?;
-            if patched_paths.contains(&path) {
-                bail!(
-                    "Two different linters proposed changes for the same file:
-                    {}.\n This is not yet supported, file an issue if you want it.",
-                    path.display()
-                );
-            }
-            patched_paths.insert(path.clone());
+/// The new contents of every file the fixes in `lint_messages` change.
+fn fixes_to_apply<'a>(
+    lint_messages: impl IntoIterator<Item = &'a LintMessage>,
+) -> Result<Vec<(AbsPath, String)>> {
+    merge_fixes(lint_messages)?
+        .into_iter()
+        .map(|fix| Ok((AbsPath::try_from(&fix.path)?, fix.replacement)))
+        .collect()
+}
 
-            std::fs::write(&path, replacement).context(format!(
-                "Failed to write apply patch to file: '{}'",
+/// Write the fixes of every linter at once, so that they are applied
+/// entirely or not at all, even when one spans several files.
+fn apply_fixes(fixes: &[(AbsPath, String)]) -> Result<()> {
+    let mut patched_paths = HashSet::new();
+    for (path, _) in fixes {
+        if !patched_paths.insert(path) {
+            bail!(
+                "Two different linters proposed changes for the same file:
+                {}.\n This is not yet supported, file an issue if you want it.",
                 path.display()
-            ))?;
+            );
         }
     }
-    Ok(())
+    patches::write_fixes(fixes)
 }
 
+/// Initialize `linters`, skipping those that are up to date unless `force`.
+/// With `dry_run`, print the plan first (only the plan, as JSON, if
+/// `json_plan`) and run the init commands in dry-run mode.
 pub fn do_init(
     linters: Vec<Linter>,
     dry_run: bool,
+    force: bool,
+    json_plan: bool,
     persistent_data_store: &PersistentDataStore,
     config_path: &AbsPath,
 ) -> Result<i32> {
@@ -80,19 +156,47 @@ pub fn do_init(
         linters.iter().map(|l| &l.code).collect::<Vec<_>>()
     );
 
-    for linter in linters {
+    let plan = InitPlan::new(&linters, &persistent_data_store.init_states()?, force);
+    if dry_run {
+        let stdout = Term::stdout();
+        if json_plan {
+            stdout.write_line(&serde_json::to_string_pretty(&plan)?)?;
+            return Ok(0);
+        }
+        plan.print(&stdout)?;
+    }
+
+    let linters: HashMap<&str, &Linter> = linters.iter().map(|l| (l.code.as_str(), l)).collect();
+    let mut init_states = BTreeMap::new();
+    for step in &plan.steps {
+        let linter = linters[step.code.as_str()];
+        if step.action == InitAction::UpToDate {
+            if !dry_run {
+                streams::diagnostics().term().write_line(&format!(
+                    "{} is up to date, skipping it (pass --force to initialize it anyway)",
+                    linter.code
+                ))?;
+            }
+            continue;
+        }
         linter.init(dry_run)?;
+        if !dry_run {
+            init_states.insert(linter.code.clone(), InitState::capture(linter));
+        }
     }
 
     persistent_data_store.update_last_init(config_path)?;
+    persistent_data_store.update_init_states(init_states)?;
 
     Ok(0)
 }
 
-fn remove_patchable_lints(lints: Vec<LintMessage>) -> Vec<LintMessage> {
+/// Drop the lints whose fixes were applied, i.e. those with a fix that
+/// `fix_filter` keeps.
+fn remove_patchable_lints(lints: Vec<LintMessage>, fix_filter: &CodeFilter) -> Vec<LintMessage> {
     lints
         .into_iter()
-        .filter(|lint| lint.replacement.is_none())
+        .filter(|lint| !(lint.has_fix() && fix_filter.keeps(lint)))
         .collect()
 }
 
@@ -106,6 +210,58 @@ fn get_paths_from_input(paths: Vec<String>) -> Result<Vec<AbsPath>> {
     Ok(ret)
 }
 
+fn is_glob(path: &str) -> bool {
+    path.contains(['*', '?', '['])
+}
+
+// Expand paths provided on the command line: directories are recursed into
+// (only considering files tracked by git, if possible) and globs are expanded.
+fn expand_paths_from_input(paths: Vec<String>) -> Result<Vec<AbsPath>> {
+    let mut ret = Vec::new();
+    for path in paths {
+        if is_glob(&path) && !Path::new(&path).exists() {
+            let mut matched = false;
+            for entry in glob::glob(&path)
+                .with_context(|| format!("Invalid glob pattern provided: '{}'", path))?
+            {
+                let entry = entry?;
+                if entry.is_file() {
+                    matched = true;
+                    ret.push(AbsPath::try_from(entry)?);
+                } else if entry.is_dir() {
+                    matched = true;
+                    ret.extend(get_paths_from_dir(AbsPath::try_from(entry)?)?);
+                }
+            }
+            if !matched {
+                warn!("No files matched the provided pattern: '{}'", path);
+            }
+            continue;
+        }
+
+        let abs_path = AbsPath::try_from(&path)
+            .with_context(|| format!("Failed to find provided file: '{}'", path))?;
+        if abs_path.is_dir() {
+            ret.extend(get_paths_from_dir(abs_path)?);
+        } else {
+            ret.push(abs_path);
+        }
+    }
+    Ok(ret)
+}
+
+fn get_paths_from_dir(dir: AbsPath) -> Result<Vec<AbsPath>> {
+    let files = match get_tracked_files_in(&dir)? {
+        Some(files) => files,
+        None => walk_dir(&dir)?,
+    };
+    log_utils::log_files(
+        &format!("Expanded directory '{}' to: ", dir.display()),
+        &files,
+    );
+    Ok(files)
+}
+
 fn get_paths_from_file(file: AbsPath) -> Result<Vec<AbsPath>> {
     let file = std::fs::read_to_string(&file).with_context(|| {
         format!(
@@ -121,19 +277,91 @@ fn get_paths_from_file(file: AbsPath) -> Result<Vec<AbsPath>> {
     get_paths_from_input(files)
 }
 
+fn split_paths(contents: &str, null_delimited: bool) -> Vec<String> {
+    let paths: Vec<&str> = if null_delimited {
+        contents.split('\0').collect()
+    } else {
+        contents.lines().collect()
+    };
+    paths
+        .into_iter()
+        .filter(|p| !p.is_empty())
+        .map(|p| p.to_string())
+        .collect()
+}
+
+fn get_paths_from_stdin(null_delimited: bool) -> Result<Vec<AbsPath>> {
+    let mut contents = String::new();
+    std::io::stdin()
+        .read_to_string(&mut contents)
+        .context("Failed to read paths from stdin")?;
+    get_paths_from_input(split_paths(&contents, null_delimited))
+}
+
+// Drop any files matching `--exclude`. Each exclusion can be a path to a file
+// or directory, or a glob pattern relative to the current working directory.
+fn exclude_paths(files: Vec<AbsPath>, excludes: &[String]) -> Result<Vec<AbsPath>> {
+    if excludes.is_empty() {
+        return Ok(files);
+    }
+    let current_dir = AbsPath::try_from(std::env::current_dir()?)?;
+
+    let mut excluded_paths = Vec::new();
+    let mut excluded_patterns = Vec::new();
+    for exclude in excludes {
+        match AbsPath::try_from(exclude) {
+            Ok(path) => excluded_paths.push(path),
+            Err(_) => excluded_patterns.push(glob::Pattern::new(exclude).with_context(|| {
+                format!(
+                    "Invalid path or pattern provided to --exclude: '{}'",
+                    exclude
+                )
+            })?),
+        }
+    }
+
+    let (excluded, files): (Vec<_>, Vec<_>) = files.into_iter().partition(|file| {
+        excluded_paths.iter().any(|path| file.starts_with(path))
+            || excluded_patterns.iter().any(|pattern| {
+                if Path::new(pattern.as_str()).is_absolute() {
+                    matches_relative_path(Path::new(""), file, pattern, PatternOptions::default())
+                } else {
+                    matches_relative_path(&current_dir, file, pattern, PatternOptions::default())
+                }
+            })
+    });
+    log_utils::log_files("Excluding files: ", &excluded);
+    Ok(files)
+}
+
 /// Represents the set of paths the user wants to lint.
+#[derive(Debug, Clone)]
 pub enum PathsOpt {
     /// The user didn't specify any paths, so we'll automatically determine
     /// which paths to check.
     Auto,
+    /// Like `Auto`, plus untracked files that aren't ignored.
+    AutoWithUntracked,
     AllFiles,
     PathsFile(AbsPath),
     PathsCmd(String),
     Paths(Vec<String>),
+    /// Read paths from stdin, either newline or NUL delimited.
+    PathsStdin {
+        null_delimited: bool,
+    },
+    /// Lint the source files of these build system targets, as reported by
+    /// running `query` in `dir`.
+    Targets {
+        targets: Vec<String>,
+        query: Vec<String>,
+        dir: AbsPath,
+    },
 }
 
 /// Represents the scope of revisions that the auto paths finder will look at to
 /// determine which paths to lint.
+#[derive(Debug, Clone)]
 pub enum RevisionOpt {
     /// Look at changes in HEAD and changes in the working tree.
     Head,
@@ -141,141 +369,1013 @@ pub enum RevisionOpt {
     Revision(String),
     /// Look at changes from merge_base(revision, HEAD)..HEAD and changes in the working tree.
     MergeBaseWith(String),
+    /// Look at changes in commits newer than this date.
+    Since(String),
+    /// Like `MergeBaseWith`, with the remote's default branch. Falls back to
+    /// `Head` if there is no such branch or no merge base with it.
+    DefaultBranch,
+    /// Look at changes staged in the index.
+    Staged,
 }
 
-#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, ArgEnum)]
+#[derive(
+    Debug,
+    Copy,
+    Clone,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    ArgEnum,
+    Deserialize
+)]
+#[serde(rename_all = "lowercase")]
 pub enum RenderOpt {
     Default,
+    /// A header with the schema version, then one lint message per line
     Json,
+    /// One lint message per line, as before the schema was versioned
+    #[serde(rename = "json-v1")]
+    JsonV1,
+    /// A single indented JSON document, with messages in a stable order
+    #[serde(rename = "json-pretty")]
+    JsonPretty,
     Oneline,
+    /// Robot comments for `gerrit review --json`
+    Gerrit,
+    /// Arcanist's lint message JSON, one line per file
+    Arc,
+    /// TeamCity inspection service messages
+    Teamcity,
+    /// Azure Pipelines logging commands
+    Azure,
+    /// GitHub Actions workflow commands
+    Github,
+    /// Markdown for `buildkite-agent annotate`
+    Buildkite,
+    /// SonarQube's generic issue import JSON
+    Sonarqube,
+    /// Only the number of issues per linter and severity, and the verdict
+    Summary,
+    /// A table with one row per issue, for spreadsheets
+    Csv,
 }
 
-pub fn do_lint(
-    linters: Vec<Linter>,
-    paths_opt: PathsOpt,
-    should_apply_patches: bool,
+impl RenderOpt {
+    /// Whether the output can be printed piecemeal as linters finish, rather
+    /// than as a single document at the end.
+    pub fn can_stream(self) -> bool {
+        !matches!(
+            self,
+            RenderOpt::JsonPretty
+                | RenderOpt::Gerrit
+                | RenderOpt::Buildkite
+                | RenderOpt::Sonarqube
+                | RenderOpt::Summary
+                | RenderOpt::Csv
+        )
+    }
+}
+
+fn render_lints(
+    stdout: &mut impl std::io::Write,
     render_opt: RenderOpt,
-    enable_spinners: bool,
-    revision_opt: RevisionOpt,
-    tee_json: Option<String>,
-) -> Result<i32> {
-    debug!(
-        "Running linters: {:?}",
-        linters.iter().map(|l| &l.code).collect::<Vec<_>>()
-    );
+    lints: &HashMap<Option<String>, Vec<LintMessage>>,
+) -> Result<PrintedLintErrors> {
+    match render_opt {
+        RenderOpt::Default => render_lint_messages(stdout, lints),
+        RenderOpt::Json => render_lint_messages_json(stdout, lints),
+        RenderOpt::JsonV1 => render_lint_messages_json_v1(stdout, lints),
+        RenderOpt::JsonPretty => render_lint_messages_json_pretty(stdout, lints),
+        RenderOpt::Oneline => render_lint_messages_oneline(stdout, lints),
+        RenderOpt::Gerrit => render_lint_messages_gerrit(stdout, lints),
+        RenderOpt::Arc => render_lint_messages_arc(stdout, lints),
+        RenderOpt::Teamcity => render_lint_messages_teamcity(stdout, lints),
+        RenderOpt::Azure => render_lint_messages_azure(stdout, lints),
+        RenderOpt::Github => render_lint_messages_github(stdout, lints),
+        RenderOpt::Buildkite => render_lint_messages_buildkite(stdout, lints),
+        RenderOpt::Sonarqube => render_lint_messages_sonarqube(stdout, lints),
+        RenderOpt::Summary => render_lint_messages_summary(stdout, lints, None),
+        RenderOpt::Csv => render_lint_messages_csv(stdout, lints),
+    }
+}
+
+/// Render the results of a whole run. Unlike `render_lints`, this knows
+/// about linters that found nothing, and whether the run failed.
+pub(crate) fn render_results(
+    stdout: &mut impl std::io::Write,
+    render_opt: RenderOpt,
+    results: &LintResults,
+) -> Result<PrintedLintErrors> {
+    match render_opt {
+        RenderOpt::Summary => render_lint_messages_summary(stdout, &results.lints, Some(results)),
+        render_opt => render_lints(stdout, render_opt, &results.lints),
+    }
+}
 
-    let mut files = match paths_opt {
-        PathsOpt::Auto => {
+// Render the results of a single linter immediately, instead of waiting for
+// all linters to finish. If spinners are active and results go to the
+// terminal they are drawing on, print above them so the output doesn't get
+// mangled by spinner redraws.
+fn stream_lints(
+    lints: &[LintMessage],
+    render_opt: RenderOpt,
+    spinner: Option<&ProgressBar>,
+) -> Result<()> {
+    if lints.is_empty() {
+        return Ok(());
+    }
+    let mut grouped = HashMap::new();
+    group_lints_by_file(&mut grouped, lints.to_vec());
+
+    // The JSON header was printed once, before any linter ran.
+    let render_opt = match render_opt {
+        RenderOpt::Json => RenderOpt::JsonV1,
+        render_opt => render_opt,
+    };
+    let mut buf = Vec::new();
+    render_lints(&mut buf, render_opt, &grouped)?;
+
+    match spinner {
+        Some(spinner) if streams::results().term().is_term() => {
+            spinner.println(String::from_utf8_lossy(&buf).trim_end_matches('\n'));
+        }
+        // Holding the lock for the whole write keeps output from different
+        // linters from interleaving.
+        _ => streams::results().write_all(&buf)?,
+    }
+    Ok(())
+}
+
+// Git LFS pointer files are tiny text files standing in for the real content.
+// Linting them is pointless at best, and at worst a patch destroys the pointer.
+const LFS_POINTER_PREFIX: &[u8] = b"version https://git-lfs.github.com/spec/v1\n";
+const LFS_POINTER_MAX_SIZE: u64 = 1024;
+
+fn is_lfs_pointer(path: &Path) -> bool {
+    let is_small =
+        std::fs::metadata(path).is_ok_and(|m| m.is_file() && m.len() < LFS_POINTER_MAX_SIZE);
+    if !is_small {
+        return false;
+    }
+    let mut prefix = [0; LFS_POINTER_PREFIX.len()];
+    std::fs::File::open(path)
+        .and_then(|mut file| file.read_exact(&mut prefix))
+        .is_ok_and(|_| prefix == LFS_POINTER_PREFIX)
+}
+
+/// Ask the build system for the source files of `targets`.
+fn get_paths_from_targets(
+    targets: &[String],
+    query: &[String],
+    dir: &AbsPath,
+) -> Result<Vec<AbsPath>> {
+    ensure!(!query.is_empty(), "`target_query` is empty");
+    let targets = targets.join(" + ");
+    let query: Vec<String> = query
+        .iter()
+        .map(|arg| arg.replace("{{TARGETS}}", &targets))
+        .collect();
+    debug!("Running target query: {:?}", query);
+
+    let output = std::process::Command::new(&query[0])
+        .args(&query[1..])
+        .current_dir(dir)
+        .output()
+        .with_context(|| format!("Failed to run target query '{}'", query[0]))?;
+    log_utils::ensure_output("Target query", &output)?;
+
+    let stdout = std::str::from_utf8(&output.stdout).context("Target query output is not UTF-8")?;
+    let mut files = Vec::new();
+    for line in stdout
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+    {
+        match AbsPath::try_from(dir.join(line)) {
+            Ok(file) => files.push(file),
+            // e.g. generated files that haven't been built.
+            Err(_) => warn!("Target query returned '{}', which doesn't exist", line),
+        }
+    }
+    Ok(files)
+}
+
+/// The commit that changes are linted relative to, substituted for
+/// `{{REVISION}}` in linter commands: the revision or merge base that was
+/// asked for, or else HEAD's parent. Empty with `--since`, or if there is no
+/// such commit.
+pub fn base_revision(revision_opt: &RevisionOpt) -> Result<String> {
+    let git_root = match get_git_root() {
+        Ok(git_root) => git_root,
+        Err(_) => return Ok(String::new()),
+    };
+    let parent = || resolve_revision(&git_root, "HEAD~").ok();
+    let base = match revision_opt {
+        RevisionOpt::Revision(revision) => Some(resolve_revision(&git_root, revision)?),
+        RevisionOpt::MergeBaseWith(revision) => Some(get_merge_base_with(&git_root, revision)?),
+        RevisionOpt::DefaultBranch => get_default_merge_base(&git_root)?.or_else(parent),
+        RevisionOpt::Head => parent(),
+        RevisionOpt::Staged => resolve_revision(&git_root, "HEAD").ok(),
+        RevisionOpt::Since(_) => None,
+    };
+    Ok(base.unwrap_or_default())
+}
+
+/// Compute the set of files to lint, sorted and deduplicated.
+pub fn get_paths(
+    paths_opt: PathsOpt,
+    revision_opt: RevisionOpt,
+    excludes: &[String],
+) -> Result<Vec<AbsPath>> {
+    let untracked = matches!(paths_opt, PathsOpt::AutoWithUntracked);
+    let files = match paths_opt {
+        PathsOpt::Auto | PathsOpt::AutoWithUntracked => {
             let git_root = get_git_root()?;
-            let relative_to = match revision_opt {
-                RevisionOpt::Head => None,
-                RevisionOpt::Revision(revision) => Some(revision),
+            let mut files = match revision_opt {
+                RevisionOpt::Head => get_changed_files(&git_root, None)?,
+                RevisionOpt::Revision(revision) => {
+                    let revision = resolve_revision(&git_root, &revision)?;
+                    get_changed_files(&git_root, Some(&revision))?
+                }
                 RevisionOpt::MergeBaseWith(merge_base_with) => {
-                    Some(get_merge_base_with(&git_root, &merge_base_with)?)
+                    let merge_base = get_merge_base_with(&git_root, &merge_base_with)?;
+                    get_changed_files(&git_root, Some(&merge_base))?
+                }
+                RevisionOpt::Since(since) => get_files_changed_since(&git_root, &since)?,
+                RevisionOpt::DefaultBranch => {
+                    let merge_base = get_default_merge_base(&git_root)?;
+                    get_changed_files(&git_root, merge_base.as_deref())?
                 }
+                RevisionOpt::Staged => get_staged_files(&git_root)?,
             };
-            get_changed_files(&git_root, relative_to.as_deref())?
+            if untracked {
+                files.extend(get_untracked_files(&git_root)?);
+            }
+            files
         }
         PathsOpt::PathsCmd(paths_cmd) => get_paths_from_cmd(&paths_cmd)?,
-        PathsOpt::Paths(paths) => get_paths_from_input(paths)?,
+        PathsOpt::Paths(paths) => expand_paths_from_input(paths)?,
         PathsOpt::PathsFile(file) => get_paths_from_file(file)?,
-        PathsOpt::AllFiles => get_paths_from_cmd("git grep -Il .")?,
+        PathsOpt::PathsStdin { null_delimited } => get_paths_from_stdin(null_delimited)?,
+        PathsOpt::AllFiles => get_paths_from_cmd("git -c core.quotepath=off grep -Il .")?,
+        PathsOpt::Targets {
+            targets,
+            query,
+            dir,
+        } => get_paths_from_targets(&targets, &query, &dir)?,
     };
 
+    let files = exclude_paths(files, excludes)?;
+    let mut files = parallel::filter_map(&files, |file| {
+        let is_pointer = is_lfs_pointer(file);
+        if is_pointer {
+            debug!("Skipping git-lfs pointer file: {}", file.display());
+        }
+        (!is_pointer).then(|| file.clone())
+    });
+
     // Sort and unique the files so we pass a consistent ordering to linters
     files.sort();
     files.dedup();
 
-    let files = Arc::new(files);
+    Ok(dedup_case_aliases(files))
+}
 
+/// Print which linters would run on which files, and with what commands,
+/// without actually running anything.
+pub fn do_lint_dry_run(linters: Vec<Linter>, files: Vec<AbsPath>) -> Result<i32> {
+    let mut stdout = Term::stdout();
+    let current_dir = std::env::current_dir()?;
+
+    for linter in linters {
+        let matches = linter.get_matches(&files);
+        if matches.is_empty() {
+            writeln!(
+                stdout,
+                "{} {} would not run: no files matched",
+                style(">>>").bold(),
+                style(&linter.code).bold()
+            )?;
+            continue;
+        }
+        // Show what the linter is actually given, which depends on its
+        // granularity.
+        let targets = linter.targets(&matches);
+        let what = match linter.granularity {
+            Granularity::File => format!("{} files", targets.len()),
+            Granularity::Directory => format!("{} directories", targets.len()),
+            Granularity::Project => format!("the project ({} files matched)", matches.len()),
+        };
+        writeln!(
+            stdout,
+            "{} {} would run on {}:",
+            style(">>>").bold(),
+            style(&linter.code).bold(),
+            what
+        )?;
+        for path in &targets {
+            writeln!(
+                stdout,
+                "    {}",
+                get_display_path(&path.to_string_lossy(), &current_dir)
+            )?;
+        }
+        for command in linter.planned_commands(&matches, "{{PATHSFILE}}") {
+            writeln!(
+                stdout,
+                "  {} {}",
+                style("command:").dim(),
+                shell_words::join(command)
+            )?;
+        }
+        writeln!(
+            stdout,
+            "  {} {}",
+            style("working directory:").dim(),
+            linter.get_config_dir().display()
+        )?;
+    }
+    Ok(0)
+}
+
+/// Filters lint messages by their `code` or `name`, as specified by
+/// `--only-codes` and `--skip-codes`.
+#[derive(Default)]
+pub struct CodeFilter {
+    only: Option<HashSet<String>>,
+    skip: HashSet<String>,
+}
+
+impl CodeFilter {
+    /// Create a filter from comma-separated lists of codes.
+    pub fn new(only: Option<&str>, skip: Option<&str>) -> Self {
+        let split = |codes: &str| -> HashSet<String> {
+            codes
+                .split(',')
+                .map(|code| code.trim().to_string())
+                .filter(|code| !code.is_empty())
+                .collect()
+        };
+        CodeFilter {
+            only: only.map(split),
+            skip: skip.map(split).unwrap_or_default(),
+        }
+    }
+
+    fn keeps(&self, lint: &LintMessage) -> bool {
+        let matches =
+            |codes: &HashSet<String>| codes.contains(&lint.code) || codes.contains(&lint.name);
+        self.only.as_ref().is_none_or(matches) && !matches(&self.skip)
+    }
+}
+
+/// Print which linters did not run, and why.
+fn report_skipped_linters(skip_reasons: &mut [(String, SkipReason)]) -> Result<()> {
+    let stderr = streams::diagnostics().term();
+    if skip_reasons.is_empty() {
+        stderr.write_line("All configured linters ran.")?;
+        return Ok(());
+    }
+    skip_reasons.sort_by(|a, b| a.0.cmp(&b.0));
+    stderr.write_line("Linters that did not run:")?;
+    for (code, reason) in skip_reasons.iter() {
+        stderr.write_line(&format!("  {}: {}", style(code).bold(), reason))?;
+    }
+    Ok(())
+}
+
+/// For each of `paths`, print which linters would lint it, and which
+/// include/exclude patterns are responsible for that.
+pub fn do_why(linters: &[Linter], paths: Vec<String>) -> Result<i32> {
+    ensure!(
+        !paths.is_empty(),
+        "No paths provided. Usage: lintrunner why <PATHS>..."
+    );
+    let current_dir = std::env::current_dir()?;
+    let mut stdout = Term::stdout();
+
+    // Patterns can be written in an import or the local overlay, and remote
+    // imports can't be read back, in which case only the source is shown.
+    let location = |code: &str, pattern: &PatternMatch| {
+        let line = std::fs::read_to_string(pattern.path)
+            .ok()
+            .and_then(|config_str| find_pattern_line(&config_str, code, pattern.pattern));
+        match line {
+            Some(line) => format!("{}:{}", pattern.path.display(), line),
+            None => pattern.path.display().to_string(),
+        }
+    };
+
+    for path in paths {
+        let abs_path = AbsPath::try_from(&path)
+            .with_context(|| format!("Failed to find provided file: '{}'", path))?;
+        writeln!(
+            stdout,
+            "{} {}:",
+            style(">>>").bold(),
+            style(get_display_path(&abs_path.to_string_lossy(), &current_dir)).underlined()
+        )?;
+        for linter in linters {
+            match linter.explain_match(&abs_path) {
+                MatchReason::NotIncluded => writeln!(
+                    stdout,
+                    "  {} {}: not linted, no include pattern matches",
+                    style("✕").red(),
+                    linter.code
+                )?,
+                MatchReason::Included(include) => writeln!(
+                    stdout,
+                    "  {} {}: linted, included by '{}' ({})",
+                    style("✓").green(),
+                    linter.code,
+                    include.pattern,
+                    location(&linter.code, &include),
+                )?,
+                MatchReason::Excluded { include, exclude } => writeln!(
+                    stdout,
+                    "  {} {}: not linted, included by '{}' ({}) but excluded by '{}' ({})",
+                    style("✕").red(),
+                    linter.code,
+                    include.pattern,
+                    location(&linter.code, &include),
+                    exclude.pattern,
+                    location(&linter.code, &exclude),
+                )?,
+            }
+        }
+    }
+    Ok(0)
+}
+
+/// Everything that happened during a lint run.
+#[derive(Debug, Default)]
+pub struct LintResults {
+    /// Lint messages, grouped by the path they pertain to. Messages that
+    /// aren't about a particular file are under `None`.
+    pub lints: HashMap<Option<String>, Vec<LintMessage>>,
+    /// The most severe way in which a linter failed, if any did.
+    pub worst_failure: Option<LinterFailure>,
+    /// Linters whose command could not be found, and so were skipped.
+    pub missing_linters: Vec<String>,
+    /// Linters that didn't match any of the files being linted.
+    pub no_matches: Vec<String>,
+    /// Linters that reported more issues than their `max_allowed` budget.
+    pub budget_overruns: Vec<(String, BudgetOverrun)>,
+    /// How many of the lints should fail the run.
+    pub failing_lints: usize,
+    /// How long each linter that had files to lint took.
+    pub durations: HashMap<String, Duration>,
+    /// Linters that were stopped (or never started) because the run was
+    /// cancelled.
+    pub interrupted: Vec<String>,
+    /// Fingerprints of each linter's inputs and results, if requested.
+    pub fingerprints: HashMap<String, LinterFingerprint>,
+    /// Fingerprints of the issues each linter found, including ones that
+    /// were filtered out, if requested.
+    pub lint_fingerprints: HashMap<String, Vec<String>>,
+}
+
+impl LintResults {
+    /// The exit code `lintrunner` would use for these results.
+    pub fn exit_code(&self) -> i32 {
+        if let Some(code) = cancel::exit_code() {
+            return code;
+        }
+        match self.worst_failure {
+            Some(failure) => failure.exit_code(),
+            None if self.failing_lints > 0 => 1,
+            None => 0,
+        }
+    }
+}
+
+/// Options that control how linters are run, as opposed to which linters
+/// run on which files.
+pub struct RunOptions {
+    pub apply_patches: bool,
+    /// When applying patches, only apply the fixes of lints this keeps, and
+    /// report the others as usual.
+    pub fix_filter: CodeFilter,
+    pub strict_missing: bool,
+    pub code_filter: CodeFilter,
+    pub progress: ProgressOpt,
+    /// If set, render each linter's lints as soon as it finishes.
+    pub stream: Option<RenderOpt>,
+    /// How long linters took on previous runs, to estimate progress.
+    pub expected_durations: HashMap<String, Duration>,
+    /// Whether to fingerprint each linter's inputs and results, to detect
+    /// flaky linters, and each issue, to find new issues in later runs.
+    pub record_fingerprints: bool,
+    /// If set, only report issues that aren't in this baseline.
+    pub baseline: Option<Baseline>,
+    /// If set, report when issues were first seen, and escalate old ones.
+    pub issue_history: Option<IssueHistory>,
+    /// The most linters to run at once, if limited.
+    pub jobs: Option<usize>,
+    /// If set, where to send events as linters start and finish.
+    pub sink: Option<Arc<dyn RenderSink>>,
+}
+
+/// Run `linters` on `files`, and collect the results.
+pub(crate) fn run_linters(
+    linters: Vec<Linter>,
+    files: Vec<AbsPath>,
+    options: RunOptions,
+) -> Result<LintResults> {
+    debug!(
+        "Running linters: {:?}",
+        linters.iter().map(|l| &l.code).collect::<Vec<_>>()
+    );
     log_utils::log_files("Linting files: ", &files);
 
+    let RunOptions {
+        apply_patches: should_apply_patches,
+        fix_filter,
+        strict_missing,
+        code_filter,
+        progress,
+        stream,
+        expected_durations,
+        record_fingerprints,
+        baseline,
+        issue_history,
+        jobs,
+        sink,
+    } = options;
+    let baseline = Arc::new(baseline);
+    let issue_history = Arc::new(issue_history);
+    let now = chrono::Utc::now();
+    // Fingerprints use paths relative to the root of the repo, if in one.
+    let repo_root: Arc<Option<PathBuf>> =
+        Arc::new(get_git_root().ok().map(|root| root.to_path_buf()));
+
     let mut thread_handles = Vec::new();
-    let spinners = Arc::new(MultiProgress::new());
+    let progress = Arc::new(LintProgress::new(
+        progress,
+        &linters.iter().map(|l| l.code.clone()).collect::<Vec<_>>(),
+        expected_durations,
+    ));
 
     // Too lazy to learn rust's fancy concurrent programming stuff, just spawn a thread per linter and join them.
-    let all_lints = Arc::new(Mutex::new(HashMap::new()));
+    let results = Arc::new(Mutex::new(LintResults::default()));
+    // Fixes are only written once every linter is done.
+    let fixes = Arc::new(Mutex::new(Vec::new()));
+    let scheduler = Arc::new(Scheduler::with_jobs(jobs));
+    let code_filter = Arc::new(code_filter);
+    let fix_filter = Arc::new(fix_filter);
 
-    for linter in linters {
-        let all_lints = Arc::clone(&all_lints);
-        let files = Arc::clone(&files);
-        let spinners = Arc::clone(&spinners);
+    // Only wait on linters that are actually part of this run.
+    let running_codes: HashSet<String> = linters.iter().map(|l| l.code.clone()).collect();
+
+    // Match every linter's patterns in one pass over the files, rather than
+    // each linter going through all of them.
+    let start = Instant::now();
+    let all_matches = LinterMatcher::new(&linters)?.matches(&files);
+    debug!("Matching files to linters took: {:?}", start.elapsed());
+
+    for (linter, matches) in linters.into_iter().zip(all_matches) {
+        let results = Arc::clone(&results);
+        let fixes = Arc::clone(&fixes);
+        let progress = Arc::clone(&progress);
+        let scheduler = Arc::clone(&scheduler);
+        let code_filter = Arc::clone(&code_filter);
+        let fix_filter = Arc::clone(&fix_filter);
+        let baseline = Arc::clone(&baseline);
+        let issue_history = Arc::clone(&issue_history);
+        let sink = sink.clone();
+        let repo_root = Arc::clone(&repo_root);
+        let run_after: Vec<String> = linter
+            .run_after
+            .iter()
+            .filter(|code| running_codes.contains(*code))
+            .cloned()
+            .collect();
 
         let handle = thread::spawn(move || -> Result<()> {
-            let mut spinner = None;
-            if enable_spinners {
-                let _spinner = spinners.add(ProgressBar::new_spinner());
-                _spinner.set_message(format!("{} running...", linter.code));
-                _spinner.enable_steady_tick(100);
-                spinner = Some(_spinner);
+            log_utils::set_current_linter(&linter.code);
+            scheduler.wait_for(&run_after);
+            let finished = scheduler.finish_on_drop(&linter.code);
+            let slot = scheduler.acquire(linter.exclusive);
+
+            let num_files = matches.len();
+            if num_files == 0 {
+                results.lock().unwrap().no_matches.push(linter.code.clone());
             }
+            progress.start(&linter.code, num_files);
+            // Like errors from the linter, errors from the sink are only
+            // returned once the linter is marked finished.
+            let started = match &sink {
+                Some(sink) => sink.event(&RenderEvent::LinterStarted {
+                    code: &linter.code,
+                    num_files,
+                }),
+                None => Ok(()),
+            };
 
-            let lints = linter.run(&files);
+            let inputs = (record_fingerprints && num_files > 0)
+                .then(|| flaky::fingerprint_inputs(&linter, &matches));
+
+            // Don't start new linters once we've been cancelled, and don't
+            // report the failures of linters that were killed.
+            let (mut lints, mut failure) = if cancel::is_cancelled() {
+                (Vec::new(), Some(LinterFailure::Crashed))
+            } else {
+                linter.run_on_matches(matches)
+            };
+            let interrupted = failure.is_some() && cancel::is_cancelled();
+            if let (Some(inputs), false) = (inputs, interrupted) {
+                let fingerprint = LinterFingerprint {
+                    inputs,
+                    results: flaky::fingerprint_results(&lints),
+                };
+                results
+                    .lock()
+                    .unwrap()
+                    .fingerprints
+                    .insert(linter.code.clone(), fingerprint);
+            }
+            if interrupted {
+                results
+                    .lock()
+                    .unwrap()
+                    .interrupted
+                    .push(linter.code.clone());
+                lints.clear();
+                failure = None;
+            }
+            if !interrupted {
+                let fingerprints = baseline::lint_fingerprints(&lints, repo_root.as_deref());
+                for (lint, fingerprint) in lints.iter_mut().zip(&fingerprints) {
+                    lint.fingerprint = Some(fingerprint.clone());
+                }
+                if let Some(issue_history) = issue_history.as_ref() {
+                    issue_history.apply(&linter.code, &mut lints, linter.escalate_after_days, now);
+                }
+                // Linter failures are always reported, even if they happened
+                // before.
+                if let (Some(baseline), None) = (baseline.as_ref(), failure) {
+                    lints = baseline.remove_known(&linter.code, lints, &fingerprints);
+                }
+                if record_fingerprints {
+                    results
+                        .lock()
+                        .unwrap()
+                        .lint_fingerprints
+                        .insert(linter.code.clone(), fingerprints);
+                }
+            }
+            // Linter failures are always reported, regardless of the filter.
+            if failure.is_none() {
+                lints.retain(|lint| code_filter.keeps(lint));
+            }
+            // A missing linter shouldn't stop everything else from being
+            // linted, so just let the user know about it.
+            let is_missing = failure == Some(LinterFailure::Missing) && !strict_missing;
+            if is_missing {
+                for lint in &mut lints {
+                    lint.severity = LintSeverity::Advice;
+                }
+                results
+                    .lock()
+                    .unwrap()
+                    .missing_linters
+                    .push(linter.code.clone());
+                failure = None;
+            }
+            if failure.is_some() {
+                let mut results = results.lock().unwrap();
+                results.worst_failure = results.worst_failure.max(failure);
+            }
+            // Known flaky linters are still reported, but can't fail the run.
+            if linter.flaky {
+                for lint in &mut lints {
+                    lint.severity = LintSeverity::Advice;
+                }
+            }
+            if failure.is_none() {
+                linter.override_severities(&mut lints);
+            }
 
             // If we're applying patches later, don't consider lints that would
             // be fixed by that.
             let lints = if should_apply_patches {
-                apply_patches(&lints)?;
-                remove_patchable_lints(lints)
+                fixes_to_apply(lints.iter().filter(|lint| fix_filter.keeps(lint))).map(
+                    |linter_fixes| {
+                        fixes.lock().unwrap().extend(linter_fixes);
+                        remove_patchable_lints(lints, &fix_filter)
+                    },
+                )
             } else {
-                lints
+                Ok(lints)
             };
 
-            let mut all_lints = all_lints.lock().unwrap();
-            let is_success = lints.is_empty();
-
-            group_lints_by_file(&mut all_lints, lints);
+            let num_issues = lints.as_ref().map_or(0, |lints| lints.len());
+            let elapsed = progress.finish(&linter.code, num_files, num_issues);
+            // Let the linters waiting on this one start. `finished` also
+            // does that if the thread panics, so that they don't wait forever.
+            drop(slot);
+            drop(finished);
+            if num_files > 0 {
+                results
+                    .lock()
+                    .unwrap()
+                    .durations
+                    .insert(linter.code.clone(), elapsed);
+            }
+            started?;
+            let lints = lints?;
 
-            let spinner_message = if is_success {
-                format!("{} {}", linter.code, style("success!").green())
-            } else {
-                format!("{} {}", linter.code, style("failure").red())
-            };
+            if let Some(sink) = &sink {
+                sink.event(&RenderEvent::LinterFinished {
+                    code: &linter.code,
+                    num_files,
+                    duration: elapsed,
+                    lints: &lints,
+                })?;
+            }
+            if let Some(render_opt) = stream {
+                stream_lints(&lints, render_opt, progress.printer())?;
+            }
 
-            if enable_spinners {
-                spinner.unwrap().finish_with_message(spinner_message);
+            let mut results = results.lock().unwrap();
+            // Missing and flaky linters were reported, but shouldn't fail the
+            // run by themselves. Neither should lints within the linter's
+            // budget.
+            if !is_missing && !linter.flaky {
+                let (failing, overruns) = linter.check_budget(&lints);
+                results.failing_lints += failing;
+                results.budget_overruns.extend(
+                    overruns
+                        .into_iter()
+                        .map(|overrun| (linter.code.clone(), overrun)),
+                );
             }
+            group_lints_by_file(&mut results.lints, lints);
             Ok(())
         });
         thread_handles.push(handle);
     }
 
-    spinners.join()?;
+    progress.join()?;
     for handle in thread_handles {
         handle.join().unwrap()?;
     }
+    if should_apply_patches {
+        // Unwrap is fine because all other owners were joined.
+        apply_fixes(&Arc::try_unwrap(fixes).ok().unwrap().into_inner().unwrap())?;
+    }
+
+    // Unwrap is fine because all other owners should have been joined.
+    let mut results = Arc::try_unwrap(results).ok().unwrap().into_inner().unwrap();
+    results.budget_overruns.sort_by(|a, b| a.0.cmp(&b.0));
+    if let Some(sink) = &sink {
+        sink.event(&RenderEvent::Finished { results: &results })?;
+    }
+    Ok(results)
+}
+
+/// Hash the contents of every config `linters` came from, to tell later which
+/// configuration produced a set of results.
+fn hash_configs(linters: &[Linter]) -> Result<String> {
+    let config_paths: BTreeSet<&AbsPath> =
+        linters.iter().map(|linter| &linter.config_path).collect();
+    let mut hasher = blake3::Hasher::new();
+    for path in config_paths {
+        let contents = std::fs::read(path)
+            .with_context(|| format!("Failed to read config '{}'", path.display()))?;
+        hasher.update(blake3::hash(&contents).as_bytes());
+    }
+    Ok(hasher.finalize().to_string())
+}
 
-    // Unwrap is fine because all other owners hsould have been joined.
-    let all_lints = all_lints.lock().unwrap();
+/// For `--only-new`: the issues found at the merge base (or at HEAD, if
+/// there is none), which are left out of the results.
+pub fn only_new_baseline(
+    linters: &[Linter],
+    revision_opt: &RevisionOpt,
+    persistent_data_store: &PersistentDataStore,
+) -> Result<Baseline> {
+    let head = get_head()
+        .ok()
+        .context("--only-new only works in a git checkout")?;
+    let base = match revision_opt {
+        RevisionOpt::MergeBaseWith(rev) => get_merge_base_with(&get_git_root()?, rev)?,
+        RevisionOpt::DefaultBranch => get_default_merge_base(&get_git_root()?)?.unwrap_or(head),
+        _ => head,
+    };
+    let baseline = Baseline::from_runs(&base, persistent_data_store.past_run_lints()?);
+    let mut unknown: Vec<&str> = linters
+        .iter()
+        .map(|linter| linter.code.as_str())
+        .filter(|code| !baseline.has_linter(code))
+        .collect();
+    unknown.sort_unstable();
+    if !unknown.is_empty() {
+        warn!(
+            "No earlier run at {} found for {}, so all of their issues are reported as new.",
+            &base[..base.len().min(12)],
+            unknown.join(", ")
+        );
+    }
+    Ok(baseline)
+}
+
+/// What `do_lint` does with the results once linters finish.
+pub struct ReportOptions {
+    pub render_opt: RenderOpt,
+    pub tee_json: Option<TeeJson>,
+    /// If set, the linters that were skipped before running, which are
+    /// listed along with the ones that had nothing to lint.
+    pub skip_reasons: Option<Vec<(String, SkipReason)>>,
+    pub webhook: Option<Webhook>,
+    pub metrics: Option<MetricsSink>,
+    /// If set, where to save the fixes that weren't applied.
+    pub save_patches: Option<PathBuf>,
+}
+
+/// Run `linters` on the files selected by `paths_opt`, report the results
+/// and record them in `persistent_data_store`. Returns the exit code.
+pub fn do_lint(
+    linters: Vec<Linter>,
+    paths_opt: PathsOpt,
+    revision_opt: RevisionOpt,
+    excludes: Vec<String>,
+    options: RunOptions,
+    report: ReportOptions,
+    persistent_data_store: &PersistentDataStore,
+) -> Result<i32> {
+    let start = std::time::Instant::now();
+    let ReportOptions {
+        render_opt,
+        tee_json,
+        skip_reasons,
+        webhook,
+        metrics,
+        save_patches,
+    } = report;
+    let stream = options.stream.is_some();
+    let should_apply_patches = options.apply_patches;
+    ensure!(
+        options.stream.is_none_or(RenderOpt::can_stream),
+        "--stream can't be used with --output {}",
+        render_opt.to_possible_value().unwrap().get_name()
+    );
+    if let Some(dir) = &save_patches {
+        patches::check_patch_dir(dir)?;
+    }
+    if let Some(tee_json) = &tee_json {
+        tee_json.check()?;
+    }
+    let _cancel_scope = cancel::cancellable();
+    // Linting files outside of a git checkout is fine, it just means there's
+    // nothing to compare against later.
+    let head = get_head().ok();
+    // Only worked out for --tee-json, since it can take a few git commands.
+    let config_hash = match &tee_json {
+        Some(_) => Some(hash_configs(&linters)?),
+        None => None,
+    };
+    let merge_base = match paths_opt {
+        PathsOpt::Auto | PathsOpt::AutoWithUntracked if tee_json.is_some() => {
+            Some(base_revision(&revision_opt)?).filter(|base| !base.is_empty())
+        }
+        _ => None,
+    };
+    let files = get_paths(paths_opt, revision_opt, &excludes)?;
+    if stream && render_opt == RenderOpt::Json {
+        render::JsonHeader::default().write(&mut streams::results().term())?;
+    }
+    let results = run_linters(linters, files, options)?;
+
+    persistent_data_store.update_linter_durations(&results.durations)?;
+    persistent_data_store.write_linter_fingerprints(&results.fingerprints)?;
+    if let Some(head) = &head {
+        persistent_data_store.write_run_lints(&RunLints {
+            head: head.clone(),
+            fingerprint_version: baseline::FINGERPRINT_VERSION,
+            linters: results.lint_fingerprints.clone(),
+        })?;
+    }
+    let mut issue_history = persistent_data_store.issue_history()?;
+    issue_history.record(
+        &results.lint_fingerprints,
+        head.as_deref(),
+        chrono::Utc::now(),
+    );
+    persistent_data_store.write_issue_history(&issue_history)?;
+    persistent_data_store.write_results(&query::StoredResults::new(
+        results.lints.values().flatten(),
+        &persistent_data_store.run_info().timestamp,
+        head.clone(),
+        get_git_root().ok().as_deref(),
+    ))?;
+
+    if let Some(mut skip_reasons) = skip_reasons {
+        for code in &results.no_matches {
+            skip_reasons.push((code.clone(), SkipReason::NoMatches));
+        }
+        for code in &results.missing_linters {
+            skip_reasons.push((code.clone(), SkipReason::Missing));
+        }
+        report_skipped_linters(&mut skip_reasons)?;
+    }
+
+    let all_lints = &results.lints;
 
     // Flush the logger before rendering results.
     log::logger().flush();
 
-    let mut stdout = Term::stdout();
+    let mut stdout = streams::results().term();
 
-    let did_print = match render_opt {
-        RenderOpt::Default => render_lint_messages(&mut stdout, &all_lints)?,
-        RenderOpt::Json => render_lint_messages_json(&mut stdout, &all_lints)?,
-        RenderOpt::Oneline => render_lint_messages_oneline(&mut stdout, &all_lints)?,
-    };
+    if stream {
+        // Everything was already printed as linters finished; just report
+        // the overall result.
+        if all_lints.is_empty() && render_opt == RenderOpt::Default {
+            render_lint_messages(&mut stdout, all_lints)?;
+        }
+    } else {
+        render_results(&mut stdout, render_opt, &results)?;
+    }
 
     if let Some(tee_json) = tee_json {
-        let mut file = OpenOptions::new()
-            .write(true)
-            .create_new(true)
-            .open(tee_json)
-            .context("Couldn't open file for --tee-json")?;
-        render_lint_messages_json(&mut file, &all_lints)?;
+        let mut file = tee_json.open()?;
+        let run_info = persistent_data_store.run_info();
+        let header = render::JsonHeader {
+            run: Some(render::RunMetadata {
+                args: run_info.args.clone(),
+                config_hash: config_hash.unwrap_or_default(),
+                revision: head,
+                merge_base,
+                timestamp: run_info.timestamp.clone(),
+                duration_secs: start.elapsed().as_secs_f64(),
+                linter_durations: results
+                    .durations
+                    .iter()
+                    .map(|(code, duration)| (code.clone(), duration.as_secs_f64()))
+                    .collect(),
+            }),
+            ..Default::default()
+        };
+        render::render_lint_messages_json_with_header(&mut file, &header, all_lints)?;
+    }
+
+    if let Some(dir) = &save_patches {
+        let saved =
+            patches::save_patches(all_lints.values().flatten(), &patches::patch_root()?, dir)?;
+        if saved > 0 {
+            streams::diagnostics().term().write_line(&format!(
+                "Saved {} patch{} to '{}', apply with `lintrunner apply {}`.",
+                saved,
+                if saved == 1 { "" } else { "es" },
+                dir.display(),
+                dir.display()
+            ))?;
+        }
     }
 
     if should_apply_patches {
         stdout.write_line("Successfully applied all patches.")?;
     }
 
-    match did_print {
-        PrintedLintErrors::No => Ok(0),
-        PrintedLintErrors::Yes => Ok(1),
+    if cancel::is_cancelled() {
+        let mut interrupted = results.interrupted.clone();
+        interrupted.sort();
+        let mut summary = format!(
+            "{} Only results from linters that finished are shown.",
+            style("Cancelled.").red().bold()
+        );
+        if !interrupted.is_empty() {
+            summary.push_str(&format!(" Interrupted: {}", interrupted.join(", ")));
+        }
+        streams::diagnostics().term().write_line(&summary)?;
+    } else if results.worst_failure.is_none() {
+        for (code, overrun) in &results.budget_overruns {
+            streams::diagnostics().term().write_line(&format!(
+                "{} {} reported {} {} issues, but only {} are allowed by `max_allowed`.",
+                style("error:").red().bold(),
+                code,
+                overrun.count,
+                overrun.severity.label().to_lowercase(),
+                overrun.max_allowed,
+            ))?;
+        }
+    }
+
+    if let Some(metrics) = metrics {
+        export_metrics(&metrics, &results, start.elapsed())?;
     }
+
+    if let Some(webhook) = webhook {
+        notify_webhook(
+            &webhook,
+            &results,
+            start.elapsed(),
+            &persistent_data_store.run_id(),
+        );
+    }
+
+    Ok(results.exit_code())
 }
 
 #[cfg(test)]
@@ -305,4 +1405,159 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn test_expand_paths() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        std::fs::create_dir_all(dir.path().join("a/b"))?;
+        std::fs::write(dir.path().join("a/foo.py"), "")?;
+        std::fs::write(dir.path().join("a/b/bar.py"), "")?;
+        std::fs::write(dir.path().join("a/b/baz.rs"), "")?;
+
+        let abs = |p: &str| AbsPath::try_from(dir.path().join(p)).unwrap();
+
+        // Directories are recursed into.
+        let mut paths = expand_paths_from_input(vec![dir.path().join("a").display().to_string()])?;
+        paths.sort();
+        let mut expected = vec![abs("a/foo.py"), abs("a/b/bar.py"), abs("a/b/baz.rs")];
+        expected.sort();
+        assert_eq!(paths, expected);
+
+        // Globs are expanded.
+        let paths =
+            expand_paths_from_input(vec![dir.path().join("a/**/*.rs").display().to_string()])?;
+        assert_eq!(paths, vec![abs("a/b/baz.rs")]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_exclude_paths() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        std::fs::create_dir_all(dir.path().join("vendored"))?;
+        std::fs::write(dir.path().join("foo.py"), "")?;
+        std::fs::write(dir.path().join("bar.rs"), "")?;
+        std::fs::write(dir.path().join("vendored/baz.py"), "")?;
+
+        let abs = |p: &str| AbsPath::try_from(dir.path().join(p)).unwrap();
+        let files = vec![abs("foo.py"), abs("bar.rs"), abs("vendored/baz.py")];
+
+        let remaining = exclude_paths(
+            files.clone(),
+            &[dir.path().join("vendored").display().to_string()],
+        )?;
+        assert_eq!(remaining, vec![abs("foo.py"), abs("bar.rs")]);
+
+        let remaining = exclude_paths(files, &[format!("{}/*.py", dir.path().display())])?;
+        assert_eq!(remaining, vec![abs("bar.rs"), abs("vendored/baz.py")]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_split_paths() {
+        assert_eq!(
+            split_paths("foo\nbar baz\n\n", false),
+            vec!["foo", "bar baz"]
+        );
+        assert_eq!(
+            split_paths("foo\0bar\nbaz\0", true),
+            vec!["foo", "bar\nbaz"]
+        );
+    }
+
+    #[test]
+    fn test_code_filter() {
+        let lint =
+            |code: &str, name: &str| LintMessage::for_test(code, LintSeverity::Warning, name);
+        let e501 = lint("FLAKE8", "E501");
+        let w503 = lint("FLAKE8", "W503");
+        let mypy = lint("MYPY", "error");
+
+        let filter = CodeFilter::default();
+        assert!(filter.keeps(&e501) && filter.keeps(&w503) && filter.keeps(&mypy));
+
+        let filter = CodeFilter::new(Some("E501,MYPY"), None);
+        assert!(filter.keeps(&e501) && !filter.keeps(&w503) && filter.keeps(&mypy));
+
+        let filter = CodeFilter::new(Some("FLAKE8"), Some("W503"));
+        assert!(filter.keeps(&e501) && !filter.keeps(&w503) && !filter.keeps(&mypy));
+    }
+
+    #[test]
+    fn test_fix_only() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let lint = |code: &str, file: &str| LintMessage {
+            path: Some(dir.path().join(file).display().to_string()),
+            original: Some("old\n".to_string()),
+            replacement: Some("new\n".to_string()),
+            ..LintMessage::for_test(code, LintSeverity::Warning, "format")
+        };
+        std::fs::write(dir.path().join("trusted.py"), "old\n")?;
+        std::fs::write(dir.path().join("experimental.py"), "old\n")?;
+        let lints = vec![
+            lint("TRUSTED", "trusted.py"),
+            lint("EXPERIMENTAL", "experimental.py"),
+        ];
+
+        let fix_filter = CodeFilter::new(Some("TRUSTED"), None);
+        apply_fixes(&fixes_to_apply(
+            lints.iter().filter(|lint| fix_filter.keeps(lint)),
+        )?)?;
+        let remaining = remove_patchable_lints(lints, &fix_filter);
+        assert_eq!(
+            std::fs::read_to_string(dir.path().join("trusted.py"))?,
+            "new\n"
+        );
+        assert_eq!(
+            std::fs::read_to_string(dir.path().join("experimental.py"))?,
+            "old\n"
+        );
+        assert_eq!(remaining.len(), 1);
+        assert_eq!(remaining[0].code, "EXPERIMENTAL");
+        Ok(())
+    }
+
+    #[test]
+    fn test_fixes_are_applied_together() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let a = dir.path().join("a.py");
+        std::fs::write(&a, "old\n")?;
+        let a = AbsPath::try_from(a)?;
+        // Another linter's fix for a file that went away in the meantime.
+        let b = dir.path().join("b.py");
+        std::fs::write(&b, "old\n")?;
+        let fixes = vec![
+            (a.clone(), "new\n".to_string()),
+            (AbsPath::try_from(b.clone())?, "new\n".to_string()),
+        ];
+        std::fs::remove_file(&b)?;
+        assert!(apply_fixes(&fixes).is_err());
+        assert_eq!(std::fs::read_to_string(&a)?, "old\n");
+
+        let fixes = vec![
+            (a.clone(), "x\n".to_string()),
+            (a.clone(), "y\n".to_string()),
+        ];
+        assert!(apply_fixes(&fixes).is_err());
+        assert_eq!(std::fs::read_to_string(&a)?, "old\n");
+        Ok(())
+    }
+
+    #[test]
+    fn test_lfs_pointer() -> Result<()> {
+        let mut pointer = NamedTempFile::new()?;
+        write!(
+            pointer,
+            "version https://git-lfs.github.com/spec/v1\n\
+             oid sha256:4d7a214614ab2935c943f9e0ff69d22eadbb8f32b1258daaa5e2ca24d17e2393\n\
+             size 12345\n"
+        )?;
+        assert!(is_lfs_pointer(pointer.path()));
+
+        let mut regular = NamedTempFile::new()?;
+        writeln!(regular, "version = 1")?;
+        assert!(!is_lfs_pointer(regular.path()));
+        Ok(())
+    }
 }
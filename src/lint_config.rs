@@ -1,21 +1,268 @@
-use std::{collections::HashSet, fs};
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    fs,
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+    time::Duration,
+};
 
-use crate::{linter::Linter, path::AbsPath};
+use crate::{
+    builtin,
+    imports::{self, ImportSpec},
+    lint_message::LintSeverity,
+    linter::{Granularity, Linter, PatternSource, PATHS_PLACEHOLDER},
+    matcher::PatternOptions,
+    path::AbsPath,
+    shell::Shell,
+    version::{VersionCheck, VersionMismatch, VersionReq},
+    watchdog::DEFAULT_SLOW_THRESHOLD,
+};
 use anyhow::{bail, ensure, Context, Result};
 use glob::Pattern;
-use log::debug;
+use log::{debug, warn};
+use regex::Regex;
 use serde::{Deserialize, Serialize};
 
 #[derive(Serialize, Deserialize)]
 pub struct LintRunnerConfig {
     #[serde(rename = "linter")]
     pub linters: Vec<LintConfig>,
+
+    /// A command that prints the source files of build system targets, one
+    /// per line, used by `--targets`. The string `{{TARGETS}}` is replaced by
+    /// the requested targets, joined with ` + `. The command is run from the
+    /// directory containing the config file, and relative paths it prints
+    /// are relative to that directory too.
+    ///
+    /// # Examples
+    /// ```toml
+    /// target_query = ['buck2', 'uquery', 'inputs({{TARGETS}})']
+    /// ```
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub target_query: Option<Vec<String>>,
+
+    /// A URL to POST a JSON summary to when a lint run finishes, like
+    /// `--notify-webhook`. The command line flag takes precedence.
+    ///
+    /// # Examples
+    /// ```toml
+    /// notify_webhook = 'https://hooks.slack.com/services/...'
+    /// ```
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub notify_webhook: Option<String>,
+
+    /// The revision to lint changes since the merge base with, when none of
+    /// `--revision`, `--merge-base-with` or `--since` is passed. If unset,
+    /// lintrunner uses the remote's default branch.
+    ///
+    /// # Examples
+    /// ```toml
+    /// merge_base_with = 'origin/develop'
+    /// ```
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub merge_base_with: Option<String>,
+
+    /// The default for [`LintConfig::shell`].
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub shell: Option<Shell>,
+
+    /// The default for [`LintConfig::case_insensitive_patterns`].
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub case_insensitive_patterns: Option<bool>,
+
+    /// The default for [`LintConfig::normalize_unicode_paths`].
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub normalize_unicode_paths: Option<bool>,
+
+    /// Rules that change the severity of every linter's issues in matching
+    /// files, after a linter's own [`LintConfig::severity_overrides`].
+    ///
+    /// # Examples
+    /// ```toml
+    /// [[severity_overrides]]
+    /// pattern = 'third_party/**'
+    /// severity = 'advice'
+    /// ```
+    #[serde(skip_serializing_if = "Vec::is_empty", default)]
+    pub severity_overrides: Vec<SeverityOverride>,
+
+    /// Named sets of run settings, selected with `--profile` or
+    /// `LINTRUNNER_PROFILE`.
+    ///
+    /// # Examples
+    /// ```toml
+    /// [profiles.ci]
+    /// skip = ['CLANGTIDY']
+    /// fail_on = 'error'
+    /// jobs = 4
+    /// ```
+    #[serde(skip_serializing_if = "BTreeMap::is_empty", default)]
+    pub profiles: BTreeMap<String, Profile>,
 }
 
+/// Settings that adjust a run, like the corresponding command line flags.
+/// Flags passed on the command line take precedence.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq)]
+#[serde(deny_unknown_fields)]
+pub struct Profile {
+    /// Linters to skip, in addition to the ones passed with `--skip`.
+    #[serde(skip_serializing_if = "Vec::is_empty", default)]
+    pub skip: Vec<String>,
+
+    /// Only run these linters, unless `--take` is passed.
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub take: Option<Vec<String>>,
+
+    /// The least severe issues that fail the run, e.g. 'error' to only
+    /// report warnings. Defaults to 'warning', so advice never fails the run
+    /// unless this is 'advice'.
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub fail_on: Option<LintSeverity>,
+
+    /// The most linters to run at once, unless `--jobs` is passed.
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub jobs: Option<usize>,
+}
+
+/// Keys that are valid at the top level of the config file.
+const CONFIG_KEYS: &[&str] = &[
+    "linter",
+    "target_query",
+    "notify_webhook",
+    "merge_base_with",
+    "shell",
+    "case_insensitive_patterns",
+    "normalize_unicode_paths",
+    "severity_overrides",
+    "profiles",
+    "import",
+];
+
+/// Keys that are valid in a `[[linter]]` table. Keep this in sync with
+/// [`LintConfig`].
+const LINTER_KEYS: &[&str] = &[
+    "code",
+    "include_patterns",
+    "exclude_patterns",
+    "command",
+    "init_command",
+    "is_formatter",
+    "run_after",
+    "exclusive",
+    "max_allowed",
+    "escalate_after_days",
+    "container",
+    "cache_dirs",
+    "slow_threshold",
+    "stack_sampler",
+    "shell",
+    "granularity",
+    "package_markers",
+    "enabled_if",
+    "version_command",
+    "version",
+    "version_mismatch",
+    "lockfiles",
+    "case_insensitive_patterns",
+    "normalize_unicode_paths",
+    "severity_overrides",
+];
+
+/// The name of the optional, git-ignored config overlay that lives next to
+/// the main config.
+pub const LOCAL_CONFIG_NAME: &str = ".lintrunner.local.toml";
+
 fn is_false(b: &bool) -> bool {
     !(*b)
 }
 
+/// Accept either a single string or a list of strings.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum StringOrVec {
+    String(String),
+    Vec(Vec<String>),
+}
+
+impl From<StringOrVec> for Vec<String> {
+    fn from(value: StringOrVec) -> Self {
+        match value {
+            StringOrVec::String(s) => vec![s],
+            StringOrVec::Vec(v) => v,
+        }
+    }
+}
+
+/// The keys a command can be specialized with, see [`LintConfig::command`].
+const PLATFORM_KEYS: &[&str] = &["windows", "macos", "linux", "unix", "default"];
+
+/// A command that is either the same on every platform, or a table of
+/// per-platform variants.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum PlatformCommand {
+    Any(StringOrVec),
+    PerPlatform(BTreeMap<String, StringOrVec>),
+}
+
+impl PlatformCommand {
+    /// The variant for the platform we're running on: the one named after the
+    /// OS, then `unix` on unix-likes, then `default`.
+    fn for_current_platform(self) -> Result<Option<Vec<String>>, String> {
+        let mut variants = match self {
+            PlatformCommand::Any(command) => return Ok(Some(command.into())),
+            PlatformCommand::PerPlatform(variants) => variants,
+        };
+        if let Some(unknown) = variants
+            .keys()
+            .find(|k| !PLATFORM_KEYS.contains(&k.as_str()))
+        {
+            return Err(format!(
+                "unknown platform '{}', expected one of {}",
+                unknown,
+                PLATFORM_KEYS.join(", ")
+            ));
+        }
+        let mut keys = vec![std::env::consts::OS];
+        if cfg!(unix) {
+            keys.push("unix");
+        }
+        keys.push("default");
+        Ok(keys
+            .into_iter()
+            .find_map(|key| variants.remove(key))
+            .map(Into::into))
+    }
+}
+
+/// Deserialize a required command, which may have per-platform variants.
+fn platform_command<'de, D>(deserializer: D) -> Result<Vec<String>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    PlatformCommand::deserialize(deserializer)?
+        .for_current_platform()
+        .map_err(serde::de::Error::custom)?
+        .ok_or_else(|| {
+            serde::de::Error::custom(format!(
+                "no command for {}; add a '{}' or 'default' variant",
+                std::env::consts::OS,
+                std::env::consts::OS
+            ))
+        })
+}
+
+/// Deserialize an optional command, which may have per-platform variants. A
+/// platform without a variant gets None.
+fn optional_platform_command<'de, D>(deserializer: D) -> Result<Option<Vec<String>>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    PlatformCommand::deserialize(deserializer)?
+        .for_current_platform()
+        .map_err(serde::de::Error::custom)
+}
+
 /// Represents a single linter, along with all the information necessary to invoke it.
 ///
 /// This goes in the linter configuration TOML file.
@@ -80,6 +327,14 @@ pub struct LintConfig {
     /// The paths in `{{PATHSFILE}}` will always be canoncalized (e.g. they are
     /// absolute paths with symlinks resolved).
     ///
+    /// An argument that is exactly `{{PATHS}}` is replaced by the paths to
+    /// lint, one argument each. If they don't fit on one command line, the
+    /// linter is run several times.
+    ///
+    /// `{{CONFIG_DIR}}`, `{{REPO_ROOT}}`, `{{DATA_DIR}}`, `{{NPROC}}` and
+    /// `{{REVISION}}` are substituted too, in `init_command` as well; see the
+    /// README for what they mean.
+    ///
     /// Commands are run with the current working directory set to the parent
     /// directory of the config file.
     ///
@@ -88,6 +343,21 @@ pub struct LintConfig {
     /// ```toml
     /// command = ['python3', 'my_linter.py', '--', '@{{PATHSFILE}}']
     /// ```
+    /// - Using a builtin linter, with options passed as `key=value`:
+    /// ```toml
+    /// command = 'builtin:whitespace'
+    /// command = ['builtin:tabs', 'indent=tabs']
+    /// ```
+    /// - Using a different command on some platforms. The variant named after
+    ///   the OS (`windows`, `macos` or `linux`) wins, then `unix` on anything
+    ///   but Windows, then `default`. This works for `init_command` and
+    ///   `stack_sampler` too; a platform without one of those simply doesn't
+    ///   have it.
+    /// ```toml
+    /// command.default = ['sh', 'lint.sh', '@{{PATHSFILE}}']
+    /// command.windows = ['pwsh', '-File', 'lint.ps1', '@{{PATHSFILE}}']
+    /// ```
+    #[serde(deserialize_with = "platform_command")]
     pub command: Vec<String>,
 
     /// A list of arguments describing how to set up the right dependencies for
@@ -107,6 +377,7 @@ pub struct LintConfig {
     /// ```toml
     /// init_command = ['python3', 'my_linter_init.py', '--dry-run={{DRYRUN}}']
     /// ```
+    #[serde(default, deserialize_with = "optional_platform_command")]
     pub init_command: Option<Vec<String>>,
 
     /// If true, this linter will be considered a formatter, and will invoked by
@@ -115,6 +386,359 @@ pub struct LintConfig {
     /// meaning of their code.
     #[serde(skip_serializing_if = "is_false", default = "bool::default")]
     pub is_formatter: bool,
+
+    /// A list of linter codes that must finish before this linter starts.
+    /// Linters without an ordering constraint between them still run in
+    /// parallel.
+    ///
+    /// This is useful for formatters that need to see each other's output
+    /// when patches are applied. Linters listed here that aren't being run
+    /// (e.g. because of `--skip`) are ignored.
+    ///
+    /// # Examples
+    /// ```toml
+    /// run_after = ['ISORT']
+    /// ```
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub run_after: Option<Vec<String>>,
+
+    /// If true, this linter will never run at the same time as any other
+    /// linter. Use this for linters that aren't safe to run concurrently with
+    /// others, e.g. because they write to a shared cache or use a huge amount
+    /// of memory. All other linters still run in parallel with each other.
+    #[serde(skip_serializing_if = "is_false", default = "bool::default")]
+    pub exclusive: bool,
+
+    /// The number of lint issues of each severity this linter may report
+    /// before the run fails. Issues within the budget are still reported.
+    /// Severities without a budget fail the run as usual.
+    ///
+    /// This is useful for ratcheting: set the budget to the current number of
+    /// warnings, and lower it over time as they get fixed.
+    ///
+    /// # Examples
+    /// ```toml
+    /// max_allowed = { warning = 50 }
+    /// ```
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_allowed: Option<MaxAllowed>,
+
+    /// Report warnings this linter first found at least this many days ago
+    /// as errors, so that warnings can't be ignored forever. How long an
+    /// issue has been around is tracked across runs by its fingerprint.
+    ///
+    /// # Examples
+    /// ```toml
+    /// escalate_after_days = 90
+    /// ```
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub escalate_after_days: Option<u32>,
+
+    /// If set, run the linter inside this container image (with `docker
+    /// run`) instead of on the host, so that everyone uses the exact same
+    /// linter version. The directory containing the config is mounted into
+    /// the container and used as the working directory; paths passed to and
+    /// reported by the linter are translated automatically.
+    ///
+    /// `lintrunner init` pulls the image.
+    ///
+    /// # Examples
+    /// ```toml
+    /// container = 'ghcr.io/org/clang-tidy:15'
+    /// ```
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub container: Option<String>,
+
+    /// Directories the linter writes to, e.g. for caching results. When
+    /// running with `--sandbox`, these are the only places (besides the
+    /// system temp directory) the linter is allowed to write to. Relative
+    /// paths are relative to the location of the config file.
+    ///
+    /// # Examples
+    /// ```toml
+    /// cache_dirs = ['.mypy_cache']
+    /// ```
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cache_dirs: Option<Vec<String>>,
+
+    /// How many seconds the linter may run before lintrunner warns about it,
+    /// naming the files it was given. Defaults to 120; 0 turns the warning
+    /// off.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub slow_threshold: Option<u64>,
+
+    /// A command to run when the linter is slow, to capture what it is
+    /// doing. `{{PID}}` is replaced with the linter's process id, and the
+    /// command's output is logged.
+    ///
+    /// # Examples
+    /// ```toml
+    /// stack_sampler = ['py-spy', 'dump', '--pid', '{{PID}}']
+    /// ```
+    #[serde(
+        skip_serializing_if = "Option::is_none",
+        default,
+        deserialize_with = "optional_platform_command"
+    )]
+    pub stack_sampler: Option<Vec<String>>,
+
+    /// How to run `command` and `init_command`: `'none'` (the default) spawns
+    /// them directly, while `'sh'`, `'bash'` and `'pwsh'` run them with that
+    /// shell. With a shell, the first element of the command is passed to it
+    /// as-is, so it can be a whole script; the remaining arguments (including
+    /// substituted placeholders) are quoted and appended. Defaults to the
+    /// top-level `shell`, if any.
+    ///
+    /// # Examples
+    /// ```toml
+    /// shell = 'bash'
+    /// command = 'git ls-files -z | xargs -0 mylinter --paths-file'
+    /// ```
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub shell: Option<Shell>,
+
+    /// What the linter is given to lint. With `'file'` (the default), it
+    /// gets the matched files. With `'directory'`, it gets the directories
+    /// containing them instead, each once, for tools like pylint that work
+    /// on whole packages. With `'project'`, it gets no paths at all (the paths
+    /// file is empty, and `{{PATHS}}` expands to nothing), and runs once if
+    /// any of the files being linted match its patterns. Use this for checks
+    /// of the whole project, like dependency audits.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub granularity: Option<Granularity>,
+
+    /// With `granularity = 'directory'`, files that mark the root of a
+    /// package. Each matched file is replaced by the closest directory above
+    /// it that contains one of these, rather than by its parent.
+    ///
+    /// # Examples
+    /// ```toml
+    /// granularity = 'directory'
+    /// package_markers = ['BUILD', 'BUILD.bazel']
+    /// ```
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub package_markers: Option<Vec<String>>,
+
+    /// Conditions that must all hold for the linter to run; otherwise it is
+    /// skipped, as if by `--skip`. Use this for linters that only work on
+    /// some platforms, or that need an optional toolchain.
+    ///
+    /// - `os`: the OS is one of these (`linux`, `macos`, `windows`, or
+    ///   `unix` for anything but Windows).
+    /// - `env`: each of these environment variables is set and not empty.
+    ///   `VAR=value` requires a specific value, and `!VAR` requires the
+    ///   variable to be unset or empty.
+    /// - `command`: this probe command succeeds. It is run from the directory
+    ///   containing the config, with its output discarded.
+    ///
+    /// # Examples
+    /// ```toml
+    /// enabled_if = { os = ['linux'], env = ['!SKIP_CUDA'], command = ['nvcc', '--version'] }
+    /// ```
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub enabled_if: Option<EnabledIf>,
+
+    /// A command that prints the version of the tool the linter runs, like
+    /// `command` it is run from the directory containing the config. Before
+    /// linting, lintrunner runs it once and compares the first version
+    /// number it prints with `version`.
+    ///
+    /// # Examples
+    /// ```toml
+    /// version_command = ['black', '--version']
+    /// ```
+    #[serde(
+        skip_serializing_if = "Option::is_none",
+        default,
+        deserialize_with = "optional_platform_command"
+    )]
+    pub version_command: Option<Vec<String>>,
+
+    /// The version of the tool the linter expects: either a version, which
+    /// also matches more specific ones (`'23.1'` matches `23.1.4`), or
+    /// comma-separated comparisons with `=`, `>`, `>=`, `<` and `<=`.
+    ///
+    /// # Examples
+    /// ```toml
+    /// version = '>=23.1, <24'
+    /// ```
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub version: Option<String>,
+
+    /// What to do when the installed version doesn't match `version`:
+    /// `'warn'` (the default) or `'fail'` to not lint at all.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub version_mismatch: Option<VersionMismatch>,
+
+    /// Files that determine what `init_command` installs, like a
+    /// requirements file. `lintrunner verify` reports the linter as out of
+    /// date when they change after `lintrunner init`. Relative paths are
+    /// relative to the location of the config file.
+    ///
+    /// # Examples
+    /// ```toml
+    /// lockfiles = ['tools/linter/requirements.txt']
+    /// ```
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub lockfiles: Option<Vec<String>>,
+
+    /// Match `include_patterns` and `exclude_patterns` ignoring case, for
+    /// repos checked out on case-insensitive file systems (macOS and Windows,
+    /// by default), where `Foo.cmake` would otherwise escape a `foo*`
+    /// exclude. Defaults to the top-level `case_insensitive_patterns`.
+    ///
+    /// # Examples
+    /// ```toml
+    /// case_insensitive_patterns = true
+    /// ```
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub case_insensitive_patterns: Option<bool>,
+
+    /// Compose accented Latin letters in paths and patterns before matching,
+    /// so that a pattern typed with `é` matches a file name macOS stores as
+    /// `e` followed by a combining accent. Defaults to the top-level
+    /// `normalize_unicode_paths`.
+    ///
+    /// # Examples
+    /// ```toml
+    /// normalize_unicode_paths = true
+    /// ```
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub normalize_unicode_paths: Option<bool>,
+
+    /// Change the severity of the linter's issues in files matching a
+    /// pattern (relative to the config file), e.g. to keep issues in vendored
+    /// code visible but not blocking. The first matching rule wins; the
+    /// top-level `severity_overrides` are tried after the linter's own.
+    /// Linter failures are never overridden.
+    ///
+    /// # Examples
+    /// ```toml
+    /// severity_overrides = [
+    ///     { pattern = 'third_party/**', severity = 'advice' },
+    ///     { pattern = 'torch/_vendor/**', severity = 'disabled' },
+    /// ]
+    /// ```
+    #[serde(skip_serializing_if = "Vec::is_empty", default)]
+    pub severity_overrides: Vec<SeverityOverride>,
+
+    /// The file that sets `include_patterns`, when the config was read from
+    /// a file. It can be an import or the local overlay.
+    #[serde(skip)]
+    pub include_patterns_source: Option<PathBuf>,
+
+    /// The file that sets `exclude_patterns`, like `include_patterns_source`.
+    #[serde(skip)]
+    pub exclude_patterns_source: Option<PathBuf>,
+}
+
+/// A rule for [`LintConfig::severity_overrides`].
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct SeverityOverride {
+    pub pattern: String,
+    pub severity: LintSeverity,
+}
+
+impl LintConfig {
+    /// Whether the linter's `enabled_if` conditions hold, with the config in
+    /// `config_dir`.
+    pub fn is_enabled(&self, config_dir: &Path) -> bool {
+        match &self.enabled_if {
+            Some(enabled_if) => match enabled_if.unmet(config_dir) {
+                Some(unmet) => {
+                    debug!("Linter '{}' is disabled: {}", self.code, unmet);
+                    false
+                }
+                None => true,
+            },
+            None => true,
+        }
+    }
+}
+
+/// Conditions for [`LintConfig::enabled_if`].
+#[derive(Serialize, Deserialize, Clone, Default)]
+#[serde(deny_unknown_fields)]
+pub struct EnabledIf {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub os: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub env: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub command: Option<Vec<String>>,
+}
+
+impl EnabledIf {
+    /// Describe the first condition that doesn't hold, if any.
+    fn unmet(&self, config_dir: &Path) -> Option<String> {
+        if let Some(os) = &self.os {
+            let current = std::env::consts::OS;
+            if !os
+                .iter()
+                .any(|os| os == current || (os == "unix" && cfg!(unix)))
+            {
+                return Some(format!("the OS is {}, not one of {:?}", current, os));
+            }
+        }
+        for condition in self.env.iter().flatten() {
+            if !env_condition_holds(condition) {
+                return Some(format!("the environment doesn't match '{}'", condition));
+            }
+        }
+        if let Some(command) = &self.command {
+            let (program, args) = command.split_first()?;
+            let succeeded = Command::new(program)
+                .args(args)
+                .current_dir(config_dir)
+                .stdin(Stdio::null())
+                .stdout(Stdio::null())
+                .stderr(Stdio::null())
+                .status()
+                .is_ok_and(|status| status.success());
+            if !succeeded {
+                return Some(format!("probe command {:?} failed", command));
+            }
+        }
+        None
+    }
+}
+
+/// Check an `enabled_if.env` condition: `VAR`, `VAR=value` or `!VAR`.
+fn env_condition_holds(condition: &str) -> bool {
+    let value = |name: &str| std::env::var(name).ok().filter(|value| !value.is_empty());
+    if let Some(name) = condition.strip_prefix('!') {
+        return value(name).is_none();
+    }
+    match condition.split_once('=') {
+        Some((name, expected)) => std::env::var(name).is_ok_and(|value| value == expected),
+        None => value(condition).is_some(),
+    }
+}
+
+/// Per-severity budgets for [`LintConfig::max_allowed`].
+#[derive(Serialize, Deserialize, Clone, Default)]
+#[serde(deny_unknown_fields)]
+pub struct MaxAllowed {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub warning: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub advice: Option<usize>,
+}
+
+impl MaxAllowed {
+    fn budgets(&self) -> BTreeMap<LintSeverity, usize> {
+        [
+            (LintSeverity::Error, self.error),
+            (LintSeverity::Warning, self.warning),
+            (LintSeverity::Advice, self.advice),
+        ]
+        .into_iter()
+        .filter_map(|(severity, max)| max.map(|max| (severity, max)))
+        .collect()
+    }
 }
 
 /// Given options specified by the user, return a list of linters to run.
@@ -126,6 +750,8 @@ pub fn get_linters_from_config(
 ) -> Result<Vec<Linter>> {
     let mut linters = Vec::new();
     let mut all_linters: HashSet<String> = HashSet::new();
+    // Unwrap is fine because the config path must be a file.
+    let config_dir = config_path.parent().unwrap();
 
     for lint_config in linter_configs {
         if all_linters.contains(&lint_config.code) {
@@ -136,30 +762,151 @@ pub fn get_linters_from_config(
         }
         all_linters.insert(lint_config.code.clone());
 
-        let include_patterns = patterns_from_strs(&lint_config.include_patterns)?;
+        let pattern_options = PatternOptions {
+            case_insensitive: lint_config.case_insensitive_patterns.unwrap_or(false),
+            normalize_unicode: lint_config.normalize_unicode_paths.unwrap_or(false),
+        };
+        let include_patterns = patterns_from_strs(&lint_config.include_patterns, pattern_options)?;
         let exclude_patterns = if let Some(exclude_patterns) = &lint_config.exclude_patterns {
-            patterns_from_strs(exclude_patterns)?
+            patterns_from_strs(exclude_patterns, pattern_options)?
         } else {
             Vec::new()
         };
+        let override_patterns: Vec<String> = lint_config
+            .severity_overrides
+            .iter()
+            .map(|rule| rule.pattern.clone())
+            .collect();
+        let severity_overrides = patterns_from_strs(&override_patterns, pattern_options)?
+            .into_iter()
+            .zip(&lint_config.severity_overrides)
+            .map(|(pattern, rule)| (pattern, rule.severity))
+            .collect();
 
         ensure!(
             !lint_config.command.is_empty(),
             "Invalid linter configuration: '{}' has an empty command list.",
             lint_config.code
         );
+        ensure!(
+            lint_config
+                .command
+                .iter()
+                .all(|arg| arg == PATHS_PLACEHOLDER || !arg.contains(PATHS_PLACEHOLDER)),
+            "Invalid linter configuration: '{}' uses {} inside an argument; it has to be an \
+             argument of its own.",
+            lint_config.code,
+            PATHS_PLACEHOLDER
+        );
+        ensure!(
+            lint_config.package_markers.is_none()
+                || lint_config.granularity == Some(Granularity::Directory),
+            "Invalid linter configuration: '{}' has package_markers, which only apply with \
+             granularity = 'directory'.",
+            lint_config.code
+        );
+        ensure!(
+            !matches!(
+                &lint_config.enabled_if,
+                Some(EnabledIf { command: Some(command), .. }) if command.is_empty()
+            ),
+            "Invalid linter configuration: '{}' has an empty enabled_if.command.",
+            lint_config.code
+        );
+        let version_check = match (&lint_config.version_command, &lint_config.version) {
+            (Some(command), Some(version)) => {
+                ensure!(
+                    !command.is_empty(),
+                    "Invalid linter configuration: '{}' has an empty version_command.",
+                    lint_config.code
+                );
+                Some(VersionCheck {
+                    command: command.clone(),
+                    requirement: VersionReq::parse(version).with_context(|| {
+                        format!("Invalid linter configuration for '{}'", lint_config.code)
+                    })?,
+                    on_mismatch: lint_config.version_mismatch.unwrap_or_default(),
+                })
+            }
+            (None, None) => None,
+            _ => bail!(
+                "Invalid linter configuration: '{}' needs both version_command and version.",
+                lint_config.code
+            ),
+        };
+        ensure!(
+            !matches!(&lint_config.stack_sampler, Some(sampler) if sampler.is_empty()),
+            "Invalid linter configuration: '{}' has an empty stack_sampler.",
+            lint_config.code
+        );
+        if builtin::builtin_name(&lint_config.command).is_some() {
+            builtin::validate(&lint_config.command).with_context(|| {
+                format!("Invalid linter configuration for '{}'", lint_config.code)
+            })?;
+        }
 
+        let pattern_source =
+            |patterns: Option<&Vec<String>>, path: &Option<PathBuf>| PatternSource {
+                patterns: patterns.cloned().unwrap_or_default(),
+                path: path.clone().unwrap_or_else(|| config_path.to_path_buf()),
+            };
         linters.push(Linter {
             code: lint_config.code.clone(),
             include_patterns,
             exclude_patterns,
+            include_source: pattern_source(
+                Some(&lint_config.include_patterns),
+                &lint_config.include_patterns_source,
+            ),
+            exclude_source: pattern_source(
+                lint_config.exclude_patterns.as_ref(),
+                &lint_config.exclude_patterns_source,
+            ),
             commands: lint_config.command.clone(),
             init_commands: lint_config.init_command.clone(),
             config_path: config_path.clone(),
+            run_after: lint_config.run_after.clone().unwrap_or_default(),
+            exclusive: lint_config.exclusive,
+            max_allowed: lint_config
+                .max_allowed
+                .as_ref()
+                .map(MaxAllowed::budgets)
+                .unwrap_or_default(),
+            escalate_after_days: lint_config.escalate_after_days,
+            container: lint_config.container.clone(),
+            cache_dirs: lint_config
+                .cache_dirs
+                .iter()
+                .flatten()
+                .map(|dir| config_dir.join(dir))
+                .collect(),
+            sandbox: false,
+            shell: lint_config.shell.unwrap_or_default(),
+            slow_threshold: match lint_config.slow_threshold {
+                Some(0) => None,
+                Some(seconds) => Some(Duration::from_secs(seconds)),
+                None => Some(DEFAULT_SLOW_THRESHOLD),
+            },
+            stack_sampler: lint_config.stack_sampler.clone(),
+            flaky: false,
+            fail_on: None,
+            placeholders: BTreeMap::new(),
+            granularity: lint_config.granularity.unwrap_or_default(),
+            package_markers: lint_config.package_markers.clone().unwrap_or_default(),
+            version_check,
+            lockfiles: lint_config
+                .lockfiles
+                .iter()
+                .flatten()
+                .map(|path| config_dir.join(path))
+                .collect(),
+            pattern_options,
+            severity_overrides,
         });
     }
 
     debug!("Found linters: {:?}", all_linters);
+    check_run_after(&linters)?;
 
     // Apply --take
     if let Some(taken_linters) = taken_linters {
@@ -189,20 +936,473 @@ pub fn get_linters_from_config(
         }
         linters.retain(|linter| !skipped_linters.contains(&linter.code));
     }
+
+    // Check `enabled_if` last, so that probes only run for linters that would
+    // run otherwise.
+    let disabled: HashSet<&str> = linter_configs
+        .iter()
+        .filter(|config| linters.iter().any(|linter| linter.code == config.code))
+        .filter(|config| !config.is_enabled(config_dir))
+        .map(|config| config.code.as_str())
+        .collect();
+    linters.retain(|linter| !disabled.contains(linter.code.as_str()));
     Ok(linters)
 }
 
+// Make sure that every `run_after` refers to a real linter and that there are
+// no cycles, which would cause linters to wait on each other forever.
+fn check_run_after(linters: &[Linter]) -> Result<()> {
+    let deps: HashMap<&str, &Vec<String>> = linters
+        .iter()
+        .map(|linter| (linter.code.as_str(), &linter.run_after))
+        .collect();
+
+    for linter in linters {
+        for dep in &linter.run_after {
+            ensure!(
+                deps.contains_key(dep.as_str()),
+                "Invalid linter configuration: '{}' should run after '{}', \
+                 but no such linter is defined.",
+                linter.code,
+                dep
+            );
+        }
+    }
+
+    // Depth-first search, tracking the current path to detect cycles.
+    fn visit<'a>(
+        code: &'a str,
+        deps: &HashMap<&'a str, &'a Vec<String>>,
+        path: &mut Vec<&'a str>,
+        done: &mut HashSet<&'a str>,
+    ) -> Result<()> {
+        if done.contains(code) {
+            return Ok(());
+        }
+        if let Some(idx) = path.iter().position(|c| *c == code) {
+            let mut cycle = path[idx..].to_vec();
+            cycle.push(code);
+            bail!(
+                "Invalid linter configuration: `run_after` has a cycle: {}",
+                cycle.join(" -> ")
+            );
+        }
+        path.push(code);
+        for dep in deps[code].iter() {
+            visit(dep, deps, path, done)?;
+        }
+        path.pop();
+        done.insert(code);
+        Ok(())
+    }
+
+    let mut done = HashSet::new();
+    for linter in linters {
+        visit(&linter.code, &deps, &mut Vec::new(), &mut done)?;
+    }
+    Ok(())
+}
+
+/// Add extra arguments to the commands of specific linters, as given by
+/// `--linter-arg CODE=ARG` or arguments after `--`. They go before the
+/// paths: before the first `--`, `{{PATHSFILE}}` or `{{PATHS}}` argument, or
+/// else at the end.
+pub fn add_linter_args(linters: &mut [Linter], linter_args: &[(String, String)]) -> Result<()> {
+    for (code, arg) in linter_args {
+        let linter = linters.iter_mut().find(|linter| &linter.code == code);
+        match linter {
+            Some(linter) => {
+                let paths_start = linter
+                    .commands
+                    .iter()
+                    .position(|part| {
+                        part == "--" || part.contains("{{PATHSFILE}}") || part.contains("{{PATHS}}")
+                    })
+                    .unwrap_or(linter.commands.len());
+                linter.commands.insert(paths_start, arg.clone());
+            }
+            None => bail!(
+                "Extra argument '{}' was provided for linter '{}', but that linter is not \
+                 being run. These linters are being run: {:?}",
+                arg,
+                code,
+                linters.iter().map(|l| &l.code).collect::<Vec<_>>(),
+            ),
+        }
+    }
+    Ok(())
+}
+
+// Find the valid key closest to an unknown one, if any is close enough that
+// the unknown key is plausibly a typo of it.
+fn suggest_key<'a>(unknown: &str, valid_keys: &[&'a str]) -> Option<&'a str> {
+    valid_keys
+        .iter()
+        .map(|key| (strsim::levenshtein(unknown, key), *key))
+        .filter(|(distance, _)| *distance <= 3)
+        .min()
+        .map(|(_, key)| key)
+}
+
+fn unknown_key_message(unknown: &str, location: &str, valid_keys: &[&str]) -> String {
+    match suggest_key(unknown, valid_keys) {
+        Some(suggestion) => format!(
+            "Unknown key '{}' {}. Did you mean '{}'?",
+            unknown, location, suggestion
+        ),
+        None => format!("Unknown key '{}' {}.", unknown, location),
+    }
+}
+
+/// Return a description of every key in the config that lintrunner doesn't
+/// know about. Such keys are otherwise silently ignored, which makes typos
+/// hard to spot.
+pub fn find_unknown_keys(config_str: &str) -> Result<Vec<String>> {
+    let config: toml::Value =
+        toml::from_str(config_str).context("Config file had invalid schema")?;
+    Ok(unknown_keys(&config, LINTER_KEYS))
+}
+
+fn unknown_keys(config: &toml::Value, linter_keys: &[&str]) -> Vec<String> {
+    let mut unknown = Vec::new();
+    let table = match config.as_table() {
+        Some(table) => table,
+        None => return unknown,
+    };
+
+    for (key, value) in table {
+        if !CONFIG_KEYS.contains(&key.as_str()) {
+            unknown.push(unknown_key_message(key, "in config", CONFIG_KEYS));
+            continue;
+        }
+        if key != "linter" {
+            continue;
+        }
+        for linter in value.as_array().into_iter().flatten() {
+            let linter = match linter.as_table() {
+                Some(linter) => linter,
+                None => continue,
+            };
+            let code = linter
+                .get("code")
+                .and_then(|code| code.as_str())
+                .unwrap_or("<unknown>");
+            for key in linter.keys() {
+                if !linter_keys.contains(&key.as_str()) {
+                    unknown.push(unknown_key_message(
+                        key,
+                        &format!("for linter '{}'", code),
+                        linter_keys,
+                    ));
+                }
+            }
+        }
+    }
+    unknown
+}
+
+/// Merge `overlay`, a config, on top of `config`, an imported one: its
+/// settings replace the imported ones, and its linters are merged like the
+/// local overlay's.
+fn merge_config(config: &mut toml::Value, overlay: toml::Value) -> Result<()> {
+    let table = config
+        .as_table_mut()
+        .context("Imported config must be a table")?;
+    table
+        .entry("linter")
+        .or_insert_with(|| toml::Value::Array(Vec::new()));
+    if let toml::Value::Table(overlay_table) = &overlay {
+        for (key, value) in overlay_table {
+            if key != "linter" {
+                table.insert(key.clone(), value.clone());
+            }
+        }
+    }
+    merge_local_config(config, overlay)
+}
+
+/// Merge a local config overlay into `config`. Each linter in the overlay
+/// either overrides the keys it specifies for the linter with the same code,
+/// adds a new linter, or (with `disabled = true`) removes the linter.
+fn merge_local_config(config: &mut toml::Value, local: toml::Value) -> Result<()> {
+    let linters = config
+        .as_table_mut()
+        .and_then(|table| table.get_mut("linter"))
+        .and_then(|linters| linters.as_array_mut())
+        .context("Config file has no linters")?;
+
+    let local_linters = match local.get("linter").and_then(|l| l.as_array()) {
+        Some(local_linters) => local_linters,
+        None => return Ok(()),
+    };
+    for local_linter in local_linters {
+        let local_linter = local_linter
+            .as_table()
+            .context("Local config linters must be tables")?;
+        let code = local_linter
+            .get("code")
+            .and_then(|code| code.as_str())
+            .context("Every linter in the local config must specify a `code`")?;
+        let existing = linters
+            .iter()
+            .position(|linter| linter.get("code").and_then(|c| c.as_str()) == Some(code));
+
+        let disabled = local_linter
+            .get("disabled")
+            .and_then(|disabled| disabled.as_bool())
+            .unwrap_or(false);
+        if disabled {
+            debug!("Local config disables linter '{}'", code);
+            if let Some(existing) = existing {
+                linters.remove(existing);
+            }
+            continue;
+        }
+
+        match existing {
+            Some(existing) => {
+                debug!("Local config overrides linter '{}'", code);
+                // Unwrap is fine because we found the code in this table.
+                let linter = linters[existing].as_table_mut().unwrap();
+                for (key, value) in local_linter {
+                    linter.insert(key.clone(), value.clone());
+                }
+            }
+            None => {
+                debug!("Local config adds linter '{}'", code);
+                linters.push(toml::Value::Table(local_linter.clone()));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// The linter patterns `config`, read from `source`, sets: for each linter
+/// code and pattern key, `source`.
+fn pattern_sources(
+    config: &toml::Value,
+    source: &Path,
+) -> HashMap<(String, &'static str), PathBuf> {
+    let mut sources = HashMap::new();
+    let linters = config
+        .get("linter")
+        .and_then(|linters| linters.as_array())
+        .into_iter()
+        .flatten();
+    for linter in linters {
+        let Some(code) = linter.get("code").and_then(|code| code.as_str()) else {
+            continue;
+        };
+        for key in ["include_patterns", "exclude_patterns"] {
+            if linter.get(key).is_some() {
+                sources.insert((code.to_string(), key), source.to_path_buf());
+            }
+        }
+    }
+    sources
+}
+
+/// Find the (1-based) line in the config where linter `code` specifies
+/// `pattern`. This is best-effort, since we don't keep source locations around
+/// when parsing the config.
+pub fn find_pattern_line(config_str: &str, code: &str, pattern: &str) -> Option<usize> {
+    let lines: Vec<&str> = config_str.lines().collect();
+    let code_re = Regex::new(&format!(
+        r#"^\s*code\s*=\s*['"]{}['"]"#,
+        regex::escape(code)
+    ))
+    .ok()?;
+    let code_line = lines.iter().position(|line| code_re.is_match(line))?;
+
+    // Figure out the extent of this linter's table.
+    let start = lines[..code_line]
+        .iter()
+        .rposition(|line| line.trim_start().starts_with("[[linter]]"))
+        .unwrap_or(0);
+    let end = lines[code_line..]
+        .iter()
+        .position(|line| line.trim_start().starts_with('['))
+        .map_or(lines.len(), |end| code_line + end);
+
+    let quoted = [format!("'{}'", pattern), format!("\"{}\"", pattern)];
+    (start..end)
+        .find(|idx| quoted.iter().any(|q| lines[*idx].contains(q.as_str())))
+        .map(|idx| idx + 1)
+}
+
 impl LintRunnerConfig {
-    pub fn new(path: &AbsPath) -> Result<LintRunnerConfig> {
-        let lint_config = fs::read_to_string(&path)
+    /// Read the config at `path`, merging in the local overlay next to it
+    /// (if `use_local` is set and the overlay exists). Unknown keys produce a
+    /// warning, or an error if `strict` is set.
+    pub fn new(path: &AbsPath, strict: bool, use_local: bool) -> Result<LintRunnerConfig> {
+        let lint_config = fs::read_to_string(path)
             .context(format!("Failed to read config file: '{}'.", path.display()))?;
-        LintRunnerConfig::new_from_string(&lint_config)
+        let mut config: toml::Value =
+            toml::from_str(&lint_config).context("Config file had invalid schema")?;
+        let imports = config
+            .as_table_mut()
+            .and_then(|table| table.remove("import"));
+        // Linters can drop imported linters the same way the local overlay
+        // drops linters.
+        let disabled_keys: Vec<&str> = LINTER_KEYS.iter().copied().chain(["disabled"]).collect();
+        // Which file sets each linter's patterns, for `lintrunner why`. Later
+        // files override earlier ones, as when merging.
+        let mut sources = pattern_sources(&config, path);
+        let mut unknown = unknown_keys(
+            &config,
+            if imports.is_some() {
+                &disabled_keys
+            } else {
+                LINTER_KEYS
+            },
+        );
+
+        // Merging goes through `toml::Value`, which loses the locations that
+        // schema errors point to, so only do it when there is something to
+        // merge.
+        let mut merged = false;
+        if let Some(imports) = imports {
+            merged = true;
+            // Unwrap is fine because the config path must be a file.
+            let config_dir = path.parent().unwrap();
+            let mut imported = toml::Value::Table(toml::value::Table::new());
+            let mut imported_sources = HashMap::new();
+            for spec in ImportSpec::parse_all(imports)? {
+                debug!("Importing linters from '{}'", spec.source());
+                let contents = imports::load(&spec, config_dir)?;
+                let import: toml::Value = toml::from_str(&contents)
+                    .with_context(|| format!("Import '{}' had invalid schema", spec.source()))?;
+                ensure!(
+                    import.get("import").is_none(),
+                    "Import '{}' can't import other files",
+                    spec.source()
+                );
+                unknown.extend(
+                    unknown_keys(&import, LINTER_KEYS)
+                        .into_iter()
+                        .map(|unknown| format!("{} (in {})", unknown, spec.source())),
+                );
+                imported_sources.extend(pattern_sources(
+                    &import,
+                    &imports::location(&spec, config_dir)?,
+                ));
+                merge_config(&mut imported, import)?;
+            }
+            merge_config(&mut imported, config)?;
+            config = imported;
+            imported_sources.extend(sources);
+            sources = imported_sources;
+        }
+
+        // Unwrap is fine because the config path must be a file.
+        let local_path = path.parent().unwrap().join(LOCAL_CONFIG_NAME);
+        if use_local && local_path.exists() {
+            debug!("Merging local config from '{}'", local_path.display());
+            let local_config = fs::read_to_string(&local_path).context(format!(
+                "Failed to read local config file: '{}'.",
+                local_path.display()
+            ))?;
+            let local_config: toml::Value =
+                toml::from_str(&local_config).context("Local config file had invalid schema")?;
+            // The overlay can additionally disable linters.
+            unknown.extend(
+                unknown_keys(&local_config, &disabled_keys)
+                    .into_iter()
+                    .map(|unknown| format!("{} (in {})", unknown, local_path.display())),
+            );
+            sources.extend(pattern_sources(&local_config, &local_path));
+            merge_local_config(&mut config, local_config)?;
+            merged = true;
+        }
+
+        let mut config: LintRunnerConfig = if merged {
+            config.try_into()
+        } else {
+            toml::from_str(&lint_config)
+        }
+        .context("Config file had invalid schema")?;
+
+        // Report unknown keys only for configs that are otherwise valid, so
+        // that they don't distract from a schema error.
+        if strict && !unknown.is_empty() {
+            bail!(
+                "Invalid config file '{}':\n{}",
+                path.display(),
+                unknown.join("\n")
+            );
+        }
+        for unknown_key in unknown {
+            warn!("{}", unknown_key);
+        }
+
+        for linter in &mut config.linters {
+            linter.include_patterns_source =
+                sources.remove(&(linter.code.clone(), "include_patterns"));
+            linter.exclude_patterns_source =
+                sources.remove(&(linter.code.clone(), "exclude_patterns"));
+        }
+
+        config.validate()?;
+        config.apply_defaults();
+        Ok(config)
     }
 
     pub fn new_from_string(config_str: &str) -> Result<LintRunnerConfig> {
-        let config: LintRunnerConfig =
+        let mut config: LintRunnerConfig =
             toml::from_str(config_str).context("Config file had invalid schema")?;
-        for linter in &config.linters {
+        config.validate()?;
+        config.apply_defaults();
+        Ok(config)
+    }
+
+    /// Look up the profile called `name`.
+    pub fn profile(&self, name: &str) -> Result<&Profile> {
+        self.profiles.get(name).with_context(|| {
+            if self.profiles.is_empty() {
+                format!("Unknown profile '{}': the config defines none", name)
+            } else {
+                format!(
+                    "Unknown profile '{}', expected one of: {}",
+                    name,
+                    self.profiles.keys().cloned().collect::<Vec<_>>().join(", ")
+                )
+            }
+        })
+    }
+
+    /// Fill in linter settings that default to a top-level one.
+    fn apply_defaults(&mut self) {
+        for linter in &mut self.linters {
+            linter.shell = linter.shell.or(self.shell);
+            linter.case_insensitive_patterns = linter
+                .case_insensitive_patterns
+                .or(self.case_insensitive_patterns);
+            linter.normalize_unicode_paths = linter
+                .normalize_unicode_paths
+                .or(self.normalize_unicode_paths);
+            linter
+                .severity_overrides
+                .extend(self.severity_overrides.iter().cloned());
+        }
+    }
+
+    fn validate(&self) -> Result<()> {
+        for (name, profile) in &self.profiles {
+            ensure!(
+                profile.jobs != Some(0),
+                "Invalid config: profile '{}' sets jobs to 0",
+                name
+            );
+            // Linters can be disabled in the local config, so this is only
+            // worth a warning.
+            let codes = profile.skip.iter().chain(profile.take.iter().flatten());
+            for code in codes {
+                if !self.linters.iter().any(|linter| &linter.code == code) {
+                    warn!("Profile '{}' refers to unknown linter '{}'", name, code);
+                }
+            }
+        }
+        for linter in &self.linters {
             if let Some(init_args) = &linter.init_command {
                 if init_args.iter().all(|arg| !arg.contains("{{DRYRUN}}")) {
                     bail!(
@@ -213,19 +1413,532 @@ impl LintRunnerConfig {
                 }
             }
         }
-
-        Ok(config)
+        Ok(())
     }
 }
 
-fn patterns_from_strs(pattern_strs: &[String]) -> Result<Vec<Pattern>> {
+fn patterns_from_strs(pattern_strs: &[String], options: PatternOptions) -> Result<Vec<Pattern>> {
     pattern_strs
         .iter()
         .map(|pattern_str| {
-            Pattern::new(pattern_str).map_err(|err| {
+            Pattern::new(&options.fold(pattern_str)).map_err(|err| {
                 anyhow::Error::msg(err)
                     .context("Could not parse pattern from linter configuration.")
             })
         })
         .collect()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::linter::MatchReason;
+
+    #[test]
+    fn unknown_keys_are_found() -> Result<()> {
+        let unknown = find_unknown_keys(
+            "\
+            typo = 1
+
+            [[linter]]
+            code = 'FOO'
+            include_patterns = ['**']
+            exclude_pattern = ['foo/**']
+            command = ['foo']
+            zzzzzzzzzz = true
+            ",
+        )?;
+        assert_eq!(
+            unknown,
+            vec![
+                "Unknown key 'exclude_pattern' for linter 'FOO'. Did you mean 'exclude_patterns'?"
+                    .to_string(),
+                "Unknown key 'zzzzzzzzzz' for linter 'FOO'.".to_string(),
+                "Unknown key 'typo' in config.".to_string(),
+            ]
+        );
+        Ok(())
+    }
+
+    fn linters_from_str(config_str: &str) -> Result<Vec<Linter>> {
+        let config = LintRunnerConfig::new_from_string(config_str)?;
+        let config_path = AbsPath::try_from(".")?;
+        get_linters_from_config(&config.linters, None, None, &config_path)
+    }
+
+    #[test]
+    fn linter_args_go_before_paths() -> Result<()> {
+        let mut linters = linters_from_str(
+            "\
+            [[linter]]
+            code = 'MYPY'
+            include_patterns = ['**']
+            command = ['python3', 'mypy_linter.py', '--', '@{{PATHSFILE}}']
+
+            [[linter]]
+            code = 'CLANGFORMAT'
+            include_patterns = ['**']
+            command = ['clang-format', '{{PATHS}}']
+
+            [[linter]]
+            code = 'SHELLCHECK'
+            include_patterns = ['**']
+            command = ['shellcheck']
+            ",
+        )?;
+        let arg = |code: &str, arg: &str| (code.to_string(), arg.to_string());
+        add_linter_args(
+            &mut linters,
+            &[
+                arg("MYPY", "--strict-equality"),
+                arg("MYPY", "--warn-unused-ignores"),
+                arg("CLANGFORMAT", "--style=llvm"),
+                arg("SHELLCHECK", "-x"),
+            ],
+        )?;
+        assert_eq!(
+            linters[0].commands,
+            [
+                "python3",
+                "mypy_linter.py",
+                "--strict-equality",
+                "--warn-unused-ignores",
+                "--",
+                "@{{PATHSFILE}}"
+            ]
+        );
+        assert_eq!(
+            linters[1].commands,
+            ["clang-format", "--style=llvm", "{{PATHS}}"]
+        );
+        assert_eq!(linters[2].commands, ["shellcheck", "-x"]);
+
+        assert!(add_linter_args(&mut linters, &[arg("FLAKE8", "-v")]).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn builtin_commands_are_checked() -> Result<()> {
+        let linters = linters_from_str(
+            "\
+            [[linter]]
+            code = 'WS'
+            include_patterns = ['**']
+            command = 'builtin:whitespace'
+            ",
+        )?;
+        assert_eq!(linters[0].commands, vec!["builtin:whitespace".to_string()]);
+
+        let err = linters_from_str(
+            "\
+            [[linter]]
+            code = 'WS'
+            include_patterns = ['**']
+            command = 'builtin:whitespcae'
+            ",
+        )
+        .err()
+        .unwrap();
+        assert!(format!("{:#}", err).contains("Unknown builtin linter 'whitespcae'"));
+        Ok(())
+    }
+
+    #[test]
+    fn platform_commands_are_picked() -> Result<()> {
+        let config = LintRunnerConfig::new_from_string(
+            "\
+            [[linter]]
+            code = 'A'
+            include_patterns = ['**']
+            command.default = ['default']
+            command.windows = ['windows']
+            command.unix = 'unix'
+            init_command = { linux = ['linux', '{{DRYRUN}}'] }
+            ",
+        )?;
+        let linter = &config.linters[0];
+        let (command, init_command) = if cfg!(windows) {
+            ("windows", None)
+        } else if cfg!(target_os = "linux") {
+            (
+                "unix",
+                Some(vec!["linux".to_string(), "{{DRYRUN}}".to_string()]),
+            )
+        } else {
+            ("unix", None)
+        };
+        assert_eq!(linter.command, vec![command.to_string()]);
+        assert_eq!(linter.init_command, init_command);
+
+        let err = LintRunnerConfig::new_from_string(
+            "\
+            [[linter]]
+            code = 'A'
+            include_patterns = ['**']
+            command.window = ['windows']
+            command.default = ['default']
+            ",
+        )
+        .err()
+        .unwrap();
+        assert!(
+            format!("{:#}", err).contains("unknown platform 'window'"),
+            "{:#}",
+            err
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn disabled_linters_are_dropped() -> Result<()> {
+        std::env::set_var("LINTRUNNER_TEST_ENABLED_IF", "yes");
+        let linters = linters_from_str(
+            "\
+            [[linter]]
+            code = 'ALWAYS'
+            include_patterns = ['**']
+            command = ['a']
+            enabled_if = { env = ['LINTRUNNER_TEST_ENABLED_IF=yes', '!LINTRUNNER_TEST_UNSET'] }
+
+            [[linter]]
+            code = 'NEVER_OS'
+            include_patterns = ['**']
+            command = ['a']
+            enabled_if = { os = ['plan9'] }
+
+            [[linter]]
+            code = 'NEVER_ENV'
+            include_patterns = ['**']
+            command = ['a']
+            enabled_if = { env = ['LINTRUNNER_TEST_UNSET'] }
+
+            [[linter]]
+            code = 'NEVER_PROBE'
+            include_patterns = ['**']
+            command = ['a']
+            enabled_if = { command = ['idonotexist'] }
+            ",
+        )?;
+        let codes: Vec<&str> = linters.iter().map(|l| l.code.as_str()).collect();
+        assert_eq!(codes, vec!["ALWAYS"]);
+        Ok(())
+    }
+
+    #[test]
+    fn profiles_are_parsed() -> Result<()> {
+        let config = LintRunnerConfig::new_from_string(
+            "\
+            [[linter]]
+            code = 'SLOW'
+            include_patterns = ['**']
+            command = ['a']
+
+            [profiles.ci]
+            take = ['SLOW']
+            fail_on = 'warning'
+            jobs = 4
+
+            [profiles.local]
+            skip = ['SLOW']
+            ",
+        )?;
+        assert_eq!(
+            config.profile("ci")?,
+            &Profile {
+                skip: Vec::new(),
+                take: Some(vec!["SLOW".to_string()]),
+                fail_on: Some(LintSeverity::Warning),
+                jobs: Some(4),
+            }
+        );
+        assert_eq!(config.profile("local")?.skip, vec!["SLOW".to_string()]);
+        let err = config.profile("nightly").unwrap_err();
+        assert!(err.to_string().contains("expected one of: ci, local"));
+
+        let err = LintRunnerConfig::new_from_string("linter = []\n[profiles.ci]\njobs = 0\n")
+            .err()
+            .unwrap();
+        assert!(err.to_string().contains("sets jobs to 0"));
+        Ok(())
+    }
+
+    #[test]
+    fn version_checks_are_validated() -> Result<()> {
+        let linters = linters_from_str(
+            "\
+            [[linter]]
+            code = 'BLACK'
+            include_patterns = ['**']
+            command = ['black']
+            version_command = ['black', '--version']
+            version = '>=23.1, <24'
+            version_mismatch = 'fail'
+            ",
+        )?;
+        let check = linters[0].version_check.as_ref().unwrap();
+        assert_eq!(check.command, vec!["black", "--version"]);
+        assert_eq!(check.on_mismatch, VersionMismatch::Fail);
+
+        let err = linters_from_str(
+            "\
+            [[linter]]
+            code = 'BLACK'
+            include_patterns = ['**']
+            command = ['black']
+            version = '23.1'
+            ",
+        )
+        .err()
+        .unwrap();
+        assert!(err.to_string().contains("needs both"));
+        Ok(())
+    }
+
+    #[test]
+    fn run_after_cycle_fails() {
+        let err = linters_from_str(
+            "\
+            [[linter]]
+            code = 'A'
+            include_patterns = ['**']
+            command = ['a']
+            run_after = ['B']
+
+            [[linter]]
+            code = 'B'
+            include_patterns = ['**']
+            command = ['b']
+            run_after = ['A']
+            ",
+        )
+        .err()
+        .unwrap();
+        assert!(err.to_string().contains("cycle: A -> B -> A"), "{}", err);
+    }
+
+    #[test]
+    fn run_after_unknown_linter_fails() {
+        let err = linters_from_str(
+            "\
+            [[linter]]
+            code = 'A'
+            include_patterns = ['**']
+            command = ['a']
+            run_after = ['NOPE']
+            ",
+        )
+        .err()
+        .unwrap();
+        assert!(err.to_string().contains("no such linter"), "{}", err);
+    }
+
+    #[test]
+    fn pattern_lines_are_found() {
+        let config = "\
+[[linter]]
+code = 'FOO'
+include_patterns = ['**/*.py']
+command = ['foo']
+
+[[linter]]
+code = 'BAR'
+include_patterns = [
+    '**/*.py',
+    \"**/*.pyi\",
+]
+exclude_patterns = ['third_party/**']
+command = ['bar']
+";
+        assert_eq!(find_pattern_line(config, "FOO", "**/*.py"), Some(3));
+        assert_eq!(find_pattern_line(config, "BAR", "**/*.py"), Some(9));
+        assert_eq!(find_pattern_line(config, "BAR", "**/*.pyi"), Some(10));
+        assert_eq!(find_pattern_line(config, "BAR", "third_party/**"), Some(12));
+        assert_eq!(find_pattern_line(config, "FOO", "third_party/**"), None);
+        assert_eq!(find_pattern_line(config, "BAZ", "**/*.py"), None);
+    }
+
+    #[test]
+    fn local_config_is_merged() -> Result<()> {
+        let mut config: toml::Value = toml::from_str(
+            "\
+[[linter]]
+code = 'FOO'
+include_patterns = ['**/*.py']
+command = ['foo']
+
+[[linter]]
+code = 'BAR'
+include_patterns = ['**']
+command = ['bar']
+",
+        )?;
+        let local: toml::Value = toml::from_str(
+            "\
+[[linter]]
+code = 'FOO'
+exclude_patterns = ['slow/**']
+
+[[linter]]
+code = 'BAR'
+disabled = true
+
+[[linter]]
+code = 'BAZ'
+include_patterns = ['**']
+command = ['baz']
+",
+        )?;
+        merge_local_config(&mut config, local)?;
+        let config: LintRunnerConfig = config.try_into()?;
+
+        let codes: Vec<&str> = config.linters.iter().map(|l| l.code.as_str()).collect();
+        assert_eq!(codes, vec!["FOO", "BAZ"]);
+        assert_eq!(config.linters[0].include_patterns, vec!["**/*.py"]);
+        assert_eq!(
+            config.linters[0].exclude_patterns,
+            Some(vec!["slow/**".to_string()])
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn pattern_sources_are_recorded() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        std::fs::write(
+            dir.path().join("shared.toml"),
+            "\
+[[linter]]
+code = 'FOO'
+include_patterns = ['**/*.py']
+exclude_patterns = ['Vendored/**']
+command = ['foo']
+",
+        )?;
+        std::fs::write(
+            dir.path().join(".lintrunner.toml"),
+            "\
+import = 'shared.toml'
+case_insensitive_patterns = true
+
+[[linter]]
+code = 'FOO'
+include_patterns = ['**/*.PY']
+",
+        )?;
+        std::fs::write(
+            dir.path().join(LOCAL_CONFIG_NAME),
+            "\
+[[linter]]
+code = 'FOO'
+exclude_patterns = ['Slow/**']
+",
+        )?;
+        let config_path = AbsPath::try_from(dir.path().join(".lintrunner.toml"))?;
+        let config = LintRunnerConfig::new(&config_path, true, true)?;
+        let linters = get_linters_from_config(&config.linters, None, None, &config_path)?;
+
+        std::fs::create_dir(dir.path().join("slow"))?;
+        std::fs::write(dir.path().join("slow/a.py"), "")?;
+        let file = AbsPath::try_from(dir.path().join("slow/a.py"))?;
+        match linters[0].explain_match(&file) {
+            MatchReason::Excluded { include, exclude } => {
+                assert_eq!(include.pattern, "**/*.PY");
+                assert_eq!(include.path, config_path.as_ref());
+                assert_eq!(exclude.pattern, "Slow/**");
+                assert_eq!(exclude.path, dir.path().join(LOCAL_CONFIG_NAME));
+            }
+            _ => bail!("Expected slow/a.py to be excluded"),
+        }
+
+        let config = LintRunnerConfig::new(&config_path, true, false)?;
+        let linters = get_linters_from_config(&config.linters, None, None, &config_path)?;
+        assert_eq!(linters[0].exclude_source.patterns, vec!["Vendored/**"]);
+        assert_eq!(
+            linters[0].exclude_source.path,
+            dir.path().join("shared.toml")
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn imported_linters_are_merged() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let shared = "\
+merge_base_with = 'origin/shared'
+
+[[linter]]
+code = 'FOO'
+include_patterns = ['**/*.py']
+command = ['foo']
+
+[[linter]]
+code = 'BAR'
+include_patterns = ['**']
+command = ['bar']
+";
+        std::fs::write(dir.path().join("shared.toml"), shared)?;
+        let config_path = dir.path().join(".lintrunner.toml");
+        let write_config = |checksum: &str| {
+            std::fs::write(
+                &config_path,
+                format!(
+                    "\
+import = [{{ source = 'shared.toml', checksum = '{}' }}]
+
+[[linter]]
+code = 'FOO'
+exclude_patterns = ['slow/**']
+
+[[linter]]
+code = 'BAR'
+disabled = true
+
+[[linter]]
+code = 'BAZ'
+include_patterns = ['**']
+command = ['baz']
+",
+                    checksum
+                ),
+            )
+        };
+
+        write_config(&blake3::hash(shared.as_bytes()).to_string())?;
+        let config_path = AbsPath::try_from(dir.path().join(".lintrunner.toml"))?;
+        let config = LintRunnerConfig::new(&config_path, true, false)?;
+        let codes: Vec<&str> = config.linters.iter().map(|l| l.code.as_str()).collect();
+        assert_eq!(codes, vec!["FOO", "BAZ"]);
+        assert_eq!(
+            config.linters[0].exclude_patterns,
+            Some(vec!["slow/**".to_string()])
+        );
+        assert_eq!(config.merge_base_with.as_deref(), Some("origin/shared"));
+
+        write_config("0000")?;
+        let err = match LintRunnerConfig::new(&config_path, true, false) {
+            Ok(_) => bail!("Expected the checksum to be checked"),
+            Err(err) => err.to_string(),
+        };
+        assert!(err.contains("pins it to 0000"), "{}", err);
+        Ok(())
+    }
+
+    #[test]
+    fn known_keys_are_fine() -> Result<()> {
+        let unknown = find_unknown_keys(
+            "\
+            [[linter]]
+            code = 'FOO'
+            include_patterns = ['**']
+            exclude_patterns = ['foo/**']
+            command = ['foo']
+            init_command = ['foo', '{{DRYRUN}}']
+            is_formatter = true
+            run_after = []
+            exclusive = true
+            ",
+        )?;
+        assert!(unknown.is_empty());
+        Ok(())
+    }
+}
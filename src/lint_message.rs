@@ -1,6 +1,20 @@
+use std::collections::BTreeMap;
+
+use anyhow::{ensure, Context, Result};
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Deserialize, Clone, Serialize, Copy)]
+#[derive(
+    Debug,
+    Deserialize,
+    Clone,
+    Serialize,
+    Copy,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord
+)]
 #[serde(rename_all = "lowercase")]
 pub enum LintSeverity {
     Error,
@@ -60,4 +74,374 @@ pub struct LintMessage {
     /// encoded as a utf-8 string.
     #[serde(skip_serializing_if = "Option::is_none")]
     pub replacement: Option<String>,
+
+    /// A fix suggested as changes to parts of the file, instead of as a
+    /// `replacement` of the entire file. Ignored if `replacement` is set.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub edits: Vec<Edit>,
+
+    /// Identifies this issue across runs, even after the lines around it
+    /// change. Linters can leave this out, and `lintrunner` will compute one
+    /// from the code, the path and the text of the flagged line.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fingerprint: Option<String>,
+
+    /// When this issue was first found, if an earlier run found it too.
+    /// Filled in by `lintrunner`, linters should leave this out.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub first_seen: Option<FirstSeen>,
+}
+
+/// When, and at which commit, an issue was first found.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FirstSeen {
+    /// In RFC 3339 format.
+    pub timestamp: String,
+    /// The commit that was checked out, if in a git checkout.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub commit: Option<String>,
+}
+
+impl FirstSeen {
+    /// How long ago the issue was first found, or `None` if the timestamp
+    /// can't be parsed.
+    pub fn age(&self, now: DateTime<Utc>) -> Option<chrono::Duration> {
+        let timestamp = DateTime::parse_from_rfc3339(&self.timestamp).ok()?;
+        Some(now.signed_duration_since(timestamp))
+    }
+}
+
+impl LintMessage {
+    /// A message with only the required fields set, for tests to override the
+    /// fields they care about with struct update syntax. Public so that the
+    /// integration tests can use it too.
+    #[doc(hidden)]
+    pub fn for_test(code: &str, severity: LintSeverity, name: &str) -> Self {
+        LintMessage {
+            path: None,
+            line: None,
+            char: None,
+            code: code.to_string(),
+            severity,
+            name: name.to_string(),
+            description: None,
+            original: None,
+            replacement: None,
+            edits: Vec::new(),
+            fingerprint: None,
+            first_seen: None,
+        }
+    }
+}
+
+/// A change to part of a file: the text between the byte offsets `start` and
+/// `end` is replaced with `replacement`.
+#[derive(Debug, Deserialize, Clone, Serialize, PartialEq, Eq)]
+pub struct Edit {
+    /// The file to change, if not the one the lint message is about. This
+    /// lets one fix change several files, e.g. renaming something in a header
+    /// and everywhere it is included.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub path: Option<String>,
+
+    /// Byte offset of the start of the text to replace.
+    pub start: usize,
+
+    /// Byte offset just past the end of the text to replace. Equal to `start`
+    /// to insert text.
+    pub end: usize,
+
+    /// The text to put in its place, encoded as a utf-8 string.
+    pub replacement: String,
+}
+
+/// A suggested fix, as the contents of the entire file before and after.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileFix {
+    pub path: String,
+    pub original: String,
+    pub replacement: String,
+}
+
+impl LintMessage {
+    /// Whether the linter suggested a fix, either as a `replacement` or as
+    /// `edits`.
+    pub fn has_fix(&self) -> bool {
+        self.replacement.is_some() || !self.edits.is_empty()
+    }
+
+    /// The fix this message suggests, one per file it changes. When the
+    /// linter didn't send the `original` text of a file, the file as it is now
+    /// is used instead.
+    pub fn fixes(&self) -> Result<Vec<FileFix>> {
+        if let (Some(path), Some(replacement)) = (&self.path, &self.replacement) {
+            return Ok(vec![FileFix {
+                path: path.clone(),
+                original: self.original_of(path)?,
+                replacement: replacement.clone(),
+            }]);
+        }
+
+        let mut edits_by_path: BTreeMap<&String, Vec<Edit>> = BTreeMap::new();
+        for edit in &self.edits {
+            let path = edit.path.as_ref().or(self.path.as_ref()).with_context(|| {
+                format!(
+                    "An edit from {} doesn't say which file it is for",
+                    self.code
+                )
+            })?;
+            edits_by_path.entry(path).or_default().push(edit.clone());
+        }
+        edits_by_path
+            .into_iter()
+            .map(|(path, edits)| {
+                let original = self.original_of(path)?;
+                let replacement = apply_edits(&original, &edits)
+                    .with_context(|| format!("Invalid fix from {} for '{}'", self.code, path))?;
+                Ok(FileFix {
+                    path: path.clone(),
+                    original,
+                    replacement,
+                })
+            })
+            .collect()
+    }
+
+    fn original_of(&self, path: &String) -> Result<String> {
+        match &self.original {
+            Some(original) if self.path.as_ref() == Some(path) => Ok(original.clone()),
+            _ => std::fs::read_to_string(path)
+                .with_context(|| format!("Failed to read '{}' to fix it", path)),
+        }
+    }
+}
+
+/// The fixes that `lints` suggest together, one per file they change. Edits
+/// one linter sends for the same file in several messages are combined into
+/// one fix; it is an error if they overlap, or if different linters change
+/// the same file.
+pub fn merge_fixes<'a>(lints: impl IntoIterator<Item = &'a LintMessage>) -> Result<Vec<FileFix>> {
+    struct Pending<'a> {
+        code: &'a str,
+        original: String,
+        edits: Vec<Edit>,
+        replacement: Option<String>,
+    }
+
+    let mut by_path: BTreeMap<String, Pending> = BTreeMap::new();
+    for lint in lints {
+        let mut changes = Vec::new();
+        if let (Some(path), Some(replacement)) = (&lint.path, &lint.replacement) {
+            changes.push((path, None, Some(replacement)));
+        } else {
+            for edit in &lint.edits {
+                let path = edit.path.as_ref().or(lint.path.as_ref()).with_context(|| {
+                    format!(
+                        "An edit from {} doesn't say which file it is for",
+                        lint.code
+                    )
+                })?;
+                changes.push((path, Some(edit), None));
+            }
+        }
+        for (path, edit, replacement) in changes {
+            if !by_path.contains_key(path) {
+                let pending = Pending {
+                    code: &lint.code,
+                    original: lint.original_of(path)?,
+                    edits: Vec::new(),
+                    replacement: None,
+                };
+                by_path.insert(path.clone(), pending);
+            }
+            // Unwrap is fine because the entry was just inserted if missing.
+            let pending = by_path.get_mut(path).unwrap();
+            ensure!(
+                pending.code == lint.code,
+                "Two different linters proposed changes for the same file: {}",
+                path
+            );
+            // Edits can be combined with other edits, and a replacement of the
+            // whole file only with the very same replacement.
+            let combinable = match replacement {
+                None => pending.replacement.is_none(),
+                Some(new) => {
+                    pending.edits.is_empty()
+                        && pending.replacement.as_ref().is_none_or(|old| old == new)
+                }
+            };
+            ensure!(
+                combinable,
+                "{} proposed several fixes for '{}' that can't be combined",
+                lint.code,
+                path
+            );
+            pending.edits.extend(edit.cloned());
+            if let Some(replacement) = replacement {
+                pending.replacement = Some(replacement.clone());
+            }
+        }
+    }
+
+    by_path
+        .into_iter()
+        .map(|(path, pending)| {
+            let replacement = match pending.replacement {
+                Some(replacement) => replacement,
+                None => apply_edits(&pending.original, &pending.edits)
+                    .with_context(|| format!("Invalid fix from {} for '{}'", pending.code, path))?,
+            };
+            Ok(FileFix {
+                path,
+                original: pending.original,
+                replacement,
+            })
+        })
+        .collect()
+}
+
+/// Apply `edits` to `original`. The edits can be in any order, but must not
+/// overlap, and must start and end on character boundaries.
+pub fn apply_edits(original: &str, edits: &[Edit]) -> Result<String> {
+    let mut edits: Vec<&Edit> = edits.iter().collect();
+    edits.sort_by_key(|edit| (edit.start, edit.end));
+
+    let mut result = String::with_capacity(original.len());
+    let mut copied = 0;
+    for edit in edits {
+        ensure!(
+            edit.start <= edit.end && edit.end <= original.len(),
+            "Edit {}..{} is outside of the file, which is {} bytes long",
+            edit.start,
+            edit.end,
+            original.len()
+        );
+        ensure!(
+            edit.start >= copied,
+            "Edit {}..{} overlaps another edit",
+            edit.start,
+            edit.end
+        );
+        ensure!(
+            original.is_char_boundary(edit.start) && original.is_char_boundary(edit.end),
+            "Edit {}..{} splits a character",
+            edit.start,
+            edit.end
+        );
+        result.push_str(&original[copied..edit.start]);
+        result.push_str(&edit.replacement);
+        copied = edit.end;
+    }
+    result.push_str(&original[copied..]);
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn edit(start: usize, end: usize, replacement: &str) -> Edit {
+        Edit {
+            path: None,
+            start,
+            end,
+            replacement: replacement.to_string(),
+        }
+    }
+
+    #[test]
+    fn edits_are_applied() -> Result<()> {
+        let original = "a = 1 \nb  = 2\n";
+        assert_eq!(
+            apply_edits(original, &[edit(8, 10, " "), edit(5, 6, "")])?,
+            "a = 1\nb = 2\n"
+        );
+        assert_eq!(
+            apply_edits(original, &[edit(0, 0, "# x\n")])?,
+            "# x\na = 1 \nb  = 2\n"
+        );
+        assert_eq!(apply_edits(original, &[])?, original);
+        Ok(())
+    }
+
+    #[test]
+    fn invalid_edits_are_rejected() {
+        assert!(apply_edits("abc", &[edit(2, 4, "")]).is_err());
+        assert!(apply_edits("abc", &[edit(2, 1, "")]).is_err());
+        assert!(apply_edits("abcd", &[edit(0, 2, ""), edit(1, 3, "")]).is_err());
+        assert!(apply_edits("café", &[edit(4, 5, "e")]).is_err());
+    }
+
+    #[test]
+    fn edits_are_optional_in_json() -> Result<()> {
+        let message: LintMessage = serde_json::from_str(
+            r#"{"path": "x.py", "line": 1, "char": null, "code": "WS", "severity": "warning",
+                "name": "trailing whitespace", "description": null, "original": "a \n",
+                "edits": [{"start": 1, "end": 2, "replacement": ""}]}"#,
+        )?;
+        assert!(message.has_fix());
+        let fixes = message.fixes()?;
+        assert_eq!(fixes.len(), 1);
+        assert_eq!(fixes[0].replacement, "a\n");
+        assert!(!serde_json::to_string(&LintMessage {
+            edits: Vec::new(),
+            ..message
+        })?
+        .contains("edits"));
+        Ok(())
+    }
+
+    #[test]
+    fn edits_can_fix_other_files() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let header = dir.path().join("foo.h");
+        let source = dir.path().join("foo.c");
+        std::fs::write(&header, "int old_name();\n")?;
+        std::fs::write(&source, "#include \"foo.h\"\nint x = old_name();\n")?;
+        let rename = |path: &std::path::Path, start| Edit {
+            path: Some(path.display().to_string()),
+            ..edit(start, start + 8, "new_name")
+        };
+        let message = LintMessage {
+            path: Some(header.display().to_string()),
+            line: Some(1),
+            edits: vec![edit(4, 12, "new_name"), rename(&source, 25)],
+            ..LintMessage::for_test("RENAME", LintSeverity::Warning, "old name")
+        };
+        let fixes = message.fixes()?;
+        assert_eq!(fixes.len(), 2);
+        assert_eq!(
+            fixes[0].replacement,
+            "#include \"foo.h\"\nint x = new_name();\n"
+        );
+        assert_eq!(fixes[1].replacement, "int new_name();\n");
+        assert!(LintMessage {
+            path: None,
+            edits: vec![edit(0, 0, "")],
+            ..message
+        }
+        .fixes()
+        .is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn edits_from_several_messages_are_merged() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("a.py");
+        std::fs::write(&path, "a = 1 \nb  = 2\n")?;
+        let lint = |code: &str, edit: Edit| LintMessage {
+            path: Some(path.display().to_string()),
+            edits: vec![edit],
+            ..LintMessage::for_test(code, LintSeverity::Warning, "whitespace")
+        };
+
+        let fixes = merge_fixes(&[lint("WS", edit(5, 6, "")), lint("WS", edit(8, 10, " "))])?;
+        assert_eq!(fixes.len(), 1);
+        assert_eq!(fixes[0].replacement, "a = 1\nb = 2\n");
+
+        assert!(merge_fixes(&[lint("WS", edit(5, 6, "")), lint("WS", edit(5, 7, ""))]).is_err());
+        assert!(merge_fixes(&[lint("WS", edit(5, 6, "")), lint("FMT", edit(8, 10, " "))]).is_err());
+        Ok(())
+    }
 }
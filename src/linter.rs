@@ -1,30 +1,247 @@
+use std::collections::BTreeMap;
 use std::io::Write;
-use std::path::Path;
-use std::process::Command;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::time::Duration;
 
 use crate::{
-    lint_message::LintMessage,
-    log_utils::{ensure_output, log_files},
+    builtin, cancel,
+    git::get_git_root_of,
+    lint_message::{LintMessage, LintSeverity},
+    log_utils::log_files,
+    matcher::PatternOptions,
+    mock::{CommandOutput, Mock},
+    offline,
     path::{path_relative_from, AbsPath},
+    process_tree::ProcessTree,
+    sandbox::{sandbox_command, SandboxPolicy},
+    shell::Shell,
+    version::VersionCheck,
+    watchdog::Watchdog,
 };
-use anyhow::{anyhow, bail, ensure, Context, Result};
+use anyhow::{anyhow, ensure, Context, Result};
 use glob::{MatchOptions, Pattern};
 use log::{debug, info};
+use serde::{Deserialize, Serialize};
 
+#[derive(Clone)]
 pub struct Linter {
     pub code: String,
     pub include_patterns: Vec<Pattern>,
     pub exclude_patterns: Vec<Pattern>,
+    /// `include_patterns` as written in the config, and where.
+    pub include_source: PatternSource,
+    /// `exclude_patterns` as written in the config, and where.
+    pub exclude_source: PatternSource,
     pub commands: Vec<String>,
     pub init_commands: Option<Vec<String>>,
     pub config_path: AbsPath,
+    pub run_after: Vec<String>,
+    pub exclusive: bool,
+    pub max_allowed: BTreeMap<LintSeverity, usize>,
+    /// Warnings first found at least this many days ago are errors.
+    pub escalate_after_days: Option<u32>,
+    pub container: Option<String>,
+    pub cache_dirs: Vec<PathBuf>,
+    pub sandbox: bool,
+    pub shell: Shell,
+    /// How long the linter may run before it is reported as slow. `None`
+    /// disables the report.
+    pub slow_threshold: Option<Duration>,
+    pub stack_sampler: Option<Vec<String>>,
+    /// Whether the linter is known to be flaky, in which case its issues are
+    /// reported as advice and don't fail the run.
+    pub flaky: bool,
+    /// The least severe issues that fail the run. Less severe ones are
+    /// reported, but never fail it. `None` means errors and warnings do, but
+    /// advice and disabled issues don't.
+    pub fail_on: Option<LintSeverity>,
+    /// Values for placeholders in the linter's commands that depend on the
+    /// run rather than on the linter, e.g. `REVISION`.
+    pub placeholders: BTreeMap<String, String>,
+    pub granularity: Granularity,
+    /// With [`Granularity::Directory`], files whose presence marks a package
+    /// root, e.g. `BUILD`.
+    pub package_markers: Vec<String>,
+    /// The version of its tool the linter expects, if declared.
+    pub version_check: Option<VersionCheck>,
+    /// Files that determine what `init_commands` installs, e.g. a
+    /// requirements file.
+    pub lockfiles: Vec<PathBuf>,
+    /// How files are compared to the include and exclude patterns, which are
+    /// already folded with these options.
+    pub pattern_options: PatternOptions,
+    /// Patterns for files whose issues get a different severity, with that
+    /// severity. The first matching pattern wins.
+    pub severity_overrides: Vec<(Pattern, LintSeverity)>,
 }
 
-fn matches_relative_path(base: &Path, from: &Path, pattern: &Pattern) -> bool {
+/// A linter's patterns as written in the config, before they are folded with
+/// its [`PatternOptions`], and the file they are written in.
+#[derive(Clone, Debug, Default)]
+pub struct PatternSource {
+    pub patterns: Vec<String>,
+    pub path: PathBuf,
+}
+
+/// What a linter is given to lint.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Granularity {
+    /// The matched files.
+    #[default]
+    File,
+    /// The directories containing the matched files, or the package roots
+    /// they belong to.
+    Directory,
+    /// Nothing: the linter checks the whole project, and runs once if any
+    /// file matches.
+    Project,
+}
+
+/// Where the config directory is mounted when running a linter in a container.
+const CONTAINER_WORKDIR: &str = "/lintrunner/src";
+/// Where the paths file is mounted when running a linter in a container.
+const CONTAINER_PATHSFILE: &str = "/lintrunner/paths";
+/// A command argument that is replaced by the paths to lint, one argument
+/// each.
+pub(crate) const PATHS_PLACEHOLDER: &str = "{{PATHS}}";
+/// How many bytes of arguments to pass to a single invocation of a linter
+/// that takes `{{PATHS}}`. This is well below the limits of the platforms we
+/// support (Windows caps the whole command line at 32K characters), leaving
+/// room for the environment.
+#[cfg(windows)]
+const ARGS_SIZE_LIMIT: usize = 24 * 1024;
+#[cfg(not(windows))]
+const ARGS_SIZE_LIMIT: usize = 128 * 1024;
+
+/// A severity for which a linter reported more issues than `max_allowed`.
+#[derive(Debug)]
+pub struct BudgetOverrun {
+    pub severity: LintSeverity,
+    pub count: usize,
+    pub max_allowed: usize,
+}
+
+/// The ways in which running a linter can fail, as opposed to the linter
+/// successfully reporting lint issues. Ordered from least to most severe.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum LinterFailure {
+    /// The linter exited with a non-zero exit code.
+    Crashed,
+    /// The linter ran, but its output did not follow the linter protocol.
+    BadOutput,
+    /// The linter's command could not be found, usually because `lintrunner
+    /// init` has not been run (or needs to be rerun).
+    Missing,
+    /// The linter reported that the tool it runs isn't set up the way
+    /// `lintrunner init` would set it up, e.g. because it is the wrong
+    /// version.
+    NeedsInit,
+}
+
+impl LinterFailure {
+    pub fn title(self) -> &'static str {
+        match self {
+            LinterFailure::Crashed => "Linter failed",
+            LinterFailure::BadOutput => "Linter output unparseable",
+            LinterFailure::Missing => "Linter not installed",
+            LinterFailure::NeedsInit => "Linter needs init",
+        }
+    }
+
+    pub fn advice(self, code: &str) -> String {
+        match self {
+            LinterFailure::Crashed => "Linter failed. This a bug, please file an issue against \
+                 the linter maintainer."
+                .to_string(),
+            LinterFailure::BadOutput => "Linter produced output that lintrunner could not \
+                 understand. This a bug, please file an issue against the linter maintainer."
+                .to_string(),
+            LinterFailure::Missing => format!(
+                "Linter '{}' could not be found, so it was skipped. Make sure it is \
+                 installed; if it is set up by `lintrunner init`, try running \
+                 `lintrunner init` again.",
+                code
+            ),
+            LinterFailure::NeedsInit => format!(
+                "Linter '{}' is not set up the way `lintrunner init` sets it up, so it \
+                 was skipped. Run `lintrunner init --take {}` to initialize it.",
+                code, code
+            ),
+        }
+    }
+
+    /// The exit code lintrunner uses when this is the most severe failure.
+    /// Exit code 1 is reserved for "lint issues were found".
+    pub fn exit_code(self) -> i32 {
+        match self {
+            LinterFailure::Crashed => 2,
+            LinterFailure::BadOutput => 3,
+            LinterFailure::Missing => 4,
+            LinterFailure::NeedsInit => 5,
+        }
+    }
+}
+
+/// Why a configured linter did not run.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SkipReason {
+    /// Excluded with `--skip`.
+    Skipped,
+    /// Not included in `--take`.
+    NotTaken,
+    /// Excluded, or not included, by the selected profile.
+    NotInProfile,
+    /// Only formatters run under `lintrunner format`.
+    NotFormatter,
+    /// None of the files being linted matched its patterns.
+    NoMatches,
+    /// Its command could not be found.
+    Missing,
+    /// Its `enabled_if` conditions don't hold.
+    Disabled,
+}
+
+impl std::fmt::Display for SkipReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let reason = match self {
+            SkipReason::Skipped => "skipped via --skip",
+            SkipReason::NotTaken => "not selected by --take",
+            SkipReason::NotInProfile => "left out by the selected profile",
+            SkipReason::NotFormatter => "not a formatter",
+            SkipReason::NoMatches => "no files matched its include/exclude patterns",
+            SkipReason::Missing => "its command could not be found, try `lintrunner init`",
+            SkipReason::Disabled => "its enabled_if conditions don't hold (see -v for why)",
+        };
+        f.write_str(reason)
+    }
+}
+
+struct LinterError {
+    kind: LinterFailure,
+    err: anyhow::Error,
+}
+
+impl LinterError {
+    fn new(kind: LinterFailure, err: anyhow::Error) -> Self {
+        LinterError { kind, err }
+    }
+}
+
+/// Whether `from`, relative to `base`, matches `pattern`, which must already
+/// be folded with `options`.
+pub(crate) fn matches_relative_path(
+    base: &Path,
+    from: &Path,
+    pattern: &Pattern,
+    options: PatternOptions,
+) -> bool {
     // Unwrap ok because we already checked that both paths are absolute.
     let relative_path = path_relative_from(from, base).unwrap();
     pattern.matches_with(
-        relative_path.to_str().unwrap(),
+        &options.fold(relative_path.to_str().unwrap()),
         MatchOptions {
             case_sensitive: true,
             // Explicitly set this option to true. Most unix implementations do
@@ -36,50 +253,486 @@ fn matches_relative_path(base: &Path, from: &Path, pattern: &Pattern) -> bool {
     )
 }
 
+/// Why a linter will or won't lint a particular file.
+pub enum MatchReason<'a> {
+    /// No include pattern matched the file.
+    NotIncluded,
+    /// The file matched this include pattern, and no exclude pattern.
+    Included(PatternMatch<'a>),
+    /// The file matched an include pattern, but was then excluded.
+    Excluded {
+        include: PatternMatch<'a>,
+        exclude: PatternMatch<'a>,
+    },
+}
+
+/// A pattern that matched a file, as written in the config.
+pub struct PatternMatch<'a> {
+    pub pattern: &'a str,
+    /// The file the pattern is written in.
+    pub path: &'a Path,
+}
+
 impl Linter {
-    fn get_config_dir(&self) -> &Path {
+    pub fn get_config_dir(&self) -> &Path {
         // Unwrap is fine here because we know this path is absolute and won't be `/`
         self.config_path.parent().unwrap()
     }
 
-    fn get_matches(&self, files: &[AbsPath]) -> Vec<AbsPath> {
+    /// Explain whether `file` would be linted by this linter, and which
+    /// pattern caused that.
+    pub fn explain_match<'a>(&'a self, file: &AbsPath) -> MatchReason<'a> {
+        let config_dir = self.get_config_dir();
+        let find = |patterns: &[Pattern], source: &'a PatternSource| {
+            let idx = patterns.iter().position(|pattern| {
+                matches_relative_path(config_dir, file, pattern, self.pattern_options)
+            })?;
+            Some(PatternMatch {
+                pattern: source.patterns[idx].as_str(),
+                path: &source.path,
+            })
+        };
+        let include = match find(&self.include_patterns, &self.include_source) {
+            Some(include) => include,
+            None => return MatchReason::NotIncluded,
+        };
+        let exclude = find(&self.exclude_patterns, &self.exclude_source);
+        match exclude {
+            Some(exclude) => MatchReason::Excluded { include, exclude },
+            None => MatchReason::Included(include),
+        }
+    }
+
+    /// Give `lints` in files that match one of the linter's severity
+    /// overrides that severity.
+    pub fn override_severities(&self, lints: &mut [LintMessage]) {
+        if self.severity_overrides.is_empty() {
+            return;
+        }
+        let config_dir = self.get_config_dir();
+        for lint in lints {
+            let Some(path) = &lint.path else {
+                continue;
+            };
+            let path = config_dir.join(path);
+            let severity = self
+                .severity_overrides
+                .iter()
+                .find(|(pattern, _)| {
+                    matches_relative_path(config_dir, &path, pattern, self.pattern_options)
+                })
+                .map(|(_, severity)| *severity);
+            if let Some(severity) = severity {
+                lint.severity = severity;
+            }
+        }
+    }
+
+    /// Check `lints` against this linter's `max_allowed` budget. Returns how
+    /// many of the lints should fail the run, and which budgets were exceeded.
+    pub fn check_budget(&self, lints: &[LintMessage]) -> (usize, Vec<BudgetOverrun>) {
+        let fail_on = self.fail_on.unwrap_or(LintSeverity::Warning);
+        let mut counts: BTreeMap<LintSeverity, usize> = BTreeMap::new();
+        for lint in lints.iter().filter(|lint| lint.severity <= fail_on) {
+            *counts.entry(lint.severity).or_default() += 1;
+        }
+
+        let mut failing = 0;
+        let mut overruns = Vec::new();
+        for (severity, count) in counts {
+            match self.max_allowed.get(&severity) {
+                Some(&max_allowed) if count <= max_allowed => {}
+                Some(&max_allowed) => {
+                    failing += count;
+                    overruns.push(BudgetOverrun {
+                        severity,
+                        count,
+                        max_allowed,
+                    });
+                }
+                None => failing += count,
+            }
+        }
+        (failing, overruns)
+    }
+
+    pub fn get_matches(&self, files: &[AbsPath]) -> Vec<AbsPath> {
         let config_dir = self.get_config_dir();
         files
             .iter()
             .filter(|name| {
-                self.include_patterns
-                    .iter()
-                    .any(|pattern| matches_relative_path(config_dir, name, pattern))
+                self.include_patterns.iter().any(|pattern| {
+                    matches_relative_path(config_dir, name, pattern, self.pattern_options)
+                })
             })
             .filter(|name| {
-                !self
-                    .exclude_patterns
-                    .iter()
-                    .any(|pattern| matches_relative_path(config_dir, name, pattern))
+                !self.exclude_patterns.iter().any(|pattern| {
+                    matches_relative_path(config_dir, name, pattern, self.pattern_options)
+                })
             })
             .cloned()
             .collect()
     }
 
-    fn run_command(&self, matched_files: Vec<AbsPath>) -> Result<Vec<LintMessage>> {
-        let tmp_file = tempfile::NamedTempFile::new()?;
-        for matched_file in &matched_files {
-            let name = matched_file
-                .to_str()
-                .ok_or_else(|| anyhow!("Could not convert path to string."))?;
-            writeln!(&tmp_file, "{}", name)?;
+    /// Whether `placeholder` (e.g. `REVISION`) appears in any of the
+    /// linter's commands.
+    pub fn uses_placeholder(&self, placeholder: &str) -> bool {
+        let placeholder = format!("{{{{{}}}}}", placeholder);
+        self.commands
+            .iter()
+            .chain(self.init_commands.iter().flatten())
+            .any(|arg| arg.contains(&placeholder))
+    }
+
+    /// Substitute the placeholders that mean the same thing for every
+    /// invocation of the linter. `config_dir` is where the linter sees the
+    /// config directory.
+    fn substitute(&self, arg: &str, config_dir: &str) -> String {
+        if !arg.contains("{{") {
+            return arg.to_string();
+        }
+        let mut arg = arg.replace("{{CONFIG_DIR}}", config_dir);
+        if arg.contains("{{REPO_ROOT}}") {
+            let config_dir = self.get_config_dir();
+            let repo_root = get_git_root_of(config_dir).unwrap_or_else(|| config_dir.into());
+            arg = arg.replace("{{REPO_ROOT}}", &repo_root.display().to_string());
+        }
+        if arg.contains("{{NPROC}}") {
+            let nproc = std::thread::available_parallelism().map_or(1, |n| n.get());
+            arg = arg.replace("{{NPROC}}", &nproc.to_string());
+        }
+        for (placeholder, value) in &self.placeholders {
+            arg = arg.replace(&format!("{{{{{}}}}}", placeholder), value);
+        }
+        arg
+    }
+
+    /// The program the linter runs, with placeholders substituted. `None` for
+    /// builtin linters, and ones that run in a shell or container, since
+    /// there is no single program on this machine to point at.
+    pub fn program(&self) -> Option<String> {
+        if self.shell != Shell::Direct
+            || self.container.is_some()
+            || builtin::builtin_name(&self.commands).is_some()
+        {
+            return None;
+        }
+        let config_dir = self.get_config_dir().display().to_string();
+        Some(self.substitute(self.commands.first()?, &config_dir))
+    }
+
+    /// The command that prints the version of the linter's tool, with
+    /// placeholders substituted, if the linter declares one.
+    pub fn version_command(&self) -> Option<Vec<String>> {
+        let check = self.version_check.as_ref()?;
+        let config_dir = self.get_config_dir().display().to_string();
+        Some(
+            self.shell.wrap(
+                check
+                    .command
+                    .iter()
+                    .map(|arg| self.substitute(arg, &config_dir))
+                    .collect(),
+            ),
+        )
+    }
+
+    /// The full command used to invoke this linter, with placeholders
+    /// substituted. `paths_file` is the file containing the paths to lint.
+    /// `{{PATHS}}` is left as-is; see [`Linter::command_args_with_paths`].
+    ///
+    /// If the linter runs in a container, this is the `docker run` invocation,
+    /// with the config directory and `paths_file` mounted into the container.
+    pub fn command_args(&self, paths_file: &str) -> Vec<String> {
+        self.command_args_with_paths(paths_file, None)
+    }
+
+    /// Like [`Linter::command_args`], but with `{{PATHS}}` replaced by
+    /// `paths`, one argument each.
+    fn command_args_with_paths(&self, paths_file: &str, paths: Option<&[String]>) -> Vec<String> {
+        // Where the linter sees the config dir and paths file.
+        let (config_dir, linter_paths_file) = match &self.container {
+            Some(_) => (CONTAINER_WORKDIR.to_string(), CONTAINER_PATHSFILE),
+            None => (self.get_config_dir().display().to_string(), paths_file),
+        };
+        let mut command = Vec::new();
+        for arg in &self.commands {
+            match paths {
+                Some(paths) if arg == PATHS_PLACEHOLDER => command.extend(paths.iter().cloned()),
+                _ => command.push(
+                    self.substitute(arg, &config_dir)
+                        .replace("{{PATHSFILE}}", linter_paths_file),
+                ),
+            }
+        }
+        let command = self.shell.wrap(command);
+
+        let container = match &self.container {
+            Some(container) => container,
+            None => return command,
+        };
+        let mut args: Vec<String> = vec!["docker".into(), "run".into(), "--rm".into()];
+        if offline::is_offline() {
+            args.push("--pull=never".into());
+        }
+        args.extend([
+            "-v".into(),
+            format!("{}:{}", self.get_config_dir().display(), CONTAINER_WORKDIR),
+            "-v".into(),
+            format!("{}:{}:ro", paths_file, CONTAINER_PATHSFILE),
+            "-w".into(),
+            CONTAINER_WORKDIR.into(),
+            container.clone(),
+        ]);
+        args.extend(command);
+        args
+    }
+
+    /// Translate a path on the host to where it is visible to the linter.
+    fn path_for_linter(&self, path: &AbsPath) -> PathBuf {
+        match (
+            &self.container,
+            path_relative_from(path, self.get_config_dir()),
+        ) {
+            (Some(_), Some(relative)) => Path::new(CONTAINER_WORKDIR).join(relative),
+            _ => path.to_path_buf(),
+        }
+    }
+
+    /// Translate a path reported by the linter back to the host.
+    fn path_from_linter(&self, path: String) -> String {
+        if self.container.is_none() {
+            return path;
+        }
+        match Path::new(&path).strip_prefix(CONTAINER_WORKDIR) {
+            Ok(relative) => self.get_config_dir().join(relative).display().to_string(),
+            Err(_) => path,
+        }
+    }
+
+    /// Translate every path in a message reported by the linter back to the
+    /// host, including the per-edit paths of a multi-file fix.
+    fn message_from_linter(&self, msg: &mut LintMessage) {
+        msg.path = msg.path.take().map(|path| self.path_from_linter(path));
+        for edit in &mut msg.edits {
+            edit.path = edit.path.take().map(|path| self.path_from_linter(path));
+        }
+    }
+
+    fn run_command(&self, matched_files: Vec<AbsPath>) -> Result<Vec<LintMessage>, LinterError> {
+        let crashed = |err| LinterError::new(LinterFailure::Crashed, err);
+
+        if builtin::builtin_name(&self.commands).is_some() {
+            debug!("Running builtin linter {}: {}", self.code, self.commands[0]);
+            return builtin::run(
+                &self.code,
+                &self.commands,
+                &matched_files,
+                self.get_config_dir(),
+            )
+            .map_err(crashed);
+        }
+
+        let output = match Mock::from_env().map_err(crashed)? {
+            Some(mock) => mock
+                .run(
+                    &self.code,
+                    &self.commands,
+                    &matched_files,
+                    self.get_config_dir(),
+                    || self.execute(&matched_files),
+                )
+                .map_err(crashed)??,
+            None => self.execute(&matched_files)?,
+        };
+
+        // A linter that needs init may well fail too, but its own diagnosis
+        // is more useful than the failure.
+        if let Some(mut reason) = needs_init_reason(&output.stdout) {
+            if let Some(init_command) = self.init_command_args(false) {
+                reason.push_str(&format!(
+                    "\nInit command: {}",
+                    shell_words::join(init_command)
+                ));
+            }
+            let err = anyhow!(reason);
+            return Err(LinterError::new(LinterFailure::NeedsInit, err));
+        }
+
+        if output.exit_code != Some(0) {
+            // Shells exit with 127 when they can't find the command to run,
+            // which is what happens to wrapper scripts whose tools are missing.
+            let kind = if output.exit_code == Some(127) {
+                LinterFailure::Missing
+            } else {
+                LinterFailure::Crashed
+            };
+            let err = anyhow!(
+                "Linter command failed with non-zero exit code.\n\
+                 STDERR:\n{}\n\nSTDOUT:{}\n",
+                String::from_utf8_lossy(&output.stderr),
+                String::from_utf8_lossy(&output.stdout),
+            );
+            return Err(LinterError::new(kind, err));
+        }
+
+        let bad_output = |err| LinterError::new(LinterFailure::BadOutput, err);
+        let stdout_str = std::str::from_utf8(&output.stdout)
+            .context("Linter output is not valid UTF-8")
+            .map_err(bad_output)?;
+        let mut messages = Vec::new();
+        for line in stdout_str.lines() {
+            if line.is_empty() {
+                continue;
+            }
+            let mut msg: LintMessage = serde_json::from_str(line)
+                .with_context(|| {
+                    format!(
+                        "Failed to deserialize output for lint adapter, line: {}",
+                        line
+                    )
+                })
+                .map_err(bad_output)?;
+            self.message_from_linter(&mut msg);
+            messages.push(msg);
+        }
+        Ok(messages)
+    }
+
+    /// What to pass to the linter for `matched_files`, depending on its
+    /// granularity: the files themselves, the directories they're in, or
+    /// nothing at all. A
+    /// file's directory is the closest one (up to the config directory)
+    /// containing one of the package markers, or else its parent.
+    pub fn targets(&self, matched_files: &[AbsPath]) -> Vec<AbsPath> {
+        match self.granularity {
+            Granularity::File => return matched_files.to_vec(),
+            Granularity::Project => return Vec::new(),
+            Granularity::Directory => {}
+        }
+        let config_dir = self.get_config_dir();
+        let mut dirs: Vec<AbsPath> = matched_files
+            .iter()
+            .filter_map(|file| {
+                let parent = file.parent()?;
+                let package = parent
+                    .ancestors()
+                    .take_while(|dir| dir.starts_with(config_dir))
+                    .find(|dir| {
+                        self.package_markers
+                            .iter()
+                            .any(|marker| dir.join(marker).is_file())
+                    })
+                    .unwrap_or(parent);
+                AbsPath::try_from(package).ok()
+            })
+            .collect();
+        dirs.sort();
+        dirs.dedup();
+        dirs
+    }
+
+    /// The command lines that lint `paths`, which are also listed in
+    /// `paths_file`, along with the paths each one covers. A command that
+    /// takes `{{PATHS}}` is split into as many as it takes to keep each
+    /// command line short enough.
+    fn invocations(&self, paths_file: &str, paths: &[String]) -> Vec<(Vec<String>, Range<usize>)> {
+        if !self.commands.iter().any(|arg| arg == PATHS_PLACEHOLDER) {
+            return vec![(self.command_args(paths_file), 0..paths.len())];
+        }
+        let base_size = self
+            .command_args_with_paths(paths_file, Some(&[]))
+            .iter()
+            .map(|arg| arg.len() + 1)
+            .sum();
+        let mut chunks = chunk_paths(paths, base_size, ARGS_SIZE_LIMIT);
+        if chunks.is_empty() {
+            // Project linters get no paths, but still have to run.
+            chunks.push(0..0);
+        }
+        if chunks.len() > 1 {
+            debug!(
+                "Splitting {} paths for linter {} into {} invocations",
+                paths.len(),
+                self.code,
+                chunks.len()
+            );
+        }
+        chunks
+            .into_iter()
+            .map(|chunk| {
+                let args = self.command_args_with_paths(paths_file, Some(&paths[chunk.clone()]));
+                (args, chunk)
+            })
+            .collect()
+    }
+
+    /// The command lines the linter would run on `matched_files`, as in
+    /// [`Linter::run_on_matches`], with `paths_file` standing in for the file
+    /// listing the paths.
+    pub fn planned_commands(
+        &self,
+        matched_files: &[AbsPath],
+        paths_file: &str,
+    ) -> Vec<Vec<String>> {
+        let paths: Vec<String> = self
+            .targets(matched_files)
+            .iter()
+            .map(|target| self.path_for_linter(target).display().to_string())
+            .collect();
+        self.invocations(paths_file, &paths)
+            .into_iter()
+            .map(|(args, _)| args)
+            .collect()
+    }
+
+    /// Spawn the linter's command on `matched_files` and wait for it. If the
+    /// command takes `{{PATHS}}`, it is run as many times as it takes to keep
+    /// the command line short enough, and the outputs are concatenated.
+    fn execute(&self, matched_files: &[AbsPath]) -> Result<CommandOutput, LinterError> {
+        let crashed = |err| LinterError::new(LinterFailure::Crashed, err);
+
+        let matched_files = &self.targets(matched_files);
+        let paths = matched_files
+            .iter()
+            .map(|matched_file| {
+                self.path_for_linter(matched_file)
+                    .to_str()
+                    .map(str::to_string)
+                    .ok_or_else(|| crashed(anyhow!("Could not convert path to string.")))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let tmp_file = tempfile::NamedTempFile::new().map_err(|e| crashed(e.into()))?;
+        for path in &paths {
+            writeln!(&tmp_file, "{}", path).map_err(|e| crashed(e.into()))?;
         }
 
         let file_path = tmp_file
             .path()
             .to_str()
-            .ok_or_else(|| anyhow!("tempfile corrupted"))?;
+            .ok_or_else(|| crashed(anyhow!("tempfile corrupted")))?;
 
-        let (program, arguments) = self.commands.split_at(1);
-        let arguments: Vec<String> = arguments
-            .iter()
-            .map(|arg| arg.replace("{{PATHSFILE}}", file_path))
-            .collect();
+        let mut output = CommandOutput {
+            exit_code: Some(0),
+            stdout: Vec::new(),
+            stderr: Vec::new(),
+        };
+        for (args, chunk) in self.invocations(file_path, &paths) {
+            let chunk_output = self.spawn(args, &matched_files[chunk])?;
+            output.exit_code = chunk_output.exit_code;
+            output.stdout.extend(chunk_output.stdout);
+            output.stderr.extend(chunk_output.stderr);
+            if output.exit_code != Some(0) {
+                break;
+            }
+        }
+        Ok(output)
+    }
+
+    /// Run `command`, which lints `files`, and wait for it.
+    fn spawn(&self, command: Vec<String>, files: &[AbsPath]) -> Result<CommandOutput, LinterError> {
+        let crashed = |err| LinterError::new(LinterFailure::Crashed, err);
+        let (program, arguments) = command.split_at(1);
 
         debug!(
             "Running linter {}: {} {}",
@@ -93,93 +746,139 @@ impl Linter {
         );
 
         let start = std::time::Instant::now();
-        let command = Command::new(&program[0])
-            .args(&arguments)
-            .current_dir(self.get_config_dir())
-            .output()
-            .with_context(|| {
-                format!(
+        let mut command = Command::new(&program[0]);
+        command.args(arguments).current_dir(self.get_config_dir());
+        let _sandbox = if self.sandbox {
+            let policy = SandboxPolicy::new(&self.cache_dirs);
+            Some(sandbox_command(&mut command, &policy).map_err(crashed)?)
+        } else {
+            None
+        };
+        ProcessTree::prepare(&mut command);
+        let child = command
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(|e| {
+                let kind = if e.kind() == std::io::ErrorKind::NotFound {
+                    LinterFailure::Missing
+                } else {
+                    LinterFailure::Crashed
+                };
+                let err = anyhow!(e).context(format!(
                     "Failed to execute linter command {} with args: {:?}",
                     program[0], arguments
-                )
+                ));
+                LinterError::new(kind, err)
             })?;
+        let _registration = cancel::register_child(ProcessTree::new(&child));
+        let _watchdog = self.slow_threshold.map(|threshold| {
+            Watchdog::start(
+                &self.code,
+                child.id(),
+                files,
+                threshold,
+                self.stack_sampler.as_deref(),
+                self.get_config_dir(),
+            )
+        });
+        let output = child.wait_with_output().map_err(|e| crashed(e.into()))?;
         debug!("Linter {} took: {:?}", self.code, start.elapsed());
-        ensure_output("Linter command", &command)?;
+        Ok(CommandOutput {
+            exit_code: output.status.code(),
+            stdout: output.stdout,
+            stderr: output.stderr,
+        })
+    }
 
-        if !&command.status.success() {
-            let stderr = std::str::from_utf8(&command.stderr)?;
-            let stdout = std::str::from_utf8(&command.stdout)?;
-            bail!(
-                "Linter command failed with non-zero exit code.\n\
-                 STDERR:\n{}\n\nSTDOUT:{}\n",
-                stderr,
-                stdout,
-            );
-        }
-        let stdout_str = std::str::from_utf8(&command.stdout)?;
-        let mut messages = Vec::new();
-        for line in stdout_str.lines() {
-            if line.is_empty() {
-                continue;
-            }
-            let msg = serde_json::from_str(line).with_context(|| {
-                format!(
-                    "Failed to deserialize output for lint adapter, line: {}",
-                    line
-                )
-            })?;
-            messages.push(msg);
-        }
-        Ok(messages)
+    /// Run the linter on whichever of `files` it matches. If the linter
+    /// failed, the failure is reported as a lint message (so it is rendered
+    /// like any other issue), and its classification is returned alongside.
+    pub fn run(&self, files: &[AbsPath]) -> (Vec<LintMessage>, Option<LinterFailure>) {
+        self.run_on_matches(self.get_matches(files))
     }
 
-    pub fn run(&self, files: &[AbsPath]) -> Vec<LintMessage> {
-        let matches = self.get_matches(files);
+    /// Like [`Linter::run`], but for a set of files that has already been
+    /// filtered through [`Linter::get_matches`].
+    pub fn run_on_matches(
+        &self,
+        matches: Vec<AbsPath>,
+    ) -> (Vec<LintMessage>, Option<LinterFailure>) {
         log_files(&format!("Linter '{}' matched files: ", self.code), &matches);
         if matches.is_empty() {
-            return Vec::new();
+            return (Vec::new(), None);
         }
         // Wrap the command in a Result to ensure uniform error handling.
         // This way, linters are guaranteed to exit cleanly, and any issue will
         // be reported using the same mechanism that we use to report regular
         // lint errors.
         match self.run_command(matches) {
-            Err(e) => {
+            Err(LinterError { kind, err }) => {
                 let err_lint = LintMessage {
                     path: None,
                     line: None,
                     char: None,
                     code: self.code.clone(),
-                    severity: crate::lint_message::LintSeverity::Error,
-                    name: "Linter failed".to_string(),
-                    description: Some(format!(
-                        "Linter failed. This a bug, please file an issue against \
-                                 the linter maintainer.\n\nCONTEXT:\n{}",
-                        e
-                    )),
+                    // The user can fix this themselves, so there's nothing
+                    // wrong with the linter to report.
+                    severity: if kind == LinterFailure::NeedsInit {
+                        LintSeverity::Advice
+                    } else {
+                        LintSeverity::Error
+                    },
+                    name: kind.title().to_string(),
+                    description: Some(format!("{}\n\nCONTEXT:\n{}", kind.advice(&self.code), err)),
                     original: None,
                     replacement: None,
+                    edits: Vec::new(),
+                    fingerprint: None,
+                    first_seen: None,
                 };
-                vec![err_lint]
+                (vec![err_lint], Some(kind))
             }
-            Ok(messages) => messages,
+            Ok(messages) => (messages, None),
         }
     }
 
     pub fn init(&self, dry_run: bool) -> Result<()> {
-        match &self.init_commands {
-            Some(init_commands) => {
-                info!("Initializing linter: '{}'", self.code);
-                if init_commands.is_empty() {
-                    return Ok(());
-                }
-
-                let dry_run = if dry_run { "1" } else { "0" };
+        if let Some(container) = &self.container {
+            if offline::is_offline() {
+                // Only a container that is already there can be used.
+                let status = Command::new("docker")
+                    .args(["image", "inspect", container])
+                    .stdout(Stdio::null())
+                    .stderr(Stdio::null())
+                    .status()
+                    .context("Failed to run docker, is it installed?")?;
+                ensure!(
+                    status.success(),
+                    "Container '{}' for '{}' hasn't been pulled, and --offline forbids pulling it",
+                    container,
+                    self.code
+                );
+            } else if dry_run {
+                info!("Would pull container '{}' for '{}'", container, self.code);
+            } else {
+                info!("Pulling container '{}' for '{}'", container, self.code);
+                let status = Command::new("docker")
+                    .args(["pull", container])
+                    .status()
+                    .context("Failed to run docker, is it installed?")?;
+                ensure!(
+                    status.success(),
+                    "Pulling container '{}' for '{}' failed",
+                    container,
+                    self.code
+                );
+            }
+        }
 
-                let init_commands: Vec<String> = init_commands
-                    .iter()
-                    .map(|arg| arg.replace("{{DRYRUN}}", dry_run))
-                    .collect();
+        if self.init_commands.is_some() {
+            info!("Initializing linter: '{}'", self.code);
+        }
+        match self.init_command_args(dry_run) {
+            Some(init_commands) => {
                 let (program, arguments) = init_commands.split_at(1);
                 debug!(
                     "Running: {} {}",
@@ -190,10 +889,12 @@ impl Linter {
                         .collect::<Vec<_>>()
                         .join(" ")
                 );
-                let status = Command::new(&program[0])
-                    .args(arguments)
-                    .current_dir(self.get_config_dir())
-                    .status()?;
+                let mut command = Command::new(&program[0]);
+                command.args(arguments).current_dir(self.get_config_dir());
+                if offline::is_offline() {
+                    command.env(offline::OFFLINE_ENV_VAR, "1");
+                }
+                let status = command.status()?;
                 ensure!(
                     status.success(),
                     "lint initializer for '{}' failed with non-zero exit code",
@@ -204,6 +905,67 @@ impl Linter {
             None => Ok(()),
         }
     }
+
+    /// The init command as it would be run, with placeholders substituted,
+    /// or `None` if there is nothing to run.
+    pub fn init_command_args(&self, dry_run: bool) -> Option<Vec<String>> {
+        let init_commands = self.init_commands.as_ref()?;
+        if init_commands.is_empty() {
+            return None;
+        }
+        let dry_run = if dry_run { "1" } else { "0" };
+        let config_dir = self.get_config_dir().display().to_string();
+        Some(
+            self.shell.wrap(
+                init_commands
+                    .iter()
+                    .map(|arg| {
+                        self.substitute(arg, &config_dir)
+                            .replace("{{DRYRUN}}", dry_run)
+                    })
+                    .collect(),
+            ),
+        )
+    }
+}
+
+/// What a linter adapter prints, instead of or along with lint messages, when
+/// the tool it runs isn't set up right: `{"needs_init": "<reason>"}`.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct NeedsInitMessage {
+    needs_init: String,
+}
+
+/// The reason the linter gave for needing init, if it printed a
+/// [`NeedsInitMessage`] on any line of `stdout`.
+fn needs_init_reason(stdout: &[u8]) -> Option<String> {
+    String::from_utf8_lossy(stdout)
+        .lines()
+        .find_map(|line| serde_json::from_str::<NeedsInitMessage>(line).ok())
+        .map(|message| message.needs_init)
+}
+
+/// Split `paths` into consecutive runs whose arguments, added to a command
+/// line of `base_size` bytes, stay within `limit`. A path that doesn't fit
+/// on its own still gets a run to itself.
+fn chunk_paths(paths: &[String], base_size: usize, limit: usize) -> Vec<Range<usize>> {
+    let mut chunks = Vec::new();
+    let mut start = 0;
+    let mut size = base_size;
+    for (i, path) in paths.iter().enumerate() {
+        let path_size = path.len() + 1;
+        if i > start && size + path_size > limit {
+            chunks.push(start..i);
+            start = i;
+            size = base_size;
+        }
+        size += path_size;
+    }
+    if start < paths.len() {
+        chunks.push(start..paths.len());
+    }
+    chunks
 }
 
 #[cfg(test)]
@@ -219,7 +981,353 @@ mod tests {
             &PathBuf::from(""),
             &PathBuf::from("foo/bar/baz"),
             &Pattern::new("foo/b*")?,
+            PatternOptions::default(),
         ));
         Ok(())
     }
+
+    fn linter_with_command(command: &[&str]) -> Result<Linter> {
+        let dir = std::env::current_dir()?;
+        Ok(Linter {
+            code: "TEST".to_string(),
+            include_patterns: vec![Pattern::new("**")?],
+            exclude_patterns: Vec::new(),
+            include_source: PatternSource {
+                patterns: vec!["**".to_string()],
+                path: dir.join("Cargo.toml"),
+            },
+            exclude_source: PatternSource::default(),
+            commands: command.iter().map(|s| s.to_string()).collect(),
+            init_commands: None,
+            config_path: AbsPath::try_from(dir.join("Cargo.toml"))?,
+            run_after: Vec::new(),
+            exclusive: false,
+            max_allowed: BTreeMap::new(),
+            escalate_after_days: None,
+            container: None,
+            cache_dirs: Vec::new(),
+            sandbox: false,
+            shell: Shell::Direct,
+            placeholders: BTreeMap::new(),
+            slow_threshold: None,
+            stack_sampler: None,
+            flaky: false,
+            fail_on: None,
+            granularity: Granularity::File,
+            package_markers: Vec::new(),
+            version_check: None,
+            lockfiles: Vec::new(),
+            pattern_options: PatternOptions::default(),
+            severity_overrides: Vec::new(),
+        })
+    }
+
+    #[test]
+    fn failures_are_classified() -> Result<()> {
+        let files = vec![AbsPath::try_from(
+            std::env::current_dir()?.join("Cargo.toml"),
+        )?];
+        let cases = [
+            (vec!["false"], Some(LinterFailure::Crashed)),
+            (vec!["idonotexist"], Some(LinterFailure::Missing)),
+            (vec!["sh", "-c", "exit 127"], Some(LinterFailure::Missing)),
+            (vec!["echo", "not json"], Some(LinterFailure::BadOutput)),
+            (
+                vec!["echo", r#"{"needs_init": "wrong version"}"#],
+                Some(LinterFailure::NeedsInit),
+            ),
+            (
+                vec!["sh", "-c", r#"echo '{"needs_init": "no venv"}'; exit 1"#],
+                Some(LinterFailure::NeedsInit),
+            ),
+            (vec!["true"], None),
+        ];
+        for (command, expected) in cases {
+            let (lints, failure) = linter_with_command(&command)?.run(&files);
+            assert_eq!(failure, expected, "{:?}", command);
+            assert_eq!(lints.len(), usize::from(expected.is_some()));
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn budget_allows_some_lints() -> Result<()> {
+        let mut linter = linter_with_command(&["true"])?;
+        linter.max_allowed.insert(LintSeverity::Warning, 2);
+        let lint = |severity| LintMessage::for_test("TEST", severity, "test");
+
+        let lints = vec![lint(LintSeverity::Warning), lint(LintSeverity::Warning)];
+        let (failing, overruns) = linter.check_budget(&lints);
+        assert_eq!(failing, 0);
+        assert!(overruns.is_empty());
+
+        // Errors have no budget, and a third warning goes over it.
+        let lints = vec![
+            lint(LintSeverity::Warning),
+            lint(LintSeverity::Warning),
+            lint(LintSeverity::Warning),
+            lint(LintSeverity::Error),
+        ];
+        let (failing, overruns) = linter.check_budget(&lints);
+        assert_eq!(failing, 4);
+        assert_eq!(overruns.len(), 1);
+        assert_eq!(overruns[0].count, 3);
+        assert_eq!(overruns[0].max_allowed, 2);
+
+        // Below the failure threshold, nothing counts against the budget.
+        linter.fail_on = Some(LintSeverity::Error);
+        let (failing, overruns) = linter.check_budget(&lints);
+        assert_eq!(failing, 1);
+        assert!(overruns.is_empty());
+
+        // Advice and disabled issues only fail the run if asked to.
+        let lints = vec![lint(LintSeverity::Advice), lint(LintSeverity::Disabled)];
+        linter.fail_on = None;
+        assert_eq!(linter.check_budget(&lints).0, 0);
+        linter.fail_on = Some(LintSeverity::Advice);
+        assert_eq!(linter.check_budget(&lints).0, 1);
+        Ok(())
+    }
+
+    #[test]
+    fn severities_are_overridden_by_path() -> Result<()> {
+        let mut linter = linter_with_command(&["true"])?;
+        linter.severity_overrides = vec![
+            (
+                Pattern::new("third_party/vendored/**")?,
+                LintSeverity::Error,
+            ),
+            (Pattern::new("third_party/**")?, LintSeverity::Advice),
+        ];
+        let lint = |path: &str| LintMessage {
+            path: Some(path.to_string()),
+            ..LintMessage::for_test("TEST", LintSeverity::Warning, "test")
+        };
+        let absolute = std::env::current_dir()?.join("third_party/b.py");
+        let mut lints = vec![
+            lint("src/a.py"),
+            lint(absolute.to_str().unwrap()),
+            lint("third_party/vendored/c.py"),
+        ];
+        linter.override_severities(&mut lints);
+        let severities: Vec<_> = lints.iter().map(|lint| lint.severity).collect();
+        // The first matching rule wins.
+        assert_eq!(
+            severities,
+            [
+                LintSeverity::Warning,
+                LintSeverity::Advice,
+                LintSeverity::Error
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn placeholders_are_substituted() -> Result<()> {
+        let mut linter = linter_with_command(&[
+            "lint",
+            "--root={{REPO_ROOT}}",
+            "--config={{CONFIG_DIR}}/lint.toml",
+            "-j{{NPROC}}",
+            "--since={{REVISION}}",
+            "@{{PATHSFILE}}",
+        ])?;
+        assert!(linter.uses_placeholder("REVISION"));
+        assert!(!linter.uses_placeholder("DATA_DIR"));
+        linter
+            .placeholders
+            .insert("REVISION".to_string(), "abc123".to_string());
+        let config_dir = linter.get_config_dir().display().to_string();
+
+        let args = linter.command_args("/tmp/paths");
+        assert!(args[1].starts_with("--root=/"), "{}", args[1]);
+        assert_eq!(args[2], format!("--config={}/lint.toml", config_dir));
+        assert!(args[3][2..].parse::<usize>()? > 0);
+        assert_eq!(args[4..], ["--since=abc123", "@/tmp/paths"]);
+
+        // Inside a container, the config directory is mounted elsewhere.
+        linter.container = Some("lint:1".to_string());
+        let args = linter.command_args("/tmp/paths");
+        assert!(args.contains(&format!("--config={}/lint.toml", CONTAINER_WORKDIR)));
+        Ok(())
+    }
+
+    #[test]
+    fn directories_are_targeted() -> Result<()> {
+        let dir = tempfile::TempDir::new()?;
+        let root = dir.path().canonicalize()?;
+        for file in [
+            "lintrunner.toml",
+            "top.py",
+            "pkg/BUILD",
+            "pkg/a.py",
+            "pkg/sub/b.py",
+            "pkg/sub/c.py",
+            "other/d.py",
+        ] {
+            let path = root.join(file);
+            std::fs::create_dir_all(path.parent().unwrap())?;
+            std::fs::write(path, "")?;
+        }
+        let mut linter = linter_with_command(&["pylint"])?;
+        linter.config_path = AbsPath::try_from(root.join("lintrunner.toml"))?;
+        let files = [
+            "top.py",
+            "pkg/a.py",
+            "pkg/sub/b.py",
+            "pkg/sub/c.py",
+            "other/d.py",
+        ]
+        .iter()
+        .map(|file| AbsPath::try_from(root.join(file)))
+        .collect::<Result<Vec<_>>>()?;
+        assert_eq!(linter.targets(&files), files);
+
+        let relative = |linter: &Linter| -> Vec<String> {
+            linter
+                .targets(&files)
+                .iter()
+                .map(|dir| {
+                    dir.strip_prefix(&root)
+                        .unwrap()
+                        .to_string_lossy()
+                        .replace('\\', "/")
+                })
+                .collect()
+        };
+        linter.granularity = Granularity::Directory;
+        assert_eq!(relative(&linter), ["", "other", "pkg", "pkg/sub"]);
+        linter.package_markers = vec!["BUILD".to_string()];
+        assert_eq!(relative(&linter), ["", "other", "pkg"]);
+        linter.granularity = Granularity::Project;
+        assert_eq!(relative(&linter), Vec::<String>::new());
+        Ok(())
+    }
+
+    #[test]
+    fn paths_are_chunked() {
+        let paths: Vec<String> = ["aaaa", "bb", "cccc", "dddddddddd", "e"]
+            .iter()
+            .map(|p| p.to_string())
+            .collect();
+        // Each path takes its length plus a separator.
+        assert_eq!(chunk_paths(&paths, 2, 100), vec![0..5]);
+        assert_eq!(chunk_paths(&paths, 2, 10), vec![0..2, 2..3, 3..4, 4..5]);
+        assert_eq!(chunk_paths(&[], 2, 10), Vec::<Range<usize>>::new());
+    }
+
+    #[test]
+    fn planned_commands_are_chunked_like_the_run() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let mut files = Vec::new();
+        for i in 0..ARGS_SIZE_LIMIT / 64 {
+            let path = dir.path().join(format!("{:0>60}.py", i));
+            std::fs::write(&path, "")?;
+            files.push(AbsPath::try_from(path)?);
+        }
+
+        let linter = linter_with_command(&["lint", "{{PATHS}}"])?;
+        let commands = linter.planned_commands(&files, "{{PATHSFILE}}");
+        assert!(commands.len() > 1);
+        assert_eq!(
+            commands
+                .iter()
+                .map(|command| command.len() - 1)
+                .sum::<usize>(),
+            files.len()
+        );
+        assert_eq!(commands[0][1], files[0].display().to_string());
+
+        let linter = linter_with_command(&["lint", "@{{PATHSFILE}}"])?;
+        assert_eq!(
+            linter.planned_commands(&files, "{{PATHSFILE}}"),
+            [["lint", "@{{PATHSFILE}}"]]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn paths_are_passed_as_arguments() -> Result<()> {
+        let linter = linter_with_command(&["lint", "--", "{{PATHS}}", "--end"])?;
+        let paths = vec!["a.py".to_string(), "b.py".to_string()];
+        assert_eq!(
+            linter.command_args_with_paths("/tmp/paths", Some(&paths)),
+            ["lint", "--", "a.py", "b.py", "--end"]
+        );
+        assert_eq!(
+            linter.command_args("/tmp/paths"),
+            ["lint", "--", "{{PATHS}}", "--end"]
+        );
+
+        let files = vec![AbsPath::try_from(
+            std::env::current_dir()?.join("Cargo.toml"),
+        )?];
+        let (lints, failure) =
+            linter_with_command(&["sh", "-c", "test -f \"$0\"", "{{PATHS}}"])?.run(&files);
+        assert_eq!(failure, None, "{:?}", lints.first().map(|l| &l.description));
+        Ok(())
+    }
+
+    #[test]
+    fn container_paths_are_translated() -> Result<()> {
+        let mut linter = linter_with_command(&["clang-tidy", "@{{PATHSFILE}}"])?;
+        linter.container = Some("ghcr.io/org/clang-tidy:15".to_string());
+        let config_dir = linter.get_config_dir().to_path_buf();
+
+        let args = linter.command_args("/tmp/paths");
+        assert_eq!(args[..3], ["docker", "run", "--rm"]);
+        assert!(args.contains(&format!("{}:{}", config_dir.display(), CONTAINER_WORKDIR)));
+        assert!(args.contains(&format!("/tmp/paths:{}:ro", CONTAINER_PATHSFILE)));
+        assert_eq!(
+            args[args.len() - 3..],
+            [
+                "ghcr.io/org/clang-tidy:15".to_string(),
+                "clang-tidy".to_string(),
+                format!("@{}", CONTAINER_PATHSFILE)
+            ]
+        );
+
+        let file = AbsPath::try_from(config_dir.join("Cargo.toml"))?;
+        let in_container = linter.path_for_linter(&file);
+        assert_eq!(
+            in_container,
+            Path::new(CONTAINER_WORKDIR).join("Cargo.toml")
+        );
+        assert_eq!(
+            linter.path_from_linter(in_container.display().to_string()),
+            file.display().to_string()
+        );
+        // Relative paths are relative to the config dir both inside and
+        // outside the container.
+        assert_eq!(
+            linter.path_from_linter("src/lib.rs".to_string()),
+            "src/lib.rs"
+        );
+
+        let header = format!("{}/include/foo.h", CONTAINER_WORKDIR);
+        let mut msg: LintMessage = serde_json::from_value(serde_json::json!({
+            "path": in_container,
+            "line": 1,
+            "char": null,
+            "code": "CLANGTIDY",
+            "severity": "warning",
+            "name": "rename",
+            "description": null,
+            "original": null,
+            "replacement": null,
+            "edits": [
+                {"start": 0, "end": 3, "replacement": "bar"},
+                {"path": header, "start": 4, "end": 7, "replacement": "bar"},
+            ],
+        }))?;
+        linter.message_from_linter(&mut msg);
+        assert_eq!(msg.path, Some(file.display().to_string()));
+        assert_eq!(msg.edits[0].path, None);
+        assert_eq!(
+            msg.edits[1].path,
+            Some(config_dir.join("include/foo.h").display().to_string())
+        );
+        Ok(())
+    }
 }
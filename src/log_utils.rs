@@ -1,12 +1,18 @@
-use anyhow::{bail, Result};
-use console::{style, Term};
+use anyhow::{anyhow, bail, Result};
+use clap::ArgEnum;
+use console::style;
 use fern::colors::{Color, ColoredLevelConfig};
+use std::cell::RefCell;
+use std::fmt;
 use std::path::Path;
 use std::process::Output;
+use std::str::FromStr;
 
 use log::Level::Trace;
 use log::{debug, log_enabled, trace, LevelFilter};
 
+use crate::streams::{self, Stream};
+
 pub fn log_files<T>(message: &str, files: &T)
 where
     T: std::fmt::Debug,
@@ -33,72 +39,265 @@ pub fn ensure_output(program_name: &str, output: &Output) -> Result<()> {
     Ok(())
 }
 
-pub fn setup_logger(log_level: LevelFilter, log_file: &Path, force_color: bool) -> Result<()> {
-    let builder = fern::Dispatch::new();
+/// Decide whether output should be colored, following the NO_COLOR, CLICOLOR
+/// and CLICOLOR_FORCE conventions. `--force-color` takes precedence over all
+/// of them.
+///
+/// Returns None if nothing was specified, in which case colors should be used
+/// only when writing to a terminal.
+pub fn color_choice(force_color: bool, env: impl Fn(&str) -> Option<String>) -> Option<bool> {
+    let is_set = |var: &str| env(var).is_some_and(|val| !val.is_empty());
+    let is_not_zero = |var: &str| env(var).is_some_and(|val| val != "0");
 
-    let isatty = Term::stderr().features().is_attended();
-    if isatty || force_color {
-        // Use colors in our terminal output if we're on a tty
-        let log_colors = ColoredLevelConfig::new()
-            .trace(Color::Cyan)
-            .debug(Color::Blue)
-            .info(Color::Green)
-            .warn(Color::Yellow)
-            .error(Color::Red);
-        builder
-            .chain(
-                fern::Dispatch::new()
-                    .format(move |out, message, record| {
-                        out.finish(format_args!(
-                            "{}{} {} {}{} {}",
-                            style("[").dim(),
-                            chrono::Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Secs, true),
-                            log_colors.color(record.level()),
-                            record.target(),
-                            style("]").dim(),
-                            message
-                        ))
-                    })
-                    .level(log_level)
-                    .chain(std::io::stderr()),
-            )
-            .chain(
-                fern::Dispatch::new()
-                    .format(move |out, message, record| {
-                        out.finish(format_args!(
-                            "[{} {} {}] {}",
-                            chrono::Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Secs, true),
-                            record.level(),
-                            record.target(),
-                            message
-                        ))
-                    })
-                    .level(LevelFilter::Trace)
-                    .chain(fern::log_file(log_file)?),
-            )
-            .apply()?;
+    if force_color || is_not_zero("CLICOLOR_FORCE") {
+        Some(true)
+    } else if is_set("NO_COLOR") || env("CLICOLOR").as_deref() == Some("0") {
+        Some(false)
     } else {
-        builder
-            .format(move |out, message, record| {
+        None
+    }
+}
+
+/// [`color_choice`], reading from the process environment.
+pub fn color_choice_from_env(force_color: bool) -> Option<bool> {
+    color_choice(force_color, |var| std::env::var(var).ok())
+}
+
+/// The format of log records.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, ArgEnum)]
+pub enum LogFormat {
+    /// `[timestamp LEVEL module] message`
+    Text,
+    /// One JSON object per line, for log aggregation.
+    Json,
+}
+
+/// A `RUST_LOG`-style filter for which log messages to show, e.g.
+/// `warn,git=trace`: a default level, and levels for specific modules. Module
+/// names without a `::` are taken to be lintrunner modules.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct LogFilter {
+    default: Option<LevelFilter>,
+    modules: Vec<(String, LevelFilter)>,
+}
+
+impl FromStr for LogFilter {
+    type Err = anyhow::Error;
+
+    fn from_str(filter: &str) -> Result<LogFilter> {
+        let parse_level = |level: &str| {
+            LevelFilter::from_str(level.trim()).map_err(|_| {
+                anyhow!(
+                    "Invalid log level '{}' in log filter, expected one of \
+                     off, error, warn, info, debug, trace",
+                    level
+                )
+            })
+        };
+
+        let mut log_filter = LogFilter::default();
+        for directive in filter.split(',').map(str::trim).filter(|d| !d.is_empty()) {
+            match directive.split_once('=') {
+                Some((module, level)) => {
+                    let module = module.trim();
+                    let module = if module.contains("::") || module == "lintrunner" {
+                        module.to_string()
+                    } else {
+                        format!("lintrunner::{}", module)
+                    };
+                    log_filter.modules.push((module, parse_level(level)?));
+                }
+                None => log_filter.default = Some(parse_level(directive)?),
+            }
+        }
+        Ok(log_filter)
+    }
+}
+
+thread_local! {
+    static CURRENT_LINTER: RefCell<Option<String>> = const { RefCell::new(None) };
+}
+
+/// Attribute log records from the current thread to linter `code`. Each
+/// linter runs in its own thread, so this is set once when the thread starts.
+pub fn set_current_linter(code: &str) {
+    CURRENT_LINTER.with(|linter| *linter.borrow_mut() = Some(code.to_string()));
+}
+
+fn timestamp() -> String {
+    chrono::Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Secs, true)
+}
+
+fn json_record(record: &log::Record, message: &fmt::Arguments, run_id: &str) -> String {
+    let linter = CURRENT_LINTER.with(|linter| linter.borrow().clone());
+    serde_json::json!({
+        "timestamp": timestamp(),
+        "level": record.level().as_str(),
+        "module": record.target(),
+        "run_id": run_id,
+        "linter": linter,
+        "message": message.to_string(),
+    })
+    .to_string()
+}
+
+fn text_dispatch() -> fern::Dispatch {
+    fern::Dispatch::new().format(|out, message, record| {
+        out.finish(format_args!(
+            "[{} {} {}] {}",
+            timestamp(),
+            record.level(),
+            record.target(),
+            message
+        ))
+    })
+}
+
+fn json_dispatch(run_id: &str) -> fern::Dispatch {
+    let run_id = run_id.to_string();
+    fern::Dispatch::new().format(move |out, message, record| {
+        out.finish(format_args!("{}", json_record(record, message, &run_id)))
+    })
+}
+
+/// Log to the diagnostics stream (stderr, unless `--results-to stderr`) at
+/// `log_level` (unless `filter` says otherwise), and everything to
+/// `log_file`. `run_id` identifies this invocation in JSON
+/// records.
+pub fn setup_logger(
+    log_level: LevelFilter,
+    filter: &LogFilter,
+    log_file: &Path,
+    use_color: Option<bool>,
+    file_format: LogFormat,
+    stderr_format: LogFormat,
+    run_id: &str,
+) -> Result<()> {
+    let isatty = streams::diagnostics().term().features().is_attended();
+    let stderr_dispatch = match stderr_format {
+        LogFormat::Json => json_dispatch(run_id),
+        // Use colors in our terminal output if we're on a tty
+        LogFormat::Text if use_color.unwrap_or(isatty) => {
+            let log_colors = ColoredLevelConfig::new()
+                .trace(Color::Cyan)
+                .debug(Color::Blue)
+                .info(Color::Green)
+                .warn(Color::Yellow)
+                .error(Color::Red);
+            fern::Dispatch::new().format(move |out, message, record| {
                 out.finish(format_args!(
-                    "[{} {} {}] {}",
-                    chrono::Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Secs, true),
-                    record.level(),
+                    "{}{} {} {}{} {}",
+                    style("[").dim(),
+                    timestamp(),
+                    log_colors.color(record.level()),
                     record.target(),
+                    style("]").dim(),
                     message
                 ))
             })
-            .chain(
-                fern::Dispatch::new()
-                    .level(log_level)
-                    .chain(std::io::stderr()),
-            )
-            .chain(
-                fern::Dispatch::new()
-                    .level(LevelFilter::Trace)
-                    .chain(fern::log_file(log_file)?),
-            )
-            .apply()?;
-    }
+        }
+        LogFormat::Text => text_dispatch(),
+    };
+    let file_dispatch = match file_format {
+        LogFormat::Json => json_dispatch(run_id),
+        LogFormat::Text => text_dispatch(),
+    };
+
+    let stderr_dispatch = filter.modules.iter().fold(
+        stderr_dispatch.level(filter.default.unwrap_or(log_level)),
+        |dispatch, (module, level)| dispatch.level_for(module.clone(), *level),
+    );
+
+    fern::Dispatch::new()
+        .chain(match streams::diagnostics() {
+            Stream::Stdout => stderr_dispatch.chain(std::io::stdout()),
+            Stream::Stderr => stderr_dispatch.chain(std::io::stderr()),
+        })
+        .chain(
+            file_dispatch
+                .level(LevelFilter::Trace)
+                .chain(fern::log_file(log_file)?),
+        )
+        .apply()?;
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+
+    fn choice(force_color: bool, vars: &[(&str, &str)]) -> Option<bool> {
+        let vars: HashMap<String, String> = vars
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+        color_choice(force_color, |var| vars.get(var).cloned())
+    }
+
+    #[test]
+    fn color_choice_conventions() {
+        assert_eq!(choice(false, &[]), None);
+        assert_eq!(choice(true, &[]), Some(true));
+
+        assert_eq!(choice(false, &[("NO_COLOR", "1")]), Some(false));
+        // An empty NO_COLOR should be ignored.
+        assert_eq!(choice(false, &[("NO_COLOR", "")]), None);
+
+        assert_eq!(choice(false, &[("CLICOLOR", "0")]), Some(false));
+        assert_eq!(choice(false, &[("CLICOLOR", "1")]), None);
+
+        assert_eq!(choice(false, &[("CLICOLOR_FORCE", "1")]), Some(true));
+        assert_eq!(choice(false, &[("CLICOLOR_FORCE", "0")]), None);
+        assert_eq!(
+            choice(false, &[("CLICOLOR_FORCE", "1"), ("NO_COLOR", "1")]),
+            Some(true)
+        );
+
+        // --force-color beats everything.
+        assert_eq!(choice(true, &[("NO_COLOR", "1")]), Some(true));
+    }
+
+    #[test]
+    fn log_filters_are_parsed() {
+        assert_eq!(
+            "warn,git=trace, lintrunner::linter=debug,fern::colors=off"
+                .parse::<LogFilter>()
+                .unwrap(),
+            LogFilter {
+                default: Some(LevelFilter::Warn),
+                modules: vec![
+                    ("lintrunner::git".to_string(), LevelFilter::Trace),
+                    ("lintrunner::linter".to_string(), LevelFilter::Debug),
+                    ("fern::colors".to_string(), LevelFilter::Off),
+                ],
+            }
+        );
+        assert_eq!("".parse::<LogFilter>().unwrap(), LogFilter::default());
+        assert!("git=loud".parse::<LogFilter>().is_err());
+    }
+
+    #[test]
+    fn json_records_are_structured() {
+        let record = |args: fmt::Arguments| {
+            let record = log::Record::builder()
+                .args(args)
+                .level(log::Level::Debug)
+                .target("lintrunner::git")
+                .build();
+            let json = json_record(&record, record.args(), "run-1");
+            serde_json::from_str::<serde_json::Value>(&json).unwrap()
+        };
+
+        let json = record(format_args!("hello {}", 1));
+        assert_eq!(json["level"], "DEBUG");
+        assert_eq!(json["module"], "lintrunner::git");
+        assert_eq!(json["run_id"], "run-1");
+        assert_eq!(json["linter"], serde_json::Value::Null);
+        assert_eq!(json["message"], "hello 1");
+
+        set_current_linter("FLAKE8");
+        assert_eq!(record(format_args!("hi"))["linter"], "FLAKE8");
+    }
+}
@@ -1,24 +1,56 @@
-use std::{collections::HashSet, convert::TryFrom, io::Write};
+use std::{
+    collections::HashSet,
+    convert::TryFrom,
+    io::Write,
+    path::{Path, PathBuf},
+};
 
-use anyhow::{Context, Result};
+use anyhow::{bail, ensure, Context, Result};
 use chrono::SecondsFormat;
-use clap::Parser;
+use clap::{CommandFactory, Parser};
 
 use lintrunner::{
-    do_init, do_lint,
-    git::get_head,
+    base_revision, cancel,
+    ci::CiProvider,
+    clean::{do_clean, CleanTarget},
+    discover::do_discover,
+    do_init, do_lint, do_lint_dry_run, do_why,
+    file_timing::do_slow_files,
+    fixtures::do_test_linter,
+    flaky::find_flaky_linters,
+    format_check::do_format_check,
+    get_paths,
+    git::{get_git_root, get_head},
+    git_lint::{git_lint_args, install_git_alias, is_revision, restore_git_prefix, Translator},
     init::check_init_changed,
-    lint_config::{get_linters_from_config, LintRunnerConfig},
-    log_utils::setup_logger,
+    lint_config::{add_linter_args, get_linters_from_config, LintRunnerConfig, Profile},
+    linter::SkipReason,
+    log_utils::{color_choice_from_env, setup_logger, LogFilter, LogFormat},
+    metrics::MetricsSink,
+    offline, only_new_baseline,
+    patches::do_apply,
     path::AbsPath,
-    persistent_data::{ExitInfo, PersistentDataStore, RunInfo},
-    rage::do_rage,
-    render::print_error,
-    PathsOpt, RenderOpt, RevisionOpt,
+    persistent_data::{DataKey, DataKeyPart, ExitInfo, PersistentDataStore, RunInfo},
+    progress::ProgressOpt,
+    publish::{do_publish, PublishTarget, Webhook},
+    query::{do_query, parse_since, QueryFilter, QueryFormat},
+    rage::{do_rage, RunSelector, UploadTarget},
+    render::{print_error, set_csv_columns, CsvColumn},
+    scaffold::{new_linter, AdapterLanguage},
+    stats::do_stats,
+    streams::{self, Stream},
+    tee::{parse_size, TeeJson, TeeMode},
+    theme::{self, Theme},
+    user_config::{ColorPreference, UserConfig},
+    verify::do_verify,
+    version::check_versions,
+    CodeFilter, PathsOpt, RenderOpt, ReportOptions, RevisionOpt, RunOptions,
 };
 use log::debug;
 
 const VERSION: &str = env!("CARGO_PKG_VERSION");
+const LOG_FILTER_ENV: &str = "LINTRUNNER_LOG";
+const PROFILE_ENV: &str = "LINTRUNNER_PROFILE";
 
 #[derive(Debug, Parser)]
 #[clap(version, name = "lintrunner", infer_subcommands(true))]
@@ -31,10 +63,26 @@ struct Args {
     #[clap(long, default_value = ".lintrunner.toml", global = true)]
     config: String,
 
+    /// If set, fail if the config file contains keys that lintrunner doesn't
+    /// recognize, instead of just warning about them.
+    #[clap(long, global = true)]
+    strict_config: bool,
+
+    /// If set, ignore the `.lintrunner.local.toml` overlay next to the config
+    /// file. Useful in CI, where only the shared config should apply.
+    #[clap(long, global = true)]
+    no_local_config: bool,
+
     /// If set, any suggested patches will be applied
     #[clap(short, long, global = true)]
     apply_patches: bool,
 
+    /// Comma-separated list of lint codes or names whose fixes to apply with
+    /// --apply-patches (or `lintrunner format`), e.g. --fix-only CLANGFORMAT.
+    /// Fixes from other lints are reported, but not applied.
+    #[clap(long, global = true)]
+    fix_only: Option<String>,
+
     /// Shell command that returns new-line separated paths to lint
     ///
     /// Example: To run on all files in the repo, use `--paths-cmd='git grep -Il .'`.
@@ -45,20 +93,56 @@ struct Args {
     #[clap(long, global = true)]
     paths_from: Option<String>,
 
+    /// Read paths to lint from stdin, one per line
+    ///
+    /// Example: `git diff --name-only -z | lintrunner --paths-stdin -z`
+    #[clap(long, conflicts_with_all = &["paths-cmd", "paths-from"], global = true)]
+    paths_stdin: bool,
+
+    /// With --paths-stdin, paths are separated by NUL characters instead of
+    /// new-lines
+    #[clap(short = 'z', requires = "paths-stdin", global = true)]
+    null_delimited: bool,
+
     /// Lint all files that differ between the working directory and the
     /// specified revision. This argument can be any <tree-ish> that is accepted
     /// by `git diff-tree`
-    #[clap(long, short, conflicts_with_all=&["paths", "paths-cmd", "paths-from"], global = true)]
+    #[clap(long, short, conflicts_with_all=&["paths", "paths-cmd", "paths-from", "paths-stdin"], global = true)]
     revision: Option<String>,
 
     /// Lint all files that differ between the merge base of HEAD with the
     /// specified revision and HEAD. This argument can be any <tree-sh> that is
-    /// accepted by `git diff-tree`
+    /// accepted by `git diff-tree`. Defaults to `merge_base_with` from the
+    /// config, or else the remote's default branch (`origin/HEAD`).
     ///
     /// Example: lintrunner -m master
-    #[clap(long, short, conflicts_with_all=&["paths", "paths-cmd", "paths-from", "revision"], global = true)]
+    #[clap(long, short, conflicts_with_all=&["paths", "paths-cmd", "paths-from", "paths-stdin", "revision"], global = true)]
     merge_base_with: Option<String>,
 
+    /// Lint all files changed in commits newer than this date. Accepts any
+    /// date that `git log --since` does.
+    ///
+    /// Example: lintrunner --since "2 weeks ago"
+    #[clap(long, conflicts_with_all=&["paths", "paths-cmd", "paths-from", "paths-stdin", "revision", "merge-base-with", "targets"], global = true)]
+    since: Option<String>,
+
+    /// Lint the files with changes staged for commit, e.g. in a pre-commit
+    /// hook. The files are linted as they are in the working tree.
+    #[clap(long, conflicts_with_all=&["paths", "paths-cmd", "paths-from", "paths-stdin", "revision", "merge-base-with", "since", "targets"], global = true)]
+    staged: bool,
+
+    /// Also lint untracked files that aren't ignored by git.
+    #[clap(long, conflicts_with_all=&["paths", "paths-cmd", "paths-from", "paths-stdin", "targets"], global = true)]
+    untracked: bool,
+
+    /// Don't lint this path, even if it would otherwise be linted. Can be a
+    /// file, a directory, or a glob pattern relative to the current directory.
+    /// May be specified multiple times.
+    ///
+    /// Example: lintrunner -m main --exclude third_party/ --exclude '**/*_pb2.py'
+    #[clap(long, global = true)]
+    exclude: Vec<String>,
+
     /// Comma-separated list of linters to skip (e.g. --skip CLANGFORMAT,NOQA)
     #[clap(long, global = true)]
     skip: Option<String>,
@@ -67,11 +151,62 @@ struct Args {
     #[clap(long, global = true)]
     take: Option<String>,
 
+    /// Use the settings in this `[profiles.NAME]` section of the config. Can
+    /// also be set with LINTRUNNER_PROFILE.
+    ///
+    /// Example: lintrunner --profile ci
+    #[clap(long, global = true)]
+    profile: Option<String>,
+
+    /// Run at most this many linters at once. Defaults to the profile's or
+    /// the user config's `jobs`, and otherwise all linters run in parallel.
+    #[clap(long, short = 'j', global = true)]
+    jobs: Option<usize>,
+
+    /// Comma-separated list of lint codes or names to report, e.g.
+    /// --only-codes E501,W503. All other lint messages are dropped.
+    #[clap(long, global = true)]
+    only_codes: Option<String>,
+
+    /// Comma-separated list of lint codes or names to drop, e.g.
+    /// --skip-codes E501 (opposite of --only-codes)
+    #[clap(long, global = true)]
+    skip_codes: Option<String>,
+
+    /// Add an argument to a linter's command for this run, in the form
+    /// CODE=ARG. It goes before the paths the linter is given. May be
+    /// specified multiple times.
+    ///
+    /// Example: lintrunner --linter-arg MYPY=--strict-equality
+    #[clap(long, global = true, allow_hyphen_values = true)]
+    linter_arg: Vec<String>,
+
     /// With 'default' show lint issues in human-readable format, for interactive use.
     /// With 'json', show lint issues as machine-readable JSON (one per line)
     /// With 'oneline', show lint issues in compact format (one per line)
-    #[clap(long, arg_enum, default_value_t = RenderOpt::Default, global=true)]
-    output: RenderOpt,
+    /// With 'gerrit', show lint issues as Gerrit robot comments
+    /// With 'arc', show lint issues as Arcanist lint JSON (one file per line)
+    /// With 'teamcity', show lint issues as TeamCity service messages
+    /// With 'azure', show lint issues as Azure Pipelines logging commands
+    /// With 'github', show lint issues as GitHub Actions workflow commands
+    /// With 'buildkite', show lint issues as Markdown for Buildkite annotations
+    /// With 'sonarqube', show lint issues in SonarQube's generic issue format
+    /// With 'csv', show lint issues as a CSV table, see --csv-columns
+    ///
+    /// Defaults to 'default', unless set in the user config. In GitHub Actions
+    /// and Azure Pipelines, defaults to their annotations.
+    #[clap(long, arg_enum, global = true)]
+    output: Option<RenderOpt>,
+
+    /// The columns of `--output csv`, separated by commas.
+    #[clap(
+        long,
+        arg_enum,
+        use_value_delimiter = true,
+        default_value = "path,line,linter,severity,name,description",
+        global = true
+    )]
+    csv_columns: Vec<CsvColumn>,
 
     #[clap(subcommand)]
     cmd: Option<SubCommand>,
@@ -79,11 +214,24 @@ struct Args {
     /// Paths to lint. lintrunner will still respect the inclusions and
     /// exclusions defined in .lintrunner.toml; manually specifying a path will
     /// not override them.
-    #[clap(conflicts_with_all = &["paths-cmd", "paths-from"], global = true)]
+    ///
+    /// Directories are expanded to all the (git-tracked) files inside them, and
+    /// glob patterns like 'src/**/*.py' are expanded to the matching files.
+    #[clap(conflicts_with_all = &["paths-cmd", "paths-from", "paths-stdin"], global = true)]
     paths: Vec<String>,
 
+    /// Arguments after `--` are added to the command of every linter selected
+    /// with --take, before the paths it is given.
+    ///
+    /// Example: lintrunner --take MYPY -- --strict-equality
+    #[clap(last = true, requires = "take", global = true)]
+    passthrough_args: Vec<String>,
+
     /// If set, always output with ANSI colors, even if we detect the output is
     /// not a user-attended terminal.
+    ///
+    /// Without this flag, the NO_COLOR, CLICOLOR and CLICOLOR_FORCE environment
+    /// variables are respected.
     #[clap(long, global = true)]
     force_color: bool,
 
@@ -93,49 +241,428 @@ struct Args {
     #[clap(long, global = true)]
     data_path: Option<String>,
 
+    /// What the data of this config is kept apart by, as a comma-separated
+    /// list of: config-path, branch. E.g. `--data-key-parts
+    /// config-path,branch` keeps init state and logs per branch.
+    #[clap(
+        long,
+        arg_enum,
+        use_value_delimiter = true,
+        default_value = "config-path",
+        global = true
+    )]
+    data_key_parts: Vec<DataKeyPart>,
+
+    /// Keep the data of this run in a directory with this name, instead of
+    /// one picked by --data-key-parts, e.g. to give each worktree its own.
+    #[clap(long, conflicts_with = "data-key-parts", global = true)]
+    data_key: Option<String>,
+
+    /// Which log messages to print, as a comma-separated list of a default
+    /// level and/or MODULE=LEVEL pairs, e.g. `warn,git=trace`. Overrides the
+    /// level set by `--verbose`. Can also be set with LINTRUNNER_LOG.
+    #[clap(long, global = true)]
+    log_filter: Option<String>,
+
+    /// The format of the log file lintrunner writes for each run.
+    #[clap(long, arg_enum, default_value = "text", global = true)]
+    log_format: LogFormat,
+
+    /// The format of log messages written to stderr.
+    #[clap(long, arg_enum, default_value = "text", global = true)]
+    stderr_log_format: LogFormat,
+
+    /// When linting finishes, POST a JSON summary of the results (exit code,
+    /// issue counts and duration) to this URL. Overrides `notify_webhook` in
+    /// the config.
+    #[clap(long, global = true)]
+    notify_webhook: Option<String>,
+
+    /// A link to the full results (e.g. a CI artifact) to include in the
+    /// --notify-webhook summary.
+    #[clap(long, global = true)]
+    report_url: Option<String>,
+
+    /// Write run metrics (durations and issue counts) to this file, in the
+    /// Prometheus text format, e.g. for node_exporter's textfile collector.
+    #[clap(long, global = true)]
+    metrics_file: Option<PathBuf>,
+
+    /// Push run metrics to the Prometheus Pushgateway at this URL.
+    #[clap(long, conflicts_with = "metrics-file", global = true)]
+    metrics_pushgateway: Option<String>,
+
+    /// Forbid any network access, e.g. on airgapped CI runners. Features that
+    /// need the network fail right away, only containers that were already
+    /// pulled are used, and init commands get LINTRUNNER_OFFLINE=1 so that
+    /// they can install from a local cache.
+    #[clap(long, global = true)]
+    offline: bool,
+
     /// If set, output json to the provided path as well as the terminal.
+    /// The path may contain %t (the time), %d (the date), %r (the run id) and
+    /// %% (a literal %).
     #[clap(long, global = true)]
     tee_json: Option<String>,
 
+    /// What to do if the --tee-json file already exists: fail ('new'), add
+    /// this run after the ones in it ('append') or replace it ('truncate').
+    #[clap(
+        long,
+        arg_enum,
+        default_value = "new",
+        requires = "tee-json",
+        global = true
+    )]
+    tee_json_mode: TeeMode,
+
+    /// Move the --tee-json file aside to <path>.1 (and older ones to <path>.2
+    /// and so on) once it is at least this big, e.g. 10M.
+    #[clap(long, parse(try_from_str = parse_size), requires = "tee-json", global = true)]
+    tee_json_max_size: Option<u64>,
+
+    /// Write the fixes linters propose to this directory, as one patch per
+    /// file, instead of just reporting them. Apply them later with
+    /// `lintrunner apply <dir>`.
+    #[clap(long, global = true)]
+    save_patches: Option<PathBuf>,
+
+    /// If set, print lint issues as soon as each linter finishes instead of
+    /// waiting for all linters to complete.
+    #[clap(long, global = true)]
+    stream: bool,
+
+    /// How to show progress while linters run. `plain` prints a line whenever
+    /// a linter starts or finishes, without redrawing anything, for CI logs.
+    /// Defaults to progress bars on a terminal, and to `plain` in CI.
+    #[clap(long, arg_enum, global = true)]
+    progress: Option<ProgressOpt>,
+
+    /// Where to print lint results. Logs, progress and errors go to the other
+    /// stream, so results are never interleaved with them.
+    #[clap(long, arg_enum, default_value = "stdout", global = true)]
+    results_to: Stream,
+
+    /// Print nothing at all, not even errors, and report only through the
+    /// exit code (and --tee-json, if passed): 0 if there are no issues.
+    /// For scripts that just need to know whether the tree is clean.
+    #[clap(long, global = true, conflicts_with = "apply-patches")]
+    check_only: bool,
+
+    /// Report which configured linters did not run, and why. Implied by
+    /// `--verbose`.
+    #[clap(long, global = true)]
+    why_skipped: bool,
+
+    /// Run linters in a sandbox, where they can read anything but only write
+    /// to their declared `cache_dirs` and the temp directory. Supported on
+    /// Linux (with Landlock) and macOS.
+    #[clap(long, global = true)]
+    sandbox: bool,
+
+    /// Only report (and fail on) issues that weren't there in the most recent
+    /// run at the merge base (with --merge-base-with), or at HEAD otherwise.
+    /// Issues are matched by the text of the flagged line, so they survive
+    /// line shifts.
+    #[clap(long, global = true)]
+    only_new: bool,
+
+    /// Report the issues of linters that gave different results for the same
+    /// files in recent runs (see `lintrunner stats --flaky`) as advice, and
+    /// don't let them fail the run.
+    #[clap(long, global = true)]
+    downgrade_flaky: bool,
+
+    /// If set, fail when a linter's command can't be found, instead of
+    /// skipping that linter with an advice.
+    #[clap(long, global = true)]
+    strict_missing: bool,
+
+    /// If set, don't run any linters. Instead, print which linters would run
+    /// on which files, and the exact commands that would be used.
+    #[clap(long)]
+    dry_run: bool,
+
+    /// Lint the source files of these build system targets (comma-separated),
+    /// e.g. --targets //foo/...,//bar:baz. Requires `target_query` to be set
+    /// in the config.
+    #[clap(long, use_value_delimiter = true, conflicts_with_all=&["paths", "paths-cmd", "paths-from", "paths-stdin", "revision", "merge-base-with"], global = true)]
+    targets: Vec<String>,
+
     /// Run lintrunner on all files in the repo. This could take a while!
-    #[clap(long, conflicts_with_all=&["paths", "paths-cmd", "paths-from", "revision", "merge-base-with"], global = true)]
+    #[clap(long, conflicts_with_all=&["paths", "paths-cmd", "paths-from", "paths-stdin", "revision", "merge-base-with", "targets", "since", "staged", "untracked"], global = true)]
     all_files: bool,
 }
 
 #[derive(Debug, Parser)]
 enum SubCommand {
-    /// Perform first-time setup for linters
+    /// Perform first-time setup for linters. Linters that are up to date (see
+    /// `lintrunner verify`) are skipped.
     Init {
-        /// If set, do not actually execute initialization commands, just print them
+        /// If set, do not actually execute initialization commands, just print
+        /// the plan: which linters would be initialized, in which order, with
+        /// which commands, and which are skipped as up to date
         #[clap(long, short)]
         dry_run: bool,
+
+        /// With --dry-run, print only the plan, as JSON
+        #[clap(long, requires = "dry-run")]
+        json: bool,
+
+        /// Initialize every linter, even those that are up to date
+        #[clap(long)]
+        force: bool,
+    },
+    /// Check that linters are still installed the way `lintrunner init` left
+    /// them, and report what is out of date.
+    Verify,
+    /// Remove the state lintrunner keeps for this repo, to recover from a
+    /// corrupted linter environment.
+    Clean {
+        /// What to remove: what `init` installed, caches, the logs and
+        /// history of past runs, or all of it
+        #[clap(long, arg_enum, default_value = "all")]
+        what: CleanTarget,
     },
     /// Run and accept changes for formatting linters only. Equivalent to
     /// `lintrunner --apply-patches --take <formatters>`.
-    Format,
+    Format {
+        /// Don't change any files, but list the files that would be
+        /// reformatted, and fail if there are any.
+        #[clap(long)]
+        check: bool,
+
+        /// With --check, also show a diff of the changes formatters would
+        /// make.
+        #[clap(long, requires = "check")]
+        diff: bool,
+    },
 
     /// Run linters. This is the default if no subcommand is provided.
     Lint,
 
+    /// Explain which linters would lint the given paths, and which include or
+    /// exclude patterns are responsible.
+    Why,
+
+    /// Run a linter on fixture files and compare its output to the expected
+    /// lint messages recorded next to each fixture in `<file>.expected.json`.
+    TestLinter {
+        /// The code of the linter to test
+        code: String,
+
+        /// Directory containing the fixture files
+        #[clap(long)]
+        fixtures: String,
+
+        /// Record the current output as the expected output
+        #[clap(long)]
+        update: bool,
+    },
+
+    /// Look at the repo's files and the tools it is already configured for,
+    /// and propose linters for it from a built-in registry.
+    Discover {
+        /// Add the proposed linters to the config (creating it if needed)
+        /// instead of printing them
+        #[clap(long)]
+        write: bool,
+    },
+
+    /// Add a new linter to the config, along with an adapter script to fill in.
+    NewLinter {
+        /// The code of the new linter, e.g. MYLINT
+        #[clap(long)]
+        name: String,
+
+        /// The language to write the adapter in
+        #[clap(long, arg_enum, default_value = "python")]
+        language: AdapterLanguage,
+
+        /// Where to put the adapter, relative to the config file
+        #[clap(long, default_value = "tools/linter/adapters")]
+        adapter_dir: PathBuf,
+    },
+
+    /// Publish lint results, as written by `--output json` or `--tee-json`,
+    /// to a code review system.
+    Publish {
+        /// Where to publish the results
+        #[clap(arg_enum)]
+        target: PublishTarget,
+
+        /// File with the lint messages to publish, one JSON object per line.
+        /// Use '-' to read from stdin.
+        #[clap(long, default_value = "-")]
+        from: String,
+
+        /// The name of the check run
+        #[clap(long, default_value = "lintrunner")]
+        name: String,
+    },
+
+    /// Show statistics about past runs, like how long each linter takes.
+    Stats {
+        /// Instead, list linters that gave different results for the same
+        /// files across recent runs
+        #[clap(long)]
+        flaky: bool,
+
+        /// Instead, show how long the issues each linter found in its last run
+        /// have been around
+        #[clap(long, conflicts_with = "flaky")]
+        ages: bool,
+    },
+
+    /// Find the files each linter spends the most time on, by running it on
+    /// batches of the files to lint and bisecting the slow batches. Takes the
+    /// same file selection options as linting, e.g. `--all-files`.
+    SlowFiles {
+        /// How many of each linter's slowest files to show
+        #[clap(long, default_value = "10")]
+        top: usize,
+
+        /// How many batches to split each linter's files into before
+        /// bisecting. More batches take longer, but find more slow files.
+        #[clap(long, default_value = "8")]
+        batches: usize,
+    },
+
+    /// Search the issues past lint runs found, e.g. `lintrunner query --code
+    /// E501 --after 2024-01-01 --path 'torch/**'`.
+    Query {
+        /// Only issues from this linter, or with this name. May be specified
+        /// multiple times.
+        #[clap(long)]
+        code: Vec<String>,
+
+        /// Only issues in files matching this glob, relative to the root of
+        /// the repo. May be specified multiple times.
+        #[clap(long)]
+        path: Vec<String>,
+
+        /// Only runs started after this: a date, an RFC 3339 timestamp or a
+        /// relative time like "3 days ago"
+        #[clap(long)]
+        after: Option<String>,
+
+        #[clap(long, arg_enum, default_value = "json")]
+        format: QueryFormat,
+    },
+
+    /// Apply fixes saved with `--save-patches`, or the replacements in lint
+    /// messages written by `--output json` or `--tee-json`.
+    Apply {
+        /// A directory written by `--save-patches`, or a JSON file
+        source: String,
+    },
+
+    /// Set up a `git lint` alias, which takes git-style arguments like
+    /// `--cached` and pathspecs.
+    InstallGitAlias {
+        /// Set the alias for this repo only, instead of for the current user
+        #[clap(long)]
+        local: bool,
+    },
+
     /// Create a bug report for a past invocation of lintrunner.
     Rage {
         /// Choose a specific invocation to report on. 0 is the most recent run.
-        #[clap(long, short)]
+        #[clap(long, short, conflicts_with_all = &["run", "after"])]
         invocation: Option<usize>,
+
+        /// Report on the run with this id, or whose id starts with this (run
+        /// ids start with the time of the run, e.g. 2024-01-31T12-00).
+        #[clap(long)]
+        run: Option<String>,
+
+        /// Only offer runs started after this: a date, an RFC 3339 timestamp
+        /// or a relative time like "1 hour ago"
+        #[clap(long, conflicts_with = "run")]
+        after: Option<String>,
+
+        /// Upload the report, with secrets redacted, and print its URL instead
+        /// of the report. Takes 'gist' (a private GitHub Gist, using
+        /// GITHUB_TOKEN) or the URL of a paste service to POST it to. Defaults
+        /// to `rage_upload` in the user config, or 'gist'.
+        #[clap(long, value_name = "gist|URL")]
+        upload: Option<Option<String>>,
     },
 }
 
 fn do_main() -> Result<i32> {
-    let args = Args::parse();
+    cancel::install_handler()?;
+    let raw_args: Vec<String> = std::env::args().collect();
+    let args = match git_lint_args(&raw_args) {
+        Some(git_args) => {
+            restore_git_prefix()?;
+            let translated = translate_git_args(&raw_args[0], git_args);
+            Args::parse_from(translated)
+        }
+        None => Args::parse(),
+    };
+    streams::set_results(args.results_to);
+    if args.check_only {
+        if let Some(SubCommand::Format { check: false, .. }) = &args.cmd {
+            bail!("--check-only can't be used to format files, pass `format --check` instead");
+        }
+        streams::silence()?;
+    }
+    set_csv_columns(args.csv_columns.clone());
+    offline::set_offline(args.offline);
+
+    // This may create the config, so handle it before trying to read one.
+    if let Some(SubCommand::NewLinter {
+        name,
+        language,
+        adapter_dir,
+    }) = &args.cmd
+    {
+        new_linter(Path::new(&args.config), name, *language, adapter_dir)?;
+        return Ok(0);
+    }
+    if let Some(SubCommand::Discover { write }) = &args.cmd {
+        return do_discover(Path::new(&args.config), *write);
+    }
+    if let Some(SubCommand::Apply { source }) = &args.cmd {
+        return do_apply(source);
+    }
+    if let Some(SubCommand::InstallGitAlias { local }) = &args.cmd {
+        return install_git_alias(*local);
+    }
+    // Publishing only needs the results of a previous run, not a config.
+    if let Some(SubCommand::Publish { target, from, name }) = &args.cmd {
+        return do_publish(*target, from, name);
+    }
 
     let config_path = AbsPath::try_from(&args.config)
         .with_context(|| format!("Could not read lintrunner config at: '{}'", args.config))?;
 
-    if args.force_color {
-        console::set_colors_enabled(true);
-        console::set_colors_enabled_stderr(true);
+    let user_config = UserConfig::load()?;
+    let ci = CiProvider::detect();
+    let output = args
+        .output
+        .or(user_config.output)
+        .or_else(|| ci.and_then(CiProvider::annotation_output))
+        .unwrap_or(RenderOpt::Default);
+
+    let use_color = color_choice_from_env(args.force_color)
+        .or_else(|| user_config.color.and_then(ColorPreference::use_color));
+    if let Some(use_color) = use_color {
+        console::set_colors_enabled(use_color);
+        console::set_colors_enabled_stderr(use_color);
+    } else if args.results_to == Stream::Stderr {
+        // Results are styled as if for stdout, so go by whether the stream
+        // they actually go to supports colors.
+        let stdout_colors = console::colors_enabled();
+        console::set_colors_enabled(console::colors_enabled_stderr());
+        console::set_colors_enabled_stderr(stdout_colors);
     }
-    let log_level = match (args.verbose, args.output != RenderOpt::Default) {
+    theme::set(
+        Theme::new(user_config.theme, &user_config.colors)
+            .context("Invalid colors in the user config")?,
+    );
+    let log_level = match (args.verbose, output != RenderOpt::Default) {
         // Default
         (0, false) => log::LevelFilter::Info,
         // If just json is asked for, suppress most output except hard errors.
@@ -153,34 +680,98 @@ fn do_main() -> Result<i32> {
         args: std::env::args().collect(),
         timestamp: chrono::Local::now().to_rfc3339_opts(SecondsFormat::Millis, true),
     };
-    let persistent_data_store = PersistentDataStore::new(&config_path, run_info)?;
+    let data_path = args
+        .data_path
+        .clone()
+        .map(PathBuf::from)
+        .or(user_config.data_path);
+    let data_key = match args.data_key.clone() {
+        Some(key) => DataKey::Explicit(key),
+        None => DataKey::Parts(args.data_key_parts.clone()),
+    };
+    let persistent_data_store =
+        PersistentDataStore::with_key(&config_path, data_path.as_deref(), &data_key, run_info)?;
+    let tee_json = match &args.tee_json {
+        Some(template) => Some(TeeJson::new(
+            template,
+            args.tee_json_mode,
+            args.tee_json_max_size,
+            &persistent_data_store.run_id(),
+            chrono::Utc::now(),
+        )?),
+        None => None,
+    };
 
+    let log_filter = args
+        .log_filter
+        .clone()
+        .or_else(|| std::env::var(LOG_FILTER_ENV).ok())
+        .unwrap_or_default()
+        .parse::<LogFilter>()?;
     setup_logger(
         log_level,
+        &log_filter,
         &persistent_data_store.log_file(),
-        args.force_color,
+        use_color,
+        args.log_format,
+        args.stderr_log_format,
+        &persistent_data_store.run_id(),
     )?;
 
     debug!("Version: {VERSION}");
     debug!("Passed args: {:?}", std::env::args());
     debug!("Computed args: {:?}", args);
-    debug!("Current rev: {}", get_head()?);
+    if let Some(ci) = ci {
+        debug!("Running in {}", ci.name());
+    }
+    match get_head() {
+        Ok(head) => debug!("Current rev: {}", head),
+        Err(err) => debug!("Couldn't determine the current rev: {}", err),
+    }
 
     let cmd = args.cmd.unwrap_or(SubCommand::Lint);
-    let lint_runner_config = LintRunnerConfig::new(&config_path)?;
+    let lint_runner_config =
+        LintRunnerConfig::new(&config_path, args.strict_config, !args.no_local_config)?;
+
+    let profile_name = args
+        .profile
+        .or_else(|| std::env::var(PROFILE_ENV).ok())
+        .filter(|name| !name.is_empty());
+    let profile = match &profile_name {
+        Some(name) => {
+            debug!("Using profile '{}'", name);
+            lint_runner_config.profile(name)?.clone()
+        }
+        None => Profile::default(),
+    };
 
-    let skipped_linters = args.skip.map(|linters| {
+    let cli_skipped_linters = args.skip.map(|linters| {
         linters
             .split(',')
             .map(|linter_name| linter_name.to_string())
             .collect::<HashSet<_>>()
     });
-    let taken_linters = args.take.map(|linters| {
+    let cli_taken_linters = args.take.map(|linters| {
         linters
             .split(',')
             .map(|linter_name| linter_name.to_string())
             .collect::<HashSet<_>>()
     });
+    // The profile's linters to skip add to --skip, while --take replaces its
+    // linters to take.
+    let mut skipped_linters = cli_skipped_linters.clone();
+    if !profile.skip.is_empty() {
+        skipped_linters
+            .get_or_insert_with(HashSet::new)
+            .extend(profile.skip.iter().cloned());
+    }
+    let taken_linters = cli_taken_linters.clone().or_else(|| {
+        profile
+            .take
+            .as_ref()
+            .map(|take| take.iter().cloned().collect())
+    });
+    let jobs = args.jobs.or(profile.jobs).or(user_config.jobs);
 
     // If we are formatting, the universe of linters to select from should be
     // restricted to only formatters.
@@ -189,7 +780,7 @@ fn do_main() -> Result<i32> {
     // but if we want to fix it we should impl Cow for LintConfig and use that
     // instead.).
     let mut placeholder = Vec::new();
-    let all_linters = if let SubCommand::Format = &cmd {
+    let all_linters = if let SubCommand::Format { .. } = &cmd {
         let iter = lint_runner_config
             .linters
             .iter()
@@ -203,17 +794,105 @@ fn do_main() -> Result<i32> {
         &lint_runner_config.linters
     };
 
-    let linters =
-        get_linters_from_config(all_linters, skipped_linters, taken_linters, &config_path)?;
+    let mut linter_args = Vec::new();
+    for linter_arg in &args.linter_arg {
+        let (code, arg) = linter_arg.split_once('=').with_context(|| {
+            format!(
+                "Invalid --linter-arg '{}', expected the form CODE=ARG",
+                linter_arg
+            )
+        })?;
+        linter_args.push((code.to_string(), arg.to_string()));
+    }
+    if let Some(taken_linters) = &taken_linters {
+        for code in taken_linters {
+            for arg in &args.passthrough_args {
+                linter_args.push((code.clone(), arg.clone()));
+            }
+        }
+    }
 
-    let enable_spinners = args.verbose == 0 && args.output == RenderOpt::Default;
+    let mut linters = get_linters_from_config(
+        all_linters,
+        skipped_linters.clone(),
+        taken_linters.clone(),
+        &config_path,
+    )?;
+    add_linter_args(&mut linters, &linter_args)?;
+    if args.sandbox {
+        for linter in &mut linters {
+            linter.sandbox = true;
+        }
+    }
+    for linter in &mut linters {
+        linter.fail_on = profile.fail_on;
+    }
+    if args.downgrade_flaky {
+        let flaky_linters = find_flaky_linters(&persistent_data_store.past_linter_fingerprints()?);
+        for linter in &mut linters {
+            if flaky_linters.contains_key(&linter.code) {
+                debug!("Downgrading flaky linter {}", linter.code);
+                linter.flaky = true;
+            }
+        }
+    }
+
+    // Figure out why the linters that were filtered out won't run, so that we
+    // can report it after linting.
+    let skip_reasons = (args.why_skipped || args.verbose > 0).then(|| {
+        lint_runner_config
+            .linters
+            .iter()
+            .filter(|config| !linters.iter().any(|linter| linter.code == config.code))
+            .map(|config| {
+                let reason = if matches!(cmd, SubCommand::Format { .. }) && !config.is_formatter {
+                    SkipReason::NotFormatter
+                } else if cli_skipped_linters
+                    .as_ref()
+                    .is_some_and(|skipped| skipped.contains(&config.code))
+                {
+                    SkipReason::Skipped
+                } else if cli_taken_linters
+                    .as_ref()
+                    .is_some_and(|taken| !taken.contains(&config.code))
+                {
+                    SkipReason::NotTaken
+                } else if skipped_linters
+                    .as_ref()
+                    .is_some_and(|skipped| skipped.contains(&config.code))
+                    || taken_linters
+                        .as_ref()
+                        .is_some_and(|taken| !taken.contains(&config.code))
+                {
+                    SkipReason::NotInProfile
+                } else {
+                    SkipReason::Disabled
+                };
+                (config.code.clone(), reason)
+            })
+            .collect::<Vec<_>>()
+    });
+
+    let progress = args.progress.unwrap_or(if ci.is_some() {
+        ProgressOpt::Plain
+    } else if args.verbose == 0 && output == RenderOpt::Default {
+        ProgressOpt::Auto
+    } else {
+        ProgressOpt::None
+    });
 
-    let revision_opt = if let Some(revision) = args.revision {
+    let revision_opt = if args.staged {
+        RevisionOpt::Staged
+    } else if let Some(revision) = args.revision {
         RevisionOpt::Revision(revision)
     } else if let Some(merge_base_with) = args.merge_base_with {
         RevisionOpt::MergeBaseWith(merge_base_with)
+    } else if let Some(since) = &args.since {
+        RevisionOpt::Since(since.clone())
+    } else if let Some(merge_base_with) = lint_runner_config.merge_base_with.clone() {
+        RevisionOpt::MergeBaseWith(merge_base_with)
     } else {
-        RevisionOpt::Head
+        RevisionOpt::DefaultBranch
     };
 
     let paths_opt = if let Some(paths_file) = args.paths_from {
@@ -222,45 +901,218 @@ fn do_main() -> Result<i32> {
         PathsOpt::PathsFile(path_file)
     } else if let Some(paths_cmd) = args.paths_cmd {
         PathsOpt::PathsCmd(paths_cmd)
+    } else if args.paths_stdin {
+        PathsOpt::PathsStdin {
+            null_delimited: args.null_delimited,
+        }
+    } else if !args.targets.is_empty() {
+        let query = lint_runner_config.target_query.clone().context(
+            "--targets requires `target_query` to be set in the config, \
+             e.g. target_query = ['buck2', 'uquery', 'inputs({{TARGETS}})']",
+        )?;
+        PathsOpt::Targets {
+            targets: args.targets,
+            query,
+            // Unwrap is fine because the config path must be a file.
+            dir: AbsPath::try_from(config_path.parent().unwrap())?,
+        }
     } else if !args.paths.is_empty() {
         PathsOpt::Paths(args.paths)
     } else if args.all_files {
         PathsOpt::AllFiles
+    } else if args.untracked {
+        PathsOpt::AutoWithUntracked
     } else {
         PathsOpt::Auto
     };
 
+    for linter in &mut linters {
+        if linter.uses_placeholder("DATA_DIR") {
+            let dir = persistent_data_store.linter_data_dir(&linter.code)?;
+            linter
+                .placeholders
+                .insert("DATA_DIR".to_string(), dir.display().to_string());
+        }
+    }
+    if linters
+        .iter()
+        .any(|linter| linter.uses_placeholder("REVISION"))
+    {
+        let revision = base_revision(&revision_opt)?;
+        for linter in &mut linters {
+            linter
+                .placeholders
+                .insert("REVISION".to_string(), revision.clone());
+        }
+    }
+
+    let webhook = args
+        .notify_webhook
+        .or_else(|| lint_runner_config.notify_webhook.clone())
+        .map(|url| Webhook {
+            url,
+            report_url: args.report_url,
+        });
+
+    // Fail before linting, rather than once the results are in.
+    if args.metrics_pushgateway.is_some() {
+        offline::ensure_online("--metrics-pushgateway")?;
+    }
+    let metrics = match (args.metrics_file, args.metrics_pushgateway) {
+        (Some(path), _) => Some(MetricsSink::File(path)),
+        (None, Some(url)) => Some(MetricsSink::Pushgateway(url)),
+        (None, None) => None,
+    };
+
+    // `lintrunner format` always applies patches.
+    let apply_patches = args.apply_patches || matches!(cmd, SubCommand::Format { .. });
     let res = match cmd {
-        SubCommand::Init { dry_run } => {
+        SubCommand::Init {
+            dry_run,
+            json,
+            force,
+        } => {
             // Just run initialization commands, don't actually lint.
-            do_init(linters, dry_run, &persistent_data_store, &config_path)
-        }
-        SubCommand::Format => {
-            check_init_changed(&persistent_data_store, &lint_runner_config)?;
-            do_lint(
+            do_init(
                 linters,
-                paths_opt,
-                true, // always apply patches when we use the format command
-                args.output,
-                enable_spinners,
-                revision_opt,
-                args.tee_json,
+                dry_run,
+                force,
+                json,
+                &persistent_data_store,
+                &config_path,
             )
         }
-        SubCommand::Lint => {
-            // Default command is to just lint.
+        SubCommand::Format { check: true, diff } => {
+            check_init_changed(&persistent_data_store, &lint_runner_config)?;
+            check_versions(&linters)?;
+            let files = get_paths(paths_opt, revision_opt, &args.exclude)?;
+            do_format_check(linters, files, progress, jobs, diff, &persistent_data_store)
+        }
+        SubCommand::Lint if args.dry_run => {
+            let files = get_paths(paths_opt, revision_opt, &args.exclude)?;
+            do_lint_dry_run(linters, files)
+        }
+        SubCommand::Format { check: false, .. } | SubCommand::Lint => {
             check_init_changed(&persistent_data_store, &lint_runner_config)?;
+            check_versions(&linters)?;
+            let baseline = if args.only_new {
+                Some(only_new_baseline(
+                    &linters,
+                    &revision_opt,
+                    &persistent_data_store,
+                )?)
+            } else {
+                None
+            };
             do_lint(
                 linters,
                 paths_opt,
-                args.apply_patches,
-                args.output,
-                enable_spinners,
                 revision_opt,
-                args.tee_json,
+                args.exclude,
+                RunOptions {
+                    apply_patches,
+                    fix_filter: CodeFilter::new(args.fix_only.as_deref(), None),
+                    strict_missing: args.strict_missing,
+                    code_filter: CodeFilter::new(
+                        args.only_codes.as_deref(),
+                        args.skip_codes.as_deref(),
+                    ),
+                    progress,
+                    stream: args.stream.then_some(output),
+                    expected_durations: persistent_data_store.linter_durations()?,
+                    record_fingerprints: true,
+                    baseline,
+                    issue_history: Some(persistent_data_store.issue_history()?),
+                    jobs,
+                    sink: None,
+                },
+                ReportOptions {
+                    render_opt: output,
+                    tee_json: tee_json.clone(),
+                    skip_reasons,
+                    webhook,
+                    metrics,
+                    save_patches: args.save_patches.clone(),
+                },
+                &persistent_data_store,
             )
         }
-        SubCommand::Rage { invocation } => do_rage(&persistent_data_store, invocation),
+        SubCommand::Verify => do_verify(&linters, &persistent_data_store),
+        SubCommand::Clean { what } => do_clean(&linters, &persistent_data_store, what),
+        SubCommand::Why => {
+            let paths = match paths_opt {
+                PathsOpt::Paths(paths) => paths,
+                _ => Vec::new(),
+            };
+            do_why(&linters, paths)
+        }
+        SubCommand::Stats { flaky, ages } => do_stats(&persistent_data_store, flaky, ages),
+        SubCommand::SlowFiles { top, batches } => {
+            check_init_changed(&persistent_data_store, &lint_runner_config)?;
+            let files = get_paths(paths_opt, revision_opt, &args.exclude)?;
+            do_slow_files(&linters, files, batches, top)
+        }
+        SubCommand::Query {
+            code,
+            path,
+            after,
+            format,
+        } => {
+            ensure!(
+                args.since.is_none(),
+                "`lintrunner query` takes --after, not --since"
+            );
+            let filter = QueryFilter {
+                codes: code,
+                since: after.as_deref().map(parse_since).transpose()?,
+                paths: path
+                    .iter()
+                    .map(|path| {
+                        glob::Pattern::new(path)
+                            .with_context(|| format!("Invalid --path pattern '{}'", path))
+                    })
+                    .collect::<Result<_>>()?,
+            };
+            do_query(&persistent_data_store, &filter, format)
+        }
+        SubCommand::Rage {
+            invocation,
+            run,
+            after,
+            upload,
+        } => {
+            ensure!(
+                args.since.is_none(),
+                "`lintrunner rage` takes --after, not --since"
+            );
+            let selector = match (invocation, run, after) {
+                (Some(invocation), _, _) => RunSelector::Invocation(invocation),
+                (None, Some(id), _) => RunSelector::Id(id),
+                (None, None, Some(after)) => RunSelector::Since(parse_since(&after)?),
+                (None, None, None) => RunSelector::Any,
+            };
+            let upload_target = upload
+                .map(|target| {
+                    let target = target
+                        .or_else(|| user_config.rage_upload.clone())
+                        .unwrap_or_else(|| "gist".to_string());
+                    UploadTarget::parse(&target)
+                })
+                .transpose()?;
+            do_rage(&persistent_data_store, &selector, upload_target)
+        }
+        SubCommand::TestLinter {
+            code,
+            fixtures,
+            update,
+        } => do_test_linter(&linters, &code, &fixtures, update),
+        SubCommand::NewLinter { .. }
+        | SubCommand::Discover { .. }
+        | SubCommand::Publish { .. }
+        | SubCommand::Apply { .. }
+        | SubCommand::InstallGitAlias { .. } => {
+            unreachable!("handled before loading the config")
+        }
     };
 
     let exit_info = match &res {
@@ -280,6 +1132,30 @@ fn do_main() -> Result<i32> {
     res
 }
 
+/// Translate the arguments of a `git lint` invocation into lintrunner's.
+fn translate_git_args(program: &str, git_args: &[String]) -> Vec<String> {
+    let command = Args::command();
+    let takes_value = |arg: &str| {
+        command.get_arguments().any(|a| {
+            let matches = match arg.strip_prefix("--") {
+                Some(long) => a.get_long() == Some(long),
+                None => arg.len() == 2 && a.get_short() == arg.chars().nth(1),
+            };
+            matches && a.is_takes_value_set()
+        })
+    };
+    let is_subcommand = |arg: &str| command.get_subcommands().any(|cmd| cmd.get_name() == arg);
+    let translator = Translator {
+        takes_value: &takes_value,
+        is_subcommand: &is_subcommand,
+        is_revision: &is_revision,
+        repo_root: get_git_root().ok().map(|root| root.to_path_buf()),
+    };
+    let translated = translator.translate(program, git_args);
+    debug!("Translated git lint args: {:?}", translated);
+    translated
+}
+
 fn main() {
     let code = match do_main() {
         Ok(code) => code,
@@ -0,0 +1,388 @@
+//! Matching files against every linter's patterns at once.
+//!
+//! Checking each file against each pattern of each linter in turn is slow in
+//! big repos: 60 linters with a dozen patterns each, times 200k files, is
+//! over a hundred million glob matches. Instead, all the patterns are compiled
+//! into a single [`RegexSet`], which finds every pattern a path matches in
+//! one pass over the path.
+
+use std::borrow::Cow;
+use std::path::{is_separator, Path, PathBuf};
+
+use anyhow::{Context, Result};
+use regex::RegexSet;
+
+use crate::{
+    linter::Linter,
+    normalize, parallel,
+    path::{path_relative_from, AbsPath},
+};
+
+/// How paths are compared to patterns, for repos checked out on
+/// case-insensitive file systems, or with file names in other scripts.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PatternOptions {
+    /// Ignore case, so that `foo*` matches `Foo.cmake`.
+    pub case_insensitive: bool,
+    /// Compose accented letters first, so that patterns match file names
+    /// however their letters are encoded.
+    pub normalize_unicode: bool,
+}
+
+impl PatternOptions {
+    /// Bring a path, or a pattern, into the form it is compared in.
+    pub fn fold<'a>(&self, s: &'a str) -> Cow<'a, str> {
+        let s = if self.normalize_unicode {
+            normalize::compose(s)
+        } else {
+            Cow::Borrowed(s)
+        };
+        if self.case_insensitive {
+            Cow::Owned(s.to_lowercase())
+        } else {
+            s
+        }
+    }
+}
+
+/// The linters' patterns, compiled.
+pub struct LinterMatcher {
+    /// Patterns are relative to the config directory of their linter, and
+    /// paths are folded with its pattern options, so there is a set for each
+    /// combination of them.
+    groups: Vec<PatternGroup>,
+    num_linters: usize,
+}
+
+struct PatternGroup {
+    config_dir: PathBuf,
+    options: PatternOptions,
+    set: RegexSet,
+    /// Whose each pattern in the set is.
+    owners: Vec<Owner>,
+}
+
+/// The index of a pattern's linter, and whether it is an exclude pattern.
+type Owner = (usize, bool);
+
+impl LinterMatcher {
+    pub fn new(linters: &[Linter]) -> Result<LinterMatcher> {
+        let mut keys: Vec<(&Path, PatternOptions)> = Vec::new();
+        for linter in linters {
+            let key = (linter.get_config_dir(), linter.pattern_options);
+            if !keys.contains(&key) {
+                keys.push(key);
+            }
+        }
+        let groups = keys
+            .into_iter()
+            .map(|(config_dir, options)| {
+                let mut regexes = Vec::new();
+                let mut owners = Vec::new();
+                for (index, linter) in linters.iter().enumerate() {
+                    if linter.get_config_dir() != config_dir || linter.pattern_options != options {
+                        continue;
+                    }
+                    for (patterns, exclude) in [
+                        (&linter.include_patterns, false),
+                        (&linter.exclude_patterns, true),
+                    ] {
+                        for pattern in patterns {
+                            regexes.push(glob_to_regex(pattern.as_str()));
+                            owners.push((index, exclude));
+                        }
+                    }
+                }
+                let set = RegexSet::new(&regexes).context("Failed to compile linter patterns")?;
+                Ok(PatternGroup {
+                    config_dir: config_dir.to_path_buf(),
+                    options,
+                    set,
+                    owners,
+                })
+            })
+            .collect::<Result<_>>()?;
+        Ok(LinterMatcher {
+            groups,
+            num_linters: linters.len(),
+        })
+    }
+
+    /// The files each linter matches, in the order the linters were given.
+    pub fn matches(&self, files: &[AbsPath]) -> Vec<Vec<AbsPath>> {
+        let mut matches = vec![Vec::new(); self.num_linters];
+        for chunk in parallel::map_chunks(files, |chunk| self.matches_in(chunk)) {
+            for (matches, chunk_matches) in matches.iter_mut().zip(chunk) {
+                matches.extend(chunk_matches);
+            }
+        }
+        matches
+    }
+
+    fn matches_in(&self, files: &[AbsPath]) -> Vec<Vec<AbsPath>> {
+        let mut matches = vec![Vec::new(); self.num_linters];
+        let mut included = vec![false; self.num_linters];
+        let mut excluded = vec![false; self.num_linters];
+        for file in files {
+            included.fill(false);
+            excluded.fill(false);
+            for group in &self.groups {
+                let Some(path) = relative_str(&group.config_dir, file) else {
+                    continue;
+                };
+                for index in group.set.matches(&group.options.fold(&path)).iter() {
+                    let (linter, exclude) = group.owners[index];
+                    if exclude {
+                        excluded[linter] = true;
+                    } else {
+                        included[linter] = true;
+                    }
+                }
+            }
+            for (linter, matches) in matches.iter_mut().enumerate() {
+                if included[linter] && !excluded[linter] {
+                    matches.push(file.clone());
+                }
+            }
+        }
+        matches
+    }
+}
+
+fn relative_str(base: &Path, file: &Path) -> Option<String> {
+    path_relative_from(file, base)?.to_str().map(str::to_string)
+}
+
+/// Translate a glob, which must be valid, into a regex that matches the same
+/// paths as [`crate::linter::matches_relative_path`]: `*`, `?` and `[...]`
+/// never match a separator, and `**` matches any number of directories. The
+/// glob must already be folded with the linter's [`PatternOptions`].
+fn glob_to_regex(glob: &str) -> String {
+    let separator = if cfg!(windows) { r"/\\" } else { "/" };
+    let chars: Vec<char> = glob.chars().collect();
+    let mut regex = String::from("(?s)^");
+    let mut i = 0;
+    while i < chars.len() {
+        match chars[i] {
+            '?' => {
+                regex.push_str(&format!("[^{}]", separator));
+                i += 1;
+            }
+            '*' if chars.get(i + 1) == Some(&'*') => {
+                i += 2;
+                if chars.get(i).copied().is_some_and(is_separator) {
+                    // Any number of directories, including none.
+                    i += 1;
+                    regex.push_str(&format!("(?:.*[{}])?", separator));
+                } else {
+                    // A trailing `**` matches everything below.
+                    regex.push_str(".*");
+                }
+            }
+            '*' => {
+                regex.push_str(&format!("[^{}]*", separator));
+                i += 1;
+            }
+            '[' => {
+                let negated = chars.get(i + 1) == Some(&'!');
+                let start = if negated { i + 2 } else { i + 1 };
+                // The first character is always part of the class, so that
+                // `[]]` matches `]`.
+                let end = start
+                    + 1
+                    + chars[start + 1..]
+                        .iter()
+                        .position(|c| *c == ']')
+                        .expect("glob was validated");
+                let class = char_class(&chars[start..end]);
+                if negated {
+                    regex.push_str(&format!("[^{}{}]", class, separator));
+                } else if class.is_empty() {
+                    // Only inverted ranges, which match nothing.
+                    regex.push_str(r"[^\x00-\x{10FFFF}]");
+                } else {
+                    regex.push_str(&format!("[{}&&[^{}]]", class, separator));
+                }
+                i = end + 1;
+            }
+            c => {
+                regex.push_str(&regex::escape(&c.to_string()));
+                i += 1;
+            }
+        }
+    }
+    regex.push('$');
+    regex
+}
+
+/// The inside of a regex character class matching the same characters as the
+/// inside of a glob's `[...]`.
+fn char_class(chars: &[char]) -> String {
+    let escape = |c: char| format!(r"\x{{{:X}}}", c as u32);
+    let mut class = String::new();
+    let mut i = 0;
+    while i < chars.len() {
+        if i + 2 < chars.len() && chars[i + 1] == '-' {
+            // Glob ranges that are backwards match nothing, regex ones are
+            // invalid.
+            if chars[i] <= chars[i + 2] {
+                class.push_str(&format!("{}-{}", escape(chars[i]), escape(chars[i + 2])));
+            }
+            i += 3;
+        } else {
+            class.push_str(&escape(chars[i]));
+            i += 1;
+        }
+    }
+    class
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::linter::matches_relative_path;
+    use glob::Pattern;
+
+    #[test]
+    fn regexes_match_like_globs() -> Result<()> {
+        let globs = [
+            "*.py",
+            "**/*.py",
+            "torch/**",
+            "torch/**/*.py",
+            "**/test_*.py",
+            "torch/?n/*.py",
+            "a/[a-c]*.txt",
+            "a/[!a-c]*.txt",
+            "a/[]x]",
+            "a/[z-a]",
+            "a/[!z-a]",
+            "a/[?]",
+            "a.b+(c)",
+            "**",
+        ];
+        let paths = [
+            "setup.py",
+            "torch/nn.py",
+            "torch/nn/functional.py",
+            "torch/nn/test_module.py",
+            "test_setup.py",
+            "torch",
+            "a/b.txt",
+            "a/d.txt",
+            "a/b/c.txt",
+            "a/]",
+            "a/x",
+            "a/?",
+            "a/z",
+            "a.b+(c)",
+            "aXb+(c)",
+        ];
+        for glob in globs {
+            let pattern = Pattern::new(glob)?;
+            let regex = regex::Regex::new(&glob_to_regex(glob))?;
+            for path in paths {
+                assert_eq!(
+                    regex.is_match(path),
+                    matches_relative_path(
+                        Path::new("/"),
+                        &Path::new("/").join(path),
+                        &pattern,
+                        PatternOptions::default()
+                    ),
+                    "{} against {}",
+                    glob,
+                    path
+                );
+            }
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn paths_are_folded() -> Result<()> {
+        let options = PatternOptions {
+            case_insensitive: true,
+            normalize_unicode: true,
+        };
+        let regex = regex::Regex::new(&glob_to_regex(&options.fold("docs/Café/foo*")))?;
+        let path = "Docs/Cafe\u{301}/Foo.cmake";
+        assert!(regex.is_match(&options.fold(path)));
+        assert!(!regex.is_match(&PatternOptions::default().fold(path)));
+        Ok(())
+    }
+
+    /// Compare against matching each pattern in turn, at the scale of a big
+    /// repo. Run with `cargo test --release -- --ignored --nocapture`.
+    #[test]
+    #[ignore]
+    fn benchmark_against_globs() -> Result<()> {
+        let globs: Vec<String> = (0..60)
+            .flat_map(|linter| {
+                [
+                    format!("dir{}/**/*.py", linter),
+                    format!("**/*.ext{}", linter),
+                    format!("dir{}/sub?/file[0-4]*.cpp", linter % 10),
+                    format!("**/generated{}/**", linter),
+                    format!("dir{}/sub1/**", linter % 7),
+                    "**/third_party/**".to_string(),
+                    "**/*.h".to_string(),
+                    format!("tools/linter{}/*.toml", linter),
+                    format!("test/test_{}*.py", linter),
+                    format!("dir{}/**/BUILD", linter % 13),
+                ]
+            })
+            .collect();
+        let extensions = ["py", "cpp", "h", "txt", "ext3"];
+        let paths: Vec<String> = (0..200_000)
+            .map(|i| {
+                format!(
+                    "dir{}/sub{}/file{}.{}",
+                    i % 100,
+                    i % 9,
+                    i,
+                    extensions[i % extensions.len()]
+                )
+            })
+            .collect();
+        let options = glob::MatchOptions {
+            case_sensitive: true,
+            require_literal_separator: true,
+            require_literal_leading_dot: false,
+        };
+
+        let start = std::time::Instant::now();
+        let patterns = globs
+            .iter()
+            .map(|glob| Pattern::new(glob))
+            .collect::<Result<Vec<_>, _>>()?;
+        let expected: usize = paths
+            .iter()
+            .map(|path| {
+                patterns
+                    .iter()
+                    .filter(|pattern| pattern.matches_with(path, options))
+                    .count()
+            })
+            .sum();
+        let glob_time = start.elapsed();
+
+        let start = std::time::Instant::now();
+        let set = RegexSet::new(globs.iter().map(|glob| glob_to_regex(glob)))?;
+        let actual: usize = paths
+            .iter()
+            .map(|path| set.matches(path).iter().count())
+            .sum();
+        let regex_time = start.elapsed();
+
+        println!(
+            "{} patterns, {} paths: globs {:?}, regex set {:?}",
+            globs.len(),
+            paths.len(),
+            glob_time,
+            regex_time
+        );
+        assert_eq!(actual, expected);
+        Ok(())
+    }
+}
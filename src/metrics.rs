@@ -0,0 +1,188 @@
+//! Run metrics in the Prometheus text format, so lint health can be graphed
+//! over time.
+//!
+//! Metrics are either written to a file, for node_exporter's textfile
+//! collector, or pushed to a Pushgateway.
+
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::io::Write as _;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use anyhow::{bail, Context, Result};
+
+use crate::{lint_message::LintSeverity, publish::http_request_raw, LintResults};
+
+/// Where to send run metrics.
+pub enum MetricsSink {
+    /// A `.prom` file, replaced atomically on each run.
+    File(PathBuf),
+    /// The URL of a Pushgateway. Metrics are pushed under the `lintrunner`
+    /// job.
+    Pushgateway(String),
+}
+
+pub fn export_metrics(sink: &MetricsSink, results: &LintResults, duration: Duration) -> Result<()> {
+    let metrics = render_metrics(results, duration, chrono::Utc::now().timestamp());
+    match sink {
+        MetricsSink::File(path) => write_atomically(path, metrics.as_bytes()),
+        MetricsSink::Pushgateway(url) => {
+            let url = format!("{}/metrics/job/lintrunner", url.trim_end_matches('/'));
+            let (status, response) = http_request_raw(
+                "PUT",
+                &url,
+                &[],
+                "text/plain; version=0.0.4",
+                metrics.as_bytes(),
+            )?;
+            if !(200..300).contains(&status) {
+                bail!(
+                    "Pushing metrics to {} failed with status {}: {}",
+                    url,
+                    status,
+                    response
+                );
+            }
+            Ok(())
+        }
+    }
+}
+
+/// Write to a temporary file first, so the collector never reads a
+/// half-written file.
+fn write_atomically(path: &Path, contents: &[u8]) -> Result<()> {
+    let dir = match path.parent() {
+        Some(parent) if !parent.as_os_str().is_empty() => parent,
+        _ => Path::new("."),
+    };
+    let mut file = tempfile::NamedTempFile::new_in(dir)
+        .with_context(|| format!("Failed to write metrics to '{}'", path.display()))?;
+    file.write_all(contents)?;
+    file.persist(path)
+        .with_context(|| format!("Failed to write metrics to '{}'", path.display()))?;
+    Ok(())
+}
+
+fn render_metrics(results: &LintResults, duration: Duration, timestamp: i64) -> String {
+    let mut out = String::new();
+    let mut metric = |name: &str, help: &str, samples: &[(String, String)]| {
+        let _ = writeln!(out, "# HELP {} {}", name, help);
+        let _ = writeln!(out, "# TYPE {} gauge", name);
+        for (labels, value) in samples {
+            let _ = writeln!(out, "{}{} {}", name, labels, value);
+        }
+    };
+
+    metric(
+        "lintrunner_run_duration_seconds",
+        "How long the lint run took.",
+        &[(String::new(), duration.as_secs_f64().to_string())],
+    );
+    metric(
+        "lintrunner_exit_code",
+        "The exit code of the lint run.",
+        &[(String::new(), results.exit_code().to_string())],
+    );
+    metric(
+        "lintrunner_last_run_timestamp_seconds",
+        "When the lint run finished, in seconds since the epoch.",
+        &[(String::new(), timestamp.to_string())],
+    );
+
+    let durations: BTreeMap<_, _> = results.durations.iter().collect();
+    metric(
+        "lintrunner_linter_duration_seconds",
+        "How long each linter took.",
+        &durations
+            .into_iter()
+            .map(|(code, duration)| {
+                (
+                    labels(&[("linter", code)]),
+                    duration.as_secs_f64().to_string(),
+                )
+            })
+            .collect::<Vec<_>>(),
+    );
+
+    let mut counts: BTreeMap<(&str, LintSeverity), usize> = BTreeMap::new();
+    for lint in results.lints.values().flatten() {
+        *counts.entry((&lint.code, lint.severity)).or_default() += 1;
+    }
+    metric(
+        "lintrunner_issues",
+        "How many lint issues each linter reported, by severity.",
+        &counts
+            .into_iter()
+            .map(|((code, severity), count)| {
+                (
+                    labels(&[
+                        ("linter", code),
+                        ("severity", &severity.label().to_lowercase()),
+                    ]),
+                    count.to_string(),
+                )
+            })
+            .collect::<Vec<_>>(),
+    );
+    out
+}
+
+fn labels(pairs: &[(&str, &str)]) -> String {
+    let pairs = pairs
+        .iter()
+        .map(|(name, value)| {
+            let value = value
+                .replace('\\', "\\\\")
+                .replace('"', "\\\"")
+                .replace('\n', "\\n");
+            format!("{}=\"{}\"", name, value)
+        })
+        .collect::<Vec<_>>();
+    format!("{{{}}}", pairs.join(","))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lint_message::LintMessage;
+
+    #[test]
+    fn metrics_are_rendered() {
+        let lint = |code: &str, severity| LintMessage::for_test(code, severity, "bad");
+        let mut results = LintResults::default();
+        results.lints.insert(
+            None,
+            vec![
+                lint("FLAKE8", LintSeverity::Warning),
+                lint("FLAKE8", LintSeverity::Warning),
+                lint("MYPY", LintSeverity::Advice),
+            ],
+        );
+        results
+            .durations
+            .insert("FLAKE8".to_string(), Duration::from_millis(250));
+
+        let metrics = render_metrics(&results, Duration::from_secs(2), 1700000000);
+        assert_eq!(
+            metrics,
+            "# HELP lintrunner_run_duration_seconds How long the lint run took.\n\
+             # TYPE lintrunner_run_duration_seconds gauge\n\
+             lintrunner_run_duration_seconds 2\n\
+             # HELP lintrunner_exit_code The exit code of the lint run.\n\
+             # TYPE lintrunner_exit_code gauge\n\
+             lintrunner_exit_code 0\n\
+             # HELP lintrunner_last_run_timestamp_seconds When the lint run finished, in seconds since the epoch.\n\
+             # TYPE lintrunner_last_run_timestamp_seconds gauge\n\
+             lintrunner_last_run_timestamp_seconds 1700000000\n\
+             # HELP lintrunner_linter_duration_seconds How long each linter took.\n\
+             # TYPE lintrunner_linter_duration_seconds gauge\n\
+             lintrunner_linter_duration_seconds{linter=\"FLAKE8\"} 0.25\n\
+             # HELP lintrunner_issues How many lint issues each linter reported, by severity.\n\
+             # TYPE lintrunner_issues gauge\n\
+             lintrunner_issues{linter=\"FLAKE8\",severity=\"warning\"} 2\n\
+             lintrunner_issues{linter=\"MYPY\",severity=\"advice\"} 1\n"
+        );
+        assert_eq!(labels(&[("a", "x\"y")]), "{a=\"x\\\"y\"}");
+    }
+}
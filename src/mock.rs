@@ -0,0 +1,225 @@
+//! Record and replay linter subprocesses, for hermetic tests.
+//!
+//! When `LINTRUNNER_MOCK_DIR` is set, each linter invocation is looked up in
+//! that directory by a key derived from the linter's code, its command and
+//! the names and contents of the files it runs on. If a recording exists, it
+//! is replayed instead of running the linter; otherwise the linter is run and
+//! its output recorded. `LINTRUNNER_MOCK_MODE` can be set to `record` (always
+//! run and re-record) or `replay` (fail if a recording is missing).
+
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::path::{path_relative_from, AbsPath};
+
+pub const MOCK_DIR_ENV: &str = "LINTRUNNER_MOCK_DIR";
+pub const MOCK_MODE_ENV: &str = "LINTRUNNER_MOCK_MODE";
+
+/// Stands in for the config directory in recorded output, so that recordings
+/// can be replayed from a different checkout.
+const CONFIG_DIR_PLACEHOLDER: &str = "{{CONFIG_DIR}}";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MockMode {
+    /// Replay if a recording exists, otherwise run and record.
+    Auto,
+    /// Always run and record, overwriting existing recordings.
+    Record,
+    /// Only replay; a missing recording is an error.
+    Replay,
+}
+
+/// The result of running a linter command.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CommandOutput {
+    /// None if the process was killed by a signal.
+    pub exit_code: Option<i32>,
+    pub stdout: Vec<u8>,
+    pub stderr: Vec<u8>,
+}
+
+/// A recorded linter invocation, as stored on disk.
+#[derive(Serialize, Deserialize)]
+struct Recording {
+    code: String,
+    command: Vec<String>,
+    paths: Vec<String>,
+    exit_code: Option<i32>,
+    stdout: String,
+    stderr: String,
+}
+
+pub struct Mock {
+    dir: PathBuf,
+    mode: MockMode,
+}
+
+impl Mock {
+    pub fn new(dir: PathBuf, mode: MockMode) -> Mock {
+        Mock { dir, mode }
+    }
+
+    /// Read the mock configuration from the environment. Returns None if
+    /// mocking is disabled.
+    pub fn from_env() -> Result<Option<Mock>> {
+        let dir = match std::env::var_os(MOCK_DIR_ENV) {
+            Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+            _ => return Ok(None),
+        };
+        let mode = match std::env::var(MOCK_MODE_ENV).as_deref() {
+            Err(_) | Ok("") | Ok("auto") => MockMode::Auto,
+            Ok("record") => MockMode::Record,
+            Ok("replay") => MockMode::Replay,
+            Ok(other) => bail!(
+                "Invalid {} '{}', expected 'auto', 'record' or 'replay'",
+                MOCK_MODE_ENV,
+                other
+            ),
+        };
+        Ok(Some(Mock::new(dir, mode)))
+    }
+
+    fn recording_path(
+        &self,
+        code: &str,
+        command: &[String],
+        files: &[AbsPath],
+        config_dir: &Path,
+    ) -> Result<(PathBuf, Vec<String>)> {
+        let mut paths: Vec<(String, &AbsPath)> = files
+            .iter()
+            .map(|file| {
+                let relative =
+                    path_relative_from(file, config_dir).unwrap_or_else(|| file.to_path_buf());
+                (relative.to_string_lossy().replace('\\', "/"), file)
+            })
+            .collect();
+        paths.sort();
+
+        let mut hasher = blake3::Hasher::new();
+        hasher.update(code.as_bytes());
+        for arg in command {
+            hasher.update(b"\0");
+            hasher.update(arg.as_bytes());
+        }
+        for (name, file) in &paths {
+            let contents = std::fs::read(file)
+                .with_context(|| format!("Failed to read '{}'", file.display()))?;
+            hasher.update(b"\0");
+            hasher.update(name.as_bytes());
+            hasher.update(blake3::hash(&contents).as_bytes());
+        }
+        let key = hasher.finalize().to_string();
+        let path = self.dir.join(format!("{}-{}.json", code, &key[..16]));
+        Ok((path, paths.into_iter().map(|(name, _)| name).collect()))
+    }
+
+    /// Replay the recorded output of linter `code` running `command` on
+    /// `files`, or run it with `run` (and record the result).
+    pub fn run<E>(
+        &self,
+        code: &str,
+        command: &[String],
+        files: &[AbsPath],
+        config_dir: &Path,
+        run: impl FnOnce() -> Result<CommandOutput, E>,
+    ) -> Result<Result<CommandOutput, E>> {
+        let (path, paths) = self.recording_path(code, command, files, config_dir)?;
+        let config_dir = config_dir.to_string_lossy();
+
+        if self.mode != MockMode::Record && path.exists() {
+            let recording: Recording = serde_json::from_str(&std::fs::read_to_string(&path)?)
+                .with_context(|| format!("Failed to parse recording '{}'", path.display()))?;
+            log::debug!("Replaying linter {} from {}", code, path.display());
+            let restore = |s: &str| s.replace(CONFIG_DIR_PLACEHOLDER, &config_dir).into_bytes();
+            return Ok(Ok(CommandOutput {
+                exit_code: recording.exit_code,
+                stdout: restore(&recording.stdout),
+                stderr: restore(&recording.stderr),
+            }));
+        }
+        if self.mode == MockMode::Replay {
+            bail!(
+                "No recording for linter '{}' at '{}'. Run with {}=record to create it.",
+                code,
+                path.display(),
+                MOCK_MODE_ENV
+            );
+        }
+
+        let output = match run() {
+            Ok(output) => output,
+            // Failures to even start the linter aren't recorded.
+            Err(err) => return Ok(Err(err)),
+        };
+        let strip = |bytes: &[u8]| {
+            String::from_utf8_lossy(bytes).replace(config_dir.as_ref(), CONFIG_DIR_PLACEHOLDER)
+        };
+        let recording = Recording {
+            code: code.to_string(),
+            command: command.to_vec(),
+            paths,
+            exit_code: output.exit_code,
+            stdout: strip(&output.stdout),
+            stderr: strip(&output.stderr),
+        };
+        std::fs::create_dir_all(&self.dir)?;
+        std::fs::write(&path, serde_json::to_string_pretty(&recording)?)
+            .with_context(|| format!("Failed to write recording '{}'", path.display()))?;
+        log::debug!("Recorded linter {} to {}", code, path.display());
+        Ok(Ok(output))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn runs_are_recorded_and_replayed() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let config_dir = dir.path().join("repo");
+        std::fs::create_dir(&config_dir)?;
+        let file = config_dir.join("a.py");
+        std::fs::write(&file, "x = 1\n")?;
+        let files = vec![AbsPath::try_from(file.as_path())?];
+        let command = vec!["flake8".to_string()];
+        let output = CommandOutput {
+            exit_code: Some(0),
+            stdout: format!("{{\"path\": \"{}\"}}\n", file.display()).into_bytes(),
+            stderr: Vec::new(),
+        };
+
+        let mock = Mock::new(dir.path().join("mocks"), MockMode::Auto);
+        let run = |expected: Option<&CommandOutput>| {
+            mock.run("FLAKE8", &command, &files, &config_dir, || {
+                Ok::<_, ()>(expected.expect("should have replayed").clone())
+            })
+            .unwrap()
+            .unwrap()
+        };
+        assert_eq!(run(Some(&output)), output);
+        // The second run is replayed.
+        assert_eq!(run(None), output);
+
+        // Paths in the recording are relative to the config dir.
+        let recording = std::fs::read_dir(dir.path().join("mocks"))?
+            .next()
+            .unwrap()?
+            .path();
+        let recording = std::fs::read_to_string(recording)?;
+        assert!(recording.contains("{{CONFIG_DIR}}/a.py"));
+
+        // Changing the file's contents invalidates the recording.
+        std::fs::write(&file, "x = 2\n")?;
+        let replay = Mock::new(dir.path().join("mocks"), MockMode::Replay);
+        assert!(replay
+            .run("FLAKE8", &command, &files, &config_dir, || Ok::<_, ()>(
+                output.clone()
+            ))
+            .is_err());
+        Ok(())
+    }
+}
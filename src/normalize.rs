@@ -0,0 +1,197 @@
+//! Unicode normalization of paths, so that patterns match file names however
+//! their accented letters are encoded.
+//!
+//! macOS stores file names decomposed (NFD): `é` is an `e` followed by a
+//! combining acute accent, while the patterns in a config are usually typed
+//! composed (NFC), as a single `é`, and don't match. Composing the paths (and
+//! patterns) fixes that. Only Latin letters with diacritics are composed,
+//! which covers the file names seen in practice without pulling in the full
+//! Unicode tables.
+
+use std::borrow::Cow;
+
+/// Compose the Latin letters with diacritics in `s`. Letters that are
+/// already composed, and all other characters, are left as they are.
+pub fn compose(s: &str) -> Cow<'_, str> {
+    if s.is_ascii() || !s.chars().any(is_combining) {
+        return Cow::Borrowed(s);
+    }
+    let mut composed = String::with_capacity(s.len());
+    let mut last: Option<char> = None;
+    for c in s.chars() {
+        if let Some(base) = last {
+            if let Some(combined) = combine(base, c) {
+                // The result may take another mark, e.g. `ü` and a macron.
+                last = Some(combined);
+                continue;
+            }
+            composed.push(base);
+        }
+        last = Some(c);
+    }
+    composed.extend(last);
+    Cow::Owned(composed)
+}
+
+fn is_combining(c: char) -> bool {
+    ('\u{300}'..='\u{36F}').contains(&c)
+}
+
+fn combine(base: char, mark: char) -> Option<char> {
+    if !is_combining(mark) {
+        return None;
+    }
+    COMPOSITIONS
+        .binary_search_by(|(b, m, _)| (*b, *m).cmp(&(base, mark)))
+        .ok()
+        .map(|i| COMPOSITIONS[i].2)
+}
+
+/// A letter and a combining mark, and the letter they compose to, for the
+/// Latin blocks (U+00C0 to U+024F and U+1E00 to U+1EFF). Sorted, for binary
+/// search. Generated with Python's `unicodedata`.
+#[rustfmt::skip]
+const COMPOSITIONS: &[(char, char, char)] = &[
+    ('A', '\u{300}', 'À'), ('A', '\u{301}', 'Á'), ('A', '\u{302}', 'Â'), ('A', '\u{303}', 'Ã'),
+    ('A', '\u{304}', 'Ā'), ('A', '\u{306}', 'Ă'), ('A', '\u{307}', 'Ȧ'), ('A', '\u{308}', 'Ä'),
+    ('A', '\u{309}', 'Ả'), ('A', '\u{30A}', 'Å'), ('A', '\u{30C}', 'Ǎ'), ('A', '\u{30F}', 'Ȁ'),
+    ('A', '\u{311}', 'Ȃ'), ('A', '\u{323}', 'Ạ'), ('A', '\u{325}', 'Ḁ'), ('A', '\u{328}', 'Ą'),
+    ('B', '\u{307}', 'Ḃ'), ('B', '\u{323}', 'Ḅ'), ('B', '\u{331}', 'Ḇ'), ('C', '\u{301}', 'Ć'),
+    ('C', '\u{302}', 'Ĉ'), ('C', '\u{307}', 'Ċ'), ('C', '\u{30C}', 'Č'), ('C', '\u{327}', 'Ç'),
+    ('D', '\u{307}', 'Ḋ'), ('D', '\u{30C}', 'Ď'), ('D', '\u{323}', 'Ḍ'), ('D', '\u{327}', 'Ḑ'),
+    ('D', '\u{32D}', 'Ḓ'), ('D', '\u{331}', 'Ḏ'), ('E', '\u{300}', 'È'), ('E', '\u{301}', 'É'),
+    ('E', '\u{302}', 'Ê'), ('E', '\u{303}', 'Ẽ'), ('E', '\u{304}', 'Ē'), ('E', '\u{306}', 'Ĕ'),
+    ('E', '\u{307}', 'Ė'), ('E', '\u{308}', 'Ë'), ('E', '\u{309}', 'Ẻ'), ('E', '\u{30C}', 'Ě'),
+    ('E', '\u{30F}', 'Ȅ'), ('E', '\u{311}', 'Ȇ'), ('E', '\u{323}', 'Ẹ'), ('E', '\u{327}', 'Ȩ'),
+    ('E', '\u{328}', 'Ę'), ('E', '\u{32D}', 'Ḙ'), ('E', '\u{330}', 'Ḛ'), ('F', '\u{307}', 'Ḟ'),
+    ('G', '\u{301}', 'Ǵ'), ('G', '\u{302}', 'Ĝ'), ('G', '\u{304}', 'Ḡ'), ('G', '\u{306}', 'Ğ'),
+    ('G', '\u{307}', 'Ġ'), ('G', '\u{30C}', 'Ǧ'), ('G', '\u{327}', 'Ģ'), ('H', '\u{302}', 'Ĥ'),
+    ('H', '\u{307}', 'Ḣ'), ('H', '\u{308}', 'Ḧ'), ('H', '\u{30C}', 'Ȟ'), ('H', '\u{323}', 'Ḥ'),
+    ('H', '\u{327}', 'Ḩ'), ('H', '\u{32E}', 'Ḫ'), ('I', '\u{300}', 'Ì'), ('I', '\u{301}', 'Í'),
+    ('I', '\u{302}', 'Î'), ('I', '\u{303}', 'Ĩ'), ('I', '\u{304}', 'Ī'), ('I', '\u{306}', 'Ĭ'),
+    ('I', '\u{307}', 'İ'), ('I', '\u{308}', 'Ï'), ('I', '\u{309}', 'Ỉ'), ('I', '\u{30C}', 'Ǐ'),
+    ('I', '\u{30F}', 'Ȉ'), ('I', '\u{311}', 'Ȋ'), ('I', '\u{323}', 'Ị'), ('I', '\u{328}', 'Į'),
+    ('I', '\u{330}', 'Ḭ'), ('J', '\u{302}', 'Ĵ'), ('K', '\u{301}', 'Ḱ'), ('K', '\u{30C}', 'Ǩ'),
+    ('K', '\u{323}', 'Ḳ'), ('K', '\u{327}', 'Ķ'), ('K', '\u{331}', 'Ḵ'), ('L', '\u{301}', 'Ĺ'),
+    ('L', '\u{30C}', 'Ľ'), ('L', '\u{323}', 'Ḷ'), ('L', '\u{327}', 'Ļ'), ('L', '\u{32D}', 'Ḽ'),
+    ('L', '\u{331}', 'Ḻ'), ('M', '\u{301}', 'Ḿ'), ('M', '\u{307}', 'Ṁ'), ('M', '\u{323}', 'Ṃ'),
+    ('N', '\u{300}', 'Ǹ'), ('N', '\u{301}', 'Ń'), ('N', '\u{303}', 'Ñ'), ('N', '\u{307}', 'Ṅ'),
+    ('N', '\u{30C}', 'Ň'), ('N', '\u{323}', 'Ṇ'), ('N', '\u{327}', 'Ņ'), ('N', '\u{32D}', 'Ṋ'),
+    ('N', '\u{331}', 'Ṉ'), ('O', '\u{300}', 'Ò'), ('O', '\u{301}', 'Ó'), ('O', '\u{302}', 'Ô'),
+    ('O', '\u{303}', 'Õ'), ('O', '\u{304}', 'Ō'), ('O', '\u{306}', 'Ŏ'), ('O', '\u{307}', 'Ȯ'),
+    ('O', '\u{308}', 'Ö'), ('O', '\u{309}', 'Ỏ'), ('O', '\u{30B}', 'Ő'), ('O', '\u{30C}', 'Ǒ'),
+    ('O', '\u{30F}', 'Ȍ'), ('O', '\u{311}', 'Ȏ'), ('O', '\u{31B}', 'Ơ'), ('O', '\u{323}', 'Ọ'),
+    ('O', '\u{328}', 'Ǫ'), ('P', '\u{301}', 'Ṕ'), ('P', '\u{307}', 'Ṗ'), ('R', '\u{301}', 'Ŕ'),
+    ('R', '\u{307}', 'Ṙ'), ('R', '\u{30C}', 'Ř'), ('R', '\u{30F}', 'Ȑ'), ('R', '\u{311}', 'Ȓ'),
+    ('R', '\u{323}', 'Ṛ'), ('R', '\u{327}', 'Ŗ'), ('R', '\u{331}', 'Ṟ'), ('S', '\u{301}', 'Ś'),
+    ('S', '\u{302}', 'Ŝ'), ('S', '\u{307}', 'Ṡ'), ('S', '\u{30C}', 'Š'), ('S', '\u{323}', 'Ṣ'),
+    ('S', '\u{326}', 'Ș'), ('S', '\u{327}', 'Ş'), ('T', '\u{307}', 'Ṫ'), ('T', '\u{30C}', 'Ť'),
+    ('T', '\u{323}', 'Ṭ'), ('T', '\u{326}', 'Ț'), ('T', '\u{327}', 'Ţ'), ('T', '\u{32D}', 'Ṱ'),
+    ('T', '\u{331}', 'Ṯ'), ('U', '\u{300}', 'Ù'), ('U', '\u{301}', 'Ú'), ('U', '\u{302}', 'Û'),
+    ('U', '\u{303}', 'Ũ'), ('U', '\u{304}', 'Ū'), ('U', '\u{306}', 'Ŭ'), ('U', '\u{308}', 'Ü'),
+    ('U', '\u{309}', 'Ủ'), ('U', '\u{30A}', 'Ů'), ('U', '\u{30B}', 'Ű'), ('U', '\u{30C}', 'Ǔ'),
+    ('U', '\u{30F}', 'Ȕ'), ('U', '\u{311}', 'Ȗ'), ('U', '\u{31B}', 'Ư'), ('U', '\u{323}', 'Ụ'),
+    ('U', '\u{324}', 'Ṳ'), ('U', '\u{328}', 'Ų'), ('U', '\u{32D}', 'Ṷ'), ('U', '\u{330}', 'Ṵ'),
+    ('V', '\u{303}', 'Ṽ'), ('V', '\u{323}', 'Ṿ'), ('W', '\u{300}', 'Ẁ'), ('W', '\u{301}', 'Ẃ'),
+    ('W', '\u{302}', 'Ŵ'), ('W', '\u{307}', 'Ẇ'), ('W', '\u{308}', 'Ẅ'), ('W', '\u{323}', 'Ẉ'),
+    ('X', '\u{307}', 'Ẋ'), ('X', '\u{308}', 'Ẍ'), ('Y', '\u{300}', 'Ỳ'), ('Y', '\u{301}', 'Ý'),
+    ('Y', '\u{302}', 'Ŷ'), ('Y', '\u{303}', 'Ỹ'), ('Y', '\u{304}', 'Ȳ'), ('Y', '\u{307}', 'Ẏ'),
+    ('Y', '\u{308}', 'Ÿ'), ('Y', '\u{309}', 'Ỷ'), ('Y', '\u{323}', 'Ỵ'), ('Z', '\u{301}', 'Ź'),
+    ('Z', '\u{302}', 'Ẑ'), ('Z', '\u{307}', 'Ż'), ('Z', '\u{30C}', 'Ž'), ('Z', '\u{323}', 'Ẓ'),
+    ('Z', '\u{331}', 'Ẕ'), ('a', '\u{300}', 'à'), ('a', '\u{301}', 'á'), ('a', '\u{302}', 'â'),
+    ('a', '\u{303}', 'ã'), ('a', '\u{304}', 'ā'), ('a', '\u{306}', 'ă'), ('a', '\u{307}', 'ȧ'),
+    ('a', '\u{308}', 'ä'), ('a', '\u{309}', 'ả'), ('a', '\u{30A}', 'å'), ('a', '\u{30C}', 'ǎ'),
+    ('a', '\u{30F}', 'ȁ'), ('a', '\u{311}', 'ȃ'), ('a', '\u{323}', 'ạ'), ('a', '\u{325}', 'ḁ'),
+    ('a', '\u{328}', 'ą'), ('b', '\u{307}', 'ḃ'), ('b', '\u{323}', 'ḅ'), ('b', '\u{331}', 'ḇ'),
+    ('c', '\u{301}', 'ć'), ('c', '\u{302}', 'ĉ'), ('c', '\u{307}', 'ċ'), ('c', '\u{30C}', 'č'),
+    ('c', '\u{327}', 'ç'), ('d', '\u{307}', 'ḋ'), ('d', '\u{30C}', 'ď'), ('d', '\u{323}', 'ḍ'),
+    ('d', '\u{327}', 'ḑ'), ('d', '\u{32D}', 'ḓ'), ('d', '\u{331}', 'ḏ'), ('e', '\u{300}', 'è'),
+    ('e', '\u{301}', 'é'), ('e', '\u{302}', 'ê'), ('e', '\u{303}', 'ẽ'), ('e', '\u{304}', 'ē'),
+    ('e', '\u{306}', 'ĕ'), ('e', '\u{307}', 'ė'), ('e', '\u{308}', 'ë'), ('e', '\u{309}', 'ẻ'),
+    ('e', '\u{30C}', 'ě'), ('e', '\u{30F}', 'ȅ'), ('e', '\u{311}', 'ȇ'), ('e', '\u{323}', 'ẹ'),
+    ('e', '\u{327}', 'ȩ'), ('e', '\u{328}', 'ę'), ('e', '\u{32D}', 'ḙ'), ('e', '\u{330}', 'ḛ'),
+    ('f', '\u{307}', 'ḟ'), ('g', '\u{301}', 'ǵ'), ('g', '\u{302}', 'ĝ'), ('g', '\u{304}', 'ḡ'),
+    ('g', '\u{306}', 'ğ'), ('g', '\u{307}', 'ġ'), ('g', '\u{30C}', 'ǧ'), ('g', '\u{327}', 'ģ'),
+    ('h', '\u{302}', 'ĥ'), ('h', '\u{307}', 'ḣ'), ('h', '\u{308}', 'ḧ'), ('h', '\u{30C}', 'ȟ'),
+    ('h', '\u{323}', 'ḥ'), ('h', '\u{327}', 'ḩ'), ('h', '\u{32E}', 'ḫ'), ('h', '\u{331}', 'ẖ'),
+    ('i', '\u{300}', 'ì'), ('i', '\u{301}', 'í'), ('i', '\u{302}', 'î'), ('i', '\u{303}', 'ĩ'),
+    ('i', '\u{304}', 'ī'), ('i', '\u{306}', 'ĭ'), ('i', '\u{308}', 'ï'), ('i', '\u{309}', 'ỉ'),
+    ('i', '\u{30C}', 'ǐ'), ('i', '\u{30F}', 'ȉ'), ('i', '\u{311}', 'ȋ'), ('i', '\u{323}', 'ị'),
+    ('i', '\u{328}', 'į'), ('i', '\u{330}', 'ḭ'), ('j', '\u{302}', 'ĵ'), ('j', '\u{30C}', 'ǰ'),
+    ('k', '\u{301}', 'ḱ'), ('k', '\u{30C}', 'ǩ'), ('k', '\u{323}', 'ḳ'), ('k', '\u{327}', 'ķ'),
+    ('k', '\u{331}', 'ḵ'), ('l', '\u{301}', 'ĺ'), ('l', '\u{30C}', 'ľ'), ('l', '\u{323}', 'ḷ'),
+    ('l', '\u{327}', 'ļ'), ('l', '\u{32D}', 'ḽ'), ('l', '\u{331}', 'ḻ'), ('m', '\u{301}', 'ḿ'),
+    ('m', '\u{307}', 'ṁ'), ('m', '\u{323}', 'ṃ'), ('n', '\u{300}', 'ǹ'), ('n', '\u{301}', 'ń'),
+    ('n', '\u{303}', 'ñ'), ('n', '\u{307}', 'ṅ'), ('n', '\u{30C}', 'ň'), ('n', '\u{323}', 'ṇ'),
+    ('n', '\u{327}', 'ņ'), ('n', '\u{32D}', 'ṋ'), ('n', '\u{331}', 'ṉ'), ('o', '\u{300}', 'ò'),
+    ('o', '\u{301}', 'ó'), ('o', '\u{302}', 'ô'), ('o', '\u{303}', 'õ'), ('o', '\u{304}', 'ō'),
+    ('o', '\u{306}', 'ŏ'), ('o', '\u{307}', 'ȯ'), ('o', '\u{308}', 'ö'), ('o', '\u{309}', 'ỏ'),
+    ('o', '\u{30B}', 'ő'), ('o', '\u{30C}', 'ǒ'), ('o', '\u{30F}', 'ȍ'), ('o', '\u{311}', 'ȏ'),
+    ('o', '\u{31B}', 'ơ'), ('o', '\u{323}', 'ọ'), ('o', '\u{328}', 'ǫ'), ('p', '\u{301}', 'ṕ'),
+    ('p', '\u{307}', 'ṗ'), ('r', '\u{301}', 'ŕ'), ('r', '\u{307}', 'ṙ'), ('r', '\u{30C}', 'ř'),
+    ('r', '\u{30F}', 'ȑ'), ('r', '\u{311}', 'ȓ'), ('r', '\u{323}', 'ṛ'), ('r', '\u{327}', 'ŗ'),
+    ('r', '\u{331}', 'ṟ'), ('s', '\u{301}', 'ś'), ('s', '\u{302}', 'ŝ'), ('s', '\u{307}', 'ṡ'),
+    ('s', '\u{30C}', 'š'), ('s', '\u{323}', 'ṣ'), ('s', '\u{326}', 'ș'), ('s', '\u{327}', 'ş'),
+    ('t', '\u{307}', 'ṫ'), ('t', '\u{308}', 'ẗ'), ('t', '\u{30C}', 'ť'), ('t', '\u{323}', 'ṭ'),
+    ('t', '\u{326}', 'ț'), ('t', '\u{327}', 'ţ'), ('t', '\u{32D}', 'ṱ'), ('t', '\u{331}', 'ṯ'),
+    ('u', '\u{300}', 'ù'), ('u', '\u{301}', 'ú'), ('u', '\u{302}', 'û'), ('u', '\u{303}', 'ũ'),
+    ('u', '\u{304}', 'ū'), ('u', '\u{306}', 'ŭ'), ('u', '\u{308}', 'ü'), ('u', '\u{309}', 'ủ'),
+    ('u', '\u{30A}', 'ů'), ('u', '\u{30B}', 'ű'), ('u', '\u{30C}', 'ǔ'), ('u', '\u{30F}', 'ȕ'),
+    ('u', '\u{311}', 'ȗ'), ('u', '\u{31B}', 'ư'), ('u', '\u{323}', 'ụ'), ('u', '\u{324}', 'ṳ'),
+    ('u', '\u{328}', 'ų'), ('u', '\u{32D}', 'ṷ'), ('u', '\u{330}', 'ṵ'), ('v', '\u{303}', 'ṽ'),
+    ('v', '\u{323}', 'ṿ'), ('w', '\u{300}', 'ẁ'), ('w', '\u{301}', 'ẃ'), ('w', '\u{302}', 'ŵ'),
+    ('w', '\u{307}', 'ẇ'), ('w', '\u{308}', 'ẅ'), ('w', '\u{30A}', 'ẘ'), ('w', '\u{323}', 'ẉ'),
+    ('x', '\u{307}', 'ẋ'), ('x', '\u{308}', 'ẍ'), ('y', '\u{300}', 'ỳ'), ('y', '\u{301}', 'ý'),
+    ('y', '\u{302}', 'ŷ'), ('y', '\u{303}', 'ỹ'), ('y', '\u{304}', 'ȳ'), ('y', '\u{307}', 'ẏ'),
+    ('y', '\u{308}', 'ÿ'), ('y', '\u{309}', 'ỷ'), ('y', '\u{30A}', 'ẙ'), ('y', '\u{323}', 'ỵ'),
+    ('z', '\u{301}', 'ź'), ('z', '\u{302}', 'ẑ'), ('z', '\u{307}', 'ż'), ('z', '\u{30C}', 'ž'),
+    ('z', '\u{323}', 'ẓ'), ('z', '\u{331}', 'ẕ'), ('Â', '\u{300}', 'Ầ'), ('Â', '\u{301}', 'Ấ'),
+    ('Â', '\u{303}', 'Ẫ'), ('Â', '\u{309}', 'Ẩ'), ('Ä', '\u{304}', 'Ǟ'), ('Å', '\u{301}', 'Ǻ'),
+    ('Æ', '\u{301}', 'Ǽ'), ('Æ', '\u{304}', 'Ǣ'), ('Ç', '\u{301}', 'Ḉ'), ('Ê', '\u{300}', 'Ề'),
+    ('Ê', '\u{301}', 'Ế'), ('Ê', '\u{303}', 'Ễ'), ('Ê', '\u{309}', 'Ể'), ('Ï', '\u{301}', 'Ḯ'),
+    ('Ô', '\u{300}', 'Ồ'), ('Ô', '\u{301}', 'Ố'), ('Ô', '\u{303}', 'Ỗ'), ('Ô', '\u{309}', 'Ổ'),
+    ('Õ', '\u{301}', 'Ṍ'), ('Õ', '\u{304}', 'Ȭ'), ('Õ', '\u{308}', 'Ṏ'), ('Ö', '\u{304}', 'Ȫ'),
+    ('Ø', '\u{301}', 'Ǿ'), ('Ü', '\u{300}', 'Ǜ'), ('Ü', '\u{301}', 'Ǘ'), ('Ü', '\u{304}', 'Ǖ'),
+    ('Ü', '\u{30C}', 'Ǚ'), ('â', '\u{300}', 'ầ'), ('â', '\u{301}', 'ấ'), ('â', '\u{303}', 'ẫ'),
+    ('â', '\u{309}', 'ẩ'), ('ä', '\u{304}', 'ǟ'), ('å', '\u{301}', 'ǻ'), ('æ', '\u{301}', 'ǽ'),
+    ('æ', '\u{304}', 'ǣ'), ('ç', '\u{301}', 'ḉ'), ('ê', '\u{300}', 'ề'), ('ê', '\u{301}', 'ế'),
+    ('ê', '\u{303}', 'ễ'), ('ê', '\u{309}', 'ể'), ('ï', '\u{301}', 'ḯ'), ('ô', '\u{300}', 'ồ'),
+    ('ô', '\u{301}', 'ố'), ('ô', '\u{303}', 'ỗ'), ('ô', '\u{309}', 'ổ'), ('õ', '\u{301}', 'ṍ'),
+    ('õ', '\u{304}', 'ȭ'), ('õ', '\u{308}', 'ṏ'), ('ö', '\u{304}', 'ȫ'), ('ø', '\u{301}', 'ǿ'),
+    ('ü', '\u{300}', 'ǜ'), ('ü', '\u{301}', 'ǘ'), ('ü', '\u{304}', 'ǖ'), ('ü', '\u{30C}', 'ǚ'),
+    ('Ă', '\u{300}', 'Ằ'), ('Ă', '\u{301}', 'Ắ'), ('Ă', '\u{303}', 'Ẵ'), ('Ă', '\u{309}', 'Ẳ'),
+    ('ă', '\u{300}', 'ằ'), ('ă', '\u{301}', 'ắ'), ('ă', '\u{303}', 'ẵ'), ('ă', '\u{309}', 'ẳ'),
+    ('Ē', '\u{300}', 'Ḕ'), ('Ē', '\u{301}', 'Ḗ'), ('ē', '\u{300}', 'ḕ'), ('ē', '\u{301}', 'ḗ'),
+    ('Ō', '\u{300}', 'Ṑ'), ('Ō', '\u{301}', 'Ṓ'), ('ō', '\u{300}', 'ṑ'), ('ō', '\u{301}', 'ṓ'),
+    ('Ś', '\u{307}', 'Ṥ'), ('ś', '\u{307}', 'ṥ'), ('Š', '\u{307}', 'Ṧ'), ('š', '\u{307}', 'ṧ'),
+    ('Ũ', '\u{301}', 'Ṹ'), ('ũ', '\u{301}', 'ṹ'), ('Ū', '\u{308}', 'Ṻ'), ('ū', '\u{308}', 'ṻ'),
+    ('ſ', '\u{307}', 'ẛ'), ('Ơ', '\u{300}', 'Ờ'), ('Ơ', '\u{301}', 'Ớ'), ('Ơ', '\u{303}', 'Ỡ'),
+    ('Ơ', '\u{309}', 'Ở'), ('Ơ', '\u{323}', 'Ợ'), ('ơ', '\u{300}', 'ờ'), ('ơ', '\u{301}', 'ớ'),
+    ('ơ', '\u{303}', 'ỡ'), ('ơ', '\u{309}', 'ở'), ('ơ', '\u{323}', 'ợ'), ('Ư', '\u{300}', 'Ừ'),
+    ('Ư', '\u{301}', 'Ứ'), ('Ư', '\u{303}', 'Ữ'), ('Ư', '\u{309}', 'Ử'), ('Ư', '\u{323}', 'Ự'),
+    ('ư', '\u{300}', 'ừ'), ('ư', '\u{301}', 'ứ'), ('ư', '\u{303}', 'ữ'), ('ư', '\u{309}', 'ử'),
+    ('ư', '\u{323}', 'ự'), ('Ʒ', '\u{30C}', 'Ǯ'), ('Ǫ', '\u{304}', 'Ǭ'), ('ǫ', '\u{304}', 'ǭ'),
+    ('Ȧ', '\u{304}', 'Ǡ'), ('ȧ', '\u{304}', 'ǡ'), ('Ȩ', '\u{306}', 'Ḝ'), ('ȩ', '\u{306}', 'ḝ'),
+    ('Ȯ', '\u{304}', 'Ȱ'), ('ȯ', '\u{304}', 'ȱ'), ('ʒ', '\u{30C}', 'ǯ'), ('Ḷ', '\u{304}', 'Ḹ'),
+    ('ḷ', '\u{304}', 'ḹ'), ('Ṛ', '\u{304}', 'Ṝ'), ('ṛ', '\u{304}', 'ṝ'), ('Ṣ', '\u{307}', 'Ṩ'),
+    ('ṣ', '\u{307}', 'ṩ'), ('Ạ', '\u{302}', 'Ậ'), ('Ạ', '\u{306}', 'Ặ'), ('ạ', '\u{302}', 'ậ'),
+    ('ạ', '\u{306}', 'ặ'), ('Ẹ', '\u{302}', 'Ệ'), ('ẹ', '\u{302}', 'ệ'), ('Ọ', '\u{302}', 'Ộ'),
+    ('ọ', '\u{302}', 'ộ'),
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decomposed_letters_are_composed() {
+        assert_eq!(compose("cafe\u{301}/Mu\u{308}ller.txt"), "café/Müller.txt");
+        // Two marks, composed one after the other.
+        assert_eq!(compose("u\u{308}\u{304}"), "ǖ");
+        assert_eq!(compose("Vie\u{323}\u{302}t"), "Việt");
+        assert!(matches!(compose("plain/path.py"), Cow::Borrowed(_)));
+        assert!(matches!(compose("café"), Cow::Borrowed(_)));
+        // A mark with nothing to combine with is kept.
+        assert_eq!(compose("\u{301}x"), "\u{301}x");
+    }
+}
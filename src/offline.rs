@@ -0,0 +1,82 @@
+//! `--offline`: running without any network access.
+//!
+//! On airgapped CI runners, anything that reaches for the network would hang
+//! until it times out. With `--offline`, features that need the network fail
+//! right away with an error saying so, containers are only used if they were
+//! pulled before, and init commands are told with `LINTRUNNER_OFFLINE=1`, so
+//! that they can install from a local cache instead.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use anyhow::{bail, Result};
+
+/// The environment variable init commands get in offline mode.
+pub const OFFLINE_ENV_VAR: &str = "LINTRUNNER_OFFLINE";
+
+static OFFLINE: AtomicBool = AtomicBool::new(false);
+
+/// Forbid network access for the rest of the run.
+pub fn set_offline(offline: bool) {
+    OFFLINE.store(offline, Ordering::SeqCst);
+}
+
+/// Whether network access is forbidden.
+pub fn is_offline() -> bool {
+    OFFLINE.load(Ordering::SeqCst)
+}
+
+/// Fail if network access is forbidden. `what` says what needed it, e.g.
+/// "Publishing results".
+pub fn ensure_online(what: &str) -> Result<()> {
+    if is_offline() {
+        bail!("{} needs network access, which --offline forbids", what);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::convert::TryFrom;
+
+    use super::*;
+    use crate::{
+        lint_config::{get_linters_from_config, LintRunnerConfig},
+        path::AbsPath,
+    };
+
+    #[test]
+    fn offline_mode_is_enforced() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let config_path = dir.path().join(".lintrunner.toml");
+        let config = "\
+            [[linter]]
+            code = 'SH'
+            include_patterns = ['**']
+            command = ['sh', '-c', 'true']
+            init_command = ['sh', '-c', 'test \"$LINTRUNNER_OFFLINE\" = 1', '{{DRYRUN}}']
+            ";
+        std::fs::write(&config_path, config)?;
+        let config = LintRunnerConfig::new_from_string(config)?;
+        let linters = get_linters_from_config(
+            &config.linters,
+            None,
+            None,
+            &AbsPath::try_from(config_path.as_path())?,
+        )?;
+
+        set_offline(true);
+        let online = ensure_online("Publishing results");
+        let init = linters[0].init(false);
+        set_offline(false);
+
+        let err = online.unwrap_err().to_string();
+        assert!(
+            err.contains("Publishing results needs network access"),
+            "{}",
+            err
+        );
+        init?;
+        assert!(linters[0].init(false).is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,190 @@
+//! Spreading the work done on every file before linting over all cores.
+//!
+//! Before any linter runs, every file to lint is canonicalized, checked for
+//! being a git-lfs pointer and matched against the linters' patterns. That is
+//! a few syscalls and some CPU per file, which adds up to seconds in a
+//! monorepo with `--all-files`, and is the bulk of short runs.
+
+use std::path::{Path, PathBuf};
+use std::sync::{Condvar, Mutex};
+
+use anyhow::Result;
+
+use crate::path::AbsPath;
+
+/// Below this many items per thread, starting the threads isn't worth it.
+const MIN_CHUNK_SIZE: usize = 512;
+
+fn num_threads() -> usize {
+    std::thread::available_parallelism().map_or(1, |n| n.get())
+}
+
+/// Apply `f` to consecutive chunks of `items`, one chunk per core, and return
+/// the results in order.
+pub fn map_chunks<T: Sync, U: Send>(items: &[T], f: impl Fn(&[T]) -> U + Sync) -> Vec<U> {
+    map_chunks_on(num_threads(), items, f)
+}
+
+fn map_chunks_on<T: Sync, U: Send>(
+    threads: usize,
+    items: &[T],
+    f: impl Fn(&[T]) -> U + Sync,
+) -> Vec<U> {
+    let chunk_size = items.len().div_ceil(threads).max(MIN_CHUNK_SIZE);
+    if items.len() <= chunk_size {
+        return vec![f(items)];
+    }
+    std::thread::scope(|scope| {
+        let handles: Vec<_> = items
+            .chunks(chunk_size)
+            .map(|chunk| scope.spawn(|| f(chunk)))
+            .collect();
+        handles
+            .into_iter()
+            // Unwrap is fine because a panic in `f` should propagate.
+            .map(|handle| handle.join().unwrap())
+            .collect()
+    })
+}
+
+/// Like `items.iter().filter_map(f)`, on all cores.
+pub fn filter_map<T: Sync, U: Send>(items: &[T], f: impl Fn(&T) -> Option<U> + Sync) -> Vec<U> {
+    map_chunks(items, |chunk| {
+        chunk.iter().filter_map(&f).collect::<Vec<_>>()
+    })
+    .into_iter()
+    .flatten()
+    .collect()
+}
+
+/// Find all files below `dir`, skipping `.git` directories, sorted.
+/// Symlinked files are included, but symlinked directories are not followed,
+/// so a link to an ancestor can't make the walk loop.
+///
+/// Each thread takes a directory to read from a shared stack and pushes the
+/// subdirectories it finds back onto it, so threads that run out of work take
+/// over parts of the tree other threads found.
+pub fn walk_dir(dir: &Path) -> Result<Vec<AbsPath>> {
+    walk_dir_on(num_threads(), dir)
+}
+
+fn walk_dir_on(threads: usize, dir: &Path) -> Result<Vec<AbsPath>> {
+    struct Queue {
+        dirs: Vec<PathBuf>,
+        /// How many directories are being read. The walk is done once no
+        /// directory is left to read and none is being read.
+        reading: usize,
+        failed: bool,
+    }
+    let queue = Mutex::new(Queue {
+        dirs: vec![dir.to_path_buf()],
+        reading: 0,
+        failed: false,
+    });
+    let changed = Condvar::new();
+
+    let walk = || -> Result<Vec<AbsPath>> {
+        let mut files = Vec::new();
+        loop {
+            let dir = {
+                let mut queue = queue.lock().unwrap();
+                loop {
+                    if queue.failed {
+                        return Ok(files);
+                    }
+                    if let Some(dir) = queue.dirs.pop() {
+                        queue.reading += 1;
+                        break dir;
+                    }
+                    if queue.reading == 0 {
+                        return Ok(files);
+                    }
+                    queue = changed.wait(queue).unwrap();
+                }
+            };
+            let mut subdirs = Vec::new();
+            let result = read_dir(&dir, &mut subdirs, &mut files);
+            let mut queue = queue.lock().unwrap();
+            queue.reading -= 1;
+            queue.failed |= result.is_err();
+            queue.dirs.extend(subdirs);
+            changed.notify_all();
+            result?;
+        }
+    };
+
+    let results: Vec<_> = std::thread::scope(|scope| {
+        let handles: Vec<_> = (0..threads).map(|_| scope.spawn(walk)).collect();
+        handles
+            .into_iter()
+            .map(|handle| handle.join().unwrap())
+            .collect()
+    });
+    let mut files = Vec::new();
+    for result in results {
+        files.extend(result?);
+    }
+    files.sort();
+    Ok(files)
+}
+
+fn read_dir(dir: &Path, subdirs: &mut Vec<PathBuf>, files: &mut Vec<AbsPath>) -> Result<()> {
+    for entry in std::fs::read_dir(dir)? {
+        let entry = entry?;
+        let file_type = entry.file_type()?;
+        let path = entry.path();
+        if file_type.is_dir() {
+            if path.file_name() != Some(std::ffi::OsStr::new(".git")) {
+                subdirs.push(path);
+            }
+        } else if file_type.is_file() || (file_type.is_symlink() && path.is_file()) {
+            files.push(AbsPath::try_from(path)?);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn results_keep_their_order() {
+        let numbers: Vec<usize> = (0..10_000).collect();
+        let chunks = map_chunks_on(4, &numbers, |chunk| chunk.to_vec());
+        assert_eq!(chunks.len(), 4);
+        assert_eq!(chunks.concat(), numbers);
+        assert_eq!(map_chunks_on(4, &[1, 2, 3], |chunk| chunk.len()), vec![3]);
+
+        let odd = filter_map(&numbers, |n| (n % 2 == 1).then_some(n * 10));
+        assert_eq!(odd.len(), 5_000);
+        assert!(odd.windows(2).all(|pair| pair[0] < pair[1]));
+    }
+
+    #[test]
+    fn all_files_are_walked() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let mut expected = Vec::new();
+        for i in 0..20 {
+            let subdir = dir.path().join(format!("d{}", i)).join("nested");
+            std::fs::create_dir_all(&subdir)?;
+            for path in [subdir.join("a.txt"), subdir.with_file_name("b.txt")] {
+                std::fs::write(&path, "")?;
+                expected.push(AbsPath::try_from(path)?);
+            }
+        }
+        std::fs::create_dir(dir.path().join(".git"))?;
+        std::fs::write(dir.path().join(".git/HEAD"), "")?;
+        #[cfg(unix)]
+        {
+            let nested = dir.path().join("d0/nested");
+            std::os::unix::fs::symlink(dir.path(), nested.join("loop"))?;
+            std::os::unix::fs::symlink(nested.join("a.txt"), nested.join("link.txt"))?;
+            expected.push(AbsPath::try_from(nested.join("link.txt"))?);
+        }
+        expected.sort();
+        assert_eq!(walk_dir_on(4, dir.path())?, expected);
+        assert_eq!(walk_dir_on(1, dir.path())?, expected);
+        Ok(())
+    }
+}
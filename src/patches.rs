@@ -0,0 +1,269 @@
+//! Saving the fixes linters propose, to apply them later.
+//!
+//! `--save-patches <dir>` writes a unified diff for each file a linter
+//! proposed a fix for, mirroring the file's path relative to the
+//! root of the repo (e.g. `<dir>/src/foo.py.patch`). The patches are in the
+//! format `git apply` takes, and `lintrunner apply <dir>` applies them all.
+//! `lintrunner apply` also takes lint messages written by `--output json` or
+//! `--tee-json`, and writes their fixes directly.
+
+use std::{
+    collections::{HashMap, HashSet},
+    convert::TryFrom,
+    io::Write,
+    path::{Component, Path, PathBuf},
+    process::Command,
+};
+
+use anyhow::{bail, ensure, Context, Result};
+use console::{style, Term};
+use log::debug;
+use similar::TextDiff;
+
+use crate::{
+    git::get_git_root,
+    lint_message::{merge_fixes, LintMessage},
+    parallel::walk_dir,
+    path::{path_relative_from, AbsPath},
+    publish::read_lint_messages,
+};
+
+const PATCH_EXTENSION: &str = "patch";
+
+/// Fail early if patches can't be saved to `dir`, so that a long lint run
+/// isn't wasted. Like `--tee-json`, this never overwrites earlier results.
+pub fn check_patch_dir(dir: &Path) -> Result<()> {
+    if dir.exists() {
+        let is_empty = std::fs::read_dir(dir)
+            .with_context(|| format!("Couldn't read --save-patches dir '{}'", dir.display()))?
+            .next()
+            .is_none();
+        ensure!(
+            is_empty,
+            "--save-patches dir '{}' already exists and isn't empty",
+            dir.display()
+        );
+    }
+    Ok(())
+}
+
+/// The directory patch paths are relative to: the root of the repo, or the
+/// current directory outside of one.
+pub fn patch_root() -> Result<PathBuf> {
+    match get_git_root() {
+        Ok(root) => Ok(root.to_path_buf()),
+        Err(_) => Ok(std::env::current_dir()?),
+    }
+}
+
+/// Write a patch for every lint in `lints` with a fix to `dir`, with
+/// paths relative to `root`. Returns how many patches were written.
+pub fn save_patches<'a>(
+    lints: impl IntoIterator<Item = &'a LintMessage>,
+    root: &Path,
+    dir: &Path,
+) -> Result<usize> {
+    let mut patches: HashMap<PathBuf, String> = HashMap::new();
+    for fix in merge_fixes(lints)? {
+        let abs_path = AbsPath::try_from(&fix.path)?;
+        let relative = path_relative_from(&abs_path, root)
+            .filter(|relative| {
+                relative
+                    .components()
+                    .all(|component| matches!(component, Component::Normal(_)))
+            })
+            .with_context(|| {
+                format!(
+                    "Can't save a patch for '{}', which is outside of '{}'",
+                    abs_path.display(),
+                    root.display()
+                )
+            })?;
+        ensure!(
+            !patches.contains_key(&relative),
+            "Two different linters proposed changes for the same file: {}",
+            relative.display()
+        );
+        // Patches use forward slashes, so that they apply anywhere.
+        let name = relative.to_string_lossy().replace('\\', "/");
+        let patch = TextDiff::from_lines(&fix.original, &fix.replacement)
+            .unified_diff()
+            .header(&format!("a/{}", name), &format!("b/{}", name))
+            .to_string();
+        patches.insert(relative, patch);
+    }
+
+    for (relative, patch) in &patches {
+        let mut patch_path = dir.join(relative).into_os_string();
+        patch_path.push(format!(".{}", PATCH_EXTENSION));
+        let patch_path = PathBuf::from(patch_path);
+        // Unwrap is fine because the patch path is inside `dir`.
+        std::fs::create_dir_all(patch_path.parent().unwrap())?;
+        debug!("Writing patch to {}", patch_path.display());
+        std::fs::write(&patch_path, patch)
+            .with_context(|| format!("Failed to write patch '{}'", patch_path.display()))?;
+    }
+    Ok(patches.len())
+}
+
+/// Write the new contents of each file in `fixes`, all or nothing: every file
+/// is first written to a temporary file next to it, and they are only moved
+/// into place once all of them were written.
+pub fn write_fixes(fixes: &[(AbsPath, String)]) -> Result<()> {
+    let mut staged = Vec::new();
+    for (path, contents) in fixes {
+        let failed = || {
+            format!(
+                "Failed to apply fix to '{}', nothing was changed",
+                path.display()
+            )
+        };
+        // Unwrap is fine because absolute paths to files have a parent.
+        let mut file =
+            tempfile::NamedTempFile::new_in(path.parent().unwrap()).with_context(failed)?;
+        file.write_all(contents.as_bytes()).with_context(failed)?;
+        let permissions = std::fs::metadata(path).with_context(failed)?.permissions();
+        std::fs::set_permissions(file.path(), permissions).with_context(failed)?;
+        staged.push((file, path));
+    }
+    for (file, path) in staged {
+        file.persist(path)
+            .with_context(|| format!("Failed to apply fix to '{}'", path.display()))?;
+    }
+    Ok(())
+}
+
+/// Apply patches saved with `--save-patches` (if `source` is a directory) or
+/// the fixes in lint messages written as JSON. Nothing is changed
+/// unless everything applies cleanly.
+pub fn do_apply(source: &str) -> Result<i32> {
+    let applied = if Path::new(source).is_dir() {
+        apply_patch_dir(Path::new(source))?
+    } else {
+        apply_json(source)?
+    };
+    Term::stdout().write_line(&format!(
+        "{} Applied fixes to {} file{}.",
+        style("ok").green(),
+        applied,
+        if applied == 1 { "" } else { "s" }
+    ))?;
+    Ok(0)
+}
+
+fn apply_patch_dir(dir: &Path) -> Result<usize> {
+    let mut patches: Vec<AbsPath> = walk_dir(dir)?
+        .into_iter()
+        .filter(|path| path.extension().is_some_and(|ext| ext == PATCH_EXTENSION))
+        .collect();
+    patches.sort();
+    if patches.is_empty() {
+        return Ok(0);
+    }
+
+    let root = get_git_root().context("Applying patches only works in a git checkout")?;
+    for check in [true, false] {
+        let output = Command::new("git")
+            .arg("apply")
+            .args(check.then_some("--check"))
+            .args(patches.iter().map(|patch| patch.as_os_str()))
+            .current_dir(&root)
+            .output()
+            .context("Failed to run git apply")?;
+        if !output.status.success() {
+            bail!(
+                "The patches in '{}' don't apply cleanly, nothing was changed:\n{}",
+                dir.display(),
+                String::from_utf8_lossy(&output.stderr).trim()
+            );
+        }
+    }
+    Ok(patches.len())
+}
+
+fn apply_json(path: &str) -> Result<usize> {
+    let lints = read_lint_messages(path)?;
+    let mut replacements: Vec<(AbsPath, String)> = Vec::new();
+    let mut seen = HashSet::new();
+    let mut stale = Vec::new();
+    for fix in merge_fixes(&lints)? {
+        let abs_path = AbsPath::try_from(&fix.path)
+            .with_context(|| format!("Failed to find file to fix: '{}'", fix.path))?;
+        ensure!(
+            seen.insert(abs_path.clone()),
+            "Two different linters proposed changes for the same file: {}",
+            abs_path.display()
+        );
+        if std::fs::read_to_string(&abs_path)? != fix.original {
+            stale.push(fix.path);
+            continue;
+        }
+        replacements.push((abs_path, fix.replacement));
+    }
+    if !stale.is_empty() {
+        bail!(
+            "These files changed since the fixes were proposed, nothing was changed:\n  {}",
+            stale.join("\n  ")
+        );
+    }
+    write_fixes(&replacements)?;
+    Ok(replacements.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lint_message::LintSeverity;
+
+    #[test]
+    fn patches_are_written_per_file() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let file = dir.path().join("src").join("foo.py");
+        std::fs::create_dir_all(file.parent().unwrap())?;
+        std::fs::write(&file, "a = 1 \nb = 2\n")?;
+        let lint = LintMessage {
+            path: Some(file.display().to_string()),
+            line: Some(1),
+            original: Some("a = 1 \nb = 2\n".to_string()),
+            replacement: Some("a = 1\nb = 2\n".to_string()),
+            ..LintMessage::for_test("WS", LintSeverity::Warning, "trailing whitespace")
+        };
+
+        let patch_dir = dir.path().join("patches");
+        check_patch_dir(&patch_dir)?;
+        assert_eq!(save_patches([&lint], dir.path(), &patch_dir)?, 1);
+        let patches = walk_dir(&patch_dir)?;
+        assert_eq!(patches.len(), 1);
+        let patch = std::fs::read_to_string(&patches[0])?;
+        assert!(patches[0].ends_with("foo.py.patch"));
+        assert!(patch.contains("-a = 1 \n+a = 1\n"));
+        assert!(check_patch_dir(&patch_dir).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn fixes_are_written_all_or_nothing() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let header = dir.path().join("foo.h");
+        std::fs::write(&header, "int old_name();\n")?;
+        let header = AbsPath::try_from(header.as_path())?;
+        let source = dir.path().join("foo.c");
+        std::fs::write(&source, "old_name();\n")?;
+        let fixes = vec![
+            (header.clone(), "int new_name();\n".to_string()),
+            (
+                AbsPath::try_from(source.as_path())?,
+                "new_name();\n".to_string(),
+            ),
+        ];
+
+        // A fix that can't be written leaves the other files alone.
+        std::fs::remove_file(&source)?;
+        assert!(write_fixes(&fixes).is_err());
+        assert_eq!(std::fs::read_to_string(&header)?, "int old_name();\n");
+
+        write_fixes(&fixes[..1])?;
+        assert_eq!(std::fs::read_to_string(&header)?, "int new_name();\n");
+        Ok(())
+    }
+}
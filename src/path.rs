@@ -1,5 +1,7 @@
 use anyhow::Result;
+use log::debug;
 use std::{
+    collections::{HashMap, HashSet},
     convert::TryFrom,
     fmt,
     ops::Deref,
@@ -141,3 +143,81 @@ pub fn get_display_path(path: &str, current_dir: &Path) -> String {
         Err(_) => path.to_string(),
     }
 }
+
+/// Drop paths that name the same file as an earlier one, but spelled with a
+/// different case, as happens when both `Foo.py` and `foo.py` are passed on a
+/// case-insensitive file system. Canonicalizing already takes care of `.`,
+/// `..`, repeated separators and symlinks, so only paths that are equal
+/// ignoring case are checked.
+pub fn dedup_case_aliases(files: Vec<AbsPath>) -> Vec<AbsPath> {
+    let mut by_folded: HashMap<String, Vec<usize>> = HashMap::new();
+    for (i, file) in files.iter().enumerate() {
+        by_folded
+            .entry(file.to_string_lossy().to_lowercase())
+            .or_default()
+            .push(i);
+    }
+    let mut aliases = HashSet::new();
+    for indices in by_folded.values().filter(|indices| indices.len() > 1) {
+        let mut seen = Vec::new();
+        for &i in indices {
+            match file_id(&files[i]) {
+                Some(id) if seen.contains(&id) => {
+                    debug!(
+                        "Skipping {}, which is another name for a file to lint",
+                        files[i].display()
+                    );
+                    aliases.insert(i);
+                }
+                Some(id) => seen.push(id),
+                None => {}
+            }
+        }
+    }
+    files
+        .into_iter()
+        .enumerate()
+        .filter(|(i, _)| !aliases.contains(i))
+        .map(|(_, file)| file)
+        .collect()
+}
+
+#[cfg(unix)]
+fn file_id(path: &Path) -> Option<(u64, u64)> {
+    use std::os::unix::fs::MetadataExt;
+    let metadata = std::fs::metadata(path).ok()?;
+    Some((metadata.dev(), metadata.ino()))
+}
+
+/// On Windows, canonicalizing already gives paths the case they have on disk.
+#[cfg(not(unix))]
+fn file_id(_path: &Path) -> Option<(u64, u64)> {
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(unix)]
+    #[test]
+    fn case_aliases_are_dropped() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        for name in ["foo.py", "bar.py", "Bar.py"] {
+            std::fs::write(dir.path().join(name), name)?;
+        }
+        // What a case-insensitive file system does, as far as we can tell.
+        std::fs::hard_link(dir.path().join("foo.py"), dir.path().join("Foo.py"))?;
+        let mut files = ["Bar.py", "Foo.py", "bar.py", "foo.py"]
+            .iter()
+            .map(|name| AbsPath::try_from(dir.path().join(name)))
+            .collect::<Result<Vec<_>>>()?;
+        files.sort();
+        let names: Vec<_> = dedup_case_aliases(files)
+            .iter()
+            .map(|file| file.file_name().unwrap().to_string_lossy().to_string())
+            .collect();
+        assert_eq!(names, ["Bar.py", "Foo.py", "bar.py"]);
+        Ok(())
+    }
+}
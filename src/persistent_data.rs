@@ -4,22 +4,67 @@
 //! This data will be placed in a platform specific location (unless overridden
 //! by the user). To distinguish between different `.lintrunner.toml` configs,
 //! we hash the absolute path to the config and include that as part of the
-//! directory structure for persistent data.
+//! directory structure for persistent data. The key can also include the
+//! branch, or be given explicitly (see [`DataKey`]), so that checkouts that
+//! share a config path (e.g. bazel-style output roots that symlink to it)
+//! don't clobber each other's data.
+//!
+//! The layout of a config's data directory is versioned. When lintrunner
+//! opens a directory written by an older version, it migrates the data in
+//! place, so that upgrading doesn't lose init state or run history.
 
-use anyhow::{anyhow, bail, Context, Result};
+use anyhow::{anyhow, bail, ensure, Context, Result};
 use directories::ProjectDirs;
 use log::debug;
 use serde::{Deserialize, Serialize};
 use std::{
+    collections::{BTreeMap, HashMap},
     fmt::Write,
     path::{Path, PathBuf},
+    time::Duration,
 };
 
-use crate::path::AbsPath;
+use crate::{
+    baseline::RunLints, clean::CleanTarget, flaky::LinterFingerprint, git::get_branch,
+    issue_history::IssueHistory, path::AbsPath, query::StoredResults, verify::InitState,
+};
 
 const CONFIG_DATA_NAME: &str = ".lintrunner.toml";
 const RUNS_DIR_NAME: &str = "runs";
+const LINTERS_DIR_NAME: &str = "linters";
+const LINTER_DURATIONS_NAME: &str = "linter_durations.json";
+const LINTER_FINGERPRINTS_NAME: &str = "linter_fingerprints.json";
+const LINTS_NAME: &str = "lints.json";
+const INIT_STATE_NAME: &str = "init_state.json";
+const ISSUE_HISTORY_NAME: &str = "issue_history.json";
 const MAX_RUNS_TO_STORE: usize = 10;
+const RESULTS_DIR_NAME: &str = "results";
+/// Stored results are only used by `lintrunner query`, so keep many more of
+/// them than of runs.
+const MAX_RESULTS_TO_STORE: usize = 500;
+const DATA_VERSION_NAME: &str = "data_version.json";
+
+/// The version of the layout of a config's data directory. Bump it, and add
+/// a migration to [`MIGRATIONS`], whenever a change to the layout or to the
+/// format of a file in it would make existing data unreadable.
+pub const DATA_VERSION: u32 = 1;
+
+/// A migration of a data directory from one version to the next.
+type Migration = fn(&Path) -> Result<()>;
+
+/// `MIGRATIONS[v]` migrates a data directory from version `v` to `v + 1`.
+const MIGRATIONS: [Migration; DATA_VERSION as usize] = [
+    // Data from before versioning already has the layout of version 1.
+    |_| Ok(()),
+];
+
+/// What `data_version.json` holds.
+#[derive(Serialize, Deserialize)]
+struct DataVersion {
+    version: u32,
+    /// The version of lintrunner that last wrote the data.
+    written_by: String,
+}
 
 /// Single way to interact with persistent data for a given run of lintrunner.
 /// This is scoped to a single .lintrunner.toml config.
@@ -49,19 +94,110 @@ impl RunInfo {
         let args = blake3::hash(self.args.join("_").as_bytes()).to_string();
         self.timestamp.clone().replace(':', "-").replace('+', "_") + "_" + &args
     }
+
+    /// The identifier of the run, as in [`PersistentDataStore::run_id`].
+    pub fn id(&self) -> String {
+        self.dir_name()
+    }
+}
+
+/// Something the data directory of a config can be keyed on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ArgEnum)]
+pub enum DataKeyPart {
+    /// The absolute path to the config.
+    ConfigPath,
+    /// The branch that is checked out, or `HEAD` if none is.
+    Branch,
+}
+
+/// Which data directory a config's data goes to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DataKey {
+    /// A directory for each combination of these parts.
+    Parts(Vec<DataKeyPart>),
+    /// A directory with this name, e.g. from `--data-key`.
+    Explicit(String),
+}
+
+impl Default for DataKey {
+    fn default() -> Self {
+        DataKey::Parts(vec![DataKeyPart::ConfigPath])
+    }
+}
+
+impl DataKey {
+    /// The name of the data directory for the config at `config_path`.
+    fn dir_name(&self, config_path: &AbsPath) -> Result<String> {
+        let parts = match self {
+            DataKey::Explicit(key) => {
+                ensure!(
+                    !key.is_empty()
+                        && key != "."
+                        && key != ".."
+                        && !key.contains(std::path::is_separator),
+                    "Invalid data key '{}': it must be usable as a directory name",
+                    key
+                );
+                return Ok(key.clone());
+            }
+            DataKey::Parts(parts) => parts,
+        };
+        let mut key = Vec::new();
+        for part in parts {
+            match part {
+                DataKeyPart::ConfigPath => key.push(config_path.to_string_lossy().to_string()),
+                DataKeyPart::Branch => key.push(format!(
+                    "branch:{}",
+                    get_branch()
+                        .context("Data can only be keyed on the branch in a git checkout")?
+                )),
+            }
+        }
+        // With only the config path, this is the same as before keys could
+        // have other parts, so existing data is still found.
+        Ok(blake3::hash(key.join("\0").as_bytes()).to_string())
+    }
 }
 
 impl PersistentDataStore {
-    pub fn new(config_path: &AbsPath, cur_run_info: RunInfo) -> Result<PersistentDataStore> {
+    /// Create a data store for the config at `config_path`. Data is stored
+    /// under `data_path` if provided, otherwise in the platform-specific
+    /// application data directory.
+    pub fn new(
+        config_path: &AbsPath,
+        data_path: Option<&Path>,
+        cur_run_info: RunInfo,
+    ) -> Result<PersistentDataStore> {
+        PersistentDataStore::with_key(config_path, data_path, &DataKey::default(), cur_run_info)
+    }
+
+    /// Like [`PersistentDataStore::new`], with the config's data directory
+    /// picked by `key`.
+    pub fn with_key(
+        config_path: &AbsPath,
+        data_path: Option<&Path>,
+        key: &DataKey,
+        cur_run_info: RunInfo,
+    ) -> Result<PersistentDataStore> {
         // Retrieve the lintrunner-wide data directory.
-        let project_dirs = ProjectDirs::from("", "", "lintrunner");
-        let project_dirs =
-            project_dirs.ok_or_else(|| anyhow!("Could not find project directories"))?;
-        let project_data_dir = project_dirs.data_dir();
+        let project_data_dir = match data_path {
+            Some(data_path) => data_path.to_path_buf(),
+            None => {
+                let project_dirs = ProjectDirs::from("", "", "lintrunner");
+                let project_dirs =
+                    project_dirs.ok_or_else(|| anyhow!("Could not find project directories"))?;
+                project_dirs.data_dir().to_path_buf()
+            }
+        };
 
         // Now compute one specific to this lintrunner config.
-        let config_path_hash = blake3::hash(config_path.to_string_lossy().as_bytes()).to_string();
-        let config_data_dir = project_data_dir.join(config_path_hash);
+        let config_data_dir = project_data_dir.join(key.dir_name(config_path)?);
+        if config_data_dir.exists() {
+            migrate(&config_data_dir, &MIGRATIONS)?;
+        } else {
+            std::fs::create_dir_all(&config_data_dir)?;
+            write_data_version(&config_data_dir, DATA_VERSION)?;
+        }
 
         // Create the runs dir as well.
         let runs_dir = config_data_dir.join(RUNS_DIR_NAME);
@@ -97,6 +233,24 @@ impl PersistentDataStore {
         Ok(())
     }
 
+    pub fn run_info(&self) -> &RunInfo {
+        &self.cur_run_info
+    }
+
+    /// A unique identifier for the current run.
+    pub fn run_id(&self) -> String {
+        self.cur_run_info.dir_name()
+    }
+
+    /// A directory where `code` can keep data between runs, substituted for
+    /// `{{DATA_DIR}}` in its commands.
+    pub fn linter_data_dir(&self, code: &str) -> Result<PathBuf> {
+        let dir = self.data_dir.join(LINTERS_DIR_NAME).join(code);
+        std::fs::create_dir_all(&dir)
+            .with_context(|| format!("Failed to create '{}'", dir.display()))?;
+        Ok(dir)
+    }
+
     pub fn log_file(&self) -> PathBuf {
         self.runs_dir
             .join(self.cur_run_info.dir_name())
@@ -206,6 +360,11 @@ impl PersistentDataStore {
 
         // Skip the first one as it is the current run.
         for dir in run_dirs.into_iter() {
+            // Runs that failed before they got going only have a log.
+            if !dir.join("run_info.json").exists() || !dir.join("exit_info.json").exists() {
+                debug!("Skipping incomplete run in {}", dir.display());
+                continue;
+            }
             debug!("Reading run info from {}", dir.display());
 
             let run_info: RunInfo =
@@ -217,6 +376,62 @@ impl PersistentDataStore {
         Ok(ret)
     }
 
+    /// Record the fingerprints of the linters that ran in this run.
+    pub fn write_linter_fingerprints(
+        &self,
+        fingerprints: &HashMap<String, LinterFingerprint>,
+    ) -> Result<()> {
+        let path = self
+            .runs_dir
+            .join(self.cur_run_info.dir_name())
+            .join(LINTER_FINGERPRINTS_NAME);
+        debug!("Writing linter fingerprints to {}", path.display());
+        std::fs::write(path, serde_json::to_string_pretty(fingerprints)?)?;
+        Ok(())
+    }
+
+    /// The linter fingerprints recorded by past runs, most recent first.
+    /// Runs that didn't lint anything are left out.
+    pub fn past_linter_fingerprints(&self) -> Result<Vec<HashMap<String, LinterFingerprint>>> {
+        let mut ret = Vec::new();
+        for dir in self.past_run_dirs()? {
+            let path = dir.join(LINTER_FINGERPRINTS_NAME);
+            if !path.exists() {
+                continue;
+            }
+            let fingerprints = serde_json::from_str(&std::fs::read_to_string(&path)?)
+                .with_context(|| format!("deserializing {}", path.display()))?;
+            ret.push(fingerprints);
+        }
+        Ok(ret)
+    }
+
+    /// Record the fingerprints of the issues found in this run.
+    pub fn write_run_lints(&self, run_lints: &RunLints) -> Result<()> {
+        let path = self
+            .runs_dir
+            .join(self.cur_run_info.dir_name())
+            .join(LINTS_NAME);
+        debug!("Writing lint fingerprints to {}", path.display());
+        std::fs::write(path, serde_json::to_string(run_lints)?)?;
+        Ok(())
+    }
+
+    /// The issue fingerprints recorded by past runs, most recent first.
+    pub fn past_run_lints(&self) -> Result<Vec<RunLints>> {
+        let mut ret = Vec::new();
+        for dir in self.past_run_dirs()? {
+            let path = dir.join(LINTS_NAME);
+            if !path.exists() {
+                continue;
+            }
+            let run_lints = serde_json::from_str(&std::fs::read_to_string(&path)?)
+                .with_context(|| format!("deserializing {}", path.display()))?;
+            ret.push(run_lints);
+        }
+        Ok(ret)
+    }
+
     pub fn last_init(&self) -> Result<Option<String>> {
         debug!(
             "Checking data file '{}/{}' to see if config has changed",
@@ -245,16 +460,248 @@ impl PersistentDataStore {
         Ok(())
     }
 
+    /// The state each linter was in right after it was last initialized.
+    pub fn init_states(&self) -> Result<BTreeMap<String, InitState>> {
+        let path = self.relative_path(INIT_STATE_NAME);
+        if !path.exists() {
+            return Ok(BTreeMap::new());
+        }
+        serde_json::from_str(&std::fs::read_to_string(&path)?)
+            .with_context(|| format!("deserializing {}", path.display()))
+    }
+
+    /// Record the state of linters that were just initialized, keeping what
+    /// was recorded for the others.
+    pub fn update_init_states(&self, states: BTreeMap<String, InitState>) -> Result<()> {
+        let mut all_states = self.init_states()?;
+        all_states.extend(states);
+        let path = self.relative_path(INIT_STATE_NAME);
+        debug!("Writing init state to {}", path.display());
+        std::fs::write(path, serde_json::to_string_pretty(&all_states)?)?;
+        Ok(())
+    }
+
+    /// Store the issues this run found, for `lintrunner query`.
+    pub fn write_results(&self, results: &StoredResults) -> Result<()> {
+        let dir = self.data_dir.join(RESULTS_DIR_NAME);
+        std::fs::create_dir_all(&dir)?;
+        let path = dir.join(format!("{}.json", self.cur_run_info.dir_name()));
+        debug!("Writing results to {}", path.display());
+        std::fs::write(path, serde_json::to_string(results)?)?;
+
+        let mut entries = self.results_files()?;
+        if entries.len() > MAX_RESULTS_TO_STORE {
+            let num_to_delete = entries.len() - MAX_RESULTS_TO_STORE;
+            for path in entries.drain(..num_to_delete) {
+                debug!("Deleting old results: {}", path.display());
+                std::fs::remove_file(path)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// The issues stored by past lint runs, oldest first.
+    pub fn past_results(&self) -> Result<Vec<StoredResults>> {
+        self.results_files()?
+            .iter()
+            .map(|path| {
+                serde_json::from_str(&std::fs::read_to_string(path)?)
+                    .with_context(|| format!("deserializing {}", path.display()))
+            })
+            .collect()
+    }
+
+    /// Stored results files, oldest first (their names start with the time
+    /// of the run).
+    fn results_files(&self) -> Result<Vec<PathBuf>> {
+        let dir = self.data_dir.join(RESULTS_DIR_NAME);
+        if !dir.exists() {
+            return Ok(Vec::new());
+        }
+        let mut entries = std::fs::read_dir(&dir)?
+            .map(|res| res.map(|e| e.path()))
+            .collect::<Result<Vec<_>, std::io::Error>>()?;
+        entries.sort_unstable();
+        Ok(entries)
+    }
+
+    /// When each issue found by past runs was first seen.
+    pub fn issue_history(&self) -> Result<IssueHistory> {
+        let path = self.relative_path(ISSUE_HISTORY_NAME);
+        if !path.exists() {
+            return Ok(IssueHistory::default());
+        }
+        serde_json::from_str(&std::fs::read_to_string(&path)?)
+            .with_context(|| format!("deserializing {}", path.display()))
+    }
+
+    pub fn write_issue_history(&self, history: &IssueHistory) -> Result<()> {
+        let path = self.relative_path(ISSUE_HISTORY_NAME);
+        debug!("Writing issue history to {}", path.display());
+        std::fs::write(path, serde_json::to_string(history)?)?;
+        Ok(())
+    }
+
+    /// How long each linter has historically taken to run, used to estimate
+    /// how much longer a run will take.
+    pub fn linter_durations(&self) -> Result<HashMap<String, Duration>> {
+        let path = self.relative_path(LINTER_DURATIONS_NAME);
+        if !path.exists() {
+            return Ok(HashMap::new());
+        }
+        let millis: HashMap<String, u64> = serde_json::from_str(&std::fs::read_to_string(path)?)
+            .context("deserializing linter durations")?;
+        Ok(millis
+            .into_iter()
+            .map(|(code, ms)| (code, Duration::from_millis(ms)))
+            .collect())
+    }
+
+    /// Record how long linters took on this run. Durations are averaged with
+    /// the previously recorded ones so that a single unusually slow (or fast)
+    /// run doesn't throw off future estimates.
+    pub fn update_linter_durations(&self, durations: &HashMap<String, Duration>) -> Result<()> {
+        let mut all_durations = self.linter_durations()?;
+        for (code, duration) in durations {
+            let duration = match all_durations.get(code) {
+                Some(old) => (*old + *duration) / 2,
+                None => *duration,
+            };
+            all_durations.insert(code.clone(), duration);
+        }
+        let millis: HashMap<&String, u64> = all_durations
+            .iter()
+            .map(|(code, duration)| (code, duration.as_millis() as u64))
+            .collect();
+
+        debug!(
+            "Writing linter durations to {}/{}",
+            self.data_dir.display(),
+            LINTER_DURATIONS_NAME
+        );
+        std::fs::write(
+            self.relative_path(LINTER_DURATIONS_NAME),
+            serde_json::to_string_pretty(&millis)?,
+        )?;
+        Ok(())
+    }
+
+    /// The files and directories holding the `target` kind of state, for
+    /// `lintrunner clean`. Only paths that exist are returned, and never the
+    /// current run's directory, which this run is still writing to.
+    pub fn state_paths(&self, target: CleanTarget) -> Result<Vec<PathBuf>> {
+        let mut paths = Vec::new();
+        if matches!(target, CleanTarget::Init | CleanTarget::All) {
+            paths.push(self.relative_path(LINTERS_DIR_NAME));
+            paths.push(self.relative_path(INIT_STATE_NAME));
+            paths.push(self.relative_path(CONFIG_DATA_NAME));
+        }
+        if matches!(target, CleanTarget::Cache | CleanTarget::All) {
+            paths.push(self.relative_path(LINTER_DURATIONS_NAME));
+        }
+        if matches!(target, CleanTarget::Logs | CleanTarget::All) {
+            paths.extend(self.past_run_dirs()?);
+            paths.push(self.relative_path(RESULTS_DIR_NAME));
+            paths.push(self.relative_path(ISSUE_HISTORY_NAME));
+        }
+        paths.retain(|path| path.exists());
+        Ok(paths)
+    }
+
     fn relative_path(&self, path: impl AsRef<Path>) -> PathBuf {
         self.data_dir.join(path)
     }
 }
 
+fn write_data_version(data_dir: &Path, version: u32) -> Result<()> {
+    let data_version = DataVersion {
+        version,
+        written_by: env!("CARGO_PKG_VERSION").to_string(),
+    };
+    std::fs::write(
+        data_dir.join(DATA_VERSION_NAME),
+        serde_json::to_string_pretty(&data_version)?,
+    )?;
+    Ok(())
+}
+
+/// Bring the data in `data_dir` up to the version `migrations` lead to, one
+/// version at a time. Data without a version predates versioning, and is
+/// version 0.
+fn migrate(data_dir: &Path, migrations: &[Migration]) -> Result<()> {
+    let path = data_dir.join(DATA_VERSION_NAME);
+    let version = if path.exists() {
+        let data_version: DataVersion = serde_json::from_str(&std::fs::read_to_string(&path)?)
+            .with_context(|| format!("deserializing {}", path.display()))?;
+        ensure!(
+            data_version.version as usize <= migrations.len(),
+            "The data in '{}' was written by lintrunner {}, in a newer format than this \
+             version understands. Upgrade lintrunner, or pass a different --data-path.",
+            data_dir.display(),
+            data_version.written_by,
+        );
+        data_version.version
+    } else {
+        0
+    };
+    for (from, migration) in migrations.iter().enumerate().skip(version as usize) {
+        debug!(
+            "Migrating data in {} from version {} to {}",
+            data_dir.display(),
+            from,
+            from + 1
+        );
+        migration(data_dir).with_context(|| {
+            format!(
+                "Failed to migrate the data in '{}' to version {}",
+                data_dir.display(),
+                from + 1
+            )
+        })?;
+        // Record each step, so that a failed migration resumes where it
+        // stopped.
+        write_data_version(data_dir, from as u32 + 1)?;
+    }
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use tempfile::NamedTempFile;
 
+    #[test]
+    fn data_is_migrated() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let version = |dir: &Path| -> Result<u32> {
+            let data_version: DataVersion =
+                serde_json::from_str(&std::fs::read_to_string(dir.join(DATA_VERSION_NAME))?)?;
+            Ok(data_version.version)
+        };
+        let migrations: [Migration; 2] = [
+            |_| Ok(()),
+            |dir| Ok(std::fs::rename(dir.join("old.json"), dir.join("new.json"))?),
+        ];
+
+        // Unversioned data is migrated all the way.
+        std::fs::write(dir.path().join("old.json"), "{}")?;
+        migrate(dir.path(), &migrations)?;
+        assert_eq!(version(dir.path())?, 2);
+        assert!(dir.path().join("new.json").exists());
+        // Migrating again does nothing.
+        migrate(dir.path(), &migrations)?;
+
+        // A failed migration leaves the data at the last version it reached.
+        std::fs::remove_file(dir.path().join(DATA_VERSION_NAME))?;
+        assert!(migrate(dir.path(), &migrations).is_err());
+        assert_eq!(version(dir.path())?, 1);
+
+        // Data from a newer lintrunner is left alone.
+        let err = migrate(dir.path(), &migrations[..0]).unwrap_err();
+        assert!(err.to_string().contains("newer format"), "{}", err);
+        Ok(())
+    }
+
     #[test]
     fn basic_data_doesnt_fail() {
         let f = NamedTempFile::new().unwrap();
@@ -264,11 +711,36 @@ mod tests {
             timestamp: "0".to_string(),
             args: vec!["foo".to_string(), "bar".to_string()],
         };
-        let store = PersistentDataStore::new(&config, run_info).unwrap();
+        let store = PersistentDataStore::new(&config, None, run_info).unwrap();
         // Try to cleanup
         std::fs::remove_dir_all(store.data_dir).unwrap();
     }
 
+    #[test]
+    fn data_keys_pick_the_data_dir() -> Result<()> {
+        let f = NamedTempFile::new()?;
+        let config = AbsPath::try_from(f.path())?;
+
+        // The default is the hash of the config path, as it always was.
+        assert_eq!(
+            DataKey::default().dir_name(&config)?,
+            blake3::hash(config.to_string_lossy().as_bytes()).to_string()
+        );
+        // Keying on the branch (this repo's, here) gives another directory.
+        let with_branch = DataKey::Parts(vec![DataKeyPart::ConfigPath, DataKeyPart::Branch]);
+        assert_ne!(
+            with_branch.dir_name(&config)?,
+            DataKey::default().dir_name(&config)?
+        );
+
+        let explicit = |key: &str| DataKey::Explicit(key.to_string()).dir_name(&config);
+        assert_eq!(explicit("worktree-2")?, "worktree-2");
+        assert!(explicit("").is_err());
+        assert!(explicit("..").is_err());
+        assert!(explicit("a/b").is_err());
+        Ok(())
+    }
+
     #[test]
     fn old_run_cleanup() {
         let f = NamedTempFile::new().unwrap();
@@ -278,7 +750,7 @@ mod tests {
             timestamp: "0".to_string(),
             args: vec!["foo".to_string(), "bar".to_string()],
         };
-        let store = PersistentDataStore::new(&config, run_info).unwrap();
+        let store = PersistentDataStore::new(&config, None, run_info).unwrap();
 
         // Simulate some more runs.
         for i in 1..20 {
@@ -286,7 +758,7 @@ mod tests {
                 timestamp: i.to_string(),
                 args: vec!["foo".to_string(), "bar".to_string()],
             };
-            let store = PersistentDataStore::new(&config, run_info).unwrap();
+            let store = PersistentDataStore::new(&config, None, run_info).unwrap();
             store
                 .write_run_info(ExitInfo { code: 0, err: None })
                 .unwrap()
@@ -299,4 +771,68 @@ mod tests {
         // Try to clean up
         std::fs::remove_dir_all(store.data_dir).unwrap();
     }
+
+    #[test]
+    fn linter_fingerprints_are_read_from_past_runs() {
+        let f = NamedTempFile::new().unwrap();
+        let config = AbsPath::try_from(f.path()).unwrap();
+        let data_dir = tempfile::tempdir().unwrap();
+
+        for i in 0..3 {
+            let run_info = RunInfo {
+                timestamp: i.to_string(),
+                args: vec!["foo".to_string()],
+            };
+            let store = PersistentDataStore::new(&config, Some(data_dir.path()), run_info).unwrap();
+            let mut fingerprints = HashMap::new();
+            fingerprints.insert(
+                "FOO".to_string(),
+                LinterFingerprint {
+                    inputs: "in".to_string(),
+                    results: i.to_string(),
+                },
+            );
+            store.write_linter_fingerprints(&fingerprints).unwrap();
+        }
+
+        let run_info = RunInfo {
+            timestamp: "3".to_string(),
+            args: vec!["foo".to_string()],
+        };
+        let store = PersistentDataStore::new(&config, Some(data_dir.path()), run_info).unwrap();
+        let history = store.past_linter_fingerprints().unwrap();
+        let results: Vec<&str> = history
+            .iter()
+            .map(|run| run["FOO"].results.as_str())
+            .collect();
+        assert_eq!(results, vec!["2", "1", "0"]);
+    }
+
+    #[test]
+    fn linter_durations_are_averaged() {
+        let f = NamedTempFile::new().unwrap();
+        let config = AbsPath::try_from(f.path()).unwrap();
+
+        let run_info = RunInfo {
+            timestamp: "0".to_string(),
+            args: vec!["foo".to_string(), "bar".to_string()],
+        };
+        let store = PersistentDataStore::new(&config, None, run_info).unwrap();
+        assert!(store.linter_durations().unwrap().is_empty());
+
+        let mut durations = HashMap::new();
+        durations.insert("FOO".to_string(), Duration::from_millis(100));
+        store.update_linter_durations(&durations).unwrap();
+
+        durations.insert("FOO".to_string(), Duration::from_millis(300));
+        durations.insert("BAR".to_string(), Duration::from_millis(50));
+        store.update_linter_durations(&durations).unwrap();
+
+        let durations = store.linter_durations().unwrap();
+        assert_eq!(durations["FOO"], Duration::from_millis(200));
+        assert_eq!(durations["BAR"], Duration::from_millis(50));
+
+        // Try to clean up
+        std::fs::remove_dir_all(store.data_dir).unwrap();
+    }
 }
@@ -0,0 +1,221 @@
+//! Terminate a linter together with every process it started.
+//!
+//! Killing just the linter process isn't enough: adapters commonly start
+//! worker processes (pip, node, mypy), which would be left running. On Unix,
+//! each linter is started in its own process group, and the group is
+//! signaled. On Windows, each linter is started suspended, put in a job
+//! object before it can start anything, then resumed; the job is terminated.
+
+use std::process::{Child, Command};
+
+/// The tree of processes rooted at a linter process.
+pub struct ProcessTree {
+    pid: u32,
+    #[cfg(windows)]
+    job: windows::Job,
+}
+
+impl ProcessTree {
+    /// Set up `command` so that the process tree it starts can be terminated
+    /// as a whole. Must be called before spawning it, and the spawned child
+    /// must be passed to [`ProcessTree::new`] right away: on Windows it
+    /// doesn't run until then.
+    pub fn prepare(command: &mut Command) {
+        #[cfg(unix)]
+        {
+            use std::os::unix::process::CommandExt;
+            command.process_group(0);
+        }
+        #[cfg(windows)]
+        {
+            use std::os::windows::process::CommandExt;
+            command.creation_flags(windows::CREATE_SUSPENDED);
+        }
+        #[cfg(not(any(unix, windows)))]
+        let _ = command;
+    }
+
+    /// Track the process tree of `child`, which was spawned from a command
+    /// set up with [`ProcessTree::prepare`]. On Windows, this also resumes
+    /// the child once it is in the job.
+    pub fn new(child: &Child) -> ProcessTree {
+        ProcessTree {
+            pid: child.id(),
+            #[cfg(windows)]
+            job: windows::Job::for_child(child),
+        }
+    }
+
+    pub fn pid(&self) -> u32 {
+        self.pid
+    }
+
+    /// Ask every process in the tree to exit.
+    pub fn terminate(&self) {
+        #[cfg(unix)]
+        self.signal(libc::SIGTERM);
+        #[cfg(windows)]
+        self.job.terminate();
+    }
+
+    /// Forcibly kill every process in the tree.
+    pub fn kill(&self) {
+        #[cfg(unix)]
+        self.signal(libc::SIGKILL);
+        #[cfg(windows)]
+        self.job.terminate();
+    }
+
+    #[cfg(unix)]
+    fn signal(&self, signal: libc::c_int) {
+        // The process group has the same id as the linter process. A
+        // negative pid signals the whole group.
+        // SAFETY: kill has no memory safety requirements.
+        unsafe {
+            libc::kill(-(self.pid as libc::pid_t), signal);
+        }
+    }
+}
+
+#[cfg(windows)]
+mod windows {
+    use std::os::windows::io::AsRawHandle;
+    use std::process::Child;
+
+    type Handle = *mut std::ffi::c_void;
+
+    pub const CREATE_SUSPENDED: u32 = 0x0000_0004;
+    const TH32CS_SNAPTHREAD: u32 = 0x0000_0004;
+    const THREAD_SUSPEND_RESUME: u32 = 0x0002;
+    const INVALID_HANDLE_VALUE: Handle = -1isize as Handle;
+
+    #[repr(C)]
+    #[allow(non_snake_case)]
+    struct THREADENTRY32 {
+        dwSize: u32,
+        cntUsage: u32,
+        th32ThreadID: u32,
+        th32OwnerProcessID: u32,
+        tpBasePri: i32,
+        tpDeltaPri: i32,
+        dwFlags: u32,
+    }
+
+    #[link(name = "kernel32")]
+    extern "system" {
+        fn CreateJobObjectW(attributes: *mut std::ffi::c_void, name: *const u16) -> Handle;
+        fn AssignProcessToJobObject(job: Handle, process: Handle) -> i32;
+        fn TerminateJobObject(job: Handle, exit_code: u32) -> i32;
+        fn CloseHandle(handle: Handle) -> i32;
+        fn CreateToolhelp32Snapshot(flags: u32, process_id: u32) -> Handle;
+        fn Thread32First(snapshot: Handle, entry: *mut THREADENTRY32) -> i32;
+        fn Thread32Next(snapshot: Handle, entry: *mut THREADENTRY32) -> i32;
+        fn OpenThread(access: u32, inherit: i32, thread_id: u32) -> Handle;
+        fn ResumeThread(thread: Handle) -> u32;
+    }
+
+    /// A job object containing a linter process. Processes it starts are
+    /// added to the job automatically.
+    pub struct Job(Handle);
+
+    // SAFETY: job object handles can be used from any thread.
+    unsafe impl Send for Job {}
+    unsafe impl Sync for Job {}
+
+    impl Job {
+        /// Put `child`, which was spawned suspended, in a new job, then
+        /// resume it. Assigning it before it runs means nothing it starts
+        /// can escape the job.
+        pub fn for_child(child: &Child) -> Job {
+            // SAFETY: null attributes and name are allowed. If creating or
+            // assigning the job fails, terminating it is a no-op, and we fall
+            // back to the linter's own handling of its children. The child is
+            // resumed either way.
+            let job = unsafe {
+                let job = CreateJobObjectW(std::ptr::null_mut(), std::ptr::null());
+                if !job.is_null() {
+                    AssignProcessToJobObject(job, child.as_raw_handle() as Handle);
+                }
+                Job(job)
+            };
+            resume(child.id());
+            job
+        }
+
+        pub fn terminate(&self) {
+            if !self.0.is_null() {
+                // SAFETY: the handle is a valid job object until dropped.
+                unsafe {
+                    TerminateJobObject(self.0, 1);
+                }
+            }
+        }
+    }
+
+    /// Resume the threads of the suspended process `pid`. `Child` doesn't
+    /// expose the handle of its main thread, so look it up in a snapshot of
+    /// the threads in the system. A process spawned suspended has only the
+    /// one.
+    fn resume(pid: u32) {
+        // SAFETY: the snapshot and thread handles are checked before use and
+        // closed once done with, and `entry.dwSize` is set as required.
+        unsafe {
+            let snapshot = CreateToolhelp32Snapshot(TH32CS_SNAPTHREAD, 0);
+            if snapshot == INVALID_HANDLE_VALUE {
+                return;
+            }
+            let mut entry: THREADENTRY32 = std::mem::zeroed();
+            entry.dwSize = std::mem::size_of::<THREADENTRY32>() as u32;
+            let mut found = Thread32First(snapshot, &mut entry) != 0;
+            while found {
+                if entry.th32OwnerProcessID == pid {
+                    let thread = OpenThread(THREAD_SUSPEND_RESUME, 0, entry.th32ThreadID);
+                    if !thread.is_null() {
+                        ResumeThread(thread);
+                        CloseHandle(thread);
+                    }
+                }
+                found = Thread32Next(snapshot, &mut entry) != 0;
+            }
+            CloseHandle(snapshot);
+        }
+    }
+
+    impl Drop for Job {
+        fn drop(&mut self) {
+            if !self.0.is_null() {
+                // SAFETY: the handle is valid and not used after this.
+                unsafe {
+                    CloseHandle(self.0);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use std::os::unix::process::ExitStatusExt;
+
+    #[test]
+    fn whole_tree_is_terminated() {
+        let dir = tempfile::tempdir().unwrap();
+        let marker = dir.path().join("marker");
+        // The background subshell would create the marker if it survived.
+        let mut command = Command::new("sh");
+        command.args([
+            "-c",
+            &format!("(sleep 1; touch {}) & wait", marker.display()),
+        ]);
+        ProcessTree::prepare(&mut command);
+        let mut child = command.spawn().unwrap();
+        let tree = ProcessTree::new(&child);
+
+        std::thread::sleep(std::time::Duration::from_millis(200));
+        tree.terminate();
+        assert_eq!(child.wait().unwrap().signal(), Some(libc::SIGTERM));
+        std::thread::sleep(std::time::Duration::from_millis(1500));
+        assert!(!marker.exists());
+    }
+}
@@ -0,0 +1,310 @@
+//! Progress reporting while linters are running.
+//!
+//! On a capable terminal, this draws one bar per linter (queued, running,
+//! finished) underneath an overall bar that tracks files completed, issues
+//! found so far, and an ETA based on how long linters took on previous runs.
+//! On a dumb terminal, or with `--progress plain`, it falls back to printing
+//! one timestamped line, without any control sequences, whenever a linter
+//! starts or finishes.
+
+use std::{
+    collections::{HashMap, HashSet},
+    sync::{Arc, Mutex},
+    thread,
+    time::{Duration, Instant},
+};
+
+use anyhow::Result;
+use clap::ArgEnum;
+use console::style;
+use indicatif::{MultiProgress, ProgressBar, ProgressDrawTarget, ProgressStyle};
+
+use crate::streams::{self, Stream};
+
+/// How to show progress while linters run.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ArgEnum)]
+pub enum ProgressOpt {
+    /// Draw progress bars on a terminal, and nothing otherwise
+    Auto,
+    /// Print a plain line whenever a linter starts or finishes, e.g. for CI
+    /// logs
+    Plain,
+    /// Don't show progress
+    None,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Mode {
+    Hidden,
+    Bars,
+    Plain,
+}
+
+#[derive(Default)]
+struct State {
+    started: HashMap<String, Instant>,
+    finished: HashSet<String>,
+    files_total: usize,
+    files_done: usize,
+    issues: usize,
+}
+
+pub struct LintProgress {
+    mode: Mode,
+    multi: MultiProgress,
+    overall: ProgressBar,
+    bars: HashMap<String, ProgressBar>,
+    codes: Vec<String>,
+    expected: HashMap<String, Duration>,
+    state: Mutex<State>,
+}
+
+fn is_dumb_terminal() -> bool {
+    matches!(std::env::var("TERM").as_deref(), Ok("dumb"))
+}
+
+/// Print a line of plain progress, with the time, so that CI logs show when
+/// each linter started and finished.
+fn print_plain(line: &str) {
+    let now = chrono::Local::now().format("%H:%M:%S");
+    drop(
+        streams::diagnostics()
+            .term()
+            .write_line(&format!("[{}] {}", now, line)),
+    );
+}
+
+fn format_duration(duration: Duration) -> String {
+    format!("{:.1}s", duration.as_secs_f64())
+}
+
+impl LintProgress {
+    /// Create a progress display for the linters identified by `codes`.
+    /// `expected` holds historical durations for (some of) those linters.
+    pub fn new(
+        progress: ProgressOpt,
+        codes: &[String],
+        expected: HashMap<String, Duration>,
+    ) -> Self {
+        let mode = match progress {
+            ProgressOpt::None => Mode::Hidden,
+            ProgressOpt::Plain => Mode::Plain,
+            ProgressOpt::Auto if !streams::diagnostics().term().is_term() => Mode::Hidden,
+            ProgressOpt::Auto if is_dumb_terminal() => Mode::Plain,
+            ProgressOpt::Auto => Mode::Bars,
+        };
+
+        let multi = MultiProgress::with_draw_target(match streams::diagnostics() {
+            Stream::Stdout => ProgressDrawTarget::stdout(),
+            Stream::Stderr => ProgressDrawTarget::stderr(),
+        });
+        let mut bars = HashMap::new();
+        let overall = if mode == Mode::Bars {
+            let overall = multi.add(ProgressBar::new(0));
+            overall.set_style(
+                ProgressStyle::default_bar()
+                    .template("{spinner} [{elapsed}] {wide_bar} {pos}/{len} files {msg}"),
+            );
+            overall.enable_steady_tick(100);
+            for code in codes {
+                let bar = multi.add(ProgressBar::new_spinner());
+                bar.set_style(ProgressStyle::default_spinner().template("  {spinner} {msg}"));
+                bar.set_message(format!("{} {}", code, style("queued").dim()));
+                bars.insert(code.clone(), bar);
+            }
+            overall
+        } else {
+            ProgressBar::hidden()
+        };
+
+        let progress = LintProgress {
+            mode,
+            multi,
+            overall,
+            bars,
+            codes: codes.to_vec(),
+            expected,
+            state: Mutex::new(State::default()),
+        };
+        progress.refresh_overall(&progress.state.lock().unwrap());
+        progress
+    }
+
+    // Estimate how much longer the run will take. Linters run in parallel, so
+    // this is the longest expected remaining time of any unfinished linter.
+    // Returns None if we have no history for some unfinished linter.
+    fn eta(&self, state: &State) -> Option<Duration> {
+        let mut eta = Duration::ZERO;
+        for code in &self.codes {
+            if state.finished.contains(code) {
+                continue;
+            }
+            let expected = self.expected.get(code)?;
+            let elapsed = state
+                .started
+                .get(code)
+                .map(|start| start.elapsed())
+                .unwrap_or_default();
+            eta = eta.max(expected.saturating_sub(elapsed));
+        }
+        Some(eta)
+    }
+
+    fn refresh_overall(&self, state: &State) {
+        if self.mode != Mode::Bars {
+            return;
+        }
+        let eta = match self.eta(state) {
+            Some(eta) if state.finished.len() < self.codes.len() => {
+                format!(", ETA ~{}", format_duration(eta))
+            }
+            _ => String::new(),
+        };
+        self.overall.set_length(state.files_total as u64);
+        self.overall.set_position(state.files_done as u64);
+        self.overall.set_message(format!(
+            "({}/{} linters finished, {} issues so far{})",
+            state.finished.len(),
+            self.codes.len(),
+            state.issues,
+            eta
+        ));
+    }
+
+    /// Mark a linter as running over `num_files` files.
+    pub fn start(&self, code: &str, num_files: usize) {
+        let mut state = self.state.lock().unwrap();
+        state.started.insert(code.to_string(), Instant::now());
+        state.files_total += num_files;
+
+        match self.mode {
+            Mode::Hidden => {}
+            Mode::Plain => {
+                print_plain(&format!("{} running on {} files...", code, num_files));
+            }
+            Mode::Bars => {
+                if let Some(bar) = self.bars.get(code) {
+                    let expected = match self.expected.get(code) {
+                        Some(expected) => format!(", usually takes {}", format_duration(*expected)),
+                        None => String::new(),
+                    };
+                    bar.set_message(format!(
+                        "{} running on {} files{}...",
+                        code, num_files, expected
+                    ));
+                    bar.enable_steady_tick(100);
+                }
+                self.refresh_overall(&state);
+            }
+        }
+    }
+
+    /// Mark a linter as finished, having reported `num_issues` issues. Returns
+    /// how long the linter ran for.
+    pub fn finish(&self, code: &str, num_files: usize, num_issues: usize) -> Duration {
+        let mut state = self.state.lock().unwrap();
+        let elapsed = state
+            .started
+            .get(code)
+            .map(|start| start.elapsed())
+            .unwrap_or_default();
+        state.finished.insert(code.to_string());
+        state.files_done += num_files;
+        state.issues += num_issues;
+
+        match self.mode {
+            Mode::Hidden => {}
+            Mode::Plain => {
+                let result = match num_issues {
+                    0 => "success!".to_string(),
+                    _ => format!("failure ({} issues)", num_issues),
+                };
+                print_plain(&format!(
+                    "{} {} in {}",
+                    code,
+                    result,
+                    format_duration(elapsed)
+                ));
+            }
+            Mode::Bars => {
+                let result = if num_issues == 0 {
+                    format!("{}", style("success!").green())
+                } else {
+                    format!("{} ({} issues)", style("failure").red(), num_issues)
+                };
+                if let Some(bar) = self.bars.get(code) {
+                    bar.finish_with_message(format!(
+                        "{} {} {}",
+                        code,
+                        result,
+                        style(format_duration(elapsed)).dim()
+                    ));
+                }
+                self.refresh_overall(&state);
+                if state.finished.len() == self.codes.len() {
+                    self.overall.finish();
+                }
+            }
+        }
+        elapsed
+    }
+
+    /// A progress bar that can be used to print output above the progress
+    /// display without mangling it, if one is being drawn.
+    pub fn printer(&self) -> Option<&ProgressBar> {
+        match self.mode {
+            Mode::Bars => Some(&self.overall),
+            Mode::Hidden | Mode::Plain => None,
+        }
+    }
+
+    /// Block until every linter has finished, drawing progress in the
+    /// meantime.
+    pub fn join(self: &Arc<Self>) -> Result<()> {
+        if self.mode != Mode::Bars {
+            return Ok(());
+        }
+        if self.codes.is_empty() {
+            self.overall.finish_and_clear();
+        }
+
+        // Periodically recompute the ETA so that it counts down between
+        // linters finishing.
+        let ticker = {
+            let progress = Arc::clone(self);
+            thread::spawn(move || {
+                while !progress.overall.is_finished() {
+                    progress.refresh_overall(&progress.state.lock().unwrap());
+                    thread::sleep(Duration::from_millis(250));
+                }
+            })
+        };
+        self.multi.join()?;
+        ticker.join().unwrap();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn eta_is_longest_remaining_linter() {
+        let codes = vec!["FAST".to_string(), "SLOW".to_string(), "NEW".to_string()];
+        let mut expected = HashMap::new();
+        expected.insert("FAST".to_string(), Duration::from_secs(1));
+        expected.insert("SLOW".to_string(), Duration::from_secs(100));
+        let progress = LintProgress::new(ProgressOpt::None, &codes, expected);
+
+        // No history for a linter means no estimate.
+        let mut state = State::default();
+        assert_eq!(progress.eta(&state), None);
+
+        state.finished.insert("NEW".to_string());
+        assert_eq!(progress.eta(&state), Some(Duration::from_secs(100)));
+
+        state.finished.insert("SLOW".to_string());
+        assert_eq!(progress.eta(&state), Some(Duration::from_secs(1)));
+    }
+}
@@ -0,0 +1,558 @@
+//! Publishing lint results to code review systems, and announcing them to
+//! webhooks.
+//!
+//! For `lintrunner publish`, results are read from a previous run's JSON
+//! output (`--output json` or `--tee-json`), so publishing doesn't need to
+//! re-run any linters. Requests are made with `curl`, which is expected to be
+//! on the PATH.
+
+use std::io::{Read, Write};
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::time::Duration;
+
+use anyhow::{bail, Context, Result};
+use clap::ArgEnum;
+use log::{debug, warn};
+use serde_json::{json, Value};
+
+use crate::{
+    git::{get_git_root, get_head},
+    lint_message::{LintMessage, LintSeverity},
+    offline,
+    path::{path_relative_from, AbsPath},
+    render::{JsonDocument, JsonHeader},
+    LintResults,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ArgEnum)]
+pub enum PublishTarget {
+    /// A GitHub check run, with an annotation per lint message. Needs
+    /// GITHUB_TOKEN and GITHUB_REPOSITORY to be set, as they are in GitHub
+    /// Actions.
+    GithubChecks,
+}
+
+/// The GitHub API accepts at most this many annotations per request.
+const MAX_ANNOTATIONS_PER_REQUEST: usize = 50;
+
+pub fn do_publish(target: PublishTarget, from: &str, name: &str) -> Result<i32> {
+    offline::ensure_online("Publishing results")?;
+    let lints = read_lint_messages(from)?;
+    match target {
+        PublishTarget::GithubChecks => publish_github_checks(&lints, name),
+    }
+}
+
+/// Read lint messages, one JSON object per line after an optional header,
+/// or as written by `--output json-pretty`, from `path` (or stdin if it is
+/// `-`).
+pub(crate) fn read_lint_messages(path: &str) -> Result<Vec<LintMessage>> {
+    let mut contents = String::new();
+    if path == "-" {
+        std::io::stdin().read_to_string(&mut contents)?;
+    } else {
+        contents = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to open lint results '{}'", path))?;
+    }
+    // Only the pretty document puts its opening brace on a line of its own.
+    if contents.lines().next().map(str::trim) == Some("{") {
+        let document = JsonDocument::parse(&contents)
+            .with_context(|| format!("Invalid lint results in '{}'", path))?;
+        return Ok(document.messages);
+    }
+    let mut lints = Vec::new();
+    for (i, line) in contents.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        // Results written by `--output json` start with a header. A file
+        // that `--tee-json-mode append` added several runs to has one per run,
+        // and only the last run counts.
+        if JsonHeader::parse(line)?.is_some() {
+            lints.clear();
+            continue;
+        }
+        let lint = serde_json::from_str(line)
+            .with_context(|| format!("Invalid lint message on line {} of '{}'", i + 1, path))?;
+        lints.push(lint);
+    }
+    Ok(lints)
+}
+
+pub(crate) fn github_env(name: &str) -> Result<String> {
+    std::env::var(name).with_context(|| format!("{} must be set to use GitHub", name))
+}
+
+fn publish_github_checks(lints: &[LintMessage], name: &str) -> Result<i32> {
+    let token = github_env("GITHUB_TOKEN")?;
+    let repository = github_env("GITHUB_REPOSITORY")?;
+    let api_url =
+        std::env::var("GITHUB_API_URL").unwrap_or_else(|_| "https://api.github.com".to_string());
+    let head_sha = match std::env::var("GITHUB_SHA") {
+        Ok(sha) => sha,
+        Err(_) => get_head()?,
+    };
+
+    let git_root = get_git_root()?;
+    let annotations = github_annotations(lints, &git_root);
+    let title = check_title(lints);
+    let summary = check_summary(lints);
+
+    let check_run = github_request(
+        "POST",
+        &format!("{}/repos/{}/check-runs", api_url, repository),
+        &token,
+        &json!({
+            "name": name,
+            "head_sha": head_sha,
+            "status": "in_progress",
+        }),
+    )?;
+    let id = check_run["id"]
+        .as_u64()
+        .context("GitHub did not return an id for the check run")?;
+    let check_run_url = format!("{}/repos/{}/check-runs/{}", api_url, repository, id);
+
+    // Annotations are appended by each update, so they can be sent in
+    // batches. The last update also completes the check run.
+    let mut batches: Vec<&[Value]> = annotations.chunks(MAX_ANNOTATIONS_PER_REQUEST).collect();
+    if batches.is_empty() {
+        batches.push(&[]);
+    }
+    let last = batches.len() - 1;
+    let mut response = Value::Null;
+    for (i, batch) in batches.into_iter().enumerate() {
+        let mut body = json!({
+            "output": {
+                "title": title,
+                "summary": summary,
+                "annotations": batch,
+            },
+        });
+        if i == last {
+            body["status"] = json!("completed");
+            body["conclusion"] = json!(check_conclusion(lints));
+        }
+        response = github_request("PATCH", &check_run_url, &token, &body)?;
+    }
+
+    if let Some(url) = response["html_url"].as_str() {
+        println!("Published {} lint issues to {}", lints.len(), url);
+    }
+    Ok(0)
+}
+
+/// Convert lint messages to check run annotations. Messages without a path
+/// can't be annotated, and only show up in the summary.
+fn github_annotations(lints: &[LintMessage], git_root: &Path) -> Vec<Value> {
+    lints
+        .iter()
+        .filter_map(|lint| {
+            let path = lint.path.as_ref()?;
+            let path = match AbsPath::try_from(path) {
+                Ok(abs_path) => path_relative_from(&abs_path, git_root)?,
+                Err(_) => path.into(),
+            };
+            let line = lint.line.unwrap_or(1);
+            let level = match lint.severity {
+                LintSeverity::Error => "failure",
+                LintSeverity::Warning => "warning",
+                LintSeverity::Advice | LintSeverity::Disabled => "notice",
+            };
+            Some(json!({
+                "path": path.to_string_lossy(),
+                "start_line": line,
+                "end_line": line,
+                "annotation_level": level,
+                "title": format!("{} ({})", lint.code, lint.name),
+                "message": lint.description.as_deref().unwrap_or(&lint.name),
+            }))
+        })
+        .collect()
+}
+
+fn check_conclusion(lints: &[LintMessage]) -> &'static str {
+    if lints
+        .iter()
+        .any(|lint| matches!(lint.severity, LintSeverity::Error | LintSeverity::Warning))
+    {
+        "failure"
+    } else if lints.is_empty() {
+        "success"
+    } else {
+        "neutral"
+    }
+}
+
+fn check_title(lints: &[LintMessage]) -> String {
+    match lints.len() {
+        0 => "No lint issues".to_string(),
+        1 => "1 lint issue".to_string(),
+        n => format!("{} lint issues", n),
+    }
+}
+
+fn check_summary(lints: &[LintMessage]) -> String {
+    let mut summary = String::new();
+    for severity in [
+        LintSeverity::Error,
+        LintSeverity::Warning,
+        LintSeverity::Advice,
+    ] {
+        let count = lints
+            .iter()
+            .filter(|lint| lint.severity == severity)
+            .count();
+        if count > 0 {
+            summary.push_str(&format!("- {}: {}\n", severity.label(), count));
+        }
+    }
+    for lint in lints.iter().filter(|lint| lint.path.is_none()) {
+        summary.push_str(&format!(
+            "\n**{} ({})**: {}\n",
+            lint.code,
+            lint.name,
+            lint.description.as_deref().unwrap_or_default()
+        ));
+    }
+    if summary.is_empty() {
+        summary.push_str("No lint issues.");
+    }
+    summary
+}
+
+/// Where to announce the outcome of a lint run.
+pub struct Webhook {
+    pub url: String,
+    /// A link to the full results, e.g. a CI artifact, included in the
+    /// announcement.
+    pub report_url: Option<String>,
+}
+
+/// POST a summary of `results` to `webhook`. Failures are only logged, since
+/// a broken notification shouldn't fail the lint run.
+pub fn notify_webhook(webhook: &Webhook, results: &LintResults, duration: Duration, run_id: &str) {
+    let payload = webhook_payload(webhook, results, duration, run_id);
+    debug!("Notifying webhook: {}", payload);
+    match http_request("POST", &webhook.url, &[], &payload) {
+        Ok((status, _)) if (200..300).contains(&status) => {}
+        Ok((status, response)) => warn!(
+            "Webhook notification failed with status {}: {}",
+            status, response
+        ),
+        Err(err) => warn!("Webhook notification failed: {:#}", err),
+    }
+}
+
+fn webhook_payload(
+    webhook: &Webhook,
+    results: &LintResults,
+    duration: Duration,
+    run_id: &str,
+) -> Value {
+    let exit_code = results.exit_code();
+    let count = |severity| {
+        results
+            .lints
+            .values()
+            .flatten()
+            .filter(|lint| lint.severity == severity)
+            .count()
+    };
+    let (errors, warnings, advice) = (
+        count(LintSeverity::Error),
+        count(LintSeverity::Warning),
+        count(LintSeverity::Advice),
+    );
+
+    // Chat services like Slack and Teams show `text`; everything else is
+    // there for tools that want the details.
+    let mut text = format!(
+        "lintrunner {}: {} errors, {} warnings, {} advice ({:.1}s)",
+        if exit_code == 0 { "passed" } else { "failed" },
+        errors,
+        warnings,
+        advice,
+        duration.as_secs_f64()
+    );
+    if let Some(report_url) = &webhook.report_url {
+        text.push_str(&format!(". Report: {}", report_url));
+    }
+    json!({
+        "text": text,
+        "exit_code": exit_code,
+        "counts": {
+            "error": errors,
+            "warning": warnings,
+            "advice": advice,
+        },
+        "duration_secs": duration.as_secs_f64(),
+        "report_url": webhook.report_url,
+        "run_id": run_id,
+    })
+}
+
+/// Make a GitHub API request and return the parsed response.
+pub(crate) fn github_request(method: &str, url: &str, token: &str, body: &Value) -> Result<Value> {
+    let (status, response) = http_request(
+        method,
+        url,
+        &[
+            ("Accept", "application/vnd.github+json"),
+            ("Authorization", &format!("Bearer {}", token)),
+        ],
+        body,
+    )?;
+    if !(200..300).contains(&status) {
+        bail!(
+            "GitHub API request {} {} failed with status {}: {}",
+            method,
+            url,
+            status,
+            response
+        );
+    }
+    serde_json::from_str(&response)
+        .with_context(|| format!("GitHub returned invalid JSON: {}", response))
+}
+
+/// Send `body` as JSON with curl, returning the status code and response
+/// body.
+pub(crate) fn http_request(
+    method: &str,
+    url: &str,
+    headers: &[(&str, &str)],
+    body: &Value,
+) -> Result<(u32, String)> {
+    http_request_raw(
+        method,
+        url,
+        headers,
+        "application/json",
+        &serde_json::to_vec(body)?,
+    )
+}
+
+/// Send `body` with curl, returning the status code and response body.
+pub(crate) fn http_request_raw(
+    method: &str,
+    url: &str,
+    headers: &[(&str, &str)],
+    content_type: &str,
+    body: &[u8],
+) -> Result<(u32, String)> {
+    let mut body_file = tempfile::NamedTempFile::new()?;
+    body_file.write_all(body)?;
+
+    let mut headers = headers.to_vec();
+    headers.push(("Content-Type", content_type));
+    let output = curl(
+        method,
+        url,
+        &headers,
+        &[
+            "--data-binary".to_string(),
+            format!("@{}", body_file.path().display()),
+            "--write-out".to_string(),
+            "\n%{http_code}".to_string(),
+        ],
+    )?;
+    let stdout = String::from_utf8_lossy(&output);
+    let (response, status) = stdout.rsplit_once('\n').unwrap_or(("", &stdout));
+    let status = status
+        .trim()
+        .parse()
+        .with_context(|| format!("curl returned an invalid status code: {}", status))?;
+    Ok((status, response.to_string()))
+}
+
+/// GET `url` with curl, following redirects, and return the response body.
+/// Unlike [`http_request_raw`], an error status fails the request.
+pub(crate) fn http_get(url: &str, headers: &[(&str, &str)]) -> Result<Vec<u8>> {
+    curl(
+        "GET",
+        url,
+        headers,
+        &["--fail".to_string(), "--location".to_string()],
+    )
+}
+
+/// Run curl, returning what it printed. Headers are passed through curl's
+/// config on stdin, so that secrets don't show up in the process list.
+fn curl(method: &str, url: &str, headers: &[(&str, &str)], args: &[String]) -> Result<Vec<u8>> {
+    offline::ensure_online(&format!("{} {}", method, url))?;
+    let mut child = Command::new("curl")
+        .args([
+            "--silent",
+            "--show-error",
+            "--request",
+            method,
+            "--config",
+            "-",
+        ])
+        .args(args)
+        .arg(url)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .context("Failed to run curl, which is needed for network requests")?;
+    {
+        let mut stdin = child.stdin.take().unwrap();
+        for (name, value) in headers {
+            writeln!(
+                stdin,
+                "header = \"{}: {}\"",
+                name,
+                value.replace('"', "\\\"")
+            )?;
+        }
+    }
+    let output = child.wait_with_output()?;
+    if !output.status.success() {
+        bail!(
+            "Request {} {} failed: {}",
+            method,
+            url,
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(output.stdout)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+    use crate::render::{
+        render_lint_messages_json, render_lint_messages_json_pretty, render_lint_messages_json_v1,
+        JSON_SCHEMA_VERSION,
+    };
+
+    fn lint(path: Option<&str>, line: Option<usize>, severity: LintSeverity) -> LintMessage {
+        LintMessage {
+            path: path.map(str::to_string),
+            line,
+            description: Some("Something is bad".to_string()),
+            ..LintMessage::for_test("TEST", severity, "bad")
+        }
+    }
+
+    #[test]
+    fn lints_become_annotations() {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().canonicalize().unwrap();
+        std::fs::create_dir(root.join("src")).unwrap();
+        std::fs::write(root.join("src/a.py"), "").unwrap();
+        let a = root.join("src/a.py").display().to_string();
+
+        let lints = vec![
+            lint(Some(&a), Some(3), LintSeverity::Error),
+            lint(Some(&a), None, LintSeverity::Advice),
+            lint(None, None, LintSeverity::Warning),
+        ];
+        let annotations = github_annotations(&lints, &root);
+        assert_eq!(
+            annotations,
+            vec![
+                json!({
+                    "path": "src/a.py",
+                    "start_line": 3,
+                    "end_line": 3,
+                    "annotation_level": "failure",
+                    "title": "TEST (bad)",
+                    "message": "Something is bad",
+                }),
+                json!({
+                    "path": "src/a.py",
+                    "start_line": 1,
+                    "end_line": 1,
+                    "annotation_level": "notice",
+                    "title": "TEST (bad)",
+                    "message": "Something is bad",
+                }),
+            ]
+        );
+        assert_eq!(check_conclusion(&lints), "failure");
+        assert_eq!(
+            check_summary(&lints),
+            "- Error: 1\n- Warning: 1\n- Advice: 1\n\n**TEST (bad)**: Something is bad\n"
+        );
+    }
+
+    #[test]
+    fn webhook_payload_summarizes_results() {
+        let mut results = LintResults {
+            failing_lints: 2,
+            ..Default::default()
+        };
+        results.lints.insert(
+            None,
+            vec![
+                lint(None, None, LintSeverity::Error),
+                lint(None, None, LintSeverity::Warning),
+                lint(None, None, LintSeverity::Advice),
+            ],
+        );
+        let webhook = Webhook {
+            url: "https://example.com/hook".to_string(),
+            report_url: Some("https://ci/artifacts/lint.json".to_string()),
+        };
+        assert_eq!(
+            webhook_payload(&webhook, &results, Duration::from_millis(1500), "run"),
+            json!({
+                "text": "lintrunner failed: 1 errors, 1 warnings, 1 advice (1.5s). \
+                         Report: https://ci/artifacts/lint.json",
+                "exit_code": 1,
+                "counts": {"error": 1, "warning": 1, "advice": 1},
+                "duration_secs": 1.5,
+                "report_url": "https://ci/artifacts/lint.json",
+                "run_id": "run",
+            })
+        );
+    }
+
+    #[test]
+    fn conclusion_follows_severity() {
+        assert_eq!(check_conclusion(&[]), "success");
+        assert_eq!(
+            check_conclusion(&[lint(None, None, LintSeverity::Advice)]),
+            "neutral"
+        );
+        assert_eq!(check_title(&[]), "No lint issues");
+    }
+
+    #[test]
+    fn json_with_and_without_header_is_read() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let mut lints = HashMap::new();
+        lints.insert(None, vec![lint(None, None, LintSeverity::Error)]);
+        for render in [
+            render_lint_messages_json,
+            render_lint_messages_json_v1,
+            render_lint_messages_json_pretty,
+        ] {
+            let path = dir.path().join("lint.json");
+            render(&mut std::fs::File::create(&path)?, &lints)?;
+            let read = read_lint_messages(&path.display().to_string())?;
+            assert_eq!(read.len(), 1);
+            assert_eq!(read[0].code, "TEST");
+        }
+
+        // Of several appended runs, the last one counts.
+        let path = dir.path().join("appended.json");
+        let mut file = std::fs::File::create(&path)?;
+        render_lint_messages_json(&mut file, &lints)?;
+        render_lint_messages_json(&mut file, &HashMap::new())?;
+        assert!(read_lint_messages(&path.display().to_string())?.is_empty());
+
+        let newer = format!(
+            "{{\"schema_version\": {}, \"lintrunner_version\": \"99.0.0\"}}",
+            JSON_SCHEMA_VERSION + 1
+        );
+        assert!(JsonHeader::parse(&newer).is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,299 @@
+//! Searching the issues past runs found.
+//!
+//! Every lint run stores the issues it found, without their fixes and with
+//! paths relative to the root of the repo. `lintrunner query` filters them by
+//! code, date and path, and prints one row per issue per run, as JSON or CSV,
+//! for ad-hoc analysis like "how many E501s were there in `torch/` each day
+//! this month".
+
+use std::io::Write;
+use std::path::Path;
+
+use anyhow::{bail, Context, Result};
+use chrono::{DateTime, NaiveDate, Utc};
+use clap::ArgEnum;
+use glob::Pattern;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    baseline::normalize_path, lint_message::LintMessage, persistent_data::PersistentDataStore,
+    render::csv_field,
+};
+
+/// The issues a lint run found, as stored for `lintrunner query`.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct StoredResults {
+    /// When the run started, in RFC 3339 format.
+    pub timestamp: String,
+    /// The commit that was checked out, if in a git checkout.
+    pub revision: Option<String>,
+    pub messages: Vec<LintMessage>,
+}
+
+impl StoredResults {
+    /// Keep what is needed to query `lints` later: fixes can be huge, and
+    /// paths relative to the current directory are meaningless later.
+    pub fn new<'a>(
+        lints: impl IntoIterator<Item = &'a LintMessage>,
+        timestamp: &str,
+        revision: Option<String>,
+        root: Option<&Path>,
+    ) -> StoredResults {
+        let messages = lints
+            .into_iter()
+            .map(|lint| LintMessage {
+                path: lint.path.as_deref().map(|path| normalize_path(path, root)),
+                original: None,
+                replacement: None,
+                edits: Vec::new(),
+                ..lint.clone()
+            })
+            .collect();
+        StoredResults {
+            timestamp: timestamp.to_string(),
+            revision,
+            messages,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ArgEnum)]
+pub enum QueryFormat {
+    /// One object per line
+    Json,
+    Csv,
+}
+
+/// Which stored issues to print.
+#[derive(Debug, Default)]
+pub struct QueryFilter {
+    /// Linter codes or issue names, e.g. `FLAKE8` or `E501`. Empty means any.
+    pub codes: Vec<String>,
+    /// Only runs that started on or after this.
+    pub since: Option<DateTime<Utc>>,
+    /// Globs the path, relative to the root of the repo, must match one of.
+    /// Empty means any.
+    pub paths: Vec<Pattern>,
+}
+
+impl QueryFilter {
+    fn keeps(&self, lint: &LintMessage) -> bool {
+        let code_matches = self.codes.is_empty()
+            || self
+                .codes
+                .iter()
+                .any(|code| *code == lint.code || *code == lint.name);
+        let path_matches = self.paths.is_empty()
+            || lint
+                .path
+                .as_ref()
+                .is_some_and(|path| self.paths.iter().any(|pattern| pattern.matches(path)));
+        code_matches && path_matches
+    }
+}
+
+/// Parse the `--after` of `query` and `rage`: a date, a full RFC 3339 timestamp, or a time relative to
+/// now, like "2 hours ago".
+pub fn parse_since(since: &str) -> Result<DateTime<Utc>> {
+    parse_since_at(since, Utc::now())
+}
+
+fn parse_since_at(since: &str, now: DateTime<Utc>) -> Result<DateTime<Utc>> {
+    if let Some(ago) = parse_ago(since) {
+        return Ok(now - ago);
+    }
+    if let Ok(date) = NaiveDate::parse_from_str(since, "%Y-%m-%d") {
+        // Unwrap is fine because midnight exists on every day.
+        return Ok(date.and_hms_opt(0, 0, 0).unwrap().and_utc());
+    }
+    match DateTime::parse_from_rfc3339(since) {
+        Ok(timestamp) => Ok(timestamp.with_timezone(&Utc)),
+        Err(_) => bail!(
+            "Invalid date '{}', expected e.g. 2024-01-31, 2024-01-31T12:00:00Z or '3 hours ago'",
+            since
+        ),
+    }
+}
+
+/// Parse e.g. "3 hours ago" or "1 day ago".
+fn parse_ago(since: &str) -> Option<chrono::Duration> {
+    let words: Vec<&str> = since.split_whitespace().collect();
+    let [count, unit, "ago"] = words[..] else {
+        return None;
+    };
+    let count: i64 = count.parse().ok()?;
+    let duration = match unit.trim_end_matches('s') {
+        "second" => chrono::Duration::seconds(count),
+        "minute" => chrono::Duration::minutes(count),
+        "hour" => chrono::Duration::hours(count),
+        "day" => chrono::Duration::days(count),
+        "week" => chrono::Duration::weeks(count),
+        _ => return None,
+    };
+    Some(duration)
+}
+
+/// A stored issue, with the run that found it.
+#[derive(Serialize)]
+struct Row<'a> {
+    timestamp: &'a str,
+    revision: Option<&'a str>,
+    #[serde(flatten)]
+    message: &'a LintMessage,
+}
+
+const CSV_COLUMNS: [&str; 10] = [
+    "timestamp",
+    "revision",
+    "path",
+    "line",
+    "char",
+    "code",
+    "severity",
+    "name",
+    "description",
+    "fingerprint",
+];
+
+impl Row<'_> {
+    fn csv_fields(&self) -> [String; CSV_COLUMNS.len()] {
+        let number = |n: Option<usize>| n.map(|n| n.to_string()).unwrap_or_default();
+        [
+            self.timestamp.to_string(),
+            self.revision.unwrap_or_default().to_string(),
+            self.message.path.clone().unwrap_or_default(),
+            number(self.message.line),
+            number(self.message.char),
+            self.message.code.clone(),
+            self.message.severity.label().to_string(),
+            self.message.name.clone(),
+            self.message.description.clone().unwrap_or_default(),
+            self.message.fingerprint.clone().unwrap_or_default(),
+        ]
+    }
+}
+
+/// Print the stored issues `filter` keeps, oldest run first.
+pub fn do_query(
+    persistent_data_store: &PersistentDataStore,
+    filter: &QueryFilter,
+    format: QueryFormat,
+) -> Result<i32> {
+    let mut stdout = std::io::stdout().lock();
+    if format == QueryFormat::Csv {
+        writeln!(stdout, "{}", CSV_COLUMNS.join(","))?;
+    }
+    for run in persistent_data_store.past_results()? {
+        let started = DateTime::parse_from_rfc3339(&run.timestamp)
+            .with_context(|| format!("Invalid timestamp in stored results: {}", run.timestamp))?;
+        if filter.since.is_some_and(|since| started < since) {
+            continue;
+        }
+        for message in run.messages.iter().filter(|lint| filter.keeps(lint)) {
+            let row = Row {
+                timestamp: &run.timestamp,
+                revision: run.revision.as_deref(),
+                message,
+            };
+            match format {
+                QueryFormat::Json => writeln!(stdout, "{}", serde_json::to_string(&row)?)?,
+                QueryFormat::Csv => {
+                    let fields: Vec<String> = row
+                        .csv_fields()
+                        .iter()
+                        .map(|field| csv_field(field))
+                        .collect();
+                    writeln!(stdout, "{}", fields.join(","))?;
+                }
+            }
+        }
+    }
+    Ok(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lint_message::LintSeverity;
+
+    fn lint(path: &str, name: &str) -> LintMessage {
+        LintMessage {
+            path: Some(path.to_string()),
+            line: Some(3),
+            description: Some("line too long, \"really\"".to_string()),
+            original: Some("x = 1\n".to_string()),
+            replacement: Some("x = 1\n".to_string()),
+            ..LintMessage::for_test("FLAKE8", LintSeverity::Warning, name)
+        }
+    }
+
+    #[test]
+    fn stored_issues_are_filtered() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let root = dir.path().canonicalize()?;
+        std::fs::create_dir(root.join("torch"))?;
+        for path in ["torch/nn.py", "setup.py"] {
+            std::fs::write(root.join(path), "x = 1\n")?;
+        }
+        let stored = StoredResults::new(
+            &[
+                lint(&root.join("torch/nn.py").display().to_string(), "E501"),
+                lint(&root.join("setup.py").display().to_string(), "E501"),
+            ],
+            "2024-01-31T12:00:00Z",
+            None,
+            Some(&root),
+        );
+        assert_eq!(stored.messages[0].path.as_deref(), Some("torch/nn.py"));
+        assert!(stored.messages[0].replacement.is_none());
+
+        let filter = QueryFilter {
+            codes: vec!["E501".to_string()],
+            since: Some(parse_since("2024-01-01")?),
+            paths: vec![Pattern::new("torch/**")?],
+        };
+        let kept: Vec<_> = stored
+            .messages
+            .iter()
+            .filter(|lint| filter.keeps(lint))
+            .collect();
+        assert_eq!(kept.len(), 1);
+        assert!(!QueryFilter {
+            codes: vec!["W291".to_string()],
+            ..Default::default()
+        }
+        .keeps(kept[0]));
+
+        let row = Row {
+            timestamp: &stored.timestamp,
+            revision: None,
+            message: kept[0],
+        };
+        let fields: Vec<String> = row.csv_fields().iter().map(|f| csv_field(f)).collect();
+        assert_eq!(
+            fields.join(","),
+            "2024-01-31T12:00:00Z,,torch/nn.py,3,,FLAKE8,Warning,E501,\"line too long, \"\"really\"\"\","
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn since_can_be_relative() -> Result<()> {
+        let now = parse_since("2024-01-31T12:00:00Z")?;
+        assert_eq!(
+            parse_since_at("1 hour ago", now)?,
+            parse_since("2024-01-31T11:00:00Z")?
+        );
+        assert_eq!(
+            parse_since_at("2 days ago", now)?,
+            parse_since("2024-01-29T12:00:00Z")?
+        );
+        assert_eq!(
+            parse_since_at("2024-01-01", now)?,
+            parse_since("2024-01-01")?
+        );
+        assert!(parse_since_at("2 fortnights ago", now).is_err());
+        assert!(parse_since_at("yesterday", now).is_err());
+        Ok(())
+    }
+}
@@ -1,12 +1,179 @@
-use crate::persistent_data::{PersistentDataStore, RunInfo};
-use anyhow::{Context, Result};
+use crate::{
+    persistent_data::{ExitInfo, PersistentDataStore, RunInfo},
+    publish::{github_env, github_request, http_request_raw},
+};
+use anyhow::{bail, Context, Result};
+use chrono::{DateTime, Utc};
 use console::style;
 use dialoguer::{theme::ColorfulTheme, Select};
+use regex::Regex;
+use serde_json::{json, Value};
 
-fn select_past_runs(persistent_data_store: &PersistentDataStore) -> Result<Option<RunInfo>> {
-    let runs = persistent_data_store.past_runs()?;
-    if runs.is_empty() {
-        return Ok(None);
+/// Where `rage --upload` sends a report.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum UploadTarget {
+    /// A private GitHub Gist, created with `GITHUB_TOKEN`.
+    Gist,
+    /// A paste service that takes the report as the body of a POST, and
+    /// responds with its URL.
+    Endpoint(String),
+}
+
+impl UploadTarget {
+    /// Parse `gist` or an http(s) URL.
+    pub fn parse(target: &str) -> Result<UploadTarget> {
+        if target == "gist" {
+            Ok(UploadTarget::Gist)
+        } else if target.starts_with("https://") || target.starts_with("http://") {
+            Ok(UploadTarget::Endpoint(target.to_string()))
+        } else {
+            bail!(
+                "Invalid upload target '{}': expected 'gist' or an http(s) URL",
+                target
+            )
+        }
+    }
+}
+
+/// Names of environment variables whose values are kept out of uploaded
+/// reports.
+const SECRET_ENV_VAR_MARKERS: &[&str] = &["TOKEN", "SECRET", "PASSWORD", "PASSWD", "KEY", "AUTH"];
+
+/// Hide what shouldn't leave the machine in `report`: the values of
+/// environment variables that look like secrets, access tokens, and the home
+/// directory, which usually has the user name in it.
+pub fn redact(report: &str) -> String {
+    let mut report = report.to_string();
+    let mut secrets: Vec<String> = std::env::vars()
+        .filter(|(name, value)| {
+            let name = name.to_uppercase();
+            value.len() >= 8 && SECRET_ENV_VAR_MARKERS.iter().any(|m| name.contains(m))
+        })
+        .map(|(_, value)| value)
+        .collect();
+    // Longest first, in case one secret contains another.
+    secrets.sort_by_key(|secret| std::cmp::Reverse(secret.len()));
+    for secret in secrets {
+        report = report.replace(&secret, "[REDACTED]");
+    }
+    // Unwrap is fine because the regex is valid.
+    let tokens =
+        Regex::new(r"\b(gh[pousr]_[A-Za-z0-9]{20,}|github_pat_[A-Za-z0-9_]{20,})").unwrap();
+    let mut report = tokens.replace_all(&report, "[REDACTED]").into_owned();
+    if let Some(home) = std::env::var_os("HOME") {
+        let home = home.to_string_lossy();
+        if home.len() > 1 {
+            report = report.replace(home.as_ref(), "~");
+        }
+    }
+    report
+}
+
+/// Upload `report` to `target`, returning the URL it can be seen at.
+fn upload(report: &str, target: &UploadTarget) -> Result<String> {
+    match target {
+        UploadTarget::Gist => {
+            let token = github_env("GITHUB_TOKEN")?;
+            let api_url = std::env::var("GITHUB_API_URL")
+                .unwrap_or_else(|_| "https://api.github.com".to_string());
+            let gist = github_request(
+                "POST",
+                &format!("{}/gists", api_url),
+                &token,
+                &json!({
+                    "description": "lintrunner rage report",
+                    "public": false,
+                    "files": {"lintrunner-rage.txt": {"content": report}},
+                }),
+            )?;
+            gist["html_url"]
+                .as_str()
+                .map(str::to_string)
+                .context("GitHub did not return a URL for the gist")
+        }
+        UploadTarget::Endpoint(url) => {
+            let (status, response) =
+                http_request_raw("POST", url, &[], "text/plain", report.as_bytes())?;
+            if !(200..300).contains(&status) {
+                bail!(
+                    "Uploading to {} failed with status {}: {}",
+                    url,
+                    status,
+                    response
+                );
+            }
+            Ok(uploaded_url(&response))
+        }
+    }
+}
+
+/// The URL in a paste service's response: either all of it, or the `url`
+/// field of a JSON object.
+fn uploaded_url(response: &str) -> String {
+    match serde_json::from_str::<Value>(response) {
+        Ok(Value::Object(object)) => match object.get("url") {
+            Some(Value::String(url)) => url.clone(),
+            _ => response.trim().to_string(),
+        },
+        _ => response.trim().to_string(),
+    }
+}
+
+/// Which past run to report on.
+#[derive(Debug, Clone)]
+pub enum RunSelector {
+    /// The run this many runs ago; 0 is the most recent.
+    Invocation(usize),
+    /// The run whose id starts with this.
+    Id(String),
+    /// A run that started at or after this time.
+    Since(DateTime<Utc>),
+    /// Whichever run the user picks.
+    Any,
+}
+
+fn select_run(
+    persistent_data_store: &PersistentDataStore,
+    selector: &RunSelector,
+) -> Result<Option<RunInfo>> {
+    if let RunSelector::Invocation(invocation) = selector {
+        return Ok(Some(persistent_data_store.past_run(*invocation)?));
+    }
+    let runs = matching_runs(persistent_data_store.past_runs()?, selector);
+    match (selector, runs.len()) {
+        (RunSelector::Any, 0) => Ok(None),
+        (RunSelector::Id(id), 0) => bail!("No stored run has an id starting with '{}'", id),
+        (RunSelector::Since(since), 0) => bail!("No stored run started since {}", since),
+        (RunSelector::Id(_) | RunSelector::Since(_), 1) => {
+            Ok(runs.into_iter().next().map(|(run_info, _)| run_info))
+        }
+        // Even a single run is confirmed when nothing narrowed the choice.
+        _ => pick_run(runs),
+    }
+}
+
+/// The runs in `runs` that `selector` matches, keeping their order.
+fn matching_runs(
+    runs: Vec<(RunInfo, ExitInfo)>,
+    selector: &RunSelector,
+) -> Vec<(RunInfo, ExitInfo)> {
+    runs.into_iter()
+        .filter(|(run_info, _)| match selector {
+            RunSelector::Id(id) => run_info.id().starts_with(id.as_str()),
+            RunSelector::Since(since) => DateTime::parse_from_rfc3339(&run_info.timestamp)
+                .is_ok_and(|timestamp| timestamp >= *since),
+            RunSelector::Invocation(_) | RunSelector::Any => true,
+        })
+        .collect()
+}
+
+fn pick_run(runs: Vec<(RunInfo, ExitInfo)>) -> Result<Option<RunInfo>> {
+    if !console::user_attended() {
+        let ids: Vec<String> = runs.iter().map(|(run_info, _)| run_info.id()).collect();
+        bail!(
+            "Several runs match, pick one with --run:\n  {}",
+            ids.join("\n  ")
+        );
     }
     let items: Vec<String> = runs
         .iter()
@@ -34,21 +201,27 @@ fn select_past_runs(persistent_data_store: &PersistentDataStore) -> Result<Optio
     Ok(selection.map(|i| runs.into_iter().nth(i).unwrap().0))
 }
 
+/// Print the report of a past run, or with `upload_target`, upload it (with
+/// secrets redacted) and print where to.
 pub fn do_rage(
     persistent_data_store: &PersistentDataStore,
-    invocation: Option<usize>,
+    selector: &RunSelector,
+    upload_target: Option<UploadTarget>,
 ) -> Result<i32> {
-    let run = match invocation {
-        Some(invocation) => Some(persistent_data_store.past_run(invocation)?),
-        None => select_past_runs(persistent_data_store)?,
-    };
+    let run = select_run(persistent_data_store, selector)?;
 
     match run {
         Some(run) => {
             let report = persistent_data_store
                 .get_run_report(&run)
                 .context("getting selected run report")?;
-            print!("{}", report);
+            match upload_target {
+                Some(target) => {
+                    let url = upload(&redact(&report), &target)?;
+                    println!("Uploaded the report to {}", style(url).bold());
+                }
+                None => print!("{}", report),
+            }
         }
         None => {
             println!("{}", style("Nothing selected, exiting.").yellow());
@@ -56,3 +229,79 @@ pub fn do_rage(
     }
     Ok(0)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reports_are_redacted() {
+        std::env::set_var("LINTRUNNER_TEST_RAGE_TOKEN", "hunter2hunter2");
+        let report = format!(
+            "args: 'lintrunner' '--token=hunter2hunter2'\n\
+             auth: ghp_{}\n",
+            "a1".repeat(18)
+        );
+        assert_eq!(
+            redact(&report),
+            "args: 'lintrunner' '--token=[REDACTED]'\nauth: [REDACTED]\n"
+        );
+    }
+
+    #[test]
+    fn runs_are_matched_by_id_and_time() -> Result<()> {
+        let run = |timestamp: &str| {
+            (
+                RunInfo {
+                    args: vec!["lintrunner".to_string()],
+                    timestamp: timestamp.to_string(),
+                },
+                ExitInfo { code: 0, err: None },
+            )
+        };
+        let runs = || {
+            vec![
+                run("2024-01-31T12:00:00.000+01:00"),
+                run("2024-01-31T09:30:00.000+01:00"),
+            ]
+        };
+        let timestamps = |selector| -> Vec<String> {
+            matching_runs(runs(), &selector)
+                .into_iter()
+                .map(|(run_info, _)| run_info.timestamp)
+                .collect()
+        };
+
+        assert_eq!(
+            timestamps(RunSelector::Id("2024-01-31T09-30".to_string())),
+            ["2024-01-31T09:30:00.000+01:00"]
+        );
+        assert_eq!(
+            timestamps(RunSelector::Since(
+                DateTime::parse_from_rfc3339("2024-01-31T10:00:00Z")?.with_timezone(&Utc)
+            )),
+            ["2024-01-31T12:00:00.000+01:00"]
+        );
+        assert_eq!(timestamps(RunSelector::Any).len(), 2);
+        Ok(())
+    }
+
+    #[test]
+    fn upload_targets_are_parsed() {
+        assert_eq!(UploadTarget::parse("gist").unwrap(), UploadTarget::Gist);
+        assert_eq!(
+            UploadTarget::parse("https://paste.example.com").unwrap(),
+            UploadTarget::Endpoint("https://paste.example.com".to_string())
+        );
+        assert!(UploadTarget::parse("pastebin").is_err());
+
+        assert_eq!(
+            uploaded_url("https://paste.example.com/abc\n"),
+            "https://paste.example.com/abc"
+        );
+        assert_eq!(
+            uploaded_url(r#"{"url": "https://paste.example.com/abc", "id": "abc"}"#),
+            "https://paste.example.com/abc"
+        );
+    }
+}
@@ -1,18 +1,121 @@
 use std::fmt;
 use std::io::Write;
-use std::{cmp, collections::HashMap, fs};
+use std::path::PathBuf;
+use std::{
+    cmp,
+    collections::{BTreeMap, HashMap},
+    fs,
+};
 
 use anyhow::{anyhow, Result};
-use console::{style, Style, Term};
+use console::style;
 use itertools::Itertools;
+use serde::{Deserialize, Serialize};
 use similar::{ChangeTag, DiffableStr, TextDiff};
 use textwrap::indent;
 
-use crate::lint_message::{LintMessage, LintSeverity};
+use crate::lint_message::LintMessage;
 use crate::path::get_display_path;
+use crate::streams;
+use crate::theme;
+
+mod arc;
+mod azure;
+mod buildkite;
+mod csv;
+mod gerrit;
+mod github;
+pub mod sink;
+mod sonarqube;
+mod summary;
+mod teamcity;
+
+pub use arc::render_lint_messages_arc;
+pub use azure::render_lint_messages_azure;
+pub use buildkite::render_lint_messages_buildkite;
+pub(crate) use csv::csv_field;
+pub use csv::{render_lint_messages_csv, set_csv_columns, CsvColumn, DEFAULT_CSV_COLUMNS};
+pub use gerrit::render_lint_messages_gerrit;
+pub use github::render_lint_messages_github;
+pub use sonarqube::render_lint_messages_sonarqube;
+pub use summary::render_lint_messages_summary;
+pub use teamcity::render_lint_messages_teamcity;
 
 static CONTEXT_LINES: usize = 3;
 
+/// The version of the JSON written by `--output json` and `--tee-json`. Bump
+/// this when consumers need to tell the new format apart. Version 1 is the
+/// bare lint messages of `--output json-v1`, without a header.
+pub const JSON_SCHEMA_VERSION: u32 = 2;
+
+/// The first line of `--output json`, describing the lint messages on the
+/// lines after it.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct JsonHeader {
+    pub schema_version: u32,
+    pub lintrunner_version: String,
+    /// What was run, so that archived results can be understood later. Only
+    /// `--tee-json` includes this, as it is written after the run.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub run: Option<RunMetadata>,
+}
+
+/// How the lint messages after a `JsonHeader` came about.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RunMetadata {
+    /// The command line lintrunner was run with.
+    pub args: Vec<String>,
+    /// A hash of the contents of the configs the linters came from.
+    pub config_hash: String,
+    /// The commit that was checked out, if in a git checkout.
+    pub revision: Option<String>,
+    /// The commit changes were linted relative to, unless specific files (or
+    /// all files) were linted.
+    pub merge_base: Option<String>,
+    /// When the run started, in RFC 3339 format.
+    pub timestamp: String,
+    pub duration_secs: f64,
+    /// How long each linter took, in seconds.
+    pub linter_durations: BTreeMap<String, f64>,
+}
+
+impl Default for JsonHeader {
+    fn default() -> Self {
+        JsonHeader {
+            schema_version: JSON_SCHEMA_VERSION,
+            lintrunner_version: env!("CARGO_PKG_VERSION").to_string(),
+            run: None,
+        }
+    }
+}
+
+impl JsonHeader {
+    /// If `line` is a header rather than a lint message, parse it, and check
+    /// that this version of lintrunner understands what follows.
+    pub fn parse(line: &str) -> Result<Option<JsonHeader>> {
+        let value: serde_json::Value = serde_json::from_str(line)?;
+        if value.get("schema_version").is_none() {
+            return Ok(None);
+        }
+        let header: JsonHeader = serde_json::from_value(value)?;
+        if header.schema_version > JSON_SCHEMA_VERSION {
+            return Err(anyhow!(
+                "These lint results were written by lintrunner {}, in a format this \
+                 version doesn't understand (schema version {}, expected at most {})",
+                header.lintrunner_version,
+                header.schema_version,
+                JSON_SCHEMA_VERSION
+            ));
+        }
+        Ok(Some(header))
+    }
+
+    pub fn write(&self, stdout: &mut impl Write) -> Result<()> {
+        writeln!(stdout, "{}", serde_json::to_string(self)?)?;
+        Ok(())
+    }
+}
+
 pub enum PrintedLintErrors {
     Yes,
     No,
@@ -71,9 +174,93 @@ pub fn render_lint_messages_oneline(
     }
 }
 
+/// All lint messages, ordered by path and position, for formats that are
+/// written as a single document.
+fn sorted_lints(lint_messages: &HashMap<Option<String>, Vec<LintMessage>>) -> Vec<&LintMessage> {
+    let mut lints: Vec<&LintMessage> = lint_messages.values().flatten().collect();
+    lints.sort_by(|a, b| {
+        (&a.path, a.line, a.char, &a.code, &a.name, &a.description).cmp(&(
+            &b.path,
+            b.line,
+            b.char,
+            &b.code,
+            &b.name,
+            &b.description,
+        ))
+    });
+    lints
+}
+
+/// The directory that paths are relative to in formats read by CI systems
+/// and code review tools: the root of the repo, or the current directory
+/// outside of one.
+fn repo_root() -> Result<PathBuf> {
+    match crate::git::get_git_root() {
+        Ok(root) => Ok(root.to_path_buf()),
+        Err(_) => Ok(std::env::current_dir()?),
+    }
+}
+
+/// The single document written by `--output json-pretty`: the header's fields,
+/// then all of the lint messages.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct JsonDocument {
+    #[serde(flatten)]
+    pub header: JsonHeader,
+    pub messages: Vec<LintMessage>,
+}
+
+impl JsonDocument {
+    /// Parse a document written by `--output json-pretty`, and check that this
+    /// version of lintrunner understands it.
+    pub fn parse(contents: &str) -> Result<JsonDocument> {
+        let document: JsonDocument = serde_json::from_str(contents)?;
+        // Reuse the header's version check.
+        JsonHeader::parse(&serde_json::to_string(&document.header)?)?;
+        Ok(document)
+    }
+}
+
+/// Render an indented document, for people to read and diff. Fields are always
+/// in the order of the schema, and messages are sorted by path, then position.
+pub fn render_lint_messages_json_pretty(
+    stdout: &mut impl Write,
+    lint_messages: &HashMap<Option<String>, Vec<LintMessage>>,
+) -> Result<PrintedLintErrors> {
+    let document = JsonDocument {
+        header: JsonHeader::default(),
+        messages: sorted_lints(lint_messages).into_iter().cloned().collect(),
+    };
+    writeln!(stdout, "{}", serde_json::to_string_pretty(&document)?)?;
+
+    if document.messages.is_empty() {
+        Ok(PrintedLintErrors::No)
+    } else {
+        Ok(PrintedLintErrors::Yes)
+    }
+}
+
+/// Render a header, then one lint message per line.
 pub fn render_lint_messages_json(
     stdout: &mut impl Write,
     lint_messages: &HashMap<Option<String>, Vec<LintMessage>>,
+) -> Result<PrintedLintErrors> {
+    render_lint_messages_json_with_header(stdout, &JsonHeader::default(), lint_messages)
+}
+
+pub fn render_lint_messages_json_with_header(
+    stdout: &mut impl Write,
+    header: &JsonHeader,
+    lint_messages: &HashMap<Option<String>, Vec<LintMessage>>,
+) -> Result<PrintedLintErrors> {
+    header.write(stdout)?;
+    render_lint_messages_json_v1(stdout, lint_messages)
+}
+
+/// Render one lint message per line, with no header.
+pub fn render_lint_messages_json_v1(
+    stdout: &mut impl Write,
+    lint_messages: &HashMap<Option<String>, Vec<LintMessage>>,
 ) -> Result<PrintedLintErrors> {
     let mut printed = false;
     for lint_message in lint_messages.values().flatten() {
@@ -93,7 +280,11 @@ pub fn render_lint_messages(
     lint_messages: &HashMap<Option<String>, Vec<LintMessage>>,
 ) -> Result<PrintedLintErrors> {
     if lint_messages.is_empty() {
-        writeln!(stdout, "{} No lint issues.", style("ok").green())?;
+        writeln!(
+            stdout,
+            "{} No lint issues.",
+            theme::current().ok.apply_to("ok")
+        )?;
 
         return Ok(PrintedLintErrors::No);
     }
@@ -124,7 +315,7 @@ pub fn render_lint_messages(
                     stdout,
                     "{} Lint for {}:\n\n",
                     style(">>>").bold(),
-                    style(path_to_print).underlined()
+                    theme::current().path.apply_to(path_to_print)
                 )?;
             }
         }
@@ -139,11 +330,29 @@ pub fn render_lint_messages(
                 }
             }
 
-            // If we have original and replacement, show the diff.
-            if let (Some(original), Some(replacement)) =
-                (&lint_message.original, &lint_message.replacement)
-            {
-                write_context_diff(stdout, original, replacement)?;
+            // If a fix was suggested, show the diff.
+            let fixes = lint_message.fixes().unwrap_or_default();
+            if !fixes.is_empty() {
+                writeln!(
+                    stdout,
+                    "\n    {}",
+                    theme::current()
+                        .hint
+                        .apply_to("You can run `lintrunner -a` to apply this patch.")
+                )?;
+                for fix in &fixes {
+                    // Fixes can change files other than the one linted.
+                    if path.as_ref() != Some(&fix.path) {
+                        writeln!(
+                            stdout,
+                            "\n    {}",
+                            theme::current()
+                                .path
+                                .apply_to(get_display_path(&fix.path, &current_dir))
+                        )?;
+                    }
+                    write_context_diff(stdout, &fix.original, &fix.replacement)?;
+                }
             } else if let (Some(highlight_line), Some(path)) = (&lint_message.line, path) {
                 // Otherwise, write the context code snippet.
                 write_context(stdout, path, highlight_line)?;
@@ -183,15 +392,15 @@ fn write_context(stdout: &mut impl Write, path: &str, highlight_line: &usize) ->
                     write!(
                         stdout,
                         "    >>> {:>width$}  |{}",
-                        style(line_number).dim(),
-                        style(line).yellow(),
+                        theme::current().context.apply_to(line_number),
+                        theme::current().highlight.apply_to(line),
                         width = max_pad
                     )?;
                 } else {
                     write!(
                         stdout,
                         "        {:>width$}  |{}",
-                        style(line_number).dim(),
+                        theme::current().context.apply_to(line_number),
                         line,
                         width = max_pad
                     )?;
@@ -216,11 +425,6 @@ fn write_context(stdout: &mut impl Write, path: &str, highlight_line: &usize) ->
 
 // Write the context, computing and styling a diff from the original to the suggested replacement.
 fn write_context_diff(stdout: &mut impl Write, original: &str, replacement: &str) -> Result<()> {
-    writeln!(
-        stdout,
-        "\n    {}",
-        style("You can run `lintrunner -a` to apply this patch.").cyan()
-    )?;
     stdout.write_all(b"\n")?;
     let diff = TextDiff::from_lines(original, replacement);
 
@@ -244,9 +448,9 @@ fn write_context_diff(stdout: &mut impl Write, original: &str, replacement: &str
         for op in group {
             for change in diff.iter_inline_changes(op) {
                 let (sign, s) = match change.tag() {
-                    ChangeTag::Delete => ("-", Style::new().red()),
-                    ChangeTag::Insert => ("+", Style::new().green()),
-                    ChangeTag::Equal => (" ", Style::new().dim()),
+                    ChangeTag::Delete => ("-", theme::current().removed.clone()),
+                    ChangeTag::Insert => ("+", theme::current().added.clone()),
+                    ChangeTag::Equal => (" ", theme::current().context.clone()),
                 };
                 let changeset = Changeset {
                     max_pad,
@@ -256,7 +460,7 @@ fn write_context_diff(stdout: &mut impl Write, original: &str, replacement: &str
                 write!(
                     stdout,
                     "    {} |{}",
-                    style(changeset).dim(),
+                    theme::current().context.apply_to(changeset),
                     s.apply_to(sign).bold()
                 )?;
                 for (emphasized, value) in change.iter_strings_lossy() {
@@ -276,20 +480,32 @@ fn write_context_diff(stdout: &mut impl Write, original: &str, replacement: &str
     Ok(())
 }
 
-// Write: `   Error  (LINTER) prefer-using-this-over-that\n`
+// Write: `   Error  (LINTER) prefer-using-this-over-that\n`, and how long
+// ago the issue was first seen if it has been around for a while.
 fn write_summary_line(stdout: &mut impl Write, lint_message: &LintMessage) -> Result<()> {
-    let error_style = match lint_message.severity {
-        LintSeverity::Error => Style::new().on_red().bold(),
-        LintSeverity::Warning | LintSeverity::Advice | LintSeverity::Disabled => {
-            Style::new().on_yellow().bold()
-        }
-    };
+    let error_style = theme::current().severity(lint_message.severity);
+    let age = lint_message
+        .first_seen
+        .as_ref()
+        .and_then(|first_seen| first_seen.age(chrono::Utc::now()))
+        .map(|age| age.num_days())
+        .filter(|days| *days > 0)
+        .map(|days| {
+            let age = format!(
+                "(first seen {} day{} ago)",
+                days,
+                if days == 1 { "" } else { "s" }
+            );
+            format!(" {}", theme::current().context.apply_to(age))
+        })
+        .unwrap_or_default();
     writeln!(
         stdout,
-        "  {} ({}) {}",
+        "  {} ({}) {}{}",
         error_style.apply_to(lint_message.severity.label()),
         lint_message.code,
         style(&lint_message.name).underlined(),
+        age,
     )?;
     Ok(())
 }
@@ -356,7 +572,7 @@ impl fmt::Display for Line {
 }
 
 pub fn print_error(err: &anyhow::Error) -> std::io::Result<()> {
-    let mut stderr = Term::stderr();
+    let mut stderr = streams::diagnostics().term();
     let mut chain = err.chain();
 
     if let Some(error) = chain.next() {
@@ -0,0 +1,110 @@
+//! Arcanist's lint message JSON, so that lintrunner can be the engine behind
+//! `arc lint`.
+//!
+//! This matches the output of `arc lint --output json`: one line per file,
+//! each an object mapping the path to its messages.
+
+use std::collections::{BTreeMap, HashMap};
+use std::io::Write;
+
+use anyhow::Result;
+use serde_json::{json, Value};
+
+use super::{repo_root, sorted_lints, PrintedLintErrors};
+use crate::{
+    lint_message::{LintMessage, LintSeverity},
+    path::get_display_path,
+};
+
+pub fn render_lint_messages_arc(
+    stdout: &mut impl Write,
+    lint_messages: &HashMap<Option<String>, Vec<LintMessage>>,
+) -> Result<PrintedLintErrors> {
+    let root = repo_root()?;
+    let mut by_path: BTreeMap<String, Vec<Value>> = BTreeMap::new();
+    for lint_message in sorted_lints(lint_messages) {
+        // Arcanist has no notion of messages without a path; they end up
+        // under an empty one.
+        let path = lint_message
+            .path
+            .as_ref()
+            .map(|path| get_display_path(path, &root))
+            .unwrap_or_default();
+        let message = arc_message(&path, lint_message);
+        by_path.entry(path).or_default().push(message);
+    }
+
+    for (path, messages) in &by_path {
+        writeln!(stdout, "{}", json!({ path: messages }))?;
+    }
+
+    if by_path.is_empty() {
+        Ok(PrintedLintErrors::No)
+    } else {
+        Ok(PrintedLintErrors::Yes)
+    }
+}
+
+fn arc_message(path: &str, lint_message: &LintMessage) -> Value {
+    let severity = match lint_message.severity {
+        LintSeverity::Error => "error",
+        LintSeverity::Warning => "warning",
+        LintSeverity::Advice => "advice",
+        LintSeverity::Disabled => "disabled",
+    };
+    let mut message = json!({
+        "path": path,
+        "line": lint_message.line,
+        "char": lint_message.char,
+        "code": lint_message.code,
+        "severity": severity,
+        "name": lint_message.name,
+        "description": lint_message.description,
+    });
+    // Arcanist replaces `original` with `replacement` at the message's
+    // position. Our patches cover the whole file, so they start at the top.
+    // Arcanist can only fix the file a message is about, so changes to other
+    // files are left out.
+    let fixes = lint_message.fixes().unwrap_or_default();
+    if let Some(fix) = fixes
+        .into_iter()
+        .find(|fix| lint_message.path.as_ref() == Some(&fix.path))
+    {
+        message["line"] = json!(1);
+        message["char"] = json!(1);
+        message["original"] = json!(fix.original);
+        message["replacement"] = json!(fix.replacement);
+    }
+    message
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn patches_span_the_whole_file() {
+        let lint_message = LintMessage {
+            path: Some("a.py".to_string()),
+            line: Some(3),
+            char: Some(4),
+            original: Some("x=1\n".to_string()),
+            replacement: Some("x = 1\n".to_string()),
+            ..LintMessage::for_test("BLACK", LintSeverity::Warning, "format")
+        };
+        assert_eq!(
+            arc_message("a.py", &lint_message),
+            json!({
+                "path": "a.py",
+                "line": 1,
+                "char": 1,
+                "code": "BLACK",
+                "severity": "warning",
+                "name": "format",
+                "description": null,
+                "original": "x=1\n",
+                "replacement": "x = 1\n",
+            })
+        );
+    }
+}
@@ -0,0 +1,100 @@
+//! Azure Pipelines logging commands, which annotate the run summary.
+//!
+//! See https://learn.microsoft.com/en-us/azure/devops/pipelines/scripts/logging-commands#logissue-log-an-error-or-warning
+
+use std::collections::HashMap;
+use std::io::Write;
+
+use anyhow::Result;
+
+use super::{repo_root, sorted_lints, PrintedLintErrors};
+use crate::{
+    lint_message::{LintMessage, LintSeverity},
+    path::get_display_path,
+};
+
+pub fn render_lint_messages_azure(
+    stdout: &mut impl Write,
+    lint_messages: &HashMap<Option<String>, Vec<LintMessage>>,
+) -> Result<PrintedLintErrors> {
+    let root = repo_root()?;
+    let mut printed = false;
+    for lint_message in sorted_lints(lint_messages) {
+        printed = true;
+        // Azure only knows errors and warnings.
+        let issue_type = match lint_message.severity {
+            LintSeverity::Error => "error",
+            LintSeverity::Warning | LintSeverity::Advice | LintSeverity::Disabled => "warning",
+        };
+        let mut properties = vec![("type", issue_type.to_string())];
+        if let Some(path) = &lint_message.path {
+            properties.push(("sourcepath", get_display_path(path, &root)));
+        }
+        if let Some(line) = lint_message.line {
+            properties.push(("linenumber", line.to_string()));
+        }
+        if let Some(char) = lint_message.char {
+            properties.push(("columnnumber", char.to_string()));
+        }
+        properties.push(("code", lint_message.code.clone()));
+
+        let properties = properties
+            .iter()
+            .map(|(name, value)| format!("{}={}", name, escape_property(value)))
+            .collect::<Vec<_>>()
+            .join(";");
+        let mut message = format!("({}) {}", lint_message.code, lint_message.name);
+        if let Some(description) = &lint_message.description {
+            message.push_str(": ");
+            message.push_str(description);
+        }
+        writeln!(
+            stdout,
+            "##vso[task.logissue {}]{}",
+            properties,
+            escape_data(&message)
+        )?;
+    }
+
+    if printed {
+        Ok(PrintedLintErrors::Yes)
+    } else {
+        Ok(PrintedLintErrors::No)
+    }
+}
+
+fn escape_data(value: &str) -> String {
+    value
+        .replace('%', "%AZP25")
+        .replace('\r', "%0D")
+        .replace('\n', "%0A")
+}
+
+fn escape_property(value: &str) -> String {
+    escape_data(value).replace(';', "%3B").replace(']', "%5D")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn messages_become_logging_commands() -> Result<()> {
+        let mut lint_messages = HashMap::new();
+        lint_messages.insert(
+            None,
+            vec![LintMessage {
+                description: Some("100% broken;\nsee log".to_string()),
+                ..LintMessage::for_test("MYPY", LintSeverity::Advice, "crashed")
+            }],
+        );
+        let mut out = Vec::new();
+        render_lint_messages_azure(&mut out, &lint_messages)?;
+        assert_eq!(
+            String::from_utf8(out)?,
+            "##vso[task.logissue type=warning;code=MYPY](MYPY) crashed: 100%AZP25 broken;%0Asee log\n"
+        );
+        assert_eq!(escape_property("a;b]"), "a%3Bb%5D");
+        Ok(())
+    }
+}
@@ -0,0 +1,184 @@
+//! Markdown for Buildkite annotations, e.g.
+//! `lintrunner --output buildkite | buildkite-agent annotate`.
+//!
+//! Messages are grouped by severity, with a collapsible section per linter.
+//! When running in Buildkite, paths link to the file at the commit being
+//! built.
+
+use std::collections::{BTreeMap, HashMap};
+use std::io::Write;
+
+use anyhow::Result;
+
+use super::{repo_root, sorted_lints, PrintedLintErrors};
+use crate::{
+    lint_message::{LintMessage, LintSeverity},
+    path::get_display_path,
+};
+
+pub fn render_lint_messages_buildkite(
+    stdout: &mut impl Write,
+    lint_messages: &HashMap<Option<String>, Vec<LintMessage>>,
+) -> Result<PrintedLintErrors> {
+    let lints = sorted_lints(lint_messages);
+    if lints.is_empty() {
+        writeln!(stdout, "No lint issues.")?;
+        return Ok(PrintedLintErrors::No);
+    }
+
+    let root = repo_root()?;
+    let file_url = file_url_from_env();
+
+    let mut by_severity: BTreeMap<LintSeverity, BTreeMap<&str, Vec<&LintMessage>>> =
+        BTreeMap::new();
+    for lint in &lints {
+        by_severity
+            .entry(lint.severity)
+            .or_default()
+            .entry(&lint.code)
+            .or_default()
+            .push(lint);
+    }
+
+    let counts = by_severity
+        .iter()
+        .map(|(severity, by_code)| {
+            let count: usize = by_code.values().map(Vec::len).sum();
+            let noun = severity.label().to_lowercase();
+            match severity {
+                LintSeverity::Error | LintSeverity::Warning if count != 1 => {
+                    format!("{} {}s", count, noun)
+                }
+                _ => format!("{} {}", count, noun),
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(", ");
+    writeln!(stdout, "### Lint: {}", counts)?;
+
+    for (severity, by_code) in &by_severity {
+        writeln!(stdout, "\n#### {}\n", severity.label())?;
+        for (code, lints) in by_code {
+            writeln!(stdout, "<details>")?;
+            writeln!(
+                stdout,
+                "<summary><strong>{}</strong> ({})</summary>\n",
+                escape(code),
+                lints.len()
+            )?;
+            for lint in lints {
+                let location = match &lint.path {
+                    Some(path) => {
+                        let path = get_display_path(path, &root);
+                        let text = match lint.line {
+                            Some(line) => format!("{}:{}", path, line),
+                            None => path.clone(),
+                        };
+                        match &file_url {
+                            Some(file_url) => {
+                                format!("[`{}`]({})", text, file_url(&path, lint.line))
+                            }
+                            None => format!("`{}`", text),
+                        }
+                    }
+                    None => "General linter failure".to_string(),
+                };
+                write!(stdout, "- {} **{}**", location, escape(&lint.name))?;
+                if let Some(description) = &lint.description {
+                    write!(
+                        stdout,
+                        ": {}",
+                        escape(&description.lines().collect::<Vec<_>>().join(" "))
+                    )?;
+                }
+                writeln!(stdout)?;
+            }
+            writeln!(stdout, "\n</details>")?;
+        }
+    }
+
+    Ok(PrintedLintErrors::Yes)
+}
+
+type FileUrl = Box<dyn Fn(&str, Option<usize>) -> String>;
+
+/// Links to files at the commit Buildkite is building, if it is hosted
+/// somewhere with GitHub-style URLs.
+fn file_url_from_env() -> Option<FileUrl> {
+    let repo = std::env::var("BUILDKITE_REPO").ok()?;
+    let commit = std::env::var("BUILDKITE_COMMIT").ok()?;
+    let web_url = web_url(&repo)?;
+    Some(Box::new(move |path, line| {
+        let mut url = format!("{}/blob/{}/{}", web_url, commit, path);
+        if let Some(line) = line {
+            url.push_str(&format!("#L{}", line));
+        }
+        url
+    }))
+}
+
+/// Turn a git remote URL into the URL of the repo's web page.
+fn web_url(remote: &str) -> Option<String> {
+    let remote = remote.trim_end_matches('/').trim_end_matches(".git");
+    if let Some(rest) = remote.strip_prefix("git@") {
+        let (host, path) = rest.split_once(':')?;
+        Some(format!("https://{}/{}", host, path))
+    } else if remote.starts_with("https://") || remote.starts_with("http://") {
+        Some(remote.to_string())
+    } else {
+        None
+    }
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn remotes_become_web_urls() {
+        assert_eq!(
+            web_url("git@github.com:org/repo.git").as_deref(),
+            Some("https://github.com/org/repo")
+        );
+        assert_eq!(
+            web_url("https://gitlab.com/org/repo.git").as_deref(),
+            Some("https://gitlab.com/org/repo")
+        );
+        assert_eq!(web_url("/srv/git/repo"), None);
+    }
+
+    #[test]
+    fn lints_are_grouped_by_severity_and_linter() -> Result<()> {
+        let lint = |code: &str, severity, line| LintMessage {
+            path: Some("/nonexistent/a.py".to_string()),
+            line: Some(line),
+            description: Some("Uses <blink>".to_string()),
+            ..LintMessage::for_test(code, severity, "bad")
+        };
+        let mut lint_messages = HashMap::new();
+        lint_messages.insert(
+            Some("/nonexistent/a.py".to_string()),
+            vec![
+                lint("MYPY", LintSeverity::Warning, 2),
+                lint("FLAKE8", LintSeverity::Error, 1),
+                lint("FLAKE8", LintSeverity::Warning, 3),
+            ],
+        );
+        let mut out = Vec::new();
+        render_lint_messages_buildkite(&mut out, &lint_messages)?;
+        let out = String::from_utf8(out)?;
+        assert!(out.starts_with("### Lint: 1 error, 2 warnings\n"), "{out}");
+        let errors = out.find("#### Error").unwrap();
+        let warnings = out.find("#### Warning").unwrap();
+        assert!(errors < warnings);
+        assert_eq!(out.matches("<details>").count(), 3);
+        assert!(out.contains("**bad**: Uses &lt;blink&gt;"));
+        Ok(())
+    }
+}
@@ -0,0 +1,128 @@
+//! A CSV table with one row per issue, to paste into a spreadsheet for triage.
+//!
+//! Which columns there are, and in what order, is set with `--csv-columns`.
+
+use std::collections::HashMap;
+use std::io::Write;
+use std::sync::OnceLock;
+
+use anyhow::Result;
+use clap::ArgEnum;
+
+use super::{repo_root, sorted_lints, PrintedLintErrors};
+use crate::{lint_message::LintMessage, path::get_display_path};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ArgEnum)]
+pub enum CsvColumn {
+    /// Relative to the root of the repo
+    Path,
+    Line,
+    Char,
+    /// The code of the linter that reported the issue
+    Linter,
+    Severity,
+    /// What kind of issue it is, e.g. `E501`
+    Name,
+    Description,
+    Fingerprint,
+}
+
+pub const DEFAULT_CSV_COLUMNS: [CsvColumn; 6] = [
+    CsvColumn::Path,
+    CsvColumn::Line,
+    CsvColumn::Linter,
+    CsvColumn::Severity,
+    CsvColumn::Name,
+    CsvColumn::Description,
+];
+
+static COLUMNS: OnceLock<Vec<CsvColumn>> = OnceLock::new();
+
+/// Use `columns` for the rest of the run. Only the first call has an effect.
+pub fn set_csv_columns(columns: Vec<CsvColumn>) {
+    let _ = COLUMNS.set(columns);
+}
+
+fn columns() -> &'static [CsvColumn] {
+    COLUMNS.get().map_or(&DEFAULT_CSV_COLUMNS, Vec::as_slice)
+}
+
+pub fn render_lint_messages_csv(
+    stdout: &mut impl Write,
+    lint_messages: &HashMap<Option<String>, Vec<LintMessage>>,
+) -> Result<PrintedLintErrors> {
+    let root = repo_root()?;
+    let columns = columns();
+    let header: Vec<String> = columns
+        .iter()
+        // Unwrap is fine because no column is skipped.
+        .map(|column| column.to_possible_value().unwrap().get_name().to_string())
+        .collect();
+    writeln!(stdout, "{}", header.join(","))?;
+
+    let lints = sorted_lints(lint_messages);
+    for lint in &lints {
+        let number = |n: Option<usize>| n.map(|n| n.to_string()).unwrap_or_default();
+        let fields: Vec<String> = columns
+            .iter()
+            .map(|column| match column {
+                CsvColumn::Path => lint
+                    .path
+                    .as_deref()
+                    .map(|path| get_display_path(path, &root))
+                    .unwrap_or_default(),
+                CsvColumn::Line => number(lint.line),
+                CsvColumn::Char => number(lint.char),
+                CsvColumn::Linter => lint.code.clone(),
+                CsvColumn::Severity => lint.severity.label().to_string(),
+                CsvColumn::Name => lint.name.clone(),
+                CsvColumn::Description => lint.description.clone().unwrap_or_default(),
+                CsvColumn::Fingerprint => lint.fingerprint.clone().unwrap_or_default(),
+            })
+            .map(|field| csv_field(&field))
+            .collect();
+        writeln!(stdout, "{}", fields.join(","))?;
+    }
+
+    if lints.is_empty() {
+        Ok(PrintedLintErrors::No)
+    } else {
+        Ok(PrintedLintErrors::Yes)
+    }
+}
+
+/// Quote `field` if it has characters that mean something in CSV.
+pub(crate) fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lint_message::LintSeverity;
+
+    #[test]
+    fn issues_become_rows() -> Result<()> {
+        let mut lint_messages = HashMap::new();
+        lint_messages.insert(
+            None,
+            vec![LintMessage {
+                line: Some(12),
+                description: Some("Line too long, \"really\"\nlong".to_string()),
+                ..LintMessage::for_test("FLAKE8", LintSeverity::Warning, "E501")
+            }],
+        );
+        let mut out = Vec::new();
+        render_lint_messages_csv(&mut out, &lint_messages)?;
+        assert_eq!(
+            String::from_utf8(out)?,
+            "path,line,linter,severity,name,description\n\
+             ,12,FLAKE8,Warning,E501,\"Line too long, \"\"really\"\"\nlong\"\n"
+        );
+        Ok(())
+    }
+}
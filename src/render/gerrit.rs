@@ -0,0 +1,212 @@
+//! Gerrit robot comments, as accepted by `gerrit review --json`.
+//!
+//! See https://gerrit-review.googlesource.com/Documentation/rest-api-changes.html#robot-comment-input
+
+use std::collections::BTreeMap;
+use std::io::Write;
+
+use anyhow::Result;
+use serde_json::{json, Value};
+use similar::TextDiff;
+
+use super::{repo_root, sorted_lints, PrintedLintErrors};
+use crate::{lint_message::LintMessage, path::get_display_path};
+
+/// Comments without a file are attached to the patchset as a whole.
+const PATCHSET_LEVEL: &str = "/PATCHSET_LEVEL";
+
+pub fn render_lint_messages_gerrit(
+    stdout: &mut impl Write,
+    lint_messages: &std::collections::HashMap<Option<String>, Vec<LintMessage>>,
+) -> Result<PrintedLintErrors> {
+    let run_id = chrono::Local::now().to_rfc3339();
+    let root = repo_root()?;
+    let mut comments: BTreeMap<String, Vec<Value>> = BTreeMap::new();
+    for lint_message in sorted_lints(lint_messages) {
+        let path = match &lint_message.path {
+            Some(path) => get_display_path(path, &root),
+            None => PATCHSET_LEVEL.to_string(),
+        };
+        let mut comment = json!({
+            "robot_id": format!("lintrunner/{}", lint_message.code),
+            "robot_run_id": run_id,
+            "message": comment_message(lint_message),
+            "unresolved": true,
+        });
+        if let Some(line) = lint_message.line {
+            comment["line"] = json!(line);
+        }
+        let fixes = lint_message.fixes().unwrap_or_default();
+        if !fixes.is_empty() {
+            // Gerrit applies all of a suggestion's replacements together, even
+            // across files.
+            let replacements: Vec<Value> = fixes
+                .iter()
+                .flat_map(|fix| {
+                    let path = get_display_path(&fix.path, &root);
+                    fix_replacements(&path, &fix.original, &fix.replacement)
+                })
+                .collect();
+            comment["fix_suggestions"] = json!([{
+                "description": format!("Apply the fix suggested by {}", lint_message.code),
+                "replacements": replacements,
+            }]);
+        }
+        comments.entry(path).or_default().push(comment);
+    }
+
+    let printed = !comments.is_empty();
+    let review = json!({
+        "tag": "autogenerated:lintrunner",
+        "robot_comments": comments,
+    });
+    writeln!(stdout, "{}", serde_json::to_string_pretty(&review)?)?;
+
+    if printed {
+        Ok(PrintedLintErrors::Yes)
+    } else {
+        Ok(PrintedLintErrors::No)
+    }
+}
+
+fn comment_message(lint_message: &LintMessage) -> String {
+    let mut message = format!(
+        "{} ({}) {}",
+        lint_message.severity.label(),
+        lint_message.code,
+        lint_message.name
+    );
+    if let Some(description) = &lint_message.description {
+        message.push_str("\n\n");
+        message.push_str(description);
+    }
+    message
+}
+
+/// Turn a whole-file replacement into Gerrit's ranged replacements, one per
+/// changed hunk.
+fn fix_replacements(path: &str, original: &str, replacement: &str) -> Vec<Value> {
+    let old_lines: Vec<&str> = original.split_inclusive('\n').collect();
+    let new_lines: Vec<&str> = replacement.split_inclusive('\n').collect();
+    // The length of a line in characters, not counting its newline.
+    let line_len = |line: usize| old_lines[line].trim_end_matches('\n').chars().count();
+    let diff = TextDiff::from_lines(original, replacement);
+
+    diff.grouped_ops(0)
+        .iter()
+        .filter_map(|group| {
+            let (first, last) = (group.first()?, group.last()?);
+            let old_range = first.old_range().start..last.old_range().end;
+            let new_range = first.new_range().start..last.new_range().end;
+            let text = new_lines[new_range].concat();
+
+            // Positions are 1-based lines and 0-based characters. A hunk
+            // normally ends at the start of the line after it.
+            let (start, end, text) = if old_range.end < old_lines.len() {
+                ((old_range.start + 1, 0), (old_range.end + 1, 0), text)
+            } else if !original.ends_with('\n') {
+                let last_line = old_lines.len().max(1);
+                let end = (
+                    last_line,
+                    old_lines.last().map_or(0, |_| line_len(last_line - 1)),
+                );
+                let start = if old_range.start < old_lines.len() {
+                    (old_range.start + 1, 0)
+                } else {
+                    end
+                };
+                (start, end, text)
+            } else {
+                // There's no line after the end of the file, so keep the final
+                // newline and replace what comes before it. If the hunk doesn't
+                // start at the top, start in front of the previous line's
+                // newline instead.
+                let end = (old_lines.len(), line_len(old_lines.len() - 1));
+                let stripped = text.strip_suffix('\n').unwrap_or(&text).to_string();
+                if old_range.start == 0 {
+                    ((1, 0), end, stripped)
+                } else if text.is_empty() {
+                    // Deleting the last lines also deletes the newline in front.
+                    ((old_range.start, line_len(old_range.start - 1)), end, text)
+                } else {
+                    let start = (old_range.start, line_len(old_range.start - 1));
+                    (start, end, format!("\n{}", stripped))
+                }
+            };
+            Some(json!({
+                "path": path,
+                "range": {
+                    "start_line": start.0,
+                    "start_character": start.1,
+                    "end_line": end.0,
+                    "end_character": end.1,
+                },
+                "replacement": text,
+            }))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Apply Gerrit replacements to `original`, the way Gerrit would.
+    fn apply(original: &str, replacements: &[Value]) -> String {
+        let lines: Vec<&str> = original.split_inclusive('\n').collect();
+        let offset = |line: u64, character: u64| -> usize {
+            let line = line as usize - 1;
+            let start: usize = lines[..line.min(lines.len())].iter().map(|l| l.len()).sum();
+            let prefix: String = lines
+                .get(line)
+                .map(|l| l.chars().take(character as usize).collect())
+                .unwrap_or_default();
+            start + prefix.len()
+        };
+        let mut result = original.to_string();
+        for replacement in replacements.iter().rev() {
+            let range = &replacement["range"];
+            let start = offset(
+                range["start_line"].as_u64().unwrap(),
+                range["start_character"].as_u64().unwrap(),
+            );
+            let end = offset(
+                range["end_line"].as_u64().unwrap(),
+                range["end_character"].as_u64().unwrap(),
+            );
+            result.replace_range(start..end, replacement["replacement"].as_str().unwrap());
+        }
+        result
+    }
+
+    #[test]
+    fn hunks_become_ranged_replacements() {
+        let replacements = fix_replacements("x.py", "a\nb\nc\nd\n", "a\nB\nc\nd\n");
+        assert_eq!(
+            replacements,
+            vec![json!({
+                "path": "x.py",
+                "range": {"start_line": 2, "start_character": 0, "end_line": 3, "end_character": 0},
+                "replacement": "B\n",
+            })]
+        );
+    }
+
+    #[test]
+    fn replacements_reproduce_the_fix() {
+        let cases = [
+            ("a\nb\nc\nd\n", "a\nB\nc\nd\nE\n"),
+            ("a\nb\nc\nd\n", "a\nb\nc\n"),
+            ("a\nb\n", "a\n\n"),
+            ("a\nb\n", "x\n"),
+            ("a\nb", "a\nc"),
+            ("a", "a\nb"),
+            ("a\nb\n", "b\na\nb\n"),
+            ("a\n\tb \n", "a\n    b\n"),
+        ];
+        for (original, fixed) in cases {
+            let replacements = fix_replacements("x.py", original, fixed);
+            assert_eq!(apply(original, &replacements), fixed, "{original:?}");
+        }
+    }
+}
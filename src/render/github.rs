@@ -0,0 +1,103 @@
+//! GitHub Actions workflow commands, which annotate the changed files and the
+//! run summary.
+//!
+//! See https://docs.github.com/en/actions/using-workflows/workflow-commands-for-github-actions#setting-an-error-message
+
+use std::collections::HashMap;
+use std::io::Write;
+
+use anyhow::Result;
+
+use super::{repo_root, sorted_lints, PrintedLintErrors};
+use crate::{
+    lint_message::{LintMessage, LintSeverity},
+    path::get_display_path,
+};
+
+pub fn render_lint_messages_github(
+    stdout: &mut impl Write,
+    lint_messages: &HashMap<Option<String>, Vec<LintMessage>>,
+) -> Result<PrintedLintErrors> {
+    let root = repo_root()?;
+    let mut printed = false;
+    for lint_message in sorted_lints(lint_messages) {
+        printed = true;
+        let command = match lint_message.severity {
+            LintSeverity::Error => "error",
+            LintSeverity::Warning => "warning",
+            LintSeverity::Advice | LintSeverity::Disabled => "notice",
+        };
+        let mut properties = Vec::new();
+        if let Some(path) = &lint_message.path {
+            properties.push(("file", get_display_path(path, &root)));
+        }
+        if let Some(line) = lint_message.line {
+            properties.push(("line", line.to_string()));
+        }
+        if let Some(char) = lint_message.char {
+            properties.push(("col", char.to_string()));
+        }
+        properties.push((
+            "title",
+            format!("({}) {}", lint_message.code, lint_message.name),
+        ));
+
+        let properties = properties
+            .iter()
+            .map(|(name, value)| format!("{}={}", name, escape_property(value)))
+            .collect::<Vec<_>>()
+            .join(",");
+        let message = lint_message
+            .description
+            .as_deref()
+            .unwrap_or(&lint_message.name);
+        writeln!(
+            stdout,
+            "::{} {}::{}",
+            command,
+            properties,
+            escape_data(message)
+        )?;
+    }
+
+    if printed {
+        Ok(PrintedLintErrors::Yes)
+    } else {
+        Ok(PrintedLintErrors::No)
+    }
+}
+
+fn escape_data(value: &str) -> String {
+    value
+        .replace('%', "%25")
+        .replace('\r', "%0D")
+        .replace('\n', "%0A")
+}
+
+fn escape_property(value: &str) -> String {
+    escape_data(value).replace(':', "%3A").replace(',', "%2C")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn messages_become_workflow_commands() -> Result<()> {
+        let mut lint_messages = HashMap::new();
+        lint_messages.insert(
+            None,
+            vec![LintMessage {
+                description: Some("100% broken,\nsee log".to_string()),
+                ..LintMessage::for_test("MYPY", LintSeverity::Advice, "crashed: badly")
+            }],
+        );
+        let mut out = Vec::new();
+        render_lint_messages_github(&mut out, &lint_messages)?;
+        assert_eq!(
+            String::from_utf8(out)?,
+            "::notice title=(MYPY) crashed%3A badly::100%25 broken,%0Asee log\n"
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,72 @@
+//! Structured events for programs that embed lintrunner.
+//!
+//! The CLI writes progress and results straight to the terminal. A daemon, a
+//! language server or language bindings want to show them their own way, so
+//! [`Runner`](crate::runner::Runner) can instead send them to a
+//! [`RenderSink`] as they happen. Linters run on threads of their own, so
+//! sinks get events from several threads at once.
+
+use std::io::Write;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use anyhow::Result;
+
+use crate::{lint_message::LintMessage, render_results, LintResults, RenderOpt};
+
+/// Something that happened during a lint run.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum RenderEvent<'a> {
+    /// A linter started running on `num_files` files.
+    LinterStarted { code: &'a str, num_files: usize },
+    /// A linter finished, and found `lints`. Lints whose fixes were applied
+    /// aren't included.
+    LinterFinished {
+        code: &'a str,
+        num_files: usize,
+        duration: Duration,
+        lints: &'a [LintMessage],
+    },
+    /// Every linter finished.
+    Finished { results: &'a LintResults },
+}
+
+/// Receives the events of a lint run. Events for different linters can
+/// arrive at the same time from different threads, but each linter's
+/// `LinterStarted` comes before its `LinterFinished`, and `Finished` comes
+/// last. An error fails the run.
+pub trait RenderSink: Send + Sync {
+    fn event(&self, event: &RenderEvent) -> Result<()>;
+}
+
+/// Renders the results of the run to a writer once it finishes, like the
+/// `lintrunner` CLI does.
+pub struct WriterSink {
+    render_opt: RenderOpt,
+    writer: Mutex<Box<dyn Write + Send>>,
+}
+
+impl WriterSink {
+    pub fn new(render_opt: RenderOpt, writer: Box<dyn Write + Send>) -> WriterSink {
+        WriterSink {
+            render_opt,
+            writer: Mutex::new(writer),
+        }
+    }
+
+    pub fn stdout(render_opt: RenderOpt) -> WriterSink {
+        WriterSink::new(render_opt, Box::new(std::io::stdout()))
+    }
+}
+
+impl RenderSink for WriterSink {
+    fn event(&self, event: &RenderEvent) -> Result<()> {
+        if let RenderEvent::Finished { results } = event {
+            let mut writer = self.writer.lock().unwrap();
+            render_results(&mut *writer, self.render_opt, results)?;
+            writer.flush()?;
+        }
+        Ok(())
+    }
+}
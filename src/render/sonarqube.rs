@@ -0,0 +1,95 @@
+//! SonarQube's generic issue import format, for
+//! `sonar.externalIssuesReportPaths`.
+//!
+//! See https://docs.sonarsource.com/sonarqube/latest/analyzing-source-code/importing-external-issues/generic-issue-import-format/
+
+use std::collections::HashMap;
+use std::io::Write;
+
+use anyhow::Result;
+use serde_json::{json, Value};
+
+use super::{repo_root, sorted_lints, PrintedLintErrors};
+use crate::{
+    lint_message::{LintMessage, LintSeverity},
+    path::get_display_path,
+};
+
+pub fn render_lint_messages_sonarqube(
+    stdout: &mut impl Write,
+    lint_messages: &HashMap<Option<String>, Vec<LintMessage>>,
+) -> Result<PrintedLintErrors> {
+    let root = repo_root()?;
+    let lints = sorted_lints(lint_messages);
+    // SonarQube attaches every issue to a file, so messages without a path
+    // can't be imported.
+    let issues: Vec<Value> = lints
+        .iter()
+        .filter_map(|lint| {
+            let path = get_display_path(lint.path.as_ref()?, &root);
+            Some(sonarqube_issue(&path, lint))
+        })
+        .collect();
+    writeln!(
+        stdout,
+        "{}",
+        serde_json::to_string_pretty(&json!({ "issues": issues }))?
+    )?;
+
+    if lints.is_empty() {
+        Ok(PrintedLintErrors::No)
+    } else {
+        Ok(PrintedLintErrors::Yes)
+    }
+}
+
+fn sonarqube_issue(path: &str, lint: &LintMessage) -> Value {
+    let severity = match lint.severity {
+        LintSeverity::Error => "CRITICAL",
+        LintSeverity::Warning => "MAJOR",
+        LintSeverity::Advice => "MINOR",
+        LintSeverity::Disabled => "INFO",
+    };
+    let mut location = json!({
+        "message": lint.description.as_deref().unwrap_or(&lint.name),
+        "filePath": path,
+    });
+    if let Some(line) = lint.line {
+        location["textRange"] = json!({ "startLine": line.max(1) });
+    }
+    json!({
+        "engineId": "lintrunner",
+        "ruleId": format!("{}/{}", lint.code, lint.name),
+        "severity": severity,
+        "type": "CODE_SMELL",
+        "primaryLocation": location,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lints_become_issues() {
+        let lint = LintMessage {
+            path: Some("a.py".to_string()),
+            line: Some(0),
+            ..LintMessage::for_test("FLAKE8", LintSeverity::Error, "E999")
+        };
+        assert_eq!(
+            sonarqube_issue("src/a.py", &lint),
+            json!({
+                "engineId": "lintrunner",
+                "ruleId": "FLAKE8/E999",
+                "severity": "CRITICAL",
+                "type": "CODE_SMELL",
+                "primaryLocation": {
+                    "message": "E999",
+                    "filePath": "src/a.py",
+                    "textRange": { "startLine": 1 },
+                },
+            })
+        );
+    }
+}
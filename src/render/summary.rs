@@ -0,0 +1,151 @@
+//! Counts of issues by linter and severity, without the issues themselves,
+//! for dashboards and for checking on runs with huge numbers of results.
+
+use std::collections::{BTreeMap, HashMap};
+use std::io::Write;
+
+use anyhow::Result;
+use console::style;
+
+use super::PrintedLintErrors;
+use crate::{
+    lint_message::{LintMessage, LintSeverity},
+    LintResults,
+};
+
+const SEVERITIES: [LintSeverity; 3] = [
+    LintSeverity::Error,
+    LintSeverity::Warning,
+    LintSeverity::Advice,
+];
+
+/// Render a table of issue counts. With the `results` of the run, linters that
+/// found nothing are listed too, followed by whether the run failed.
+pub fn render_lint_messages_summary(
+    stdout: &mut impl Write,
+    lint_messages: &HashMap<Option<String>, Vec<LintMessage>>,
+    results: Option<&LintResults>,
+) -> Result<PrintedLintErrors> {
+    let mut counts: BTreeMap<&str, [usize; SEVERITIES.len()]> = BTreeMap::new();
+    if let Some(results) = results {
+        for code in results.durations.keys() {
+            counts.entry(code).or_default();
+        }
+    }
+    let mut total = [0; SEVERITIES.len()];
+    for lint in lint_messages.values().flatten() {
+        // Disabled issues are counted as advice. Neither fails the run unless
+        // a linter's `fail_on` asks for it.
+        let column = SEVERITIES
+            .iter()
+            .position(|severity| *severity == lint.severity)
+            .unwrap_or(SEVERITIES.len() - 1);
+        counts.entry(&lint.code).or_default()[column] += 1;
+        total[column] += 1;
+    }
+
+    let width = counts
+        .keys()
+        .map(|code| code.len())
+        .chain(["Linter".len(), "Total".len()])
+        .max()
+        .unwrap_or_default();
+    let row = |name: &str, counts: &[usize]| {
+        let counts: Vec<String> = counts
+            .iter()
+            .zip(SEVERITIES)
+            .map(|(count, severity)| format!("{:>w$}", count, w = severity.label().len()))
+            .collect();
+        format!("{:width$}  {}", name, counts.join("  "), width = width)
+    };
+    let header: Vec<&str> = SEVERITIES.iter().map(|severity| severity.label()).collect();
+    writeln!(
+        stdout,
+        "{}",
+        style(format!(
+            "{:width$}  {}",
+            "Linter",
+            header.join("  "),
+            width = width
+        ))
+        .bold()
+    )?;
+    for (code, counts) in &counts {
+        writeln!(stdout, "{}", row(code, counts))?;
+    }
+    writeln!(stdout, "{}", style(row("Total", &total)).bold())?;
+
+    if let Some(results) = results {
+        let exit_code = results.exit_code();
+        let verdict = match results.worst_failure {
+            _ if exit_code == 0 => style("ok".to_string()).green(),
+            Some(failure) => style(failure.title().to_string()).red(),
+            None => style(format!(
+                "{} {} the run",
+                results.failing_lints,
+                if results.failing_lints == 1 {
+                    "issue fails"
+                } else {
+                    "issues fail"
+                }
+            ))
+            .red(),
+        };
+        writeln!(stdout, "\n{} (exit code {})", verdict, exit_code)?;
+    }
+
+    if total.iter().sum::<usize>() > 0 {
+        Ok(PrintedLintErrors::Yes)
+    } else {
+        Ok(PrintedLintErrors::No)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lint(code: &str, severity: LintSeverity) -> LintMessage {
+        LintMessage {
+            path: Some("a.py".to_string()),
+            line: Some(1),
+            ..LintMessage::for_test(code, severity, "bad")
+        }
+    }
+
+    #[test]
+    fn issues_are_counted_by_linter_and_severity() -> Result<()> {
+        console::set_colors_enabled(false);
+        let lints = vec![
+            lint("FLAKE8", LintSeverity::Error),
+            lint("FLAKE8", LintSeverity::Advice),
+            lint("WS", LintSeverity::Warning),
+            lint("WS", LintSeverity::Disabled),
+        ];
+        // Only the error and the warning fail the run by default.
+        let mut results = LintResults {
+            failing_lints: 2,
+            ..Default::default()
+        };
+        results
+            .durations
+            .insert("MYPY".to_string(), Default::default());
+        results.lints.insert(Some("a.py".to_string()), lints);
+
+        let mut out = Vec::new();
+        render_lint_messages_summary(&mut out, &results.lints, Some(&results))?;
+        assert_eq!(
+            String::from_utf8(out)?,
+            "\
+Linter  Error  Warning  Advice
+FLAKE8      1        0       1
+MYPY        0        0       0
+WS          0        1       1
+Total       1        1       2
+
+2 issues fail the run (exit code 1)
+"
+        );
+        Ok(())
+    }
+}
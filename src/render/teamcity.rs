@@ -0,0 +1,134 @@
+//! TeamCity service messages, which show up in the Code Inspection tab.
+//!
+//! See https://www.jetbrains.com/help/teamcity/service-messages.html#Reporting+Inspections
+
+use std::collections::{HashMap, HashSet};
+use std::io::Write;
+
+use anyhow::Result;
+
+use super::{repo_root, sorted_lints, PrintedLintErrors};
+use crate::{
+    lint_message::{LintMessage, LintSeverity},
+    path::get_display_path,
+};
+
+pub fn render_lint_messages_teamcity(
+    stdout: &mut impl Write,
+    lint_messages: &HashMap<Option<String>, Vec<LintMessage>>,
+) -> Result<PrintedLintErrors> {
+    let root = repo_root()?;
+    let mut declared = HashSet::new();
+    let mut printed = false;
+    for lint_message in sorted_lints(lint_messages) {
+        printed = true;
+        let message = match &lint_message.description {
+            Some(description) => description.as_str(),
+            None => lint_message.name.as_str(),
+        };
+
+        // Messages without a file can't be inspections, so report them as
+        // problems with the build instead.
+        let path = match &lint_message.path {
+            Some(path) => get_display_path(path, &root),
+            None => {
+                writeln!(
+                    stdout,
+                    "##teamcity[buildProblem description='{}' identity='{}']",
+                    escape(&format!("{} ({})", message, lint_message.code)),
+                    escape(&lint_message.code)
+                )?;
+                continue;
+            }
+        };
+
+        // Each kind of inspection has to be declared before it's reported.
+        let type_id = format!("{}/{}", lint_message.code, lint_message.name);
+        if declared.insert(type_id.clone()) {
+            writeln!(
+                stdout,
+                "##teamcity[inspectionType id='{}' name='{}' category='{}' description='{}']",
+                escape(&type_id),
+                escape(&lint_message.name),
+                escape(&lint_message.code),
+                escape(&format!("{} ({})", lint_message.name, lint_message.code))
+            )?;
+        }
+        let severity = match lint_message.severity {
+            LintSeverity::Error => "ERROR",
+            LintSeverity::Warning => "WARNING",
+            LintSeverity::Advice => "WEAK WARNING",
+            LintSeverity::Disabled => "INFO",
+        };
+        write!(
+            stdout,
+            "##teamcity[inspection typeId='{}' message='{}' file='{}'",
+            escape(&type_id),
+            escape(message),
+            escape(&path)
+        )?;
+        if let Some(line) = lint_message.line {
+            write!(stdout, " line='{}'", line)?;
+        }
+        writeln!(stdout, " SEVERITY='{}']", severity)?;
+    }
+
+    if printed {
+        Ok(PrintedLintErrors::Yes)
+    } else {
+        Ok(PrintedLintErrors::No)
+    }
+}
+
+/// Escape a value in a service message.
+fn escape(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '|' => escaped.push_str("||"),
+            '\'' => escaped.push_str("|'"),
+            '\n' => escaped.push_str("|n"),
+            '\r' => escaped.push_str("|r"),
+            '[' => escaped.push_str("|["),
+            ']' => escaped.push_str("|]"),
+            c if !c.is_ascii() => escaped.push_str(&format!("|0x{:04x}", c as u32)),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn values_are_escaped() {
+        assert_eq!(escape("it's [x|y]\nok é"), "it|'s |[x||y|]|nok |0x00e9");
+    }
+
+    #[test]
+    fn inspection_types_are_declared_once() -> Result<()> {
+        let lint = |line| LintMessage {
+            path: Some("/nonexistent/a.py".to_string()),
+            line: Some(line),
+            description: Some("Line too long".to_string()),
+            ..LintMessage::for_test("FLAKE8", LintSeverity::Error, "E501")
+        };
+        let mut lint_messages = HashMap::new();
+        lint_messages.insert(
+            Some("/nonexistent/a.py".to_string()),
+            vec![lint(1), lint(2)],
+        );
+        let mut out = Vec::new();
+        render_lint_messages_teamcity(&mut out, &lint_messages)?;
+        let out = String::from_utf8(out)?;
+        assert_eq!(
+            out,
+            "##teamcity[inspectionType id='FLAKE8/E501' name='E501' category='FLAKE8' description='E501 (FLAKE8)']\n\
+             ##teamcity[inspection typeId='FLAKE8/E501' message='Line too long' file='/nonexistent/a.py' line='1' SEVERITY='ERROR']\n\
+             ##teamcity[inspection typeId='FLAKE8/E501' message='Line too long' file='/nonexistent/a.py' line='2' SEVERITY='ERROR']\n"
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,342 @@
+//! A builder for running linters from other Rust programs, without shelling
+//! out to `lintrunner` and parsing its output.
+//!
+//! ```no_run
+//! use lintrunner::runner::{ConfigSource, PatchPolicy, Runner};
+//! use lintrunner::PathsOpt;
+//!
+//! let results = Runner::new(ConfigSource::File(".lintrunner.toml".into()))
+//!     .paths(PathsOpt::AllFiles)
+//!     .patch_policy(PatchPolicy::Report)
+//!     .run()?;
+//! for lint in results.lints.values().flatten() {
+//!     println!("{}: {}", lint.code, lint.name);
+//! }
+//! std::process::exit(results.exit_code());
+//! # Ok::<(), anyhow::Error>(())
+//! ```
+//!
+//! To show progress and results as linters finish, pass a
+//! [`RenderSink`](crate::render::sink::RenderSink) to
+//! [`Runner::render_sink`].
+
+use std::collections::HashSet;
+use std::convert::TryFrom;
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use anyhow::{Context, Result};
+
+use crate::{
+    get_paths,
+    lint_config::{get_linters_from_config, LintRunnerConfig},
+    linter::Linter,
+    path::AbsPath,
+    progress::ProgressOpt,
+    render::sink::RenderSink,
+    run_linters, CodeFilter, LintResults, PathsOpt, RevisionOpt, RunOptions,
+};
+
+/// Where to get the linters to run from.
+pub enum ConfigSource {
+    /// Read the config file at this path (and its `.lintrunner.local.toml`,
+    /// unless disabled with [`Runner::use_local_config`]).
+    File(PathBuf),
+    /// Use linters that have already been loaded, e.g. with
+    /// [`get_linters_from_config`].
+    Linters(Vec<Linter>),
+}
+
+/// What to do with the fixes that linters suggest.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum PatchPolicy {
+    /// Leave files alone, and report the fixes as part of the lint messages.
+    #[default]
+    Report,
+    /// Write fixes to disk, and leave fixed lints out of the results.
+    Apply,
+}
+
+/// Builder for a lint run. See the [module documentation](self) for an example.
+pub struct Runner {
+    config: ConfigSource,
+    use_local_config: bool,
+    take: Option<HashSet<String>>,
+    skip: Option<HashSet<String>>,
+    paths: PathsOpt,
+    revision: RevisionOpt,
+    excludes: Vec<String>,
+    patch_policy: PatchPolicy,
+    fix_filter: CodeFilter,
+    render_sink: Option<Arc<dyn RenderSink>>,
+    strict_missing: bool,
+    code_filter: CodeFilter,
+    jobs: Option<usize>,
+}
+
+impl Runner {
+    /// Create a runner for the linters in `config`. By default, it lints the
+    /// files changed in the working tree and HEAD, doesn't apply patches and
+    /// doesn't render anything.
+    pub fn new(config: ConfigSource) -> Runner {
+        Runner {
+            config,
+            use_local_config: true,
+            take: None,
+            skip: None,
+            paths: PathsOpt::Auto,
+            revision: RevisionOpt::Head,
+            excludes: Vec::new(),
+            patch_policy: PatchPolicy::default(),
+            fix_filter: CodeFilter::default(),
+            render_sink: None,
+            strict_missing: false,
+            code_filter: CodeFilter::default(),
+            jobs: None,
+        }
+    }
+
+    /// Whether to merge in `.lintrunner.local.toml`. Defaults to true.
+    pub fn use_local_config(mut self, use_local_config: bool) -> Self {
+        self.use_local_config = use_local_config;
+        self
+    }
+
+    /// Only run the linters with these codes.
+    pub fn take<I: IntoIterator<Item = String>>(mut self, codes: I) -> Self {
+        self.take = Some(codes.into_iter().collect());
+        self
+    }
+
+    /// Don't run the linters with these codes.
+    pub fn skip<I: IntoIterator<Item = String>>(mut self, codes: I) -> Self {
+        self.skip = Some(codes.into_iter().collect());
+        self
+    }
+
+    /// Which files to lint.
+    pub fn paths(mut self, paths: PathsOpt) -> Self {
+        self.paths = paths;
+        self
+    }
+
+    /// Which changes to lint, when `paths` is [`PathsOpt::Auto`].
+    pub fn revision(mut self, revision: RevisionOpt) -> Self {
+        self.revision = revision;
+        self
+    }
+
+    /// Paths (or globs) to never lint.
+    pub fn excludes(mut self, excludes: Vec<String>) -> Self {
+        self.excludes = excludes;
+        self
+    }
+
+    pub fn patch_policy(mut self, patch_policy: PatchPolicy) -> Self {
+        self.patch_policy = patch_policy;
+        self
+    }
+
+    /// With [`PatchPolicy::Apply`], only apply the fixes of lints that pass
+    /// `fix_filter`, and report the others.
+    pub fn fix_filter(mut self, fix_filter: CodeFilter) -> Self {
+        self.fix_filter = fix_filter;
+        self
+    }
+
+    /// Send events to `render_sink` as linters start and finish, e.g. a
+    /// [`WriterSink`](crate::render::sink::WriterSink) to render the results
+    /// like the CLI does. By default, nothing is rendered.
+    pub fn render_sink(mut self, render_sink: impl RenderSink + 'static) -> Self {
+        self.render_sink = Some(Arc::new(render_sink));
+        self
+    }
+
+    /// Treat linters whose command can't be found as failures, instead of
+    /// skipping them.
+    pub fn strict_missing(mut self, strict_missing: bool) -> Self {
+        self.strict_missing = strict_missing;
+        self
+    }
+
+    /// Only keep lint messages that pass `code_filter`.
+    pub fn code_filter(mut self, code_filter: CodeFilter) -> Self {
+        self.code_filter = code_filter;
+        self
+    }
+
+    /// Run at most `jobs` linters at once. By default, every linter runs in
+    /// parallel.
+    pub fn jobs(mut self, jobs: usize) -> Self {
+        self.jobs = Some(jobs);
+        self
+    }
+
+    /// Load the linters this runner would run.
+    pub fn linters(&self) -> Result<Vec<Linter>> {
+        match &self.config {
+            ConfigSource::File(path) => {
+                let config_path = AbsPath::try_from(path.as_path()).with_context(|| {
+                    format!("Could not read lintrunner config at: '{}'", path.display())
+                })?;
+                let config = LintRunnerConfig::new(&config_path, false, self.use_local_config)?;
+                get_linters_from_config(
+                    &config.linters,
+                    self.skip.clone(),
+                    self.take.clone(),
+                    &config_path,
+                )
+            }
+            ConfigSource::Linters(linters) => Ok(linters
+                .iter()
+                .filter(|linter| {
+                    self.take
+                        .as_ref()
+                        .is_none_or(|take| take.contains(&linter.code))
+                        && !self
+                            .skip
+                            .as_ref()
+                            .is_some_and(|skip| skip.contains(&linter.code))
+                })
+                .cloned()
+                .collect()),
+        }
+    }
+
+    /// Run the linters and return the results.
+    pub fn run(self) -> Result<LintResults> {
+        let linters = self.linters()?;
+        let files = get_paths(self.paths, self.revision, &self.excludes)?;
+        run_linters(
+            linters,
+            files,
+            RunOptions {
+                apply_patches: self.patch_policy == PatchPolicy::Apply,
+                fix_filter: self.fix_filter,
+                strict_missing: self.strict_missing,
+                code_filter: self.code_filter,
+                progress: ProgressOpt::None,
+                stream: None,
+                expected_durations: Default::default(),
+                record_fingerprints: false,
+                baseline: None,
+                issue_history: None,
+                jobs: self.jobs,
+                sink: self.render_sink,
+            },
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::bail;
+
+    use super::*;
+    use crate::render::sink::RenderEvent;
+    use std::sync::Mutex;
+
+    /// Records a line for every event it gets.
+    struct EventLog(Arc<Mutex<Vec<String>>>);
+
+    impl RenderSink for EventLog {
+        fn event(&self, event: &RenderEvent) -> Result<()> {
+            let line = match event {
+                RenderEvent::LinterStarted { code, num_files } => {
+                    format!("started {} {}", code, num_files)
+                }
+                RenderEvent::LinterFinished { code, lints, .. } => {
+                    format!("finished {} {}", code, lints.len())
+                }
+                RenderEvent::Finished { .. } => "finished".to_string(),
+            };
+            self.0.lock().unwrap().push(line);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn runner_runs_linters() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let config_path = dir.path().join(".lintrunner.toml");
+        std::fs::write(
+            &config_path,
+            "[[linter]]\ncode = 'WS'\ninclude_patterns = ['*.txt']\n\
+             command = 'builtin:whitespace'\n\n\
+             [[linter]]\ncode = 'EOF'\ninclude_patterns = ['*.txt']\n\
+             command = 'builtin:newline-eof'\n",
+        )?;
+        let file = dir.path().join("a.txt");
+        std::fs::write(&file, "x \n")?;
+        let paths = PathsOpt::Paths(vec![file.display().to_string()]);
+
+        let runner = Runner::new(ConfigSource::File(config_path.clone()))
+            .paths(paths.clone())
+            .skip(["EOF".to_string()]);
+        assert_eq!(runner.linters()?.len(), 1);
+        let results = runner.run()?;
+        assert_eq!(results.lints.values().flatten().count(), 1);
+        assert_eq!(results.exit_code(), 1);
+        assert_eq!(std::fs::read_to_string(&file)?, "x \n");
+
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let results = Runner::new(ConfigSource::File(config_path))
+            .paths(paths)
+            .patch_policy(PatchPolicy::Apply)
+            .render_sink(EventLog(Arc::clone(&events)))
+            .run()?;
+        assert!(results.lints.is_empty());
+        assert_eq!(std::fs::read_to_string(&file)?, "x\n");
+
+        // The fixed lints aren't reported, and the events for a linter
+        // arrive in order.
+        let events = events.lock().unwrap().clone();
+        for code in ["WS", "EOF"] {
+            let started = events
+                .iter()
+                .position(|e| e == &format!("started {} 1", code));
+            let finished = events
+                .iter()
+                .position(|e| e == &format!("finished {} 0", code));
+            assert!(started.is_some() && started < finished, "{:?}", events);
+        }
+        assert_eq!(events.last().map(String::as_str), Some("finished"));
+        Ok(())
+    }
+
+    /// Fails as soon as a linter starts.
+    struct FailingSink;
+
+    impl RenderSink for FailingSink {
+        fn event(&self, event: &RenderEvent) -> Result<()> {
+            match event {
+                RenderEvent::LinterStarted { code, .. } => bail!("can't show {}", code),
+                _ => Ok(()),
+            }
+        }
+    }
+
+    #[test]
+    fn sink_errors_fail_the_run() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let config_path = dir.path().join(".lintrunner.toml");
+        std::fs::write(
+            &config_path,
+            "[[linter]]\ncode = 'EOF'\ninclude_patterns = ['*.txt']\n\
+             command = 'builtin:newline-eof'\nrun_after = ['WS']\n\n\
+             [[linter]]\ncode = 'WS'\ninclude_patterns = ['*.txt']\n\
+             command = 'builtin:whitespace'\n",
+        )?;
+        let file = dir.path().join("a.txt");
+        std::fs::write(&file, "x\n")?;
+
+        // EOF waits for WS, so this would hang if WS never finished.
+        let err = Runner::new(ConfigSource::File(config_path))
+            .paths(PathsOpt::Paths(vec![file.display().to_string()]))
+            .render_sink(FailingSink)
+            .run()
+            .unwrap_err();
+        assert!(err.to_string().starts_with("can't show"), "{}", err);
+        Ok(())
+    }
+}
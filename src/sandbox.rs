@@ -0,0 +1,220 @@
+//! Opt-in sandboxing for linter subprocesses.
+//!
+//! A sandboxed linter can read (and execute) anything, but can only write to
+//! its declared cache directories, the system temp directory and `/dev`. In
+//! particular, it can't modify the repo: any fixes have to be reported as
+//! patches, which lintrunner applies itself.
+//!
+//! On Linux this uses Landlock, on macOS `sandbox-exec`. Other platforms are
+//! not supported.
+
+use std::path::PathBuf;
+use std::process::Command;
+
+use anyhow::Result;
+
+/// The directories a sandboxed linter is allowed to write to.
+pub struct SandboxPolicy {
+    pub writable: Vec<PathBuf>,
+}
+
+impl SandboxPolicy {
+    pub fn new(cache_dirs: &[PathBuf]) -> SandboxPolicy {
+        let mut writable = cache_dirs.to_vec();
+        writable.push(std::env::temp_dir());
+        writable.push(PathBuf::from("/dev"));
+        SandboxPolicy { writable }
+    }
+}
+
+/// Keeps whatever resources the sandbox needs alive until the sandboxed
+/// command has been spawned.
+pub struct SandboxGuard {
+    #[cfg(target_os = "linux")]
+    _ruleset: std::os::unix::io::OwnedFd,
+}
+
+/// Restrict `command` according to `policy`. The returned guard must be kept
+/// alive until the command has been spawned.
+#[cfg(target_os = "linux")]
+pub fn sandbox_command(command: &mut Command, policy: &SandboxPolicy) -> Result<SandboxGuard> {
+    use std::os::unix::io::AsRawFd;
+    use std::os::unix::process::CommandExt;
+
+    let ruleset = landlock::create_ruleset(policy)?;
+    let fd = ruleset.as_raw_fd();
+    // SAFETY: the closure only makes async-signal-safe system calls.
+    unsafe {
+        command.pre_exec(move || landlock::restrict_self(fd));
+    }
+    Ok(SandboxGuard { _ruleset: ruleset })
+}
+
+#[cfg(target_os = "macos")]
+pub fn sandbox_command(command: &mut Command, policy: &SandboxPolicy) -> Result<SandboxGuard> {
+    let mut profile = String::from(
+        "(version 1)(allow default)(deny file-write*)(allow file-write* (literal \"/dev/null\")",
+    );
+    for dir in &policy.writable {
+        // sandbox-exec needs the real path, e.g. /private/tmp instead of /tmp.
+        let dir = dir.canonicalize().unwrap_or_else(|_| dir.clone());
+        profile.push_str(&format!(" (subpath {:?})", dir.display().to_string()));
+    }
+    profile.push(')');
+
+    let mut sandboxed = Command::new("sandbox-exec");
+    sandboxed.arg("-p").arg(profile).arg(command.get_program());
+    sandboxed.args(command.get_args());
+    if let Some(dir) = command.get_current_dir() {
+        sandboxed.current_dir(dir);
+    }
+    *command = sandboxed;
+    Ok(SandboxGuard {})
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos")))]
+pub fn sandbox_command(_command: &mut Command, _policy: &SandboxPolicy) -> Result<SandboxGuard> {
+    anyhow::bail!("Sandboxing linters is only supported on Linux and macOS")
+}
+
+#[cfg(target_os = "linux")]
+mod landlock {
+    use std::fs::File;
+    use std::io;
+    use std::os::unix::fs::OpenOptionsExt;
+    use std::os::unix::io::{AsRawFd, FromRawFd, OwnedFd, RawFd};
+
+    use anyhow::{bail, Context, Result};
+
+    use super::SandboxPolicy;
+
+    // From linux/landlock.h. We only use the first ABI version, which every
+    // kernel with Landlock supports.
+    const ACCESS_FS_EXECUTE: u64 = 1 << 0;
+    const ACCESS_FS_READ_FILE: u64 = 1 << 2;
+    const ACCESS_FS_READ_DIR: u64 = 1 << 3;
+    const ACCESS_FS_ALL: u64 = (1 << 13) - 1;
+    const ACCESS_FS_READ: u64 = ACCESS_FS_EXECUTE | ACCESS_FS_READ_FILE | ACCESS_FS_READ_DIR;
+    const RULE_PATH_BENEATH: libc::c_int = 1;
+
+    #[repr(C)]
+    struct RulesetAttr {
+        handled_access_fs: u64,
+    }
+
+    #[repr(C, packed)]
+    struct PathBeneathAttr {
+        allowed_access: u64,
+        parent_fd: i32,
+    }
+
+    pub fn create_ruleset(policy: &SandboxPolicy) -> Result<OwnedFd> {
+        let attr = RulesetAttr {
+            handled_access_fs: ACCESS_FS_ALL,
+        };
+        // SAFETY: attr is a valid landlock_ruleset_attr of the given size.
+        let fd = unsafe {
+            libc::syscall(
+                libc::SYS_landlock_create_ruleset,
+                &attr as *const RulesetAttr,
+                std::mem::size_of::<RulesetAttr>(),
+                0u32,
+            )
+        };
+        if fd < 0 {
+            let err = io::Error::last_os_error();
+            bail!(
+                "Could not set up the linter sandbox, is Landlock enabled in this kernel? ({})",
+                err
+            );
+        }
+        // SAFETY: the syscall returned a new file descriptor that we own.
+        let ruleset = unsafe { OwnedFd::from_raw_fd(fd as RawFd) };
+
+        add_rule(&ruleset, "/".as_ref(), ACCESS_FS_READ)?;
+        for dir in &policy.writable {
+            // Cache dirs may not exist yet if the linter has never run.
+            if std::fs::create_dir_all(dir).is_err() {
+                continue;
+            }
+            add_rule(&ruleset, dir, ACCESS_FS_ALL)?;
+        }
+        Ok(ruleset)
+    }
+
+    fn add_rule(ruleset: &OwnedFd, path: &std::path::Path, access: u64) -> Result<()> {
+        let dir = File::options()
+            .read(true)
+            .custom_flags(libc::O_PATH | libc::O_CLOEXEC)
+            .open(path)
+            .with_context(|| format!("Could not open '{}' for sandboxing", path.display()))?;
+        let attr = PathBeneathAttr {
+            allowed_access: access,
+            parent_fd: dir.as_raw_fd(),
+        };
+        // SAFETY: attr is a valid landlock_path_beneath_attr.
+        let res = unsafe {
+            libc::syscall(
+                libc::SYS_landlock_add_rule,
+                ruleset.as_raw_fd(),
+                RULE_PATH_BENEATH,
+                &attr as *const PathBeneathAttr,
+                0u32,
+            )
+        };
+        if res != 0 {
+            return Err(io::Error::last_os_error())
+                .with_context(|| format!("Could not allow access to '{}'", path.display()));
+        }
+        Ok(())
+    }
+
+    /// Restrict the current process with `ruleset`. This runs between fork and
+    /// exec, so it must only do async-signal-safe things.
+    pub fn restrict_self(ruleset: RawFd) -> io::Result<()> {
+        // SAFETY: these are plain system calls with no pointer arguments.
+        unsafe {
+            if libc::prctl(libc::PR_SET_NO_NEW_PRIVS, 1, 0, 0, 0) != 0 {
+                return Err(io::Error::last_os_error());
+            }
+            if libc::syscall(libc::SYS_landlock_restrict_self, ruleset, 0u32) != 0 {
+                return Err(io::Error::last_os_error());
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sandbox_blocks_writes_outside_cache_dirs() -> Result<()> {
+        let repo = tempfile::tempdir_in(std::env::current_dir()?)?;
+        let cache = tempfile::tempdir_in(std::env::current_dir()?)?;
+        let policy = SandboxPolicy::new(&[cache.path().to_path_buf()]);
+
+        let run = |script: &str| -> Result<bool> {
+            let mut command = Command::new("sh");
+            command.arg("-c").arg(script);
+            let _guard = match sandbox_command(&mut command, &policy) {
+                Ok(guard) => guard,
+                // Landlock isn't available everywhere; nothing to test then.
+                Err(_) => return Ok(true),
+            };
+            Ok(command.status()?.success())
+        };
+
+        assert!(run(&format!("echo hi > {}/out", cache.path().display()))?);
+        let blocked = !run(&format!(
+            "(echo hi > {}/out) 2>/dev/null",
+            repo.path().display()
+        ))?;
+        let landlock_available = landlock::create_ruleset(&policy).is_ok();
+        assert_eq!(blocked, landlock_available);
+        // Reading is always fine.
+        assert!(run("cat /etc/hostname > /dev/null || true")?);
+        Ok(())
+    }
+}
@@ -0,0 +1,290 @@
+//! `lintrunner new-linter`: generate the boilerplate for a new linter.
+
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, ensure, Context, Result};
+use clap::ArgEnum;
+use console::style;
+
+use crate::lint_config::LintRunnerConfig;
+
+/// The language to write a new linter adapter in.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, ArgEnum)]
+pub enum AdapterLanguage {
+    Python,
+    Shell,
+}
+
+const PYTHON_TEMPLATE: &str = r#"#!/usr/bin/env python3
+"""Adapter for the {{CODE}} linter.
+
+lintrunner calls this script with `@<file>`, where <file> lists the paths to
+lint, one per line. For every problem found, print one LintMessage as JSON on
+its own line to stdout, then exit 0. A non-zero exit code tells lintrunner the
+linter itself failed.
+
+The message format is described at
+https://docs.rs/lintrunner/latest/lintrunner/lint_message/struct.LintMessage.html
+"""
+
+import argparse
+import json
+import sys
+from enum import Enum
+from typing import List, NamedTuple, Optional
+
+LINTER_CODE = "{{CODE}}"
+
+
+class LintSeverity(str, Enum):
+    ERROR = "error"
+    WARNING = "warning"
+    ADVICE = "advice"
+    DISABLED = "disabled"
+
+
+class LintMessage(NamedTuple):
+    path: Optional[str]
+    line: Optional[int]
+    char: Optional[int]
+    code: str
+    severity: LintSeverity
+    name: str
+    original: Optional[str]
+    replacement: Optional[str]
+    description: Optional[str]
+
+
+def check_file(path: str) -> List[LintMessage]:
+    # TODO: replace this example check with the real one.
+    messages = []
+    with open(path, encoding="utf-8", errors="replace") as f:
+        for lineno, line in enumerate(f, start=1):
+            if "FIXME" in line:
+                messages.append(
+                    LintMessage(
+                        path=path,
+                        line=lineno,
+                        char=line.index("FIXME") + 1,
+                        code=LINTER_CODE,
+                        severity=LintSeverity.WARNING,
+                        name="fixme",
+                        original=None,
+                        replacement=None,
+                        description="Resolve this FIXME before landing.",
+                    )
+                )
+    return messages
+
+
+def main() -> None:
+    parser = argparse.ArgumentParser(
+        description=f"{LINTER_CODE} linter adapter",
+        fromfile_prefix_chars="@",
+    )
+    parser.add_argument("filenames", nargs="+", help="paths to lint")
+    args = parser.parse_args()
+
+    for path in args.filenames:
+        for message in check_file(path):
+            print(json.dumps(message._asdict()), flush=True)
+
+
+if __name__ == "__main__":
+    sys.exit(main())
+"#;
+
+const SHELL_TEMPLATE: &str = r#"#!/usr/bin/env bash
+# Adapter for the {{CODE}} linter.
+#
+# lintrunner calls this script with `@<file>`, where <file> lists the paths to
+# lint, one per line. For every problem found, print one LintMessage as JSON on
+# its own line to stdout, then exit 0. A non-zero exit code tells lintrunner
+# the linter itself failed.
+#
+# The message format is described at
+# https://docs.rs/lintrunner/latest/lintrunner/lint_message/struct.LintMessage.html
+set -euo pipefail
+
+paths_file="${1#@}"
+
+while IFS= read -r path; do
+    # TODO: replace this example check with the real one. Note that paths
+    # containing quotes or backslashes need escaping to be valid JSON.
+    { grep -n "FIXME" "$path" || true; } | while IFS=: read -r line _; do
+        printf '{"path": "%s", "line": %s, "char": null, "code": "{{CODE}}", "severity": "warning", "name": "fixme", "original": null, "replacement": null, "description": "Resolve this FIXME before landing."}\n' "$path" "$line"
+    done
+done < "$paths_file"
+"#;
+
+impl AdapterLanguage {
+    fn extension(self) -> &'static str {
+        match self {
+            AdapterLanguage::Python => "py",
+            AdapterLanguage::Shell => "sh",
+        }
+    }
+
+    fn interpreter(self) -> &'static str {
+        match self {
+            AdapterLanguage::Python => "python3",
+            AdapterLanguage::Shell => "bash",
+        }
+    }
+
+    fn template(self) -> &'static str {
+        match self {
+            AdapterLanguage::Python => PYTHON_TEMPLATE,
+            AdapterLanguage::Shell => SHELL_TEMPLATE,
+        }
+    }
+}
+
+fn config_stub(code: &str, interpreter: &str, adapter: &str) -> String {
+    format!(
+        "
+[[linter]]
+code = '{code}'
+# TODO: set the files this linter should run on.
+include_patterns = ['**']
+command = [
+    '{interpreter}',
+    '{adapter}',
+    '@{{{{PATHSFILE}}}}',
+]
+"
+    )
+}
+
+/// Append a stub for linter `code` to the config at `config_path`, and write
+/// an adapter script for it to `adapter_dir` (relative to the config).
+/// Returns the path of the adapter.
+pub fn new_linter(
+    config_path: &Path,
+    code: &str,
+    language: AdapterLanguage,
+    adapter_dir: &Path,
+) -> Result<PathBuf> {
+    ensure!(
+        !code.is_empty()
+            && code
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-'),
+        "Invalid linter name '{}': use only letters, numbers, '_' and '-'.",
+        code
+    );
+
+    let config = if config_path.exists() {
+        std::fs::read_to_string(config_path)
+            .with_context(|| format!("Failed to read config at '{}'", config_path.display()))?
+    } else {
+        String::new()
+    };
+    if !config.is_empty() {
+        let existing = LintRunnerConfig::new_from_string(&config)?;
+        if existing.linters.iter().any(|linter| linter.code == code) {
+            bail!(
+                "A linter named '{}' already exists in '{}'.",
+                code,
+                config_path.display()
+            );
+        }
+    }
+
+    let config_dir = match config_path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir.to_path_buf(),
+        _ => PathBuf::from("."),
+    };
+    let adapter = adapter_dir.join(format!(
+        "{}_linter.{}",
+        code.to_lowercase(),
+        language.extension()
+    ));
+    let adapter_path = config_dir.join(&adapter);
+    ensure!(
+        !adapter_path.exists(),
+        "Adapter '{}' already exists.",
+        adapter_path.display()
+    );
+
+    if let Some(parent) = adapter_path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    std::fs::write(&adapter_path, language.template().replace("{{CODE}}", code))
+        .with_context(|| format!("Failed to write '{}'", adapter_path.display()))?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(&adapter_path, std::fs::Permissions::from_mode(0o755))?;
+    }
+
+    // Paths in the config use forward slashes, even on Windows.
+    let adapter_str = adapter.to_string_lossy().replace('\\', "/");
+    let mut config_file = std::fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(config_path)?;
+    if !config.is_empty() && !config.ends_with('\n') {
+        writeln!(config_file)?;
+    }
+    let stub = config_stub(code, language.interpreter(), &adapter_str);
+    let stub = if config.is_empty() {
+        stub.trim_start()
+    } else {
+        &stub
+    };
+    write!(config_file, "{}", stub)?;
+
+    println!(
+        "{} Added linter '{}' to {} and wrote its adapter to {}.",
+        style("Done!").green().bold(),
+        code,
+        config_path.display(),
+        adapter_path.display()
+    );
+    println!("Next, set its include_patterns and fill in the check in the adapter.");
+    Ok(adapter_path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn new_linter_is_scaffolded() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let config_path = dir.path().join(".lintrunner.toml");
+        std::fs::write(
+            &config_path,
+            "[[linter]]\ncode = 'OLD'\ninclude_patterns = ['**']\ncommand = ['old']",
+        )?;
+
+        let adapter = new_linter(
+            &config_path,
+            "MYLINT",
+            AdapterLanguage::Python,
+            Path::new("tools/linter"),
+        )?;
+        assert_eq!(adapter, dir.path().join("tools/linter/mylint_linter.py"));
+        assert!(std::fs::read_to_string(&adapter)?.contains("LINTER_CODE = \"MYLINT\""));
+
+        let config = LintRunnerConfig::new_from_string(&std::fs::read_to_string(&config_path)?)?;
+        let linter = &config.linters[1];
+        assert_eq!(linter.code, "MYLINT");
+        assert_eq!(
+            linter.command,
+            vec!["python3", "tools/linter/mylint_linter.py", "@{{PATHSFILE}}"]
+        );
+
+        // Adding the same linter twice fails.
+        assert!(new_linter(
+            &config_path,
+            "MYLINT",
+            AdapterLanguage::Shell,
+            Path::new("tools/linter"),
+        )
+        .is_err());
+        Ok(())
+    }
+}
@@ -0,0 +1,218 @@
+//! Coordinates when linters are allowed to start.
+//!
+//! Every linter runs on its own thread, but a linter may declare (via
+//! `run_after`) that it must not start until some other linters have
+//! finished. Linters without such constraints between them run in parallel.
+//!
+//! Linters marked `exclusive` additionally never run at the same time as any
+//! other linter, and with a job limit, only that many linters run at once.
+
+use std::{
+    collections::HashSet,
+    sync::{Condvar, Mutex, RwLock, RwLockReadGuard, RwLockWriteGuard},
+};
+
+#[derive(Default)]
+pub struct Scheduler {
+    finished: Mutex<HashSet<String>>,
+    cvar: Condvar,
+    // Regular linters hold this for reading while they run, exclusive linters
+    // hold it for writing.
+    running: RwLock<()>,
+    // The most linters that may run at once, if limited, and how many are.
+    jobs: Option<usize>,
+    running_jobs: Mutex<usize>,
+    jobs_cvar: Condvar,
+}
+
+/// Permission to run a linter. The linter should hold on to this until it is
+/// done running.
+pub struct RunSlot<'a> {
+    _lock: RunLock<'a>,
+    _job: Option<JobSlot<'a>>,
+}
+
+enum RunLock<'a> {
+    Shared { _guard: RwLockReadGuard<'a, ()> },
+    Exclusive { _guard: RwLockWriteGuard<'a, ()> },
+}
+
+struct JobSlot<'a>(&'a Scheduler);
+
+impl Drop for JobSlot<'_> {
+    fn drop(&mut self) {
+        *self.0.running_jobs.lock().unwrap() -= 1;
+        self.0.jobs_cvar.notify_one();
+    }
+}
+
+/// Marks a linter as finished when dropped, so that the linters waiting on it
+/// proceed even if it returns early or panics.
+pub struct FinishGuard<'a> {
+    scheduler: &'a Scheduler,
+    code: String,
+}
+
+impl Drop for FinishGuard<'_> {
+    fn drop(&mut self) {
+        self.scheduler.mark_finished(&self.code);
+    }
+}
+
+impl Scheduler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// A scheduler that runs at most `jobs` linters at once. `None` means no
+    /// limit.
+    pub fn with_jobs(jobs: Option<usize>) -> Self {
+        Scheduler {
+            jobs: jobs.map(|jobs| jobs.max(1)),
+            ..Self::default()
+        }
+    }
+
+    /// Block until every linter in `deps` has finished.
+    pub fn wait_for(&self, deps: &[String]) {
+        let mut finished = self.finished.lock().unwrap();
+        while !deps.iter().all(|dep| finished.contains(dep)) {
+            finished = self.cvar.wait(finished).unwrap();
+        }
+    }
+
+    /// Block until the linter is allowed to run. Exclusive linters wait until
+    /// no other linter is running, and nothing else starts while they run.
+    pub fn acquire(&self, exclusive: bool) -> RunSlot<'_> {
+        // Take a job before the lock, so that a linter waiting for a job
+        // never holds up an exclusive one.
+        let job = self.jobs.map(|jobs| {
+            let mut running_jobs = self.running_jobs.lock().unwrap();
+            while *running_jobs >= jobs {
+                running_jobs = self.jobs_cvar.wait(running_jobs).unwrap();
+            }
+            *running_jobs += 1;
+            JobSlot(self)
+        });
+        let lock = if exclusive {
+            RunLock::Exclusive {
+                _guard: self.running.write().unwrap(),
+            }
+        } else {
+            RunLock::Shared {
+                _guard: self.running.read().unwrap(),
+            }
+        };
+        RunSlot {
+            _lock: lock,
+            _job: job,
+        }
+    }
+
+    /// A guard that marks the linter `code` as finished when it goes out of
+    /// scope.
+    pub fn finish_on_drop(&self, code: &str) -> FinishGuard<'_> {
+        FinishGuard {
+            scheduler: self,
+            code: code.to_string(),
+        }
+    }
+
+    /// Mark a linter as finished, waking up anything waiting on it.
+    pub fn mark_finished(&self, code: &str) {
+        self.finished.lock().unwrap().insert(code.to_string());
+        self.cvar.notify_all();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{sync::Arc, thread, time::Duration};
+
+    use super::*;
+
+    #[test]
+    fn waits_for_dependencies() {
+        let scheduler = Arc::new(Scheduler::new());
+        let order = Arc::new(Mutex::new(Vec::new()));
+
+        let handle = {
+            let scheduler = Arc::clone(&scheduler);
+            let order = Arc::clone(&order);
+            thread::spawn(move || {
+                scheduler.wait_for(&["FIRST".to_string()]);
+                order.lock().unwrap().push("SECOND");
+            })
+        };
+
+        thread::sleep(Duration::from_millis(50));
+        order.lock().unwrap().push("FIRST");
+        scheduler.mark_finished("FIRST");
+        handle.join().unwrap();
+
+        assert_eq!(*order.lock().unwrap(), vec!["FIRST", "SECOND"]);
+    }
+
+    #[test]
+    fn panicking_linter_still_finishes() {
+        let scheduler = Arc::new(Scheduler::new());
+
+        let panicking = {
+            let scheduler = Arc::clone(&scheduler);
+            thread::spawn(move || {
+                let _finished = scheduler.finish_on_drop("FIRST");
+                let _slot = scheduler.acquire(false);
+                panic!("linter thread panicked");
+            })
+        };
+        let dependent = {
+            let scheduler = Arc::clone(&scheduler);
+            thread::spawn(move || {
+                scheduler.wait_for(&["FIRST".to_string()]);
+                let _slot = scheduler.acquire(false);
+            })
+        };
+
+        assert!(panicking.join().is_err());
+        dependent.join().unwrap();
+    }
+
+    #[test]
+    fn exclusive_runs_alone() {
+        let scheduler = Arc::new(Scheduler::new());
+        let shared = scheduler.acquire(false);
+
+        let handle = {
+            let scheduler = Arc::clone(&scheduler);
+            thread::spawn(move || {
+                let _slot = scheduler.acquire(true);
+            })
+        };
+
+        // The exclusive linter can't start while another one is running.
+        thread::sleep(Duration::from_millis(50));
+        assert!(!handle.is_finished());
+
+        drop(shared);
+        handle.join().unwrap();
+    }
+
+    #[test]
+    fn jobs_are_limited() {
+        let scheduler = Arc::new(Scheduler::with_jobs(Some(1)));
+        let first = scheduler.acquire(false);
+
+        let handle = {
+            let scheduler = Arc::clone(&scheduler);
+            thread::spawn(move || {
+                let _slot = scheduler.acquire(false);
+            })
+        };
+
+        thread::sleep(Duration::from_millis(50));
+        assert!(!handle.is_finished());
+
+        drop(first);
+        handle.join().unwrap();
+    }
+}
@@ -0,0 +1,96 @@
+//! Running linter commands through a shell.
+//!
+//! By default, commands are spawned directly: the first argument is the
+//! program and the rest are passed to it verbatim. With a shell configured,
+//! the command is turned into a script for that shell instead, so it can use
+//! pipes, globs, environment variables and so on.
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Shell {
+    /// Spawn the command directly, without a shell.
+    #[default]
+    #[serde(rename = "none")]
+    Direct,
+    Sh,
+    Bash,
+    Pwsh,
+}
+
+impl Shell {
+    /// Turn `args` into the command that runs them with this shell. The first
+    /// argument is used as-is, so it can be a whole script; the rest are
+    /// quoted and appended to it.
+    pub fn wrap(self, args: Vec<String>) -> Vec<String> {
+        let (script, rest) = match args.split_first() {
+            Some(split) => split,
+            None => return args,
+        };
+        let (invocation, quote): (&[&str], fn(&str) -> String) = match self {
+            Shell::Direct => return args,
+            Shell::Sh => (&["sh", "-c"], posix_quote),
+            Shell::Bash => (&["bash", "-c"], posix_quote),
+            Shell::Pwsh => (
+                &["pwsh", "-NoProfile", "-NonInteractive", "-Command"],
+                pwsh_quote,
+            ),
+        };
+        let script = std::iter::once(script.clone())
+            .chain(rest.iter().map(|arg| quote(arg)))
+            .collect::<Vec<_>>()
+            .join(" ");
+        invocation
+            .iter()
+            .map(|arg| arg.to_string())
+            .chain(std::iter::once(script))
+            .collect()
+    }
+}
+
+fn posix_quote(arg: &str) -> String {
+    shell_words::quote(arg).into_owned()
+}
+
+fn pwsh_quote(arg: &str) -> String {
+    // Inside single quotes, PowerShell only treats a doubled quote specially.
+    format!("'{}'", arg.replace('\'', "''"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    #[test]
+    fn direct_is_untouched() {
+        let command = args(&["flake8", "--select", "E 501"]);
+        assert_eq!(Shell::Direct.wrap(command.clone()), command);
+    }
+
+    #[test]
+    fn scripts_are_wrapped() {
+        assert_eq!(
+            Shell::Sh.wrap(args(&["cat paths | xargs flake8", "it's", "@/tmp/paths"])),
+            args(&[
+                "sh",
+                "-c",
+                "cat paths | xargs flake8 'it'\\''s' @/tmp/paths"
+            ])
+        );
+        assert_eq!(
+            Shell::Pwsh.wrap(args(&["Get-Content", "it's"])),
+            args(&[
+                "pwsh",
+                "-NoProfile",
+                "-NonInteractive",
+                "-Command",
+                "Get-Content 'it''s'"
+            ])
+        );
+    }
+}
@@ -0,0 +1,118 @@
+use crate::{flaky::find_flaky_linters, persistent_data::PersistentDataStore};
+use anyhow::Result;
+use console::{style, Term};
+
+/// Show what lintrunner knows about past runs: how long each linter usually
+/// takes, with `flaky`, which linters gave inconsistent results, or with
+/// `ages`, how old their issues are.
+pub fn do_stats(
+    persistent_data_store: &PersistentDataStore,
+    flaky: bool,
+    ages: bool,
+) -> Result<i32> {
+    let stdout = Term::stdout();
+    if ages {
+        return print_ages(&stdout, persistent_data_store);
+    }
+    if flaky {
+        let history = persistent_data_store.past_linter_fingerprints()?;
+        let flaky_linters = find_flaky_linters(&history);
+        if flaky_linters.is_empty() {
+            stdout.write_line(&format!(
+                "{} No flaky linters found in the last {} runs.",
+                style("ok").green(),
+                history.len()
+            ))?;
+            return Ok(0);
+        }
+        for (code, flaky) in &flaky_linters {
+            stdout.write_line(&format!(
+                "{} gave {} different results for the same files in {} of the last {} runs",
+                style(code).bold(),
+                flaky.distinct_results,
+                flaky.runs,
+                history.len()
+            ))?;
+        }
+        return Ok(0);
+    }
+
+    let mut durations: Vec<_> = persistent_data_store
+        .linter_durations()?
+        .into_iter()
+        .collect();
+    if durations.is_empty() {
+        stdout.write_line("No linter runs recorded yet.")?;
+        return Ok(0);
+    }
+    // Slowest first.
+    durations.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    let width = durations
+        .iter()
+        .map(|(code, _)| code.len())
+        .max()
+        .unwrap_or(0);
+    stdout.write_line("Typical linter durations:")?;
+    for (code, duration) in durations {
+        stdout.write_line(&format!(
+            "  {:width$}  {:.1}s",
+            code,
+            duration.as_secs_f64(),
+            width = width
+        ))?;
+    }
+    Ok(0)
+}
+
+/// Upper bounds, in days, of the age groups issues are counted in.
+const AGE_GROUPS: [(i64, &str); 3] = [(7, "< 1 week"), (30, "< 30 days"), (90, "< 90 days")];
+
+fn print_ages(stdout: &Term, persistent_data_store: &PersistentDataStore) -> Result<i32> {
+    let history = persistent_data_store.issue_history()?;
+    let ages = history.ages(chrono::Utc::now());
+    if ages.is_empty() {
+        stdout.write_line("No issues recorded yet.")?;
+        return Ok(0);
+    }
+    let width = ages.keys().map(|code| code.len()).max().unwrap_or(0);
+    let headers: Vec<&str> = AGE_GROUPS
+        .iter()
+        .map(|(_, label)| *label)
+        .chain(["older", "oldest"])
+        .collect();
+    stdout.write_line(&format!(
+        "  {:width$}  {}",
+        "",
+        style(headers.join("  ")).bold(),
+        width = width
+    ))?;
+    for (code, ages) in &ages {
+        let mut counts = [0; AGE_GROUPS.len() + 1];
+        for age in ages {
+            let group = AGE_GROUPS
+                .iter()
+                .position(|(days, _)| age.num_days() < *days)
+                .unwrap_or(AGE_GROUPS.len());
+            counts[group] += 1;
+        }
+        let oldest = ages
+            .iter()
+            .max()
+            .map(|age| format!("{} days", age.num_days()))
+            .unwrap_or_default();
+        let columns: Vec<String> = counts
+            .iter()
+            .map(|count| count.to_string())
+            .chain([oldest])
+            .zip(&headers)
+            .map(|(value, header)| format!("{:>w$}", value, w = header.len()))
+            .collect();
+        stdout.write_line(&format!(
+            "  {:width$}  {}",
+            code,
+            columns.join("  "),
+            width = width
+        ))?;
+    }
+    Ok(0)
+}
@@ -0,0 +1,110 @@
+//! Which of stdout and stderr lintrunner writes what to.
+//!
+//! Lint results, as rendered by `--output`, go to one stream, and
+//! diagnostics (logs, progress, errors and status messages like "Saved 2
+//! patches") go to the other. By default results go to stdout, so that e.g.
+//! `lintrunner --output json | jq` only ever sees JSON. `--results-to stderr`
+//! swaps the two, for wrappers that capture stdout for something else.
+
+use std::{io::Write, sync::OnceLock};
+
+use clap::ArgEnum;
+use console::Term;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ArgEnum)]
+pub enum Stream {
+    Stdout,
+    Stderr,
+}
+
+impl Stream {
+    pub fn term(self) -> Term {
+        match self {
+            Stream::Stdout => Term::stdout(),
+            Stream::Stderr => Term::stderr(),
+        }
+    }
+
+    /// Write `buf` in one go, holding the lock on the stream, so that
+    /// concurrent writes from other threads can't end up in the middle of
+    /// it.
+    pub fn write_all(self, buf: &[u8]) -> std::io::Result<()> {
+        match self {
+            Stream::Stdout => {
+                let mut stdout = std::io::stdout().lock();
+                stdout.write_all(buf)?;
+                stdout.flush()
+            }
+            Stream::Stderr => {
+                let mut stderr = std::io::stderr().lock();
+                stderr.write_all(buf)?;
+                stderr.flush()
+            }
+        }
+    }
+
+    fn other(self) -> Stream {
+        match self {
+            Stream::Stdout => Stream::Stderr,
+            Stream::Stderr => Stream::Stdout,
+        }
+    }
+}
+
+static RESULTS: OnceLock<Stream> = OnceLock::new();
+
+/// Send results to `stream`, and diagnostics to the other one, for the rest
+/// of the run. Only the first call has an effect.
+pub fn set_results(stream: Stream) {
+    let _ = RESULTS.set(stream);
+}
+
+/// Where lint results go.
+pub fn results() -> Stream {
+    *RESULTS.get().unwrap_or(&Stream::Stdout)
+}
+
+/// Where everything that isn't a lint result goes.
+pub fn diagnostics() -> Stream {
+    results().other()
+}
+
+/// Send everything written to stdout and stderr, by lintrunner or the
+/// linters it runs, to the null device for the rest of the run.
+#[cfg(unix)]
+pub fn silence() -> anyhow::Result<()> {
+    use std::os::unix::io::AsRawFd;
+
+    let null = std::fs::OpenOptions::new().write(true).open("/dev/null")?;
+    for fd in [libc::STDOUT_FILENO, libc::STDERR_FILENO] {
+        // SAFETY: both descriptors are open, and dup2 replaces `fd` atomically.
+        if unsafe { libc::dup2(null.as_raw_fd(), fd) } == -1 {
+            return Err(std::io::Error::last_os_error().into());
+        }
+    }
+    Ok(())
+}
+
+/// Send everything written to stdout and stderr, by lintrunner or the
+/// linters it runs, to the null device for the rest of the run.
+#[cfg(windows)]
+pub fn silence() -> anyhow::Result<()> {
+    use std::os::windows::io::IntoRawHandle;
+
+    #[link(name = "kernel32")]
+    extern "system" {
+        fn SetStdHandle(std_handle: u32, handle: *mut std::ffi::c_void) -> i32;
+    }
+    const STD_OUTPUT_HANDLE: u32 = -11i32 as u32;
+    const STD_ERROR_HANDLE: u32 = -12i32 as u32;
+
+    for std_handle in [STD_OUTPUT_HANDLE, STD_ERROR_HANDLE] {
+        let null = std::fs::OpenOptions::new().write(true).open("NUL")?;
+        // SAFETY: the handle is valid, and ownership passes to the process's
+        // standard handles.
+        if unsafe { SetStdHandle(std_handle, null.into_raw_handle()) } == 0 {
+            return Err(std::io::Error::last_os_error().into());
+        }
+    }
+    Ok(())
+}
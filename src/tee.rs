@@ -0,0 +1,234 @@
+//! Writing `--tee-json` archives.
+//!
+//! The path may contain placeholders, expanded once when the run starts: `%t`
+//! for the time (like `20240131T120000Z`), `%d` for the date, `%r` for the
+//! run id and `%%` for a literal `%`. By default an existing file is never
+//! overwritten; `--tee-json-mode` can instead append each run to it, or
+//! truncate it. With `--tee-json-max-size`, a file that has grown past the
+//! limit is moved aside to `<path>.1` (and an older `<path>.1` to `<path>.2`,
+//! and so on) before the run is written, so nothing is ever lost.
+
+use std::{
+    fs::{File, OpenOptions},
+    path::{Path, PathBuf},
+};
+
+use anyhow::{bail, ensure, Context, Result};
+use chrono::{DateTime, Utc};
+use clap::ArgEnum;
+use log::debug;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ArgEnum)]
+pub enum TeeMode {
+    /// Fail if the file exists
+    New,
+    /// Add this run after the runs already in the file
+    Append,
+    /// Replace the file's contents
+    Truncate,
+}
+
+/// Where, and how, to write a copy of the results as JSON.
+#[derive(Debug, Clone)]
+pub struct TeeJson {
+    pub path: PathBuf,
+    pub mode: TeeMode,
+    /// Rotate the file once it is at least this many bytes.
+    pub max_size: Option<u64>,
+}
+
+impl TeeJson {
+    /// Expand the placeholders in `template` for the run identified by
+    /// `run_id`, which started at `now`.
+    pub fn new(
+        template: &str,
+        mode: TeeMode,
+        max_size: Option<u64>,
+        run_id: &str,
+        now: DateTime<Utc>,
+    ) -> Result<TeeJson> {
+        Ok(TeeJson {
+            path: PathBuf::from(expand_template(template, run_id, now)?),
+            mode,
+            max_size,
+        })
+    }
+
+    /// Fail early if the results can't be written, so that a long lint run
+    /// isn't wasted.
+    pub fn check(&self) -> Result<()> {
+        ensure!(
+            self.mode != TeeMode::New || !self.path.exists() || self.needs_rotation(),
+            "--tee-json file '{}' already exists, pass --tee-json-mode to append to \
+             or truncate it",
+            self.path.display()
+        );
+        Ok(())
+    }
+
+    /// Open the file to write this run's results to, rotating it first if it
+    /// is too big.
+    pub fn open(&self) -> Result<File> {
+        if self.needs_rotation() {
+            rotate(&self.path)?;
+        }
+        if let Some(parent) = self
+            .path
+            .parent()
+            .filter(|parent| !parent.as_os_str().is_empty())
+        {
+            std::fs::create_dir_all(parent)
+                .with_context(|| format!("Failed to create '{}'", parent.display()))?;
+        }
+        let mut options = OpenOptions::new();
+        match self.mode {
+            TeeMode::New => options.write(true).create_new(true),
+            TeeMode::Append => options.append(true).create(true),
+            TeeMode::Truncate => options.write(true).create(true).truncate(true),
+        };
+        options.open(&self.path).with_context(|| {
+            format!(
+                "Couldn't open file for --tee-json: '{}'",
+                self.path.display()
+            )
+        })
+    }
+
+    fn needs_rotation(&self) -> bool {
+        match (self.max_size, std::fs::metadata(&self.path)) {
+            (Some(max_size), Ok(metadata)) => metadata.len() >= max_size,
+            _ => false,
+        }
+    }
+}
+
+fn expand_template(template: &str, run_id: &str, now: DateTime<Utc>) -> Result<String> {
+    let mut expanded = String::new();
+    let mut chars = template.chars();
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            expanded.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('t') => expanded.push_str(&now.format("%Y%m%dT%H%M%SZ").to_string()),
+            Some('d') => expanded.push_str(&now.format("%Y-%m-%d").to_string()),
+            Some('r') => expanded.push_str(run_id),
+            Some('%') => expanded.push('%'),
+            Some(other) => bail!(
+                "Unknown placeholder '%{}' in --tee-json path, expected %t, %d, %r or %%",
+                other
+            ),
+            None => bail!("--tee-json path ends with a lone '%', write '%%' for a literal one"),
+        }
+    }
+    Ok(expanded)
+}
+
+/// Move `path` to `<path>.1`, after moving any older rotated files one number
+/// up.
+fn rotate(path: &Path) -> Result<()> {
+    let rotated = |n: usize| {
+        let mut rotated = path.as_os_str().to_owned();
+        rotated.push(format!(".{}", n));
+        PathBuf::from(rotated)
+    };
+    let mut last = 0;
+    while rotated(last + 1).exists() {
+        last += 1;
+    }
+    for n in (0..=last).rev() {
+        let from = if n == 0 {
+            path.to_path_buf()
+        } else {
+            rotated(n)
+        };
+        debug!(
+            "Rotating {} to {}",
+            from.display(),
+            rotated(n + 1).display()
+        );
+        std::fs::rename(&from, rotated(n + 1))
+            .with_context(|| format!("Failed to rotate --tee-json file '{}'", from.display()))?;
+    }
+    Ok(())
+}
+
+/// Parse a size like `500000`, `64K`, `10M` or `1G` (in powers of 1024).
+pub fn parse_size(size: &str) -> Result<u64> {
+    let size = size.trim();
+    let (number, unit) = match size.find(|c: char| !c.is_ascii_digit()) {
+        Some(i) => size.split_at(i),
+        None => (size, ""),
+    };
+    let multiplier = match unit.to_ascii_uppercase().as_str() {
+        "" | "B" => 1,
+        "K" | "KB" => 1 << 10,
+        "M" | "MB" => 1 << 20,
+        "G" | "GB" => 1 << 30,
+        _ => bail!(
+            "Invalid size '{}', expected e.g. 500000, 64K, 10M or 1G",
+            size
+        ),
+    };
+    let number: u64 = number
+        .parse()
+        .with_context(|| format!("Invalid size '{}'", size))?;
+    Ok(number * multiplier)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn templates_are_expanded() -> Result<()> {
+        let now = Utc.with_ymd_and_hms(2024, 1, 31, 12, 0, 0).unwrap();
+        assert_eq!(
+            expand_template("lint-%d/%t-%r-100%%.json", "abc", now)?,
+            "lint-2024-01-31/20240131T120000Z-abc-100%.json"
+        );
+        assert!(expand_template("lint-%x.json", "abc", now).is_err());
+        assert!(expand_template("lint-%", "abc", now).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn sizes_are_parsed() -> Result<()> {
+        assert_eq!(parse_size("500")?, 500);
+        assert_eq!(parse_size("64k")?, 64 * 1024);
+        assert_eq!(parse_size("10MB")?, 10 * 1024 * 1024);
+        assert!(parse_size("10 parsecs").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn big_files_are_rotated() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join("lint.json");
+        let tee = TeeJson {
+            path: path.clone(),
+            mode: TeeMode::Append,
+            max_size: Some(10),
+        };
+        for run in ["first\n", "second\n", "third run\n"] {
+            tee.check()?;
+            std::io::Write::write_all(&mut tee.open()?, run.as_bytes())?;
+        }
+        assert_eq!(std::fs::read_to_string(&path)?, "third run\n");
+        assert_eq!(
+            std::fs::read_to_string(dir.path().join("lint.json.1"))?,
+            "first\nsecond\n"
+        );
+        assert!(!dir.path().join("lint.json.2").exists());
+
+        let tee = TeeJson {
+            mode: TeeMode::New,
+            max_size: None,
+            ..tee
+        };
+        assert!(tee.check().is_err());
+        Ok(())
+    }
+}
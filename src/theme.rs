@@ -0,0 +1,221 @@
+//! The colors lintrunner's default output uses.
+//!
+//! The user config picks a named theme with `theme`, and can override single
+//! styles in its `[colors]` table. Styles are written the way `console` parses
+//! them: dot-separated colors and attributes, like `black.on_yellow.bold`.
+
+use std::{collections::BTreeMap, sync::OnceLock};
+
+use anyhow::{bail, Result};
+use console::Style;
+use serde::Deserialize;
+
+use crate::lint_message::LintSeverity;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ThemeName {
+    #[default]
+    Default,
+    /// For terminals with a light background, where yellow text is unreadable.
+    Light,
+    /// Bold, reversed and background colors only, no dimmed text.
+    HighContrast,
+    /// Blue and yellow instead of green and red, for red-green colorblindness.
+    Colorblind,
+}
+
+/// The style of each kind of thing the default output highlights.
+#[derive(Debug, Clone)]
+pub struct Theme {
+    pub error: Style,
+    pub warning: Style,
+    pub advice: Style,
+    /// Paths of files with lint issues.
+    pub path: Style,
+    /// The line a lint issue is about, in the context shown around it.
+    pub highlight: Style,
+    /// Line numbers and unchanged lines.
+    pub context: Style,
+    /// Lines a suggested fix adds.
+    pub added: Style,
+    /// Lines a suggested fix removes.
+    pub removed: Style,
+    /// Hints about what to do next.
+    pub hint: Style,
+    pub ok: Style,
+}
+
+const STYLE_NAMES: [&str; 10] = [
+    "error",
+    "warning",
+    "advice",
+    "path",
+    "highlight",
+    "context",
+    "added",
+    "removed",
+    "hint",
+    "ok",
+];
+
+impl ThemeName {
+    fn styles(self) -> [&'static str; STYLE_NAMES.len()] {
+        match self {
+            ThemeName::Default => [
+                "on_red.bold",
+                "on_yellow.bold",
+                "on_yellow.bold",
+                "underlined",
+                "yellow",
+                "dim",
+                "green",
+                "red",
+                "cyan",
+                "green",
+            ],
+            ThemeName::Light => [
+                "white.on_red.bold",
+                "black.on_yellow.bold",
+                "black.on_cyan.bold",
+                "underlined",
+                "blue.bold",
+                "dim",
+                "green",
+                "red",
+                "blue",
+                "green",
+            ],
+            ThemeName::HighContrast => [
+                "white.on_red.bold",
+                "black.on_yellow.bold",
+                "black.on_white.bold",
+                "bold.underlined",
+                "reverse",
+                "",
+                "green.bold",
+                "red.bold",
+                "bold",
+                "green.bold",
+            ],
+            ThemeName::Colorblind => [
+                "white.on_magenta.bold",
+                "black.on_yellow.bold",
+                "black.on_cyan.bold",
+                "underlined",
+                "cyan",
+                "dim",
+                "blue",
+                "yellow",
+                "cyan",
+                "blue",
+            ],
+        }
+    }
+}
+
+impl Theme {
+    /// Build a theme from `name`, with the styles in `overrides` (keyed by
+    /// the names of `Theme`'s fields) replacing its own.
+    pub fn new(name: ThemeName, overrides: &BTreeMap<String, String>) -> Result<Theme> {
+        let mut styles: Vec<Style> = name
+            .styles()
+            .iter()
+            .map(|style| Style::from_dotted_str(style))
+            .collect();
+        for (name, style) in overrides {
+            let Some(i) = STYLE_NAMES.iter().position(|known| known == name) else {
+                bail!(
+                    "Unknown color '{}', expected one of: {}",
+                    name,
+                    STYLE_NAMES.join(", ")
+                );
+            };
+            check_style(style)?;
+            styles[i] = Style::from_dotted_str(style);
+        }
+        let mut styles = styles.into_iter();
+        let mut next = || styles.next().unwrap();
+        Ok(Theme {
+            error: next(),
+            warning: next(),
+            advice: next(),
+            path: next(),
+            highlight: next(),
+            context: next(),
+            added: next(),
+            removed: next(),
+            hint: next(),
+            ok: next(),
+        })
+    }
+
+    pub fn severity(&self, severity: LintSeverity) -> &Style {
+        match severity {
+            LintSeverity::Error => &self.error,
+            LintSeverity::Warning => &self.warning,
+            LintSeverity::Advice | LintSeverity::Disabled => &self.advice,
+        }
+    }
+}
+
+/// `console` silently ignores parts of a style it doesn't know, so check for
+/// typos here.
+fn check_style(style: &str) -> Result<()> {
+    const COLORS: [&str; 8] = [
+        "black", "red", "green", "yellow", "blue", "magenta", "cyan", "white",
+    ];
+    const ATTRIBUTES: [&str; 10] = [
+        "bold",
+        "dim",
+        "underlined",
+        "blink",
+        "blink_fast",
+        "strikethrough",
+        "reverse",
+        "hidden",
+        "bright",
+        "on_bright",
+    ];
+    for part in style.split('.').filter(|part| !part.is_empty()) {
+        let color = part.strip_prefix("on_").unwrap_or(part);
+        let known =
+            COLORS.contains(&color) || ATTRIBUTES.contains(&part) || color.parse::<u8>().is_ok();
+        if !known {
+            bail!("Unknown style '{}' in '{}'", part, style);
+        }
+    }
+    Ok(())
+}
+
+static THEME: OnceLock<Theme> = OnceLock::new();
+
+/// Use `theme` for the rest of the run. Only the first call has an effect.
+pub fn set(theme: Theme) {
+    let _ = THEME.set(theme);
+}
+
+/// The theme in use, or the default one if none was set.
+pub fn current() -> &'static Theme {
+    THEME.get_or_init(|| Theme::new(ThemeName::Default, &BTreeMap::new()).unwrap())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn colors_can_be_overridden() -> Result<()> {
+        let overrides: BTreeMap<String, String> =
+            [("warning".to_string(), "black.on_cyan.bold".to_string())].into();
+        let theme = Theme::new(ThemeName::HighContrast, &overrides)?;
+        assert_eq!(theme.warning, Style::new().black().on_cyan().bold());
+        assert_eq!(theme.highlight, Style::new().reverse());
+
+        let typo: BTreeMap<String, String> = [("eror".to_string(), "red".to_string())].into();
+        assert!(Theme::new(ThemeName::Default, &typo).is_err());
+        let typo: BTreeMap<String, String> = [("error".to_string(), "rde".to_string())].into();
+        assert!(Theme::new(ThemeName::Default, &typo).is_err());
+        Ok(())
+    }
+}
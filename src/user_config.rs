@@ -0,0 +1,132 @@
+//! Per-user defaults for lintrunner, so that people don't have to pass the same
+//! flags on every invocation.
+//!
+//! The config lives in a platform-specific location (e.g.
+//! `~/.config/lintrunner/config.toml` on Linux). Anything set here is
+//! overridden by the corresponding command line flag.
+
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Context, Result};
+use directories::ProjectDirs;
+use log::debug;
+use serde::Deserialize;
+
+use crate::{theme::ThemeName, RenderOpt};
+
+const USER_CONFIG_NAME: &str = "config.toml";
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct UserConfig {
+    /// Default for `--data-path`.
+    pub data_path: Option<PathBuf>,
+
+    /// Whether to color output. Environment variables like NO_COLOR and
+    /// `--force-color` take precedence over this.
+    pub color: Option<ColorPreference>,
+
+    /// Default for `--output`.
+    pub output: Option<RenderOpt>,
+
+    /// Default for `--jobs`. The repo's profile takes precedence over this.
+    pub jobs: Option<usize>,
+
+    /// Where `lintrunner rage --upload` sends reports: 'gist' or the URL of
+    /// a paste service.
+    pub rage_upload: Option<String>,
+
+    /// The named set of colors to use.
+    #[serde(default)]
+    pub theme: ThemeName,
+
+    /// Styles that replace the theme's, by what they are for (e.g. `error`
+    /// or `path`).
+    #[serde(default)]
+    pub colors: BTreeMap<String, String>,
+}
+
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ColorPreference {
+    Always,
+    Never,
+    Auto,
+}
+
+impl ColorPreference {
+    /// Whether colors should be forced on or off. None means colors should be
+    /// used only when writing to a terminal.
+    pub fn use_color(self) -> Option<bool> {
+        match self {
+            ColorPreference::Always => Some(true),
+            ColorPreference::Never => Some(false),
+            ColorPreference::Auto => None,
+        }
+    }
+}
+
+impl UserConfig {
+    /// Load the user config from its default location. A missing config is
+    /// not an error; it just means nothing is overridden.
+    pub fn load() -> Result<UserConfig> {
+        match ProjectDirs::from("", "", "lintrunner") {
+            Some(dirs) => UserConfig::from_path(&dirs.config_dir().join(USER_CONFIG_NAME)),
+            None => Ok(UserConfig::default()),
+        }
+    }
+
+    pub fn from_path(path: &Path) -> Result<UserConfig> {
+        if !path.exists() {
+            return Ok(UserConfig::default());
+        }
+        debug!("Reading user config from '{}'", path.display());
+        let contents = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read user config: '{}'", path.display()))?;
+        toml::from_str(&contents)
+            .with_context(|| format!("User config '{}' had invalid schema", path.display()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn user_config_is_parsed() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let path = dir.path().join(USER_CONFIG_NAME);
+
+        // A missing config is fine.
+        let config = UserConfig::from_path(&path)?;
+        assert!(config.data_path.is_none() && config.output.is_none());
+
+        std::fs::write(
+            &path,
+            "data_path = '/tmp/lintrunner'\ncolor = 'never'\noutput = 'oneline'\njobs = 4\n",
+        )?;
+        let config = UserConfig::from_path(&path)?;
+        assert_eq!(config.data_path, Some(PathBuf::from("/tmp/lintrunner")));
+        assert_eq!(
+            config.color.and_then(ColorPreference::use_color),
+            Some(false)
+        );
+        assert_eq!(config.output, Some(RenderOpt::Oneline));
+        assert_eq!(config.jobs, Some(4));
+
+        std::fs::write(
+            &path,
+            "theme = 'high-contrast'\n[colors]\nerror = 'white.on_blue.bold'\n",
+        )?;
+        let config = UserConfig::from_path(&path)?;
+        assert_eq!(config.theme, ThemeName::HighContrast);
+        assert_eq!(config.colors["error"], "white.on_blue.bold");
+
+        std::fs::write(&path, "colour = 'never'\n")?;
+        assert!(UserConfig::from_path(&path).is_err());
+        Ok(())
+    }
+}
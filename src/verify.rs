@@ -0,0 +1,259 @@
+//! Checking that linters are still installed the way `lintrunner init` left
+//! them.
+//!
+//! After initializing a linter, `init` records what it installed: the init
+//! command it ran, hashes of the linter's lockfiles and of its program, and
+//! the tool's version. `lintrunner verify` compares the current state against
+//! that record, and reports exactly what is out of date.
+
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+};
+
+use anyhow::Result;
+use console::{style, Term};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    linter::Linter,
+    persistent_data::PersistentDataStore,
+    version::{run_version_command, Version},
+};
+
+/// What a linter looked like right after it was initialized.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct InitState {
+    pub init_command: Option<Vec<String>>,
+    /// Hashes of the linter's lockfiles, by path. Missing files have no hash.
+    pub lockfiles: BTreeMap<PathBuf, Option<String>>,
+    /// Where the linter's program was found, and the hash of its contents.
+    pub program: Option<(PathBuf, String)>,
+    /// The version the linter's `version_command` printed.
+    pub version: Option<String>,
+}
+
+impl InitState {
+    /// Capture the current state of `linter`.
+    pub fn capture(linter: &Linter) -> InitState {
+        InitState {
+            init_command: linter.init_commands.clone(),
+            lockfiles: linter
+                .lockfiles
+                .iter()
+                .map(|path| (path.clone(), hash_file(path)))
+                .collect(),
+            program: linter
+                .program()
+                .and_then(|program| find_program(&program, linter.get_config_dir()))
+                .and_then(|path| {
+                    let hash = hash_file(&path)?;
+                    Some((path, hash))
+                }),
+            version: installed_version(linter),
+        }
+    }
+}
+
+fn hash_file(path: &Path) -> Option<String> {
+    std::fs::read(path)
+        .ok()
+        .map(|contents| blake3::hash(&contents).to_string())
+}
+
+fn installed_version(linter: &Linter) -> Option<String> {
+    let command = linter.version_command()?;
+    let output = run_version_command(&command, linter.get_config_dir()).ok()?;
+    Version::extract(&output).map(|version| version.to_string())
+}
+
+/// Find the file `program` refers to: a path relative to `dir` if it has a
+/// directory component, or otherwise the first match on `PATH`.
+pub fn find_program(program: &str, dir: &Path) -> Option<PathBuf> {
+    let extensions: &[&str] = if cfg!(windows) { &["", ".exe"] } else { &[""] };
+    let candidates: Vec<PathBuf> = if Path::new(program).components().count() > 1 {
+        vec![dir.join(program)]
+    } else {
+        let path = std::env::var_os("PATH")?;
+        std::env::split_paths(&path)
+            .map(|entry| entry.join(program))
+            .collect()
+    };
+    candidates.into_iter().find_map(|candidate| {
+        extensions
+            .iter()
+            .map(|ext| {
+                let mut candidate = candidate.clone().into_os_string();
+                candidate.push(ext);
+                PathBuf::from(candidate)
+            })
+            .find(|candidate| candidate.is_file())
+    })
+}
+
+/// Everything about `linter` that differs from `recorded`, the state it was
+/// initialized in. Empty if it is up to date.
+pub fn find_problems(linter: &Linter, recorded: Option<&InitState>) -> Vec<String> {
+    let current = InitState::capture(linter);
+    let mut problems = Vec::new();
+
+    let recorded = match recorded {
+        Some(recorded) => recorded,
+        None if linter.init_commands.is_some() => {
+            problems.push("never initialized".to_string());
+            &current
+        }
+        None => &current,
+    };
+
+    if current.init_command != recorded.init_command {
+        problems.push("init_command changed since it was initialized".to_string());
+    }
+    for (path, hash) in &current.lockfiles {
+        match (recorded.lockfiles.get(path), hash) {
+            (_, None) => problems.push(format!("lockfile '{}' is missing", path.display())),
+            (Some(old), Some(_)) if old == hash => {}
+            (Some(_), Some(_)) => problems.push(format!(
+                "lockfile '{}' changed since it was initialized",
+                path.display()
+            )),
+            (None, Some(_)) => problems.push(format!(
+                "lockfile '{}' wasn't there when it was initialized",
+                path.display()
+            )),
+        }
+    }
+    match (&recorded.program, &current.program, linter.program()) {
+        (_, None, Some(program)) => {
+            problems.push(format!("'{}' is not installed", program));
+        }
+        (Some((old_path, _)), Some((path, _)), _) if old_path != path => problems.push(format!(
+            "'{}' is now used instead of '{}'",
+            path.display(),
+            old_path.display()
+        )),
+        (Some((_, old_hash)), Some((path, hash)), _) if old_hash != hash => problems.push(format!(
+            "'{}' changed since it was initialized",
+            path.display()
+        )),
+        _ => {}
+    }
+    if let Some(check) = &linter.version_check {
+        match &current.version {
+            Some(version) => {
+                // Unwrap is fine because the version was extracted from text.
+                if !check.requirement.matches(&Version::parse(version).unwrap()) {
+                    problems.push(format!(
+                        "version {} is installed, but {} is expected",
+                        version, check.requirement
+                    ));
+                } else if recorded.version.as_ref().is_some_and(|old| old != version) {
+                    problems.push(format!(
+                        "version {} is installed, but it was initialized with {}",
+                        version,
+                        recorded.version.as_ref().unwrap()
+                    ));
+                }
+            }
+            None => problems.push("its version_command didn't print a version".to_string()),
+        }
+    }
+    problems
+}
+
+/// Report which linters are out of date with respect to their recorded init
+/// state. Returns 1 if any are.
+pub fn do_verify(linters: &[Linter], persistent_data_store: &PersistentDataStore) -> Result<i32> {
+    let stdout = Term::stdout();
+    let recorded = persistent_data_store.init_states()?;
+    let width = linters
+        .iter()
+        .map(|linter| linter.code.len())
+        .max()
+        .unwrap_or(0);
+
+    let mut out_of_date = 0;
+    for linter in linters {
+        let problems = find_problems(linter, recorded.get(&linter.code));
+        if problems.is_empty() {
+            stdout.write_line(&format!(
+                "  {:width$}  {}",
+                linter.code,
+                style("ok").green(),
+                width = width
+            ))?;
+            continue;
+        }
+        out_of_date += 1;
+        stdout.write_line(&format!(
+            "  {:width$}  {}",
+            style(&linter.code).bold(),
+            style("out of date").red(),
+            width = width
+        ))?;
+        for problem in problems {
+            stdout.write_line(&format!("  {:width$}    - {}", "", problem, width = width))?;
+        }
+    }
+
+    if out_of_date == 0 {
+        return Ok(0);
+    }
+    stdout.write_line(&format!(
+        "\n{} linter{} out of date. Run `lintrunner init` to bring {} up to date.",
+        out_of_date,
+        if out_of_date == 1 { " is" } else { "s are" },
+        if out_of_date == 1 { "it" } else { "them" },
+    ))?;
+    Ok(1)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::convert::TryFrom;
+
+    use super::*;
+    use crate::{
+        lint_config::{get_linters_from_config, LintRunnerConfig},
+        path::AbsPath,
+    };
+
+    #[test]
+    fn lockfile_changes_are_found() -> Result<()> {
+        let dir = tempfile::tempdir()?;
+        let config_path = dir.path().join(".lintrunner.toml");
+        let config = "\
+            [[linter]]
+            code = 'SH'
+            include_patterns = ['**']
+            command = ['sh', '-c', 'true']
+            init_command = ['sh', '-c', 'true', '{{DRYRUN}}']
+            lockfiles = ['requirements.txt']
+            ";
+        std::fs::write(&config_path, config)?;
+        std::fs::write(dir.path().join("requirements.txt"), "flake8==6.0.0\n")?;
+        let config = LintRunnerConfig::new_from_string(config)?;
+        let linters = get_linters_from_config(
+            &config.linters,
+            None,
+            None,
+            &AbsPath::try_from(config_path.as_path())?,
+        )?;
+        let linter = &linters[0];
+
+        assert_eq!(find_problems(linter, None), vec!["never initialized"]);
+        let recorded = InitState::capture(linter);
+        assert!(recorded.program.is_some());
+        assert!(find_problems(linter, Some(&recorded)).is_empty());
+
+        std::fs::write(dir.path().join("requirements.txt"), "flake8==7.0.0\n")?;
+        let problems = find_problems(linter, Some(&recorded));
+        assert_eq!(problems.len(), 1);
+        assert!(problems[0].contains("changed since it was initialized"));
+
+        std::fs::remove_file(dir.path().join("requirements.txt"))?;
+        let problems = find_problems(linter, Some(&recorded));
+        assert!(problems[0].contains("is missing"));
+        Ok(())
+    }
+}
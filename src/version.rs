@@ -0,0 +1,295 @@
+//! Checking that linters run the tool versions the config expects.
+//!
+//! A linter can declare a `version_command` that prints the version of the
+//! tool it runs, and the `version` it expects. Before linting, each distinct
+//! command is run once, and linters whose tool doesn't match are reported,
+//! so that a different version installed locally and in CI doesn't go
+//! unnoticed.
+
+use std::{
+    cmp::Ordering,
+    collections::{BTreeMap, HashMap},
+    fmt,
+    process::Command,
+    thread,
+};
+
+use anyhow::{bail, Context, Result};
+use console::style;
+use log::debug;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+
+use crate::{linter::Linter, streams};
+
+/// A dotted version number, like `23.1.0`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Version(Vec<u64>);
+
+impl Version {
+    pub fn parse(s: &str) -> Option<Version> {
+        s.split('.')
+            .map(|part| part.parse().ok())
+            .collect::<Option<Vec<_>>>()
+            .map(Version)
+    }
+
+    /// Find the first version number in the output of a `--version` command,
+    /// e.g. `23.1.0` in `black, 23.1.0 (compiled: yes)`.
+    pub fn extract(output: &str) -> Option<Version> {
+        let re = Regex::new(r"\d+(\.\d+)+|\d+").unwrap();
+        re.find(output)
+            .and_then(|version| Version::parse(version.as_str()))
+    }
+
+    /// Compare with missing components treated as 0, so that `1.2` equals
+    /// `1.2.0`.
+    fn compare(&self, other: &Version) -> Ordering {
+        let len = self.0.len().max(other.0.len());
+        let part = |v: &Version, i: usize| v.0.get(i).copied().unwrap_or(0);
+        (0..len)
+            .map(|i| part(self, i).cmp(&part(other, i)))
+            .find(|ordering| ordering.is_ne())
+            .unwrap_or(Ordering::Equal)
+    }
+
+    /// Whether `self` starts with all of `prefix`'s components.
+    fn starts_with(&self, prefix: &Version) -> bool {
+        self.0.starts_with(&prefix.0)
+    }
+}
+
+impl fmt::Display for Version {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let parts: Vec<String> = self.0.iter().map(|part| part.to_string()).collect();
+        f.write_str(&parts.join("."))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Op {
+    /// A bare version, which matches itself and anything more specific, so
+    /// `1.2` matches `1.2.7` but not `1.20`.
+    Prefix,
+    Exact,
+    Greater,
+    GreaterEq,
+    Less,
+    LessEq,
+}
+
+/// A version requirement: comma-separated comparisons that must all hold,
+/// like `>=23.1, <24`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VersionReq {
+    source: String,
+    comparisons: Vec<(Op, Version)>,
+}
+
+impl VersionReq {
+    pub fn parse(s: &str) -> Result<VersionReq> {
+        let mut comparisons = Vec::new();
+        for comparison in s.split(',') {
+            let comparison = comparison.trim();
+            let (op, version) = [
+                (">=", Op::GreaterEq),
+                ("<=", Op::LessEq),
+                ("==", Op::Exact),
+                (">", Op::Greater),
+                ("<", Op::Less),
+                ("=", Op::Exact),
+            ]
+            .iter()
+            .find_map(|(prefix, op)| comparison.strip_prefix(prefix).map(|rest| (*op, rest)))
+            .unwrap_or((Op::Prefix, comparison));
+            let version = Version::parse(version.trim()).with_context(|| {
+                format!(
+                    "Invalid version requirement '{}': '{}' is not a version",
+                    s, comparison
+                )
+            })?;
+            comparisons.push((op, version));
+        }
+        Ok(VersionReq {
+            source: s.to_string(),
+            comparisons,
+        })
+    }
+
+    pub fn matches(&self, version: &Version) -> bool {
+        self.comparisons.iter().all(|(op, required)| {
+            let ordering = version.compare(required);
+            match op {
+                Op::Prefix => version.starts_with(required),
+                Op::Exact => ordering.is_eq(),
+                Op::Greater => ordering.is_gt(),
+                Op::GreaterEq => ordering.is_ge(),
+                Op::Less => ordering.is_lt(),
+                Op::LessEq => ordering.is_le(),
+            }
+        })
+    }
+}
+
+impl fmt::Display for VersionReq {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.source)
+    }
+}
+
+/// What to do when a linter's tool isn't the expected version.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum VersionMismatch {
+    /// Print a warning, and lint anyway.
+    #[default]
+    Warn,
+    /// Don't lint at all.
+    Fail,
+}
+
+/// A linter's expected tool version, and how to find the installed one.
+#[derive(Debug, Clone)]
+pub struct VersionCheck {
+    pub command: Vec<String>,
+    pub requirement: VersionReq,
+    pub on_mismatch: VersionMismatch,
+}
+
+/// Run each linter's version command (once per distinct command) and compare
+/// the result against the version the linter expects. Mismatches are printed
+/// as warnings, or, for linters with `version_mismatch = 'fail'`, returned as
+/// an error.
+pub fn check_versions(linters: &[Linter]) -> Result<()> {
+    let checks: Vec<(&Linter, &VersionCheck, Vec<String>)> = linters
+        .iter()
+        .filter_map(|linter| {
+            let check = linter.version_check.as_ref()?;
+            Some((linter, check, linter.version_command()?))
+        })
+        .collect();
+
+    // Linters often share a tool, so only run each command once.
+    let mut outputs: HashMap<&[String], Result<String>> = HashMap::new();
+    thread::scope(|scope| {
+        let mut handles = BTreeMap::new();
+        for (linter, _, command) in &checks {
+            handles.entry(command.as_slice()).or_insert_with(|| {
+                let config_dir = linter.get_config_dir();
+                scope.spawn(move || run_version_command(command, config_dir))
+            });
+        }
+        for (command, handle) in handles {
+            outputs.insert(command, handle.join().unwrap());
+        }
+    });
+
+    let stderr = streams::diagnostics().term();
+    let mut failures = Vec::new();
+    for (linter, check, command) in &checks {
+        let output = match &outputs[command.as_slice()] {
+            Ok(output) => output,
+            Err(err) => {
+                // The linter itself will most likely fail the same way, and
+                // that's reported as a missing linter.
+                debug!("Couldn't check the version of '{}': {:#}", linter.code, err);
+                continue;
+            }
+        };
+        let message = match Version::extract(output) {
+            Some(version) if check.requirement.matches(&version) => {
+                debug!("Linter '{}' has version {}", linter.code, version);
+                continue;
+            }
+            Some(version) => format!(
+                "Linter '{}' expects version {}, but {} is installed.",
+                linter.code, check.requirement, version
+            ),
+            None => format!(
+                "Linter '{}' expects version {}, but its version command printed no version: {}",
+                linter.code,
+                check.requirement,
+                output.trim()
+            ),
+        };
+        match check.on_mismatch {
+            VersionMismatch::Warn => {
+                stderr.write_line(&format!(
+                    "{}",
+                    style(format!(
+                        "WARNING: {} You may need to run `lintrunner init`.",
+                        message
+                    ))
+                    .bold()
+                    .yellow()
+                ))?;
+            }
+            VersionMismatch::Fail => failures.push(message),
+        }
+    }
+    if !failures.is_empty() {
+        bail!(
+            "{}\nRun `lintrunner init` to install the expected versions.",
+            failures.join("\n")
+        );
+    }
+    Ok(())
+}
+
+/// Run a version command from `config_dir`, and return what it printed.
+pub fn run_version_command(command: &[String], config_dir: &std::path::Path) -> Result<String> {
+    debug!("Running: {}", command.join(" "));
+    let output = Command::new(&command[0])
+        .args(&command[1..])
+        .current_dir(config_dir)
+        .output()
+        .with_context(|| format!("Failed to run '{}'", command[0]))?;
+    if !output.status.success() {
+        bail!("'{}' exited with {}", command.join(" "), output.status);
+    }
+    // Some tools print their version to stderr.
+    let mut text = String::from_utf8_lossy(&output.stdout).into_owned();
+    if text.trim().is_empty() {
+        text = String::from_utf8_lossy(&output.stderr).into_owned();
+    }
+    Ok(text)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn matches(req: &str, version: &str) -> bool {
+        VersionReq::parse(req)
+            .unwrap()
+            .matches(&Version::parse(version).unwrap())
+    }
+
+    #[test]
+    fn requirements_are_matched() {
+        assert!(matches("1.2", "1.2.7"));
+        assert!(!matches("1.2", "1.20"));
+        assert!(matches("=1.2", "1.2.0"));
+        assert!(!matches("==1.2", "1.2.1"));
+        assert!(matches(">=23.1, <24", "23.12.1"));
+        assert!(!matches(">=23.1, <24", "24.0"));
+        assert!(!matches(">1.0", "1"));
+        assert!(matches("<=1.0", "1"));
+        assert!(VersionReq::parse(">=latest").is_err());
+    }
+
+    #[test]
+    fn versions_are_extracted() {
+        let extract = |output| Version::extract(output).map(|v| v.to_string());
+        assert_eq!(
+            extract("black, 23.1.0 (compiled: yes)"),
+            Some("23.1.0".to_string())
+        );
+        assert_eq!(
+            extract("mypy 1.4.1 (compiled: yes)\n"),
+            Some("1.4.1".to_string())
+        );
+        assert_eq!(extract("clang-format version 17"), Some("17".to_string()));
+        assert_eq!(extract("no version here"), None);
+    }
+}
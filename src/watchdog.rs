@@ -0,0 +1,170 @@
+//! Diagnostics for linters that take suspiciously long.
+//!
+//! When a linter runs past its `slow_threshold`, a warning says which files
+//! it was given and how long it has been running. If the linter has a
+//! `stack_sampler` configured, that command is run against the linter
+//! process and its output is logged too, so that "lintrunner is stuck"
+//! reports come with something to go on.
+
+use std::path::Path;
+use std::process::Command;
+use std::sync::mpsc;
+use std::time::Duration;
+
+use log::{debug, warn};
+
+use crate::path::{get_display_path, AbsPath};
+
+/// How long a linter may run before it is reported, unless configured
+/// otherwise.
+pub const DEFAULT_SLOW_THRESHOLD: Duration = Duration::from_secs(120);
+
+/// Replaced with the linter's process id in a `stack_sampler` command.
+const PID_PLACEHOLDER: &str = "{{PID}}";
+
+/// How many files to name when reporting a slow linter.
+const MAX_FILES_SHOWN: usize = 5;
+
+/// Watches a running linter until dropped.
+pub struct Watchdog {
+    // Dropping the sender wakes up the watching thread.
+    _done: mpsc::Sender<()>,
+}
+
+impl Watchdog {
+    pub fn start(
+        code: &str,
+        pid: u32,
+        files: &[AbsPath],
+        threshold: Duration,
+        stack_sampler: Option<&[String]>,
+        config_dir: &Path,
+    ) -> Watchdog {
+        let (done, finished) = mpsc::channel::<()>();
+        let code = code.to_string();
+        let files = describe_files(files);
+        let sampler = stack_sampler.map(|sampler| {
+            sampler
+                .iter()
+                .map(|arg| arg.replace(PID_PLACEHOLDER, &pid.to_string()))
+                .collect::<Vec<_>>()
+        });
+        let config_dir = config_dir.to_path_buf();
+
+        std::thread::spawn(move || {
+            if finished.recv_timeout(threshold) != Err(mpsc::RecvTimeoutError::Timeout) {
+                return;
+            }
+            warn!(
+                "Linter {} has been running for more than {}s (pid {}), on {}",
+                code,
+                threshold.as_secs_f32(),
+                pid,
+                files
+            );
+            if let Some(sampler) = sampler {
+                sample_stack(&code, &sampler, &config_dir);
+            }
+        });
+        Watchdog { _done: done }
+    }
+}
+
+fn sample_stack(code: &str, sampler: &[String], config_dir: &Path) {
+    debug!("Sampling stack of linter {}: {:?}", code, sampler);
+    let output = Command::new(&sampler[0])
+        .args(&sampler[1..])
+        .current_dir(config_dir)
+        .output();
+    match output {
+        Ok(output) => warn!(
+            "Stack sample of linter {}:\n{}{}",
+            code,
+            String::from_utf8_lossy(&output.stdout),
+            String::from_utf8_lossy(&output.stderr)
+        ),
+        Err(err) => warn!(
+            "Failed to sample stack of linter {} with {}: {}",
+            code, sampler[0], err
+        ),
+    }
+}
+
+fn describe_files(files: &[AbsPath]) -> String {
+    let current_dir = std::env::current_dir().unwrap_or_default();
+    let mut shown: Vec<String> = files
+        .iter()
+        .take(MAX_FILES_SHOWN)
+        .map(|file| get_display_path(&file.to_string_lossy(), &current_dir))
+        .collect();
+    if files.len() > MAX_FILES_SHOWN {
+        shown.push(format!("{} more", files.len() - MAX_FILES_SHOWN));
+    }
+    match shown.split_last() {
+        None => "no files".to_string(),
+        Some((last, [])) => last.clone(),
+        Some((last, rest)) => format!("{} and {}", rest.join(", "), last),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn files_are_summarized() {
+        let dir = tempfile::tempdir().unwrap();
+        let files: Vec<AbsPath> = (0..7)
+            .map(|i| {
+                let path = dir.path().join(format!("{i}.py"));
+                std::fs::write(&path, "").unwrap();
+                AbsPath::try_from(path).unwrap()
+            })
+            .collect();
+        assert_eq!(describe_files(&[]), "no files");
+        assert!(describe_files(&files[..1]).ends_with("0.py"));
+        let summary = describe_files(&files);
+        assert!(summary.ends_with("4.py and 2 more"), "{summary}");
+        assert!(!summary.contains("5.py"), "{summary}");
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn stack_sampler_runs_when_threshold_is_exceeded() {
+        let dir = tempfile::tempdir().unwrap();
+        let sampler = vec![
+            "sh".to_string(),
+            "-c".to_string(),
+            "echo {{PID}} > sampled".to_string(),
+        ];
+        let sampled = dir.path().join("sampled");
+
+        // A linter that finishes in time isn't sampled.
+        drop(Watchdog::start(
+            "FAST",
+            1,
+            &[],
+            Duration::from_millis(10),
+            Some(&sampler),
+            dir.path(),
+        ));
+        std::thread::sleep(Duration::from_millis(50));
+        assert!(!sampled.exists());
+
+        let _watchdog = Watchdog::start(
+            "SLOW",
+            1234,
+            &[],
+            Duration::from_millis(10),
+            Some(&sampler),
+            dir.path(),
+        );
+        for _ in 0..100 {
+            if sampled.exists() && !std::fs::read_to_string(&sampled).unwrap().is_empty() {
+                break;
+            }
+            std::thread::sleep(Duration::from_millis(20));
+        }
+        assert_eq!(std::fs::read_to_string(&sampled).unwrap(), "1234\n");
+    }
+}
@@ -132,12 +132,8 @@ fn simple_linter() -> Result<()> {
         path: Some("tests/fixtures/fake_source_file.rs".to_string()),
         line: Some(9),
         char: Some(1),
-        code: "DUMMY".to_string(),
-        name: "dummy failure".to_string(),
-        severity: LintSeverity::Advice,
-        original: None,
-        replacement: None,
         description: Some("A dummy linter failure".to_string()),
+        ..LintMessage::for_test("DUMMY", LintSeverity::Advice, "dummy failure")
     };
     let config = temp_config_returning_msg(lint_message)?;
 
@@ -149,12 +145,43 @@ fn simple_linter() -> Result<()> {
     ));
     // Run on a file to ensure that the linter is run.
     cmd.arg("README.md");
-    cmd.assert().failure();
+    cmd.assert().success();
     assert_output_snapshot("simple_linter", &mut cmd)?;
 
     Ok(())
 }
 
+#[test]
+fn check_only_prints_nothing() -> Result<()> {
+    let data_path = tempfile::tempdir()?;
+    let lint_message = LintMessage {
+        path: Some("tests/fixtures/fake_source_file.rs".to_string()),
+        line: Some(9),
+        char: Some(1),
+        description: Some("A dummy linter failure".to_string()),
+        ..LintMessage::for_test("DUMMY", LintSeverity::Error, "dummy failure")
+    };
+    let config = temp_config_returning_msg(lint_message)?;
+
+    let mut cmd = Command::cargo_bin("lintrunner")?;
+    cmd.arg(format!("--config={}", config.path().to_str().unwrap()));
+    cmd.arg(format!(
+        "--data-path={}",
+        data_path.path().to_str().unwrap()
+    ));
+    cmd.arg("--check-only");
+    cmd.arg("README.md");
+    cmd.assert().failure().stdout("").stderr("");
+
+    // Errors aren't printed either.
+    let mut cmd = Command::cargo_bin("lintrunner")?;
+    cmd.arg("--config=asdfasdfasdf");
+    cmd.arg("--check-only");
+    cmd.assert().failure().stdout("").stderr("");
+
+    Ok(())
+}
+
 #[test]
 #[cfg_attr(target_os = "windows", ignore)] // path is rendered differently
 fn simple_linter_oneline() -> Result<()> {
@@ -163,12 +190,8 @@ fn simple_linter_oneline() -> Result<()> {
         path: Some("tests/fixtures/fake_source_file.rs".to_string()),
         line: Some(9),
         char: Some(1),
-        code: "DUMMY".to_string(),
-        name: "dummy failure".to_string(),
-        severity: LintSeverity::Advice,
-        original: None,
-        replacement: None,
         description: Some("A dummy linter failure".to_string()),
+        ..LintMessage::for_test("DUMMY", LintSeverity::Advice, "dummy failure")
     };
     let config = temp_config_returning_msg(lint_message)?;
 
@@ -181,7 +204,7 @@ fn simple_linter_oneline() -> Result<()> {
     // Run on a file to ensure that the linter is run.
     cmd.arg("README.md");
     cmd.arg("--output=oneline");
-    cmd.assert().failure();
+    cmd.assert().success();
     assert_output_snapshot("simple_linter_oneline", &mut cmd)?;
 
     Ok(())
@@ -208,6 +231,91 @@ fn simple_linter_fails_on_nonexistent_file() -> Result<()> {
     Ok(())
 }
 
+#[test]
+fn severity_override_to_advice_passes() -> Result<()> {
+    let data_path = tempfile::tempdir()?;
+    let dir = tempfile::tempdir()?;
+    let file = dir.path().join("vendored").join("a.py");
+    std::fs::create_dir(file.parent().unwrap())?;
+    std::fs::write(&file, "x = 1\n")?;
+    let lint_message = LintMessage {
+        path: Some(file.to_str().unwrap().to_string()),
+        line: Some(1),
+        ..LintMessage::for_test("TESTLINTER", LintSeverity::Error, "bad")
+    };
+    let config = dir.path().join(".lintrunner.toml");
+    std::fs::write(
+        &config,
+        format!(
+            "\
+                [[linter]]
+                code = 'TESTLINTER'
+                include_patterns = ['**']
+                command = ['echo', '{}']
+                severity_overrides = [{{ pattern = 'vendored/**', severity = 'advice' }}]
+            ",
+            serde_json::to_string(&lint_message)?
+        ),
+    )?;
+
+    let mut cmd = Command::cargo_bin("lintrunner")?;
+    cmd.arg(format!("--config={}", config.to_str().unwrap()));
+    cmd.arg(format!(
+        "--data-path={}",
+        data_path.path().to_str().unwrap()
+    ));
+    cmd.arg(file.to_str().unwrap());
+    cmd.assert().success().code(0);
+
+    Ok(())
+}
+
+#[test]
+#[cfg_attr(target_os = "windows", ignore)] // uses sh
+fn linter_args_go_before_paths() -> Result<()> {
+    let data_path = tempfile::tempdir()?;
+    // The linter fails unless the extra arguments come before the `--`.
+    let config = temp_config(
+        "\
+            [[linter]]
+            code = 'TESTLINTER'
+            include_patterns = ['**']
+            command = ['sh', '-c', 'test \"$1\" = --strict && test \"$2\" = --', 'linter', '--', '@{{PATHSFILE}}']
+        ",
+    )?;
+
+    let mut cmd = Command::cargo_bin("lintrunner")?;
+    cmd.arg(format!("--config={}", config.path().to_str().unwrap()));
+    cmd.arg(format!(
+        "--data-path={}",
+        data_path.path().to_str().unwrap()
+    ));
+    cmd.arg("--linter-arg=TESTLINTER=--strict");
+    cmd.arg("README.md");
+    cmd.assert().success();
+
+    let mut cmd = Command::cargo_bin("lintrunner")?;
+    cmd.arg(format!("--config={}", config.path().to_str().unwrap()));
+    cmd.arg(format!(
+        "--data-path={}",
+        data_path.path().to_str().unwrap()
+    ));
+    cmd.args(["--take", "TESTLINTER", "README.md", "--", "--strict"]);
+    cmd.assert().success();
+
+    // Without the argument, the linter fails.
+    let mut cmd = Command::cargo_bin("lintrunner")?;
+    cmd.arg(format!("--config={}", config.path().to_str().unwrap()));
+    cmd.arg(format!(
+        "--data-path={}",
+        data_path.path().to_str().unwrap()
+    ));
+    cmd.arg("README.md");
+    cmd.assert().failure();
+
+    Ok(())
+}
+
 #[test]
 fn duplicate_code_fails() -> Result<()> {
     let config = temp_config(
@@ -240,12 +348,8 @@ fn linter_providing_nonexistent_path_degrades_gracefully() -> Result<()> {
         path: Some("i_dont_exist_wow".to_string()),
         line: Some(3),
         char: Some(1),
-        code: "DUMMY".to_string(),
-        name: "dummy failure".to_string(),
-        severity: LintSeverity::Advice,
-        original: None,
-        replacement: None,
         description: Some("A dummy linter failure".to_string()),
+        ..LintMessage::for_test("DUMMY", LintSeverity::Advice, "dummy failure")
     };
     let config = temp_config_returning_msg(lint_message)?;
 
@@ -258,7 +362,7 @@ fn linter_providing_nonexistent_path_degrades_gracefully() -> Result<()> {
 
     // Run the linter on this file.
     cmd.arg("tests/integration_test.rs");
-    cmd.assert().failure();
+    cmd.assert().success();
     assert_output_snapshot(
         "linter_providing_nonexistent_path_degrades_gracefully",
         &mut cmd,
@@ -314,12 +418,36 @@ fn linter_nonexistent_command() -> Result<()> {
 
     // Run the linter on this file.
     cmd.arg("tests/integration_test.rs");
-    cmd.assert().failure();
+    cmd.assert().success();
     assert_output_snapshot("linter_nonexistent_command", &mut cmd)?;
 
     Ok(())
 }
 
+#[test]
+fn linter_nonexistent_command_strict() -> Result<()> {
+    let data_path = tempfile::tempdir()?;
+    let config = temp_config(
+        "\
+            [[linter]]
+            code = 'TESTLINTER'
+            include_patterns = ['**']
+            command = ['idonotexist']
+        ",
+    )?;
+    let mut cmd = Command::cargo_bin("lintrunner")?;
+    cmd.arg(format!("--config={}", config.path().to_str().unwrap()));
+    cmd.arg(format!(
+        "--data-path={}",
+        data_path.path().to_str().unwrap()
+    ));
+    cmd.arg("--strict-missing");
+    cmd.arg("tests/integration_test.rs");
+    cmd.assert().code(4);
+
+    Ok(())
+}
+
 #[test]
 #[cfg_attr(target_os = "windows", ignore)] // path is rendered differently
 fn simple_linter_replacement_message() -> Result<()> {
@@ -328,9 +456,6 @@ fn simple_linter_replacement_message() -> Result<()> {
         path: Some("tests/fixtures/fake_source_file.rs".to_string()),
         line: Some(9),
         char: Some(1),
-        code: "DUMMY".to_string(),
-        name: "dummy failure".to_string(),
-        severity: LintSeverity::Advice,
         original: Some(
             "\
             foo\n\
@@ -366,6 +491,7 @@ fn simple_linter_replacement_message() -> Result<()> {
             .to_string(),
         ),
         description: Some("A dummy linter failure".to_string()),
+        ..LintMessage::for_test("DUMMY", LintSeverity::Advice, "dummy failure")
     };
     let config = temp_config_returning_msg(lint_message)?;
 
@@ -377,7 +503,7 @@ fn simple_linter_replacement_message() -> Result<()> {
     ));
     // Run on a file to ensure that the linter is run.
     cmd.arg("README.md");
-    cmd.assert().failure();
+    cmd.assert().success();
     assert_output_snapshot("simple_linter_replacement_message", &mut cmd)?;
 
     Ok(())
@@ -596,6 +722,42 @@ fn excluding_dryrun_fails() -> Result<()> {
     Ok(())
 }
 
+#[test]
+fn dry_run_shows_what_linters_are_given() -> Result<()> {
+    let config = temp_config(
+        "\
+            [[linter]]
+            code = 'PROJECT'
+            include_patterns = ['**']
+            command = ['echo', '@{{PATHSFILE}}']
+            granularity = 'project'
+
+            [[linter]]
+            code = 'DIRS'
+            include_patterns = ['**']
+            command = ['echo', '@{{PATHSFILE}}']
+            granularity = 'directory'
+        ",
+    )?;
+    let mut cmd = Command::cargo_bin("lintrunner")?;
+    cmd.arg(format!("--config={}", config.path().to_str().unwrap()));
+    cmd.args(["--dry-run", "src/lib.rs", "src/main.rs", "README.md"]);
+    let output = cmd.output()?;
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout)?;
+    assert!(
+        stdout.contains("PROJECT would run on the project (3 files matched):\n  command:"),
+        "{stdout}"
+    );
+    assert!(
+        stdout.contains("DIRS would run on 2 directories:"),
+        "{stdout}"
+    );
+    assert!(!stdout.contains("main.rs"), "{stdout}");
+
+    Ok(())
+}
+
 #[test]
 fn format_command_doesnt_use_nonformat_linter() -> Result<()> {
     let data_path = tempfile::tempdir()?;
@@ -603,12 +765,8 @@ fn format_command_doesnt_use_nonformat_linter() -> Result<()> {
         path: Some("tests/fixtures/fake_source_file.rs".to_string()),
         line: Some(9),
         char: Some(1),
-        code: "DUMMY".to_string(),
-        name: "dummy failure".to_string(),
-        severity: LintSeverity::Advice,
-        original: None,
-        replacement: None,
         description: Some("A dummy linter failure".to_string()),
+        ..LintMessage::for_test("DUMMY", LintSeverity::Advice, "dummy failure")
     };
     let config = temp_config(&format!(
         "\
@@ -644,12 +802,8 @@ fn rage_command_basic() -> Result<()> {
         path: Some("tests/fixtures/fake_source_file.rs".to_string()),
         line: Some(9),
         char: Some(1),
-        code: "DUMMY".to_string(),
-        name: "dummy failure".to_string(),
-        severity: LintSeverity::Advice,
-        original: None,
-        replacement: None,
         description: Some("A dummy linter failure".to_string()),
+        ..LintMessage::for_test("DUMMY", LintSeverity::Advice, "dummy failure")
     };
     let config = temp_config_returning_msg(lint_message)?;
 
@@ -661,7 +815,7 @@ fn rage_command_basic() -> Result<()> {
     ));
     // Run on a file to ensure that the linter is run.
     cmd.arg("README.md");
-    cmd.assert().failure();
+    cmd.assert().success();
 
     // Now run rage
     let mut cmd = Command::cargo_bin("lintrunner")?;
@@ -683,12 +837,8 @@ fn tee_json() -> Result<()> {
         path: Some("tests/fixtures/fake_source_file.rs".to_string()),
         line: Some(9),
         char: Some(1),
-        code: "DUMMY".to_string(),
-        name: "dummy failure".to_string(),
-        severity: LintSeverity::Advice,
-        original: None,
-        replacement: None,
         description: Some("A dummy linter failure".to_string()),
+        ..LintMessage::for_test("DUMMY", LintSeverity::Advice, "dummy failure")
     };
     let config = temp_config_returning_msg(lint_message)?;
 
@@ -704,10 +854,15 @@ fn tee_json() -> Result<()> {
     ));
     // Run on a file to ensure that the linter is run.
     cmd.arg("README.md");
-    cmd.assert().failure();
+    cmd.assert().success();
 
     let tee_json = std::fs::read_to_string(data_path.path().join("foo.json"))?;
 
+    // The header changes with every release, so check it separately.
+    let (header, tee_json) = tee_json.split_once('\n').unwrap();
+    let header: serde_json::Value = serde_json::from_str(header)?;
+    assert_eq!(header["schema_version"], 2);
+    assert_eq!(header["lintrunner_version"], env!("CARGO_PKG_VERSION"));
     assert_snapshot!("tee_json", tee_json);
 
     Ok(())
@@ -721,9 +876,6 @@ fn linter_replacement_trailing_newlines() -> Result<()> {
         path: Some("tests/fixtures/fake_source_file.rs".to_string()),
         line: Some(9),
         char: Some(1),
-        code: "DUMMY".to_string(),
-        name: "dummy failure".to_string(),
-        severity: LintSeverity::Advice,
         original: Some(
             "\
             foo       \n\
@@ -743,6 +895,7 @@ fn linter_replacement_trailing_newlines() -> Result<()> {
             .to_string(),
         ),
         description: Some("A dummy linter failure".to_string()),
+        ..LintMessage::for_test("DUMMY", LintSeverity::Advice, "dummy failure")
     };
     let config = temp_config_returning_msg(lint_message)?;
 
@@ -755,8 +908,73 @@ fn linter_replacement_trailing_newlines() -> Result<()> {
     cmd.arg(format!("--force-color"));
     // Run on a file to ensure that the linter is run.
     cmd.arg("README.md");
-    cmd.assert().failure();
+    cmd.assert().success();
     assert_output_snapshot("linter_replacement_trailing_newlines", &mut cmd)?;
 
     Ok(())
 }
+
+#[test]
+fn stream_prints_results() -> Result<()> {
+    let data_path = tempfile::tempdir()?;
+    let lint_message = LintMessage {
+        path: Some("tests/fixtures/fake_source_file.rs".to_string()),
+        line: Some(9),
+        char: Some(1),
+        description: Some("A dummy linter failure".to_string()),
+        ..LintMessage::for_test("DUMMY", LintSeverity::Advice, "dummy failure")
+    };
+    let config = temp_config_returning_msg(lint_message)?;
+
+    let mut cmd = Command::cargo_bin("lintrunner")?;
+    cmd.arg(format!("--config={}", config.path().to_str().unwrap()));
+    cmd.arg(format!(
+        "--data-path={}",
+        data_path.path().to_str().unwrap()
+    ));
+    cmd.arg("--stream");
+    cmd.arg("--output=oneline");
+    // Run on a file to ensure that the linter is run.
+    cmd.arg("README.md");
+    cmd.assert().success();
+
+    // Results should be printed exactly once, even though they were streamed.
+    let stdout = String::from_utf8(cmd.output()?.stdout)?;
+    assert_eq!(stdout.matches("[DUMMY/dummy failure]").count(), 1);
+
+    Ok(())
+}
+
+#[test]
+fn why_skipped_reports_reasons() -> Result<()> {
+    let data_path = tempfile::tempdir()?;
+    let config = temp_config(
+        "\
+            [[linter]]
+            code = 'TESTLINTER'
+            include_patterns = ['**/*.nomatch']
+            command = ['true']
+
+            [[linter]]
+            code = 'SKIPPED'
+            include_patterns = ['**']
+            command = ['true']
+        ",
+    )?;
+    let mut cmd = Command::cargo_bin("lintrunner")?;
+    cmd.arg(format!("--config={}", config.path().to_str().unwrap()));
+    cmd.arg(format!(
+        "--data-path={}",
+        data_path.path().to_str().unwrap()
+    ));
+    cmd.arg("--why-skipped");
+    cmd.arg("--skip=SKIPPED");
+    cmd.arg("README.md");
+    cmd.assert().success();
+
+    let stderr = String::from_utf8(cmd.output()?.stderr)?;
+    assert!(stderr.contains("SKIPPED: skipped via --skip"));
+    assert!(stderr.contains("TESTLINTER: no files matched"));
+
+    Ok(())
+}